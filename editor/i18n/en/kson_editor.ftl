@@ -16,6 +16,7 @@ open=Open
 save=Save
 save_as=Save As
 export_ksh=Export Ksh
+export_ksh_legacy=Export Ksh (KSM 1.60)
 exit=Exit
 edit=Edit
 remove_note=Remove {$lane} note
@@ -69,4 +70,141 @@ alter_effect=Effect updated: {$name}
 remove_fx_effect=Removed effect {$effect} from note
 insert_fx_effect=Added effect {$effect} to note
 fullscreen=Fullscreen
-ui_scale=UI Scale
\ No newline at end of file
+ui_scale=UI Scale
+follow_mode=Playback follow
+follow_none=None
+follow_page=Page
+follow_centered=Centered
+font_scale=Font Scale
+pretty_kson=Indent saved .kson files
+untitled=Untitled
+playing_measure=Measure {$measure}
+comments=Comments
+add_comment=Add Comment
+edit_comment=Edit Comment
+remove_comment=Remove Comment
+go_to=Go To
+author_name=Author name
+preview_file=Preview File:
+import_timing_menu=Import Timing...
+import_timing=Import Timing
+import_timing_warning={$count} notes will play at a different time in the audio with the imported timing. Import anyway?
+notes_outside_audio={$count} notes outside audio
+notes_outside_audio_hover=These notes are placed before the audio starts or after it ends and will play in silence.
+append_chart_menu=Append Chart...
+append_chart=Append Chart
+cut_before_measure=Remove Everything Before This Measure
+cut_after_measure=Remove Everything After This Measure
+chip_sound=Chip Sound
+remove_chip_sound=Remove Chip Sound
+preview=Preview
+save_chart_first=Save the chart first so samples can be copied to its folder.
+save_failed=Failed to save chart
+lock_timing=Lock Timing
+lock_timing_hover=Prevents changes to BPM, time signatures and the audio offset of this chart
+timing_locked=Timing is locked
+playback_rate=Speed
+playback_rate_hover=Plays the song slower without changing its pitch, applies from the next time playback starts
+restore_backup=Restore Backup
+restore_backup_menu=Restore from Backup...
+no_backups=No backups of this chart
+backup_count=Backups kept per chart
+backup_folder=Backup folder
+backup_folder_default=Next to chart
+visual_metronome=Metronome
+visual_metronome_hover=Flashes the edges of the chart on every beat during playback, stronger on the first beat of a measure, and shows the current measure and beat
+fx_audio_files=FX Audio Files:
+fx_audio_files_hover=Pre-rendered audio with the chart's effects applied, played instead of live effects while FX buttons are held. Written after the main file in KSH (m=main.ogg;main_f.ogg)
+import_audio_files=Import audio files...
+chart_issues=Chart Issues
+chart_issues_hover=Lists mistakes in the chart like overlapping notes and lasers outside of the track
+no_chart_issues=No issues found
+laser_lane={$side} Laser
+issue_overlapping_notes=Overlapping notes on {$lane}
+issue_laser_out_of_range={$lane} is outside of the track
+issue_empty_laser_section={$lane} section has less than two points
+issue_zero_bpm=BPM is zero
+issue_missing_time_signature=No time signature at the first measure
+issue_note_past_audio_end={$lane} note continues past the end of the audio
+bgm_volume=BGM
+bgm_volume_hover=Volume of the song outside of effects, click to mute
+fx_volume=FX
+fx_volume_hover=Volume of the effected song, click to mute and hear the song without effects
+copy=Copy
+cut=Cut
+cut_selection=Cut Selection
+paste=Paste
+paste_conflict=Paste Conflict
+paste_conflict_warning={$count} notes in the chart overlap the pasted notes.
+paste_overwrite=Overwrite
+paste_keep_existing=Keep Existing
+delete_selection=Delete Selection
+chart_stats=Chart Statistics
+chart_stats_hover=Shows how much of the chart is spent holding notes and how notes are split between the hands
+hold_uptime=Hold uptime
+laser_uptime=Laser uptime
+both_hands=Both hands
+both_hands_hover=Notes hit while the other hand is busy with a note, hold or laser
+hand_balance=Hand balance
+hand_balance_value={$left} / {$right} ({$share} left)
+one_hand_trills=One Hand Trills
+no_one_hand_trills=No one hand trills found
+one_hand_trill=Measure {$measure}: {$side} hand, {$notes} notes
+mirror=Mirror
+mirror_hover=Flips the selection horizontally, or the whole chart when nothing is selected
+mirror_selection=Mirror Selection
+mirror_chart=Mirror Chart
+wide_laser=Wide {$side} Laser (2x)
+set_wide_laser=Set {$side} Laser Width
+playtest=Playtest
+playtest_hover=Opens the chart in the game, paused at the cursor
+game_path=Game executable
+game_path_unset=Not set
+playtest_no_game=Set the game executable in the preferences to playtest
+playtest_not_saved=Save the chart to playtest it
+max_chain=Max Chain
+max_ex_score=Max EX Score
+lane_toggle_marker=Lanes
+shake_marker=Shake
+recover_autosave=Recover Autosave
+recover_autosave_prompt=An autosave from {$time} is newer than the saved chart. Recover it?
+recover=Recover
+discard=Discard
+split_view=Split View
+split_view_hover=Shows a second view of the chart below, with its own scroll position and zoom
+close_split_view=Close
+import_midi_menu=Import MIDI...
+import_midi=Import MIDI
+midi_quantize=Quantize
+midi_notes={$count} notes, {$unmapped} on keys not mapped to a lane
+difficulties=Difficulties
+difficulties_hover=Lists the charts in the folder of the open chart and switches between them
+difficulties_unsaved=Save the chart to list the other difficulties in its folder
+difficulty_entry={$difficulty} {$level} - {$file}
+copy_song_info=Copy Song Info
+copy_song_info_hover=Copies the title, artist and music settings of this chart to the other difficulties
+waveform=Waveform
+waveform_hover=Shows the waveform of the chart's audio behind the track, the first time a song is opened it takes a moment to read
+patterns=Patterns
+patterns_hover=Library of saved note and laser patterns that can be inserted at the cursor in any chart
+save_pattern=Save as Pattern
+pattern_name=Name
+pattern_tags=Tags
+pattern_tags_hover=Separated by commas
+filter=Filter
+pattern_quantize=Snap to
+no_patterns=Select notes and use Save as Pattern to add patterns
+pattern_length={$beats} beats
+insert=Insert
+remove=Remove
+insert_pattern=Insert Pattern
+playback_sounds=Playback Sounds
+playback_sounds_hover=Sounds played along with the chart during playback, changes are heard the next time playback starts
+metronome_volume=Metronome
+chip_volume=BT/FX Clap
+slam_volume=Laser Slam
+set_laser_curve=Set {$side} Laser Curve
+laser_curve=Laser Curve
+curve_time=Control point time
+curve_value=Control point value
+linear_curve=Linear
@@ -16,6 +16,7 @@ open=Öppna
 save=Spara
 save_as=Spara som
 export_ksh=Exportera Ksh
+export_ksh_legacy=Exportera Ksh (KSM 1.60)
 preferences=Inställningar
 exit=Avsluta
 edit=Redigera
@@ -63,4 +64,141 @@ preview_offset=Förhandsgranskningsförskjutning
 preview_duration=Förhandsgranskningslängd
 left=Vänster
 right=Höger
-effect_definitions=Effekt Definitioner
\ No newline at end of file
+effect_definitions=Effekt Definitioner
+follow_mode=Uppspelningsföljning
+follow_none=Ingen
+follow_page=Sida
+follow_centered=Centrerad
+font_scale=Textstorlek
+pretty_kson=Indentera sparade .kson filer
+untitled=Namnlös
+playing_measure=Takt {$measure}
+comments=Kommentarer
+add_comment=Lägg till kommentar
+edit_comment=Redigera kommentar
+remove_comment=Ta bort kommentar
+go_to=Gå till
+author_name=Författarnamn
+preview_file=Förhandsgranskningsfil:
+import_timing_menu=Importera timing...
+import_timing=Importera timing
+import_timing_warning={$count} noter kommer spelas vid en annan tid i ljudet med den importerade timingen. Importera ändå?
+notes_outside_audio={$count} noter utanför ljudet
+notes_outside_audio_hover=Dessa noter ligger innan ljudet börjar eller efter att det slutar och kommer spelas i tystnad.
+append_chart_menu=Lägg till chart...
+append_chart=Lägg till chart
+cut_before_measure=Ta bort allt före denna takt
+cut_after_measure=Ta bort allt efter denna takt
+chip_sound=Chipljud
+remove_chip_sound=Ta bort chipljud
+preview=Förhandsgranska
+save_chart_first=Spara chartet först så att ljud kan kopieras till dess mapp.
+save_failed=Kunde inte spara chartet
+lock_timing=Lås timing
+lock_timing_hover=Förhindrar ändringar av BPM, taktarter och ljudförskjutning i detta chart
+timing_locked=Timing är låst
+playback_rate=Hastighet
+playback_rate_hover=Spelar låten långsammare utan att ändra tonhöjden, gäller från nästa gång uppspelningen startar
+restore_backup=Återställ säkerhetskopia
+restore_backup_menu=Återställ från säkerhetskopia...
+no_backups=Inga säkerhetskopior av detta chart
+backup_count=Säkerhetskopior per chart
+backup_folder=Mapp för säkerhetskopior
+backup_folder_default=Bredvid chartet
+visual_metronome=Metronom
+visual_metronome_hover=Blinkar kanterna av chartet på varje taktslag under uppspelning, starkare på taktens första slag, och visar aktuell takt och taktslag
+fx_audio_files=FX-ljudfiler:
+fx_audio_files_hover=Förrenderat ljud med chartets effekter, spelas i stället för live-effekter medan FX-knapparna hålls ned. Skrivs efter huvudfilen i KSH (m=main.ogg;main_f.ogg)
+import_audio_files=Importera ljudfiler...
+chart_issues=Problem i chartet
+chart_issues_hover=Listar misstag i chartet som överlappande noter och lasrar utanför banan
+no_chart_issues=Inga problem hittades
+laser_lane={$side} laser
+issue_overlapping_notes=Överlappande noter på {$lane}
+issue_laser_out_of_range={$lane} är utanför banan
+issue_empty_laser_section={$lane}-sektionen har färre än två punkter
+issue_zero_bpm=BPM är noll
+issue_missing_time_signature=Ingen taktart i första takten
+issue_note_past_audio_end={$lane}-not fortsätter efter ljudets slut
+bgm_volume=BGM
+bgm_volume_hover=Volym för låten utanför effekter, klicka för att tysta
+fx_volume=FX
+fx_volume_hover=Volym för låten med effekter, klicka för att tysta och höra låten utan effekter
+copy=Kopiera
+cut=Klipp ut
+cut_selection=Klipp ut markering
+paste=Klistra in
+paste_conflict=Inklistringskonflikt
+paste_conflict_warning={$count} noter i charten överlappar de inklistrade noterna.
+paste_overwrite=Skriv över
+paste_keep_existing=Behåll befintliga
+delete_selection=Ta bort markering
+chart_stats=Chartstatistik
+chart_stats_hover=Visar hur mycket av chartet som består av hållna noter och hur noterna fördelas mellan händerna
+hold_uptime=Hålltid
+laser_uptime=Lasertid
+both_hands=Båda händerna
+both_hands_hover=Noter som slås medan den andra handen är upptagen med en not, hållning eller laser
+hand_balance=Handbalans
+hand_balance_value={$left} / {$right} ({$share} vänster)
+one_hand_trills=Enhandsdrillar
+no_one_hand_trills=Inga enhandsdrillar hittades
+one_hand_trill=Takt {$measure}: {$side} hand, {$notes} noter
+mirror=Spegla
+mirror_hover=Vänder markeringen horisontellt, eller hela chartet när inget är markerat
+mirror_selection=Spegla markering
+mirror_chart=Spegla chart
+wide_laser=Bred {$side} laser (2x)
+set_wide_laser=Ändra {$side} laserbredd
+playtest=Testspela
+playtest_hover=Öppnar chartet i spelet, pausat vid markören
+game_path=Spelets programfil
+game_path_unset=Inte vald
+playtest_no_game=Välj spelets programfil i inställningarna för att testspela
+playtest_not_saved=Spara chartet för att testspela det
+max_chain=Max kedja
+max_ex_score=Max EX-poäng
+lane_toggle_marker=Filer
+shake_marker=Skak
+recover_autosave=Återställ autosparning
+recover_autosave_prompt=En autosparning från {$time} är nyare än det sparade chartet. Vill du återställa den?
+recover=Återställ
+discard=Släng
+split_view=Delad vy
+split_view_hover=Visar en andra vy av chartet nedanför, med egen scrollposition och zoom
+close_split_view=Stäng
+import_midi_menu=Importera MIDI...
+import_midi=Importera MIDI
+midi_quantize=Kvantisera
+midi_notes={$count} noter, {$unmapped} på tangenter som inte hör till något spår
+difficulties=Svårighetsgrader
+difficulties_hover=Listar charten i samma mapp som det öppna chartet och växlar mellan dem
+difficulties_unsaved=Spara chartet för att lista de andra svårighetsgraderna i dess mapp
+difficulty_entry={$difficulty} {$level} - {$file}
+copy_song_info=Kopiera låtinfo
+copy_song_info_hover=Kopierar titel, artist och musikinställningar från det här chartet till de andra svårighetsgraderna
+waveform=Vågform
+waveform_hover=Visar vågformen av chartets ljud bakom banan, första gången en låt öppnas tar det en stund att läsa in
+patterns=Mönster
+patterns_hover=Bibliotek med sparade not- och lasermönster som kan infogas vid markören i vilket chart som helst
+save_pattern=Spara som mönster
+pattern_name=Namn
+pattern_tags=Taggar
+pattern_tags_hover=Separerade med kommatecken
+filter=Filter
+pattern_quantize=Fäst till
+no_patterns=Markera noter och använd Spara som mönster för att lägga till mönster
+pattern_length={$beats} taktslag
+insert=Infoga
+remove=Ta bort
+insert_pattern=Infoga mönster
+playback_sounds=Uppspelningsljud
+playback_sounds_hover=Ljud som spelas tillsammans med chartet under uppspelning, ändringar hörs nästa gång uppspelningen startar
+metronome_volume=Metronom
+chip_volume=BT/FX-klapp
+slam_volume=Laserslag
+set_laser_curve=Ställ in {$side} laserkurva
+laser_curve=Laserkurva
+curve_time=Kontrollpunktens tid
+curve_value=Kontrollpunktens värde
+linear_curve=Linjär
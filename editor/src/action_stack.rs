@@ -86,10 +86,16 @@ where
     }
 
     pub fn save(&mut self) {
-        match self.undo_stack.last() {
-            Some(a) => self.saved = Some(a.id),
-            None => self.saved = None,
-        }
+        self.saved = self.save_point();
+    }
+
+    /// Identifies the current state, for marking it as saved once a save has finished.
+    pub fn save_point(&self) -> Option<u32> {
+        self.undo_stack.last().map(|a| a.id)
+    }
+
+    pub fn saved_at(&mut self, save_point: Option<u32>) {
+        self.saved = save_point;
     }
 
     pub fn saved(&self) -> bool {
@@ -0,0 +1,192 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::bail;
+use eframe::egui::{self, RichText};
+use kson::{Annotation, Chart};
+
+use crate::{
+    chart_editor::MainState,
+    i18n::{self, fl},
+    DialogShortcuts,
+};
+
+/// A comment being written, `original` is `None` when it is new.
+#[derive(Debug, Clone)]
+pub struct AnnotationEdit {
+    pub original: Option<Annotation>,
+    pub annotation: Annotation,
+}
+
+impl AnnotationEdit {
+    pub fn new(y: u32, author: String) -> Self {
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .unwrap_or_default();
+
+        Self {
+            original: None,
+            annotation: Annotation {
+                y,
+                text: String::new(),
+                author,
+                created,
+            },
+        }
+    }
+
+    pub fn edit(annotation: &Annotation) -> Self {
+        Self {
+            original: Some(annotation.clone()),
+            annotation: annotation.clone(),
+        }
+    }
+}
+
+pub fn format_created(created: u64) -> String {
+    chrono::DateTime::from_timestamp(created as i64, 0)
+        .map(|x| {
+            x.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
+/// Hover text for a comment marker.
+pub fn annotation_tooltip(ui: &mut egui::Ui, annotation: &Annotation) {
+    ui.label(
+        RichText::new(format!(
+            "{} {}",
+            annotation.author,
+            format_created(annotation.created)
+        ))
+        .weak(),
+    );
+    ui.label(&annotation.text);
+}
+
+fn remove_annotation(state: &mut MainState, annotation: Annotation) {
+    state
+        .actions
+        .new_action(fl!("remove_comment"), move |c: &mut Chart| {
+            c.editor.annotation.retain(|x| *x != annotation);
+            Ok(())
+        });
+}
+
+pub fn annotation_panel(state: &mut MainState) -> impl egui::Widget + '_ {
+    move |ui: &mut egui::Ui| {
+        ui.heading(i18n::fl!("comments"));
+
+        let mut jump_to = None;
+        let mut edit = None;
+        let mut remove = None;
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for annotation in &state.chart.editor.annotation {
+                ui.separator();
+                let measure = state.chart.tick_to_measure(annotation.y) + 1;
+                ui.horizontal(|ui| {
+                    ui.strong(fl!("playing_measure", measure = measure));
+                    ui.label(RichText::new(&annotation.author).weak());
+                    ui.label(RichText::new(format_created(annotation.created)).weak());
+                });
+                ui.label(&annotation.text);
+                ui.horizontal(|ui| {
+                    if ui.button(fl!("go_to")).clicked() {
+                        jump_to = Some(annotation.y);
+                    }
+                    if ui.button(fl!("edit")).clicked() {
+                        edit = Some(AnnotationEdit::edit(annotation));
+                    }
+                    if ui.button(fl!("remove_comment")).clicked() {
+                        remove = Some(annotation.clone());
+                    }
+                });
+            }
+        });
+
+        if let Some(tick) = jump_to {
+            state.go_to_tick(tick);
+        }
+
+        if edit.is_some() {
+            state.annotation_edit = edit;
+        }
+
+        if let Some(annotation) = remove {
+            remove_annotation(state, annotation);
+        }
+
+        ui.separator();
+        let add = ui.button(fl!("add_comment"));
+        if add.clicked() {
+            state.annotation_edit = Some(AnnotationEdit::new(
+                state.cursor_line,
+                state.author_name.clone(),
+            ));
+        }
+        add
+    }
+}
+
+/// Shows the dialog for writing a comment if one is being edited.
+pub fn annotation_dialog(
+    state: &mut MainState,
+    ctx: &egui::Context,
+    shortcuts: &mut DialogShortcuts,
+) {
+    let Some(mut edit) = state.annotation_edit.take() else {
+        return;
+    };
+    let (confirm, cancel) = shortcuts.take();
+
+    let title = if edit.original.is_some() {
+        fl!("edit_comment")
+    } else {
+        fl!("add_comment")
+    };
+
+    let mut open = true;
+    let mut done = false;
+    egui::Window::new(title)
+        .id(egui::Id::new("annotation_dialog"))
+        .open(&mut open)
+        .show(ctx, |ui| {
+            let text = ui.text_edit_multiline(&mut edit.annotation.text);
+            crate::focus_if_unfocused(ui, &text);
+            ui.add_space(10.0);
+            done = (ui.button(fl!("ok")).clicked() || confirm) && !edit.annotation.text.is_empty();
+        });
+
+    if done {
+        let AnnotationEdit {
+            original,
+            annotation,
+        } = edit;
+
+        let description = if original.is_some() {
+            fl!("edit_comment")
+        } else {
+            fl!("add_comment")
+        };
+
+        state.actions.new_action(description, move |c: &mut Chart| {
+            let annotations = &mut c.editor.annotation;
+            match &original {
+                Some(original) => {
+                    let Some(existing) = annotations.iter_mut().find(|x| **x == *original) else {
+                        bail!("Comment no longer exists")
+                    };
+                    *existing = annotation.clone();
+                }
+                None => annotations.push(annotation.clone()),
+            }
+            annotations.sort_by_key(|x| x.y);
+            Ok(())
+        });
+    } else if open && !cancel {
+        state.annotation_edit = Some(edit);
+    }
+}
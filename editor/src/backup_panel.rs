@@ -0,0 +1,246 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use eframe::egui::{self, ScrollArea};
+use kson::Chart;
+use serde::{Deserialize, Serialize};
+
+use crate::{chart_editor::MainState, i18n::fl, DialogShortcuts};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupOptions {
+    /// Backups kept per chart, 0 disables backups
+    pub count: usize,
+    /// Folder backups are written to, `None` for a `backup` folder next to the chart
+    pub folder: Option<PathBuf>,
+}
+
+impl Default for BackupOptions {
+    fn default() -> Self {
+        Self {
+            count: 5,
+            folder: None,
+        }
+    }
+}
+
+impl BackupOptions {
+    fn folder_for(&self, chart_path: &Path) -> Option<PathBuf> {
+        self.folder
+            .clone()
+            .or_else(|| chart_path.parent().map(|x| x.join("backup")))
+    }
+}
+
+/// Backups are named `<chart name>.<timestamp>.<extension>`, the timestamp sorts by age.
+fn backup_prefix(chart_path: &Path) -> Option<(String, String)> {
+    let stem = chart_path.file_stem()?.to_str()?;
+    let extension = chart_path
+        .extension()
+        .and_then(|x| x.to_str())
+        .unwrap_or("");
+    Some((format!("{stem}."), format!(".{extension}")))
+}
+
+/// Backups of the chart at `chart_path`, newest first.
+pub fn list_backups(chart_path: &Path, options: &BackupOptions) -> Vec<PathBuf> {
+    let (Some(folder), Some((prefix, suffix))) =
+        (options.folder_for(chart_path), backup_prefix(chart_path))
+    else {
+        return vec![];
+    };
+
+    let Ok(entries) = std::fs::read_dir(folder) else {
+        return vec![];
+    };
+
+    let mut backups: Vec<PathBuf> = entries
+        .filter_map(|x| x.ok())
+        .map(|x| x.path())
+        .filter(|x| {
+            x.file_name()
+                .and_then(|x| x.to_str())
+                .and_then(|x| x.strip_prefix(&prefix)?.strip_suffix(&suffix))
+                .is_some_and(|x| x.len() == 19 && x.chars().all(|c| c.is_ascii_digit() || c == '-'))
+        })
+        .collect();
+
+    backups.sort();
+    backups.reverse();
+    backups
+}
+
+/// Copies the file at `chart_path` to the backup folder before it is overwritten and removes
+/// the oldest backups beyond the configured count.
+pub fn backup(chart_path: &Path, options: &BackupOptions) -> Result<()> {
+    if options.count == 0 || !chart_path.exists() {
+        return Ok(());
+    }
+
+    let folder = options
+        .folder_for(chart_path)
+        .ok_or(anyhow!("Invalid chart path"))?;
+    let (prefix, suffix) = backup_prefix(chart_path).ok_or(anyhow!("Invalid chart path"))?;
+    let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S-%3f");
+
+    std::fs::create_dir_all(&folder)?;
+    std::fs::copy(
+        chart_path,
+        folder.join(format!("{prefix}{timestamp}{suffix}")),
+    )?;
+
+    for old in list_backups(chart_path, options).iter().skip(options.count) {
+        std::fs::remove_file(old)?;
+    }
+
+    Ok(())
+}
+
+fn backup_time(path: &Path) -> String {
+    std::fs::metadata(path)
+        .and_then(|x| x.modified())
+        .map(|x| {
+            chrono::DateTime::<chrono::Local>::from(x)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        })
+        .unwrap_or_else(|_| path.display().to_string())
+}
+
+/// Lists the backups of the open chart, picking one replaces the chart with it as an undoable
+/// action.
+pub fn backup_dialog(state: &mut MainState, ctx: &egui::Context, shortcuts: &mut DialogShortcuts) {
+    let Some(backups) = state.backup_restore.take() else {
+        return;
+    };
+    let (_, cancel) = shortcuts.take();
+
+    let mut open = true;
+    let mut picked = None;
+    egui::Window::new(fl!("restore_backup"))
+        .id(egui::Id::new("backup_dialog"))
+        .open(&mut open)
+        .show(ctx, |ui| {
+            if backups.is_empty() {
+                ui.label(fl!("no_backups"));
+                return;
+            }
+
+            ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                for backup in &backups {
+                    if ui
+                        .button(backup_time(backup))
+                        .on_hover_text(backup.display().to_string())
+                        .clicked()
+                    {
+                        picked = Some(backup.clone());
+                    }
+                }
+            });
+        });
+
+    if let Some(picked) = picked {
+        match Chart::from_file(&picked) {
+            Ok(chart) => state
+                .actions
+                .new_action(fl!("restore_backup"), move |c: &mut Chart| {
+                    *c = chart.clone();
+                    Ok(())
+                }),
+            Err(e) => log::error!("Failed to open backup {}: {e}", picked.display()),
+        }
+    } else if open && !cancel {
+        state.backup_restore = Some(backups);
+    }
+}
+
+/// Autosaves go next to the chart as `<chart name>.autosave.kson`, or in the app data folder
+/// for charts that have not been saved yet.
+pub fn autosave_path(chart_path: Option<&Path>) -> Option<PathBuf> {
+    match chart_path {
+        Some(path) => {
+            let stem = path.file_stem()?.to_str()?;
+            Some(path.with_file_name(format!("{stem}.autosave.kson")))
+        }
+        None => directories_next::ProjectDirs::from("", "", "KSON Editor")
+            .map(|x| x.data_dir().join("unsaved.autosave.kson")),
+    }
+}
+
+pub fn write_autosave(path: &Path, chart: &Chart, options: kson::KsonWriteOptions) -> Result<()> {
+    if let Some(folder) = path.parent() {
+        std::fs::create_dir_all(folder)?;
+    }
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+    chart.to_kson(&mut writer, options)?;
+    writer.into_inner().map_err(|e| e.into_error())?;
+    Ok(())
+}
+
+/// Removes the autosave of a chart once it has been saved.
+pub fn remove_autosave(chart_path: Option<&Path>) {
+    if let Some(path) = autosave_path(chart_path).filter(|x| x.exists()) {
+        if let Err(e) = std::fs::remove_file(&path) {
+            log::warn!("Failed to remove autosave {}: {e}", path.display());
+        }
+    }
+}
+
+/// The autosave of the chart at `chart_path` if it was written after the chart was saved.
+pub fn find_autosave(chart_path: Option<&Path>) -> Option<PathBuf> {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|x| x.modified()).ok();
+    let autosave = autosave_path(chart_path)?;
+    let autosave_time = modified(&autosave)?;
+    match chart_path.and_then(modified) {
+        Some(saved_time) if saved_time >= autosave_time => None,
+        _ => Some(autosave),
+    }
+}
+
+/// Offers to restore an autosave newer than the open chart, restoring is an undoable action.
+pub fn recovery_dialog(
+    state: &mut MainState,
+    ctx: &egui::Context,
+    shortcuts: &mut DialogShortcuts,
+) {
+    let Some(autosave) = state.autosave_recovery.take() else {
+        return;
+    };
+    let (_, cancel) = shortcuts.take();
+
+    let mut recover = false;
+    let mut discard = false;
+    egui::Window::new(fl!("recover_autosave"))
+        .id(egui::Id::new("recovery_dialog"))
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label(fl!(
+                "recover_autosave_prompt",
+                time = backup_time(&autosave)
+            ));
+            ui.horizontal(|ui| {
+                recover = ui.button(fl!("recover")).clicked();
+                discard = ui.button(fl!("discard")).clicked();
+            });
+        });
+
+    if recover {
+        match Chart::from_file(&autosave) {
+            Ok(chart) => state
+                .actions
+                .new_action(fl!("recover_autosave"), move |c: &mut Chart| {
+                    *c = chart.clone();
+                    Ok(())
+                }),
+            Err(e) => log::error!("Failed to open autosave {}: {e}", autosave.display()),
+        }
+    } else if discard {
+        if let Err(e) = std::fs::remove_file(&autosave) {
+            log::warn!("Failed to remove autosave {}: {e}", autosave.display());
+        }
+    } else if !cancel {
+        state.autosave_recovery = Some(autosave);
+    }
+}
@@ -1,30 +1,39 @@
+use crate::annotation_panel::{annotation_tooltip, AnnotationEdit};
+use crate::backup_panel::{self, BackupOptions};
+use crate::key_sound_panel::KeySoundEdit;
+use crate::midi_import::{MidiImport, MidiLaneMapping};
+use crate::pattern_library::{NewPattern, PatternPanel};
+use crate::project::{copy_song_meta, Project};
 use crate::tools::*;
+use crate::waveform::WaveformCache;
 use crate::*;
 use anyhow::{anyhow, bail, Result};
 
 use eframe::egui::epaint::{Mesh, Vertex, WHITE_UV};
 use eframe::egui::{
-    pos2, Align2, Color32, Context, PointerButton, Pos2, Rect, Response, Sense, Shape, Stroke,
+    pos2, vec2, Align2, Color32, Context, PointerButton, Pos2, Rect, Response, Sense, Shape,
+    Slider, Stroke,
 };
 use eframe::egui::{Painter, Rgba};
 
 use eframe::epaint::FontId;
 use egui::Ui;
 use kson::overlaps::Overlaps;
-use kson::{ByPulseOption, GraphPoint, GraphSectionPoint, Interval, Ksh, Vox, KSON_RESOLUTION};
+use kson::{ByPulseOption, GraphPoint, GraphSectionPoint, Interval, Ksh, KSON_RESOLUTION};
 use kson_music_playback as playback;
 
 use puffin::profile_scope;
 
 use rodio::OutputStream;
-use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
 use std::ffi::OsStr;
 use std::fs::File;
-use std::io::prelude::*;
-use std::io::BufReader;
+use std::io::BufWriter;
 use std::path::Path;
 use std::path::PathBuf;
-use std::time::Duration;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::time::{Duration, Instant};
 pub const EGUI_ID: &str = "chart_editor";
 
 pub struct MainState {
@@ -39,8 +48,185 @@ pub struct MainState {
     pub current_tool: ChartTool,
     pub actions: action_stack::ActionStack<kson::Chart>,
     pub screen: ScreenState,
+    /// Second view of the chart shown below the main one, scrolled and zoomed on its own
+    pub split_screen: Option<ScreenState>,
     pub audio_playback: playback::AudioPlayback,
     pub laser_colors: [Color32; 2],
+    pub kson_options: kson::KsonWriteOptions,
+    pub chart_states: HashMap<PathBuf, ChartViewState>,
+    pub author_name: String,
+    pub annotation_edit: Option<AnnotationEdit>,
+    pub timing_import: Option<TimingImport>,
+    /// Paste waiting for a choice of how to handle the notes it overlaps
+    pub paste_conflict: Option<PendingPaste>,
+    pub key_sound_edit: Option<KeySoundEdit>,
+    /// MIDI file waiting for its notes to be mapped to lanes
+    pub midi_import: Option<MidiImport>,
+    /// MIDI keys placed on each lane by MIDI imports
+    pub midi_mapping: MidiLaneMapping,
+    pub backup_options: BackupOptions,
+    /// Backups listed in the restore dialog while it is open
+    pub backup_restore: Option<Vec<PathBuf>>,
+    pub background_save: Option<BackgroundSave>,
+    /// Blocks BPM, time signature and offset edits while editing notes
+    pub timing_locked: bool,
+    /// Shown until dismissed when a background save fails
+    pub save_error: Option<String>,
+    /// Length in ms of the audio file at the path, cached so the file is only decoded once
+    pub audio_length: Option<(PathBuf, Option<f64>)>,
+    /// Flash the edges of the chart view on beats during playback
+    pub visual_metronome: bool,
+    /// Draw the waveform of the chart's audio behind the track
+    pub show_waveform: bool,
+    pub waveform: WaveformCache,
+    /// Chart slice from the last copy, pasted with its first tick at the cursor
+    pub clipboard: Option<kson::Chart>,
+    /// Game executable charts are playtested in
+    pub game_path: Option<PathBuf>,
+    /// Time and state of the chart at the last autosave
+    pub last_autosave: (Instant, Option<u32>),
+    /// Autosave newer than the open chart, offered for recovery until dismissed
+    pub autosave_recovery: Option<PathBuf>,
+    /// Other difficulties in the folder of the open chart
+    pub project: Option<Project>,
+    pub patterns: PatternPanel,
+    /// Laser segment whose curve is being set in the curve dialog
+    pub laser_curve_edit: Option<LaserCurveEdit>,
+}
+
+/// Time between autosaves while the chart has unsaved changes
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Beat line most recently passed during playback.
+pub struct MetronomeBeat {
+    /// Counted from 1
+    pub measure: u32,
+    /// Beat within the measure, counted from 1
+    pub beat: u32,
+    pub is_measure: bool,
+    /// How far playback is from this beat line to the next, from 0 to 1
+    pub progress: f64,
+}
+
+/// A save running on a worker thread.
+pub struct BackgroundSave {
+    result: Receiver<Result<()>>,
+    save_point: Option<u32>,
+    path: PathBuf,
+}
+
+/// BPM, time signatures and audio offset read from another chart.
+pub struct TimingImport {
+    pub bpm: kson::ByPulse<f64>,
+    pub time_sig: kson::ByMeasureIdx<kson::TimeSignature>,
+    pub offset: i32,
+    /// Number of notes that would be heard at a different point in the audio
+    pub shifted_notes: usize,
+}
+
+impl TimingImport {
+    pub fn new(current: &kson::Chart, source: &kson::Chart) -> Self {
+        let mut imported = current.clone();
+        imported.beat.bpm = source.beat.bpm.clone();
+        imported.beat.time_sig = source.beat.time_sig.clone();
+        imported.audio.bgm.offset = source.audio.bgm.offset;
+
+        let note = &current.note;
+        let shifted_notes = note
+            .bt
+            .iter()
+            .flatten()
+            .chain(note.fx.iter().flatten())
+            .map(|x| x.y)
+            .chain(note.laser.iter().flatten().map(|x| x.tick()))
+            .filter(|tick| {
+                let before = current.tick_to_ms(*tick) - current.audio.bgm.offset as f64;
+                let after = imported.tick_to_ms(*tick) - imported.audio.bgm.offset as f64;
+                (before - after).abs() >= 1.0
+            })
+            .count();
+
+        Self {
+            bpm: imported.beat.bpm,
+            time_sig: imported.beat.time_sig,
+            offset: imported.audio.bgm.offset,
+            shifted_notes,
+        }
+    }
+
+    pub fn apply(self, actions: &mut action_stack::ActionStack<kson::Chart>) {
+        let TimingImport {
+            bpm,
+            time_sig,
+            offset,
+            ..
+        } = self;
+
+        actions.new_action(fl!("import_timing"), move |c| {
+            c.beat.bpm = bpm.clone();
+            c.beat.time_sig = time_sig.clone();
+            c.audio.bgm.offset = offset;
+            Ok(())
+        });
+    }
+}
+
+/// Clipboard paste overlapping notes already in the chart.
+pub struct PendingPaste {
+    pub clip: kson::Chart,
+    pub tick: u32,
+    /// Number of notes in the chart the paste overlaps
+    pub conflicts: usize,
+    /// Name of the undo step
+    pub action: String,
+}
+
+impl PendingPaste {
+    pub fn apply(
+        self,
+        actions: &mut action_stack::ActionStack<kson::Chart>,
+        conflict: kson::PasteConflict,
+    ) {
+        let PendingPaste {
+            clip, tick, action, ..
+        } = self;
+        actions.new_action(action, move |c| {
+            c.paste_resolving(&clip, tick, conflict);
+            Ok(())
+        });
+    }
+}
+
+/// Editor state remembered per chart file, restored when the chart is opened again.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ChartViewState {
+    pub cursor_line: u32,
+    pub x_offset: f32,
+    pub beats_per_col: u32,
+    pub tool: ChartTool,
+    #[serde(default)]
+    pub timing_locked: bool,
+}
+
+/// How the view follows the playhead during playback.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FollowMode {
+    None,
+    /// Jump a full column when the playhead leaves the view
+    #[default]
+    Page,
+    /// Scroll continuously with the playhead in the middle of the view
+    Centered,
+}
+
+impl FollowMode {
+    pub fn next(self) -> Self {
+        match self {
+            FollowMode::None => FollowMode::Page,
+            FollowMode::Page => FollowMode::Centered,
+            FollowMode::Centered => FollowMode::None,
+        }
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -58,6 +244,7 @@ pub struct ScreenState {
     pub x_offset_target: f32,
     pub beat_res: u32,
     pub curve_per_tick: f32,
+    pub follow_mode: FollowMode,
 }
 
 type MakeVertFn = Box<dyn Fn(&[f32; 3]) -> Vertex>;
@@ -421,6 +608,27 @@ impl ScreenState {
         }
     }
 
+    pub fn follow(&mut self, tick: f64) {
+        let col = tick / self.ticks_per_col() as f64;
+        match self.follow_mode {
+            FollowMode::None => {}
+            FollowMode::Page => {
+                let col_x = col.floor() as f32 * self.track_spacing();
+                let visible_width = self.w - (self.w % self.track_spacing());
+                if col_x < self.x_offset_target
+                    || col_x + self.track_spacing() > self.x_offset_target + visible_width
+                {
+                    self.x_offset_target = col_x;
+                }
+            }
+            FollowMode::Centered => {
+                let x = col as f32 * self.track_spacing() + self.track_width;
+                self.x_offset_target = (x - self.w / 2.0).max(0.0);
+                self.x_offset = self.x_offset_target;
+            }
+        }
+    }
+
     pub fn get_control_point_pos_section(
         &self,
         points: &[GraphSectionPoint],
@@ -555,6 +763,8 @@ impl MainState {
             (c, None)
         };
 
+        let project = save_path.as_deref().and_then(Project::scan);
+
         MainState {
             chart: new_chart.clone(),
             screen: ScreenState {
@@ -571,6 +781,7 @@ impl MainState {
                 x_offset_target: 0.0,
                 beat_res: 48,
                 curve_per_tick: 1.5,
+                follow_mode: FollowMode::default(),
             },
             gui_event_queue: VecDeque::new(),
             save_path,
@@ -587,7 +798,336 @@ impl MainState {
                 Color32::from_rgba_unmultiplied(194, 6, 140, 127),
             ],
             audio_out: None,
+            kson_options: kson::KsonWriteOptions {
+                sort_keys: true,
+                pretty: false,
+            },
+            chart_states: HashMap::new(),
+            author_name: String::new(),
+            annotation_edit: None,
+            timing_import: None,
+            paste_conflict: None,
+            key_sound_edit: None,
+            midi_import: None,
+            midi_mapping: MidiLaneMapping::default(),
+            backup_options: BackupOptions::default(),
+            backup_restore: None,
+            background_save: None,
+            timing_locked: false,
+            save_error: None,
+            audio_length: None,
+            visual_metronome: false,
+            show_waveform: true,
+            waveform: WaveformCache::default(),
+            clipboard: None,
+            game_path: None,
+            last_autosave: (Instant::now(), None),
+            autosave_recovery: None,
+            split_screen: None,
+            project,
+            patterns: PatternPanel::default(),
+            laser_curve_edit: None,
+        }
+    }
+
+    /// Moves the cursor to `tick` and scrolls its column into view.
+    pub fn go_to_tick(&mut self, tick: u32) {
+        self.cursor_line = tick;
+        let target = self.screen.tick_to_pos(tick).0 + self.screen.x_offset;
+        self.screen.x_offset_target = (target - (target % self.screen.track_spacing())).max(0.0);
+    }
+
+    /// Copies the selected slice of the chart to the clipboard, also as KSON text to the system
+    /// clipboard so it can be pasted in other editor windows.
+    fn copy_selection(&mut self, ctx: &Context) -> Option<Selection> {
+        let selection = self.cursor_object.as_ref()?.selection()?;
+        let clip = self
+            .chart
+            .copy_range(selection.start, selection.end, selection.lanes);
+
+        let mut text = vec![];
+        match clip.to_kson(&mut text, kson::KsonWriteOptions::default()) {
+            Ok(()) => ctx.output_mut(|o| o.copied_text = String::from_utf8_lossy(&text).into()),
+            Err(e) => log::warn!("Failed to serialize clipboard: {e}"),
+        }
+
+        self.clipboard = Some(clip);
+        Some(selection)
+    }
+
+    /// Pastes `clip` with its first tick at the cursor, asking how to resolve overlapping notes.
+    pub fn paste_at_cursor(&mut self, clip: kson::Chart, action: String) {
+        let tick = self.cursor_line;
+        let conflicts = self.chart.paste_conflicts(&clip, tick);
+        if conflicts == 0 {
+            self.actions.new_action(action, move |c: &mut kson::Chart| {
+                c.paste(&clip, tick);
+                Ok(())
+            });
+        } else {
+            self.paste_conflict = Some(PendingPaste {
+                clip,
+                tick,
+                conflicts,
+                action,
+            });
+        }
+    }
+
+    /// Remembers the view state of the currently open chart.
+    pub fn store_view_state(&mut self) {
+        let Some(path) = self.save_path.clone() else {
+            return;
+        };
+
+        self.chart_states.insert(
+            path,
+            ChartViewState {
+                cursor_line: self.cursor_line,
+                x_offset: self.screen.x_offset_target,
+                beats_per_col: self.screen.beats_per_col,
+                tool: self.current_tool,
+                timing_locked: self.timing_locked,
+            },
+        );
+    }
+
+    /// Restores the view state of the currently open chart if it has been opened before.
+    pub fn restore_view_state(&mut self) {
+        let Some(state) = self
+            .save_path
+            .as_ref()
+            .and_then(|path| self.chart_states.get(path))
+            .copied()
+        else {
+            self.timing_locked = false;
+            return;
+        };
+
+        self.timing_locked = state.timing_locked;
+        self.cursor_line = state.cursor_line;
+        self.screen.x_offset = state.x_offset;
+        self.screen.x_offset_target = state.x_offset;
+        self.screen.beats_per_col = state.beats_per_col;
+        self.gui_event_queue
+            .push_back(GuiEvent::ToolChanged(state.tool));
+    }
+
+    /// Lists the difficulties in the folder of the open chart, keeping the edits of the ones
+    /// already open when the folder is the same.
+    pub fn refresh_project(&mut self) {
+        let mut project = self.save_path.as_deref().and_then(Project::scan);
+        if let (Some(project), Some(old)) = (&mut project, self.project.take()) {
+            if old.folder == project.folder {
+                for old_chart in old.charts {
+                    if let Some(chart) =
+                        project.charts.iter_mut().find(|x| x.path == old_chart.path)
+                    {
+                        chart.actions = old_chart.actions;
+                    }
+                }
+            }
+        }
+        self.project = project;
+    }
+
+    /// Switches to another difficulty of the song, keeping the edits of both.
+    pub fn switch_difficulty(&mut self, path: &Path) -> Result<()> {
+        if self.save_path.as_deref() == Some(path) {
+            return Ok(());
+        }
+        let project = self
+            .project
+            .as_mut()
+            .ok_or_else(|| anyhow!("No song folder open"))?;
+        let index = project
+            .charts
+            .iter()
+            .position(|x| x.path == path)
+            .ok_or_else(|| anyhow!("{} is not a difficulty of the song", path.display()))?;
+        // Read first so a broken chart leaves the open one as it was
+        let chart = project.charts[index].actions()?.get_current()?;
+
+        self.finish_background_save(true);
+        self.store_view_state();
+        if self.audio_playback.is_playing() {
+            self.audio_playback.stop();
+            drop(self.audio_out.take());
+        }
+
+        let project = self.project.as_mut().expect("Checked above");
+        let actions = project.charts[index].actions.take().expect("Read above");
+        let previous = std::mem::replace(&mut self.actions, actions);
+        if let Some(current) = project
+            .charts
+            .iter_mut()
+            .find(|x| self.save_path.as_ref() == Some(&x.path))
+        {
+            current.difficulty = self.chart.meta.difficulty;
+            current.level = self.chart.meta.level;
+            current.actions = Some(previous);
+        }
+
+        self.chart = chart;
+        self.save_path = Some(path.to_path_buf());
+        self.autosave_recovery = backup_panel::find_autosave(self.save_path.as_deref());
+        self.restore_view_state();
+        Ok(())
+    }
+
+    /// Copies the song metadata and music settings of the open chart to the other
+    /// difficulties, as an edit of each that can be undone there.
+    pub fn copy_song_info(&mut self) -> Result<()> {
+        let Some(project) = &mut self.project else {
+            return Ok(());
+        };
+
+        for chart in &mut project.charts {
+            if self.save_path.as_ref() == Some(&chart.path) {
+                continue;
+            }
+            let meta = self.chart.meta.clone();
+            let bgm = self.chart.audio.bgm.clone();
+            chart
+                .actions()?
+                .new_action(fl!("copy_song_info"), move |c: &mut kson::Chart| {
+                    copy_song_meta(&meta, &mut c.meta);
+                    c.audio.bgm = bgm.clone();
+                    Ok(())
+                });
         }
+        Ok(())
+    }
+
+    /// Whether the open chart and all other difficulties are saved.
+    pub fn all_saved(&self) -> bool {
+        self.actions.saved() && !self.project.as_ref().is_some_and(Project::unsaved)
+    }
+
+    /// Saves the other difficulties with unsaved changes, then the open chart.
+    pub fn save_all(&mut self) -> Result<bool> {
+        if let Some(project) = &mut self.project {
+            for chart in &mut project.charts {
+                let Some(actions) = chart.actions.as_mut().filter(|x| !x.saved()) else {
+                    continue;
+                };
+                write_chart(
+                    &chart.path,
+                    &actions.get_current()?,
+                    self.kson_options,
+                    &self.backup_options,
+                )?;
+                backup_panel::remove_autosave(Some(chart.path.as_path()));
+                actions.save();
+            }
+        }
+        self.save()
+    }
+
+    /// Writes the chart to a temporary file and opens it in the game, paused at the cursor.
+    /// Paths to files in the chart folder are made absolute so the game finds them.
+    fn playtest(&self) -> Result<()> {
+        let game_path = self
+            .game_path
+            .as_ref()
+            .ok_or(anyhow!("No game executable set"))?;
+        let folder = self
+            .save_path
+            .as_ref()
+            .and_then(|x| x.parent())
+            .ok_or(anyhow!("Save the chart before playtesting"))?;
+
+        let mut chart = self.chart.clone();
+        let absolute = |filename: &mut String| {
+            if !filename.is_empty() {
+                *filename = folder
+                    .join(filename.as_str())
+                    .to_string_lossy()
+                    .into_owned();
+            }
+        };
+        absolute(&mut chart.audio.bgm.filename);
+        chart
+            .audio
+            .bgm
+            .legacy
+            .fp_filenames
+            .iter_mut()
+            .for_each(absolute);
+        absolute(&mut chart.meta.jacket_filename);
+
+        let path = std::env::temp_dir().join("kson_editor_playtest.kson");
+        let mut writer = BufWriter::new(File::create(&path)?);
+        chart.to_kson(&mut writer, self.kson_options)?;
+        writer.into_inner().map_err(|e| e.into_error())?;
+
+        std::process::Command::new(game_path)
+            .arg(&path)
+            .arg("--practice-tick")
+            .arg(self.cursor_line.to_string())
+            .spawn()?;
+        Ok(())
+    }
+
+    fn bgm_path(&self) -> Option<PathBuf> {
+        let folder = self.save_path.as_ref()?.parent()?;
+        let filename = self.chart.audio.bgm.filename.split(';').next()?;
+        (!filename.is_empty()).then(|| folder.join(filename))
+    }
+
+    /// Reads the length of the chart's audio when the audio file changes.
+    fn update_audio_length(&mut self) {
+        let path = self.bgm_path();
+        if self.audio_length.as_ref().map(|x| &x.0) == path.as_ref() {
+            return;
+        }
+
+        self.audio_length = path.map(|path| {
+            let length = audio_length_ms(&path);
+            if length.is_none() {
+                log::warn!("Could not read audio length of {}", path.display());
+            }
+            (path, length)
+        });
+    }
+
+    /// Tick range where the chart has audio, `None` if the audio length is unknown.
+    pub fn audio_tick_range(&self) -> Option<(u32, u32)> {
+        let length = self.audio_length.as_ref()?.1?;
+        let offset = self.chart.audio.bgm.offset as f64;
+        Some((
+            self.chart.ms_to_tick(-offset),
+            self.chart.ms_to_tick(length - offset),
+        ))
+    }
+
+    /// Number of notes that start before or end after the audio.
+    pub fn notes_outside_audio(&self) -> usize {
+        let Some((start, end)) = self.audio_tick_range() else {
+            return 0;
+        };
+        let outside = |y: u32, l: u32| y < start || y + l > end;
+
+        let notes = self
+            .chart
+            .note
+            .bt
+            .iter()
+            .chain(self.chart.note.fx.iter())
+            .flatten()
+            .filter(|n| outside(n.y, n.l))
+            .count();
+
+        let lasers = self
+            .chart
+            .note
+            .laser
+            .iter()
+            .flatten()
+            .filter(|s| outside(s.tick(), s.last().map(|p| p.ry).unwrap_or_default()))
+            .count();
+
+        notes + lasers
     }
 
     #[allow(unused)]
@@ -620,6 +1160,69 @@ impl MainState {
         }
     }
 
+    pub fn metronome_beat(&self, tick: f64) -> Option<MetronomeBeat> {
+        let mut last: Option<MetronomeBeat> = None;
+        let mut last_tick = 0;
+
+        for (line, is_measure) in self.chart.beat_line_iter() {
+            if line as f64 > tick {
+                return last.map(|beat| MetronomeBeat {
+                    progress: (tick - last_tick as f64) / (line - last_tick) as f64,
+                    ..beat
+                });
+            }
+
+            let (measure, beat) = match &last {
+                Some(last) if !is_measure => (last.measure, last.beat + 1),
+                Some(last) => (last.measure + 1, 1),
+                None => (1, 1),
+            };
+            last = Some(MetronomeBeat {
+                measure,
+                beat,
+                is_measure,
+                progress: 0.0,
+            });
+            last_tick = line;
+        }
+
+        None
+    }
+
+    /// Flashes the edges of `rect` on every beat, stronger on measures, and shows the current
+    /// measure and beat.
+    fn draw_metronome(&self, painter: &Painter, rect: Rect) {
+        if !self.visual_metronome || !self.audio_playback.is_playing() {
+            return;
+        }
+
+        let Some(beat) = self.metronome_beat(self.audio_playback.get_tick(&self.chart)) else {
+            return;
+        };
+
+        let (color, width) = if beat.is_measure {
+            (Color32::YELLOW, 12.0)
+        } else {
+            (Color32::WHITE, 6.0)
+        };
+        let strength = (1.0 - beat.progress * 2.0).max(0.0) as f32;
+        if strength > 0.0 {
+            painter.rect_stroke(
+                rect.shrink(width / 2.0),
+                0.0,
+                Stroke::new(width, color.gamma_multiply(strength)),
+            );
+        }
+
+        painter.text(
+            pos2(rect.right() - 20.0, rect.top() + 20.0),
+            Align2::RIGHT_TOP,
+            format!("{}:{}", beat.measure, beat.beat),
+            FontId::monospace(24.0),
+            Color32::WHITE,
+        );
+    }
+
     pub fn draw_cursor_line(&self, painter: &Painter, tick: u32, color: Color32) {
         let (x, y) = self.screen.tick_to_pos(tick);
         let x = x + self.screen.track_width / 2.0;
@@ -721,30 +1324,131 @@ impl MainState {
     pub fn save(&mut self) -> Result<bool> {
         match (&self.save_path, self.actions.get_current()) {
             (None, Ok(chart)) => {
-                if let Some(new_path) = save_chart_as(&chart).unwrap_or_else(|e| {
-                    println!("Failed to save chart:");
-                    println!("\t{}", e);
-                    None
-                }) {
+                if let Some(new_path) =
+                    save_chart_as(&chart, self.kson_options, &self.backup_options).unwrap_or_else(
+                        |e| {
+                            println!("Failed to save chart:");
+                            println!("\t{}", e);
+                            None
+                        },
+                    )
+                {
+                    backup_panel::remove_autosave(None);
                     self.save_path = Some(new_path);
                     self.actions.save();
+                    self.store_view_state();
+                    self.refresh_project();
                     Ok(true)
                 } else {
                     Ok(false)
                 }
             }
             (Some(path), Ok(chart)) => {
-                let mut file = File::create(path)?;
-                profile_scope!("Write kson");
-                file.write_all(serde_json::to_string(&chart)?.as_bytes())?;
+                self.finish_background_save(true);
+                write_chart(path, &chart, self.kson_options, &self.backup_options)?;
+                backup_panel::remove_autosave(Some(path.as_path()));
                 self.actions.save();
+                self.store_view_state();
                 Ok(true)
             }
             _ => bail!("Could not save chart."),
         }
     }
 
+    /// Saves to the current path on a worker thread so large charts don't stall the UI,
+    /// falls back to [`Self::save`] when the chart has not been saved before.
+    pub fn save_in_background(&mut self) -> Result<()> {
+        let Some(path) = self.save_path.clone() else {
+            self.save()?;
+            return Ok(());
+        };
+
+        // Only one save writes to the file at a time
+        self.finish_background_save(true);
+
+        let chart = self.actions.get_current()?;
+        let options = self.kson_options;
+        let backup_options = self.backup_options.clone();
+        let (tx, rx) = channel();
+        let thread_path = path.clone();
+        std::thread::spawn(move || {
+            _ = tx.send(write_chart(&thread_path, &chart, options, &backup_options));
+        });
+
+        self.background_save = Some(BackgroundSave {
+            result: rx,
+            save_point: self.actions.save_point(),
+            path,
+        });
+        Ok(())
+    }
+
+    /// Marks the chart as saved once the background save is done, `wait` blocks until then.
+    fn finish_background_save(&mut self, wait: bool) {
+        let Some(save) = &self.background_save else {
+            return;
+        };
+
+        let result = if wait {
+            save.result.recv().map_err(|_| TryRecvError::Disconnected)
+        } else {
+            save.result.try_recv()
+        };
+
+        let result = match result {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Err(anyhow!("Save was interrupted")),
+        };
+
+        let Some(save) = self.background_save.take() else {
+            return;
+        };
+
+        match result {
+            Ok(()) if self.save_path.as_ref() == Some(&save.path) => {
+                backup_panel::remove_autosave(Some(save.path.as_path()));
+                self.actions.saved_at(save.save_point);
+                self.store_view_state();
+            }
+            Ok(()) => {}
+            Err(e) => {
+                log::error!("Failed to save {}: {e}", save.path.display());
+                self.save_error = Some(e.to_string());
+            }
+        }
+    }
+
+    /// Writes the chart to its autosave on a worker thread while it has changes that are
+    /// neither saved nor autosaved.
+    fn autosave(&mut self) {
+        let save_point = self.actions.save_point();
+        let (last_time, last_save_point) = self.last_autosave;
+        if self.actions.saved()
+            || last_save_point == save_point
+            || last_time.elapsed() < AUTOSAVE_INTERVAL
+        {
+            return;
+        }
+
+        self.last_autosave = (Instant::now(), save_point);
+        let Some(path) = backup_panel::autosave_path(self.save_path.as_deref()) else {
+            return;
+        };
+        let Ok(chart) = self.actions.get_current() else {
+            return;
+        };
+        let options = self.kson_options;
+        std::thread::spawn(move || {
+            if let Err(e) = backup_panel::write_autosave(&path, &chart, options) {
+                log::warn!("Failed to autosave {}: {e}", path.display());
+            }
+        });
+    }
+
     pub fn update(&mut self, ctx: &Context) -> Result<()> {
+        self.finish_background_save(false);
+        self.autosave();
         while let Some(e) = self.gui_event_queue.pop_front() {
             match e {
                 GuiEvent::Open => {
@@ -753,26 +1457,90 @@ impl MainState {
                         println!("\t{}", e);
                         None
                     }) {
-                        self.chart = new_chart.0.clone();
-                        self.actions.reset(new_chart.0);
-                        self.save_path = Some(new_chart.1);
+                        // Difficulties of the open song keep their edits
+                        if self
+                            .project
+                            .as_ref()
+                            .is_some_and(|x| x.contains(&new_chart.1))
+                        {
+                            if let Err(e) = self.switch_difficulty(&new_chart.1) {
+                                println!("Failed to open chart:");
+                                println!("\t{}", e);
+                            }
+                        } else {
+                            self.store_view_state();
+                            self.chart = new_chart.0.clone();
+                            self.actions.reset(new_chart.0);
+                            self.save_path = Some(new_chart.1);
+                            self.autosave_recovery =
+                                backup_panel::find_autosave(self.save_path.as_deref());
+                            self.restore_view_state();
+                            self.refresh_project();
+                        }
+                    }
+                }
+                GuiEvent::ImportTiming if self.timing_locked => {}
+                GuiEvent::ImportTiming => {
+                    if let Some((source, _)) = open_chart().unwrap_or_else(|e| {
+                        println!("Failed to open chart:");
+                        println!("\t{}", e);
+                        None
+                    }) {
+                        let import = TimingImport::new(&self.chart, &source);
+                        if import.shifted_notes == 0 {
+                            import.apply(&mut self.actions);
+                        } else {
+                            self.timing_import = Some(import);
+                        }
+                    }
+                }
+                GuiEvent::ImportMidi => {
+                    if let Ok(nfd::Response::Okay(path)) =
+                        nfd::open_file_dialog(Some("mid,midi"), None)
+                    {
+                        match MidiImport::open(Path::new(&path)) {
+                            Ok(import) => self.midi_import = Some(import),
+                            Err(e) => {
+                                println!("Failed to open MIDI file:");
+                                println!("\t{}", e);
+                            }
+                        }
+                    }
+                }
+                GuiEvent::AppendChart => {
+                    if let Some((other, _)) = open_chart().unwrap_or_else(|e| {
+                        println!("Failed to open chart:");
+                        println!("\t{}", e);
+                        None
+                    }) {
+                        self.actions
+                            .new_action(fl!("append_chart"), move |c: &mut kson::Chart| {
+                                c.append(&other, 1);
+                                Ok(())
+                            });
                     }
                 }
                 GuiEvent::Save => {
-                    self.save()?;
+                    self.save_in_background()?;
                 }
                 GuiEvent::SaveAs => {
                     if let Ok(chart) = self.actions.get_current() {
-                        if let Some(new_path) = save_chart_as(&chart).unwrap_or_else(|e| {
-                            println!("Failed to save chart:");
-                            println!("\t{}", e);
-                            None
-                        }) {
+                        if let Some(new_path) =
+                            save_chart_as(&chart, self.kson_options, &self.backup_options)
+                                .unwrap_or_else(|e| {
+                                    println!("Failed to save chart:");
+                                    println!("\t{}", e);
+                                    None
+                                })
+                        {
                             self.save_path = Some(new_path);
                             self.actions.save();
+                            self.refresh_project();
                         }
                     }
                 }
+                GuiEvent::ToolChanged(ChartTool::BPM | ChartTool::TimeSig)
+                    if self.timing_locked => {}
                 GuiEvent::ToolChanged(new_tool) => {
                     if self.current_tool != new_tool {
                         self.cursor_object = match new_tool {
@@ -784,11 +1552,86 @@ impl MainState {
                             ChartTool::BPM => Some(Box::new(BpmTool::new())),
                             ChartTool::TimeSig => Some(Box::new(TimeSigTool::new())),
                             ChartTool::Camera => Some(Box::<CameraTool>::default()),
+                            ChartTool::Select => Some(Box::<SelectTool>::default()),
                         };
                         self.current_tool = new_tool;
                         ctx.request_repaint();
                     }
                 }
+                GuiEvent::ToggleTimingLock => {
+                    self.timing_locked = !self.timing_locked;
+                    if self.timing_locked
+                        && matches!(self.current_tool, ChartTool::BPM | ChartTool::TimeSig)
+                    {
+                        self.gui_event_queue
+                            .push_back(GuiEvent::ToolChanged(ChartTool::None));
+                    }
+                    self.store_view_state();
+                }
+                GuiEvent::ToggleVisualMetronome => self.visual_metronome = !self.visual_metronome,
+                GuiEvent::ToggleWaveform => self.show_waveform = !self.show_waveform,
+                GuiEvent::ToggleSplitView => {
+                    self.split_screen = match self.split_screen {
+                        Some(_) => None,
+                        None => Some(self.screen),
+                    }
+                }
+                GuiEvent::Copy => {
+                    self.copy_selection(ctx);
+                }
+                GuiEvent::Cut => {
+                    if let Some(Selection { start, end, lanes }) = self.copy_selection(ctx) {
+                        self.actions.new_action(
+                            fl!("cut_selection"),
+                            move |c: &mut kson::Chart| {
+                                c.remove_range(start, end, lanes);
+                                Ok(())
+                            },
+                        );
+                    }
+                }
+                GuiEvent::DeleteSelection => {
+                    let selection = self.cursor_object.as_ref().and_then(|x| x.selection());
+                    if let Some(Selection { start, end, lanes }) = selection {
+                        self.actions.new_action(
+                            fl!("delete_selection"),
+                            move |c: &mut kson::Chart| {
+                                c.remove_range(start, end, lanes);
+                                Ok(())
+                            },
+                        );
+                    }
+                }
+                GuiEvent::Mirror => {
+                    let selection = self.cursor_object.as_ref().and_then(|x| x.selection());
+                    let (desc, start, end) = match selection {
+                        Some(Selection { start, end, .. }) => (fl!("mirror_selection"), start, end),
+                        None => (fl!("mirror_chart"), 0, u32::MAX),
+                    };
+                    self.actions.new_action(desc, move |c: &mut kson::Chart| {
+                        c.mirror(start, end);
+                        Ok(())
+                    });
+                }
+                GuiEvent::Playtest => {
+                    if let Err(e) = self.playtest() {
+                        log::warn!("Could not playtest: {e}");
+                    }
+                }
+                GuiEvent::Paste => {
+                    if let Some(clip) = self.clipboard.clone() {
+                        self.paste_at_cursor(clip, fl!("paste"));
+                    }
+                }
+                GuiEvent::SavePattern => {
+                    let selection = self.cursor_object.as_ref().and_then(|x| x.selection());
+                    if let Some(Selection { start, end, lanes }) = selection {
+                        self.patterns.new_pattern = Some(NewPattern::new(
+                            self.chart.copy_range(start, end, lanes),
+                            end - start,
+                        ));
+                    }
+                }
                 GuiEvent::Undo => self.actions.undo(),
                 GuiEvent::Redo => self.actions.redo(),
                 GuiEvent::NewChart(new_chart_opts) => {
@@ -818,39 +1661,58 @@ impl MainState {
                             fp_filenames: vec![],
                         },
                     };
-                    self.save_path = if let Some(save_path) = new_chart_opts.destination {
-                        //copy audio file
-                        let mut audio_new_path = save_path.clone();
-                        audio_new_path.push(
-                            audio_pathbuf
-                                .file_name()
-                                .ok_or(anyhow!("Invalid filename"))?,
-                        );
-                        if !audio_new_path.exists() {
-                            std::fs::copy(audio_pathbuf, audio_new_path)?;
-                        }
-                        Some(save_path)
-                    } else {
-                        Some(
-                            audio_pathbuf
-                                .parent()
-                                .ok_or(anyhow!("Invalid path"))?
-                                .to_path_buf(),
-                        )
+                    let folder = match new_chart_opts.destination {
+                        Some(destination) => destination,
+                        None => audio_pathbuf
+                            .parent()
+                            .ok_or(anyhow!("Invalid path"))?
+                            .to_path_buf(),
                     };
-
-                    let mut kson_path =
-                        self.save_path.clone().ok_or(anyhow!("Invalid save path"))?;
-                    kson_path.push(new_chart_opts.filename);
+                    let mut kson_path = folder.join(new_chart_opts.filename);
                     kson_path.set_extension("kson");
-                    self.save_path = Some(kson_path.clone());
-                    if let Ok(mut file) = File::create(kson_path) {
-                        file.write_all(serde_json::to_string(&new_chart)?.as_bytes())?;
+
+                    //copy audio file
+                    let audio_new_path = folder.join(
+                        audio_pathbuf
+                            .file_name()
+                            .ok_or(anyhow!("Invalid filename"))?,
+                    );
+                    let written = if audio_new_path.exists() {
+                        Ok(())
+                    } else {
+                        std::fs::copy(&audio_pathbuf, &audio_new_path)
+                            .map(|_| ())
+                            .map_err(anyhow::Error::from)
+                    }
+                    .and_then(|_| {
+                        write_chart(
+                            &kson_path,
+                            &new_chart,
+                            self.kson_options,
+                            &self.backup_options,
+                        )
+                    });
+
+                    match written {
+                        Ok(()) => {
+                            self.store_view_state();
+                            self.save_path = Some(kson_path);
+                            self.actions.reset(new_chart.clone());
+                            self.chart = new_chart;
+                            self.refresh_project();
+                        }
+                        Err(e) => {
+                            log::error!("Failed to save {}: {e}", kson_path.display());
+                            self.save_error = Some(e.to_string());
+                        }
                     }
-                    self.actions.reset(new_chart.clone());
-                    self.chart = new_chart;
                 }
-                GuiEvent::ExportKsh => {
+                GuiEvent::ExportKsh | GuiEvent::ExportKshLegacy => {
+                    let compatibility = if matches!(e, GuiEvent::ExportKshLegacy) {
+                        kson::KshCompatibility::Legacy160
+                    } else {
+                        kson::KshCompatibility::Current
+                    };
                     if let Ok(chart) = self.actions.get_current() {
                         let dialog_result = nfd::open_save_dialog(Some("ksh"), None);
 
@@ -859,7 +1721,10 @@ impl MainState {
                             path.set_extension("ksh");
                             let file = File::create(&path)?;
                             profile_scope!("Write KSH");
-                            chart.to_ksh(file)?;
+                            chart.to_ksh_with_options(
+                                file,
+                                kson::KshWriteOptions { compatibility },
+                            )?;
                         }
                     }
                 }
@@ -889,8 +1754,7 @@ impl MainState {
                                 self.audio_playback.play();
                                 drop(self.audio_out.take());
                                 let audio_out = OutputStream::try_default()?;
-                                use rodio::source::Source;
-                                let audio_file = self
+                                let mut audio_file = self
                                     .audio_playback
                                     .get_source()
                                     .expect("Source not available");
@@ -898,9 +1762,9 @@ impl MainState {
                                 self.audio_playback.set_fx_enable(true, true);
 
                                 self.audio_playback.play();
-                                audio_out.1.play_raw(
-                                    audio_file.skip_duration(Duration::from_millis(ms as _)),
-                                )?;
+                                audio_file.skip(Duration::from_millis(ms as _));
+                                let audio_file = self.audio_playback.stretch(audio_file);
+                                audio_out.1.play_raw(audio_file)?;
                                 self.audio_out = Some(audio_out);
                             }
                             Err(msg) => {
@@ -909,6 +1773,9 @@ impl MainState {
                         }
                     }
                 }
+                GuiEvent::CycleFollowMode => {
+                    self.screen.follow_mode = self.screen.follow_mode.next()
+                }
                 GuiEvent::Home => self.screen.x_offset_target = 0.0,
                 GuiEvent::End => {
                     let mut target: f32 = 0.0;
@@ -960,9 +1827,27 @@ impl MainState {
         if let Ok(current_chart) = self.actions.get_current() {
             self.chart = current_chart;
         }
+        self.update_audio_length();
+        let waveform_path = self.bgm_path().filter(|_| self.show_waveform);
+        self.waveform.update(waveform_path);
+        if self.waveform.is_loading() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+
+        if self.audio_playback.is_playing() {
+            self.screen
+                .follow(self.audio_playback.get_tick(&self.chart));
+        }
 
         let delta_time = (10.0 * ctx.input(|x| x.unstable_dt)).min(1.0);
-        if self.screen.update(delta_time, KSON_RESOLUTION) || self.audio_playback.is_playing() {
+        let split_scrolling = self
+            .split_screen
+            .as_mut()
+            .is_some_and(|x| x.update(delta_time, KSON_RESOLUTION));
+        if self.screen.update(delta_time, KSON_RESOLUTION)
+            || split_scrolling
+            || self.audio_playback.is_playing()
+        {
             ctx.request_repaint();
         }
 
@@ -981,6 +1866,8 @@ impl MainState {
         //draw notes
         let mut track_line_builder = Vec::new();
         let mut track_measure_builder = Vec::new();
+        let mut no_audio_builder = Vec::new();
+        let mut waveform_builder = Vec::new();
         let mut bt_builder = Vec::new();
         let mut long_bt_builder = Vec::new();
         let mut fx_builder = Vec::new();
@@ -1035,6 +1922,63 @@ impl MainState {
                         color,
                     ));
                 }
+
+                //regions without audio
+                if let Some((audio_start, audio_end)) = self.audio_tick_range() {
+                    let regions = [
+                        (min_tick_render, audio_start.min(max_tick_render)),
+                        (audio_end.max(min_tick_render), max_tick_render),
+                    ];
+                    for (start, end) in regions {
+                        if start >= end {
+                            continue;
+                        }
+                        let interval = Interval {
+                            y: start,
+                            l: end - start,
+                        };
+                        for (x, y, h, _) in self.screen.interval_to_ranges(&interval) {
+                            no_audio_builder.push(Shape::rect_filled(
+                                rect_xy_wh([
+                                    x + self.screen.track_width / 2.0,
+                                    y,
+                                    self.screen.track_width,
+                                    h,
+                                ]),
+                                0.0,
+                                Color32::from_rgba_unmultiplied(128, 0, 0, 60),
+                            ));
+                        }
+                    }
+                }
+
+                //waveform, one bar every couple of pixels across the bt lanes
+                if let Some(waveform) = self.waveform.get().filter(|_| self.show_waveform) {
+                    let offset = self.chart.audio.bgm.offset as f64;
+                    let step = ((2.0 / self.screen.tick_height).ceil() as u32).max(1);
+                    let center = self.screen.track_width / 2.0 + lane_width * 3.0;
+                    let mut tick = min_tick_render - min_tick_render % step;
+                    let mut start_ms = self.chart.tick_to_ms(tick) + offset;
+                    while tick < max_tick_render {
+                        let end_ms = self.chart.tick_to_ms(tick + step) + offset;
+                        let w = waveform.peak(start_ms, end_ms) * lane_width * 4.0;
+                        if w > 0.0 {
+                            let (x, y) = self.screen.tick_to_pos(tick);
+                            waveform_builder.push(Shape::rect_filled(
+                                rect_xy_wh([
+                                    x + center - w / 2.0,
+                                    y,
+                                    w,
+                                    -(step as f32 * self.screen.tick_height),
+                                ]),
+                                0.0,
+                                Color32::from_rgba_unmultiplied(60, 140, 220, 90),
+                            ));
+                        }
+                        tick += step;
+                        start_ms = end_ms;
+                    }
+                }
             }
 
             //bt
@@ -1173,6 +2117,8 @@ impl MainState {
             //track
             {
                 profile_scope!("Track Mesh");
+                painter.extend(no_audio_builder);
+                painter.extend(waveform_builder);
                 painter.extend(track_line_builder);
                 painter.extend(track_measure_builder);
             }
@@ -1254,6 +2200,41 @@ impl MainState {
                         }
                     }
                 }
+
+                for (lasers, color) in self.chart.note.laser.iter().zip(self.laser_colors) {
+                    let color = Color32::from_rgb(color.r(), color.g(), color.b());
+                    for section in lasers.iter().filter(|x| x.wide() == 2) {
+                        let tick = section.tick();
+                        let entry = ("2x".to_string(), color);
+
+                        match changes.binary_search_by(|c| c.0.cmp(&tick)) {
+                            Ok(idx) => changes[idx].1.push(entry),
+                            Err(new_idx) => {
+                                let new_vec = vec![entry];
+                                changes.insert(new_idx, (tick, new_vec));
+                            }
+                        }
+                    }
+                }
+
+                let camera = &self.chart.camera;
+                let lane_toggles = camera.lane_toggle.iter().map(|x| {
+                    let color = Color32::from_rgb(150, 150, 255);
+                    (x.0, (fl!("lane_toggle_marker"), color))
+                });
+                let shakes = camera.shake.iter().map(|x| {
+                    let color = Color32::from_rgb(255, 140, 0);
+                    (x.0, (fl!("shake_marker"), color))
+                });
+                for (tick, entry) in lane_toggles.chain(shakes) {
+                    match changes.binary_search_by(|c| c.0.cmp(&tick)) {
+                        Ok(idx) => changes[idx].1.push(entry),
+                        Err(new_idx) => {
+                            let new_vec = vec![entry];
+                            changes.insert(new_idx, (tick, new_vec));
+                        }
+                    }
+                }
             }
 
             {
@@ -1282,9 +2263,107 @@ impl MainState {
             }
         }
 
+        //Comments
+        {
+            profile_scope!("Comments");
+            let color = Color32::from_rgb(255, 200, 0);
+            let radius = 5.0;
+            let hover_pos = ui.ctx().pointer_hover_pos();
+            let mut hovered = None;
+
+            for annotation in &self.chart.editor.annotation {
+                if annotation.y < min_tick_render {
+                    continue;
+                } else if annotation.y > max_tick_render {
+                    break;
+                }
+
+                let (x, y) = self.screen.tick_to_pos(annotation.y);
+                let center = pos2(x + self.screen.track_width / 4.0, y);
+                painter.line_segment(
+                    [center, pos2(x + self.screen.track_width / 2.0, y)],
+                    Stroke::new(1.0, color),
+                );
+                painter.circle_filled(center, radius, color);
+
+                if hover_pos.is_some_and(|pos| pos.distance(center) <= radius * 1.5) {
+                    hovered = Some(annotation);
+                }
+            }
+
+            if let Some(annotation) = hovered {
+                egui::show_tooltip_at_pointer(ui.ctx(), ui.id().with("comment_tooltip"), |ui| {
+                    annotation_tooltip(ui, annotation)
+                });
+            }
+        }
+
+        self.draw_metronome(&painter, ui.max_rect());
+
         Ok(interact)
     }
 
+    /// Draws the chart, and the split view below it while it is open. Returns the response of
+    /// each view.
+    pub fn draw_views(&mut self, ui: &mut Ui) -> Result<(Response, Option<Response>)> {
+        if self.split_screen.is_none() {
+            return Ok((self.draw(ui)?, None));
+        }
+
+        let rect = ui.max_rect();
+        let split_y = rect.center().y.round();
+        let main_rect = Rect::from_min_max(rect.min, pos2(rect.max.x, split_y));
+        let split_rect = Rect::from_min_max(pos2(rect.min.x, split_y), rect.max);
+
+        let main = self.draw(&ui.child_ui_with_id_source(main_rect, *ui.layout(), "main_view"))?;
+        let mut split_ui = ui.child_ui_with_id_source(split_rect, *ui.layout(), "split_view");
+        let split = self
+            .in_split_view(|state| state.draw(&split_ui))
+            .transpose()?;
+        ui.painter().hline(
+            rect.x_range(),
+            split_y,
+            Stroke::new(2.0, Color32::DARK_GRAY),
+        );
+
+        // Zoom and close buttons in the top margin of the split view
+        let header = Rect::from_min_size(
+            split_rect.min + vec2(4.0, 0.0),
+            vec2(split_rect.width() - 8.0, 20.0),
+        );
+        split_ui.allocate_ui_at_rect(header, |ui| {
+            ui.horizontal(|ui| {
+                if let Some(split) = &mut self.split_screen {
+                    ui.add(
+                        Slider::new(&mut split.beats_per_col, 4..=32)
+                            .clamp_to_range(true)
+                            .text(fl!("beats_per_col")),
+                    );
+                }
+                if ui.small_button(fl!("close_split_view")).clicked() {
+                    self.split_screen = None;
+                }
+            });
+        });
+
+        Ok((main, split))
+    }
+
+    /// Runs `f` with the split view as the current screen, so positions are mapped with its
+    /// scroll and zoom. Returns `None` while the split view is closed.
+    pub fn in_split_view<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> Option<R> {
+        let mut split = self.split_screen.take()?;
+        std::mem::swap(&mut self.screen, &mut split);
+        let result = f(self);
+        std::mem::swap(&mut self.screen, &mut split);
+        self.split_screen = Some(split);
+        Some(result)
+    }
+
+    pub fn split_view_contains(&self, pos: Pos2) -> bool {
+        self.split_screen.is_some_and(|x| pos.y >= x.top)
+    }
+
     pub fn drag_start(&mut self, button: PointerButton, x: f32, y: f32, modifiers: &Modifiers) {
         if let PointerButton::Primary = button {
             let res = KSON_RESOLUTION;
@@ -1400,13 +2479,82 @@ impl MainState {
     }
 
     pub(crate) fn context_menu(&mut self, ui: &mut Ui, pos: Pos2) {
-        let (lane, tick, _tick_f) = self.get_clicked_data(pos);
+        let (lane, tick, tick_f) = self.get_clicked_data(pos);
+
+        if ui.button(fl!("add_comment")).clicked() {
+            self.annotation_edit = Some(AnnotationEdit::new(tick, self.author_name.clone()));
+            ui.close_menu();
+        }
+
+        let measure = self.chart.tick_to_measure(tick);
+        if ui.button(fl!("cut_before_measure")).clicked() {
+            let start = self.chart.measure_to_tick(measure);
+            self.actions
+                .new_action(fl!("cut_before_measure"), move |c: &mut kson::Chart| {
+                    c.trim(start, u32::MAX);
+                    Ok(())
+                });
+            ui.close_menu();
+        }
+        if ui.button(fl!("cut_after_measure")).clicked() {
+            let end = self.chart.measure_to_tick(measure + 1);
+            self.actions
+                .new_action(fl!("cut_after_measure"), move |c: &mut kson::Chart| {
+                    c.trim(0, end);
+                    Ok(())
+                });
+            ui.close_menu();
+        }
+
+        for (side, lasers) in self.chart.note.laser.iter().enumerate() {
+            let Some(section_index) = lasers.iter().position(|x| {
+                tick >= x.tick() && tick <= x.tick() + x.last().map(|p| p.ry).unwrap_or(0)
+            }) else {
+                continue;
+            };
+
+            let side_name = if side == 0 { fl!("left") } else { fl!("right") };
+            let mut wide = lasers[section_index].wide() == 2;
+            if ui
+                .checkbox(&mut wide, fl!("wide_laser", side = side_name.clone()))
+                .changed()
+            {
+                self.actions.new_action(
+                    fl!("set_wide_laser", side = side_name.clone()),
+                    move |c: &mut kson::Chart| {
+                        let section = c.note.laser[side]
+                            .get_mut(section_index)
+                            .ok_or(anyhow!("Laser section not found"))?;
+                        section.2 = if wide { 2 } else { 1 };
+                        Ok(())
+                    },
+                );
+                ui.close_menu();
+            }
+
+            if let Some(edit) =
+                LaserCurveEdit::at(&lasers[section_index], side, section_index, tick_f)
+            {
+                if ui
+                    .button(fl!("set_laser_curve", side = side_name))
+                    .clicked()
+                {
+                    self.laser_curve_edit = Some(edit);
+                    ui.close_menu();
+                }
+            }
+        }
 
         let index = if lane < 3.0 { 0 } else { 1 };
 
         let mut fx = self.chart.note.fx[index].iter();
 
         if let Some(fx) = fx.find(|x| x.contains(tick)) {
+            if fx.l == 0 && ui.button(fl!("chip_sound")).clicked() {
+                self.key_sound_edit = Some(KeySoundEdit::new(&self.chart, index, fx.y));
+                ui.close_menu();
+            }
+
             let effects = &self.chart.audio.audio_effect;
             let mut effect_keys: Vec<&String> = effects.fx.def.keys().collect();
             effect_keys.sort();
@@ -1457,8 +2605,6 @@ impl MainState {
                     }
                 };
             }
-        } else {
-            ui.close_menu();
         }
     }
 }
@@ -1467,6 +2613,20 @@ fn get_extension_from_filename(filename: &str) -> Option<&str> {
     Path::new(filename).extension().and_then(OsStr::to_str)
 }
 
+/// Decodes the whole file if the format does not report its length.
+fn audio_length_ms(path: &Path) -> Option<f64> {
+    use rodio::Source;
+
+    let file = File::open(path).ok()?;
+    let decoder = rodio::Decoder::new(std::io::BufReader::new(file)).ok()?;
+    if let Some(duration) = decoder.total_duration() {
+        return Some(duration.as_secs_f64() * 1000.0);
+    }
+
+    let samples_per_ms = decoder.sample_rate() as f64 * decoder.channels() as f64 / 1000.0;
+    Some(decoder.count() as f64 / samples_per_ms)
+}
+
 //https://github.com/m4saka/ksh2kson/issues/4#issuecomment-573343229
 pub fn do_curve(x: f64, a: f64, b: f64) -> f64 {
     let t = if x < f64::EPSILON || a < f64::EPSILON {
@@ -1477,26 +2637,37 @@ pub fn do_curve(x: f64, a: f64, b: f64) -> f64 {
     2.0 * (1.0 - t) * t * b + t * t
 }
 
-fn open_chart_file(path: PathBuf) -> Result<Option<(kson::Chart, PathBuf)>> {
-    match path.extension().and_then(OsStr::to_str).unwrap_or_default() {
-        "ksh" => {
-            let mut data = String::from("");
-            File::open(&path)?.read_to_string(&mut data)?;
-            Ok(Some((kson::Chart::from_ksh(&data)?, path)))
-        }
-        "kson" => {
-            let file = File::open(&path)?;
-            let reader = BufReader::new(file);
-            profile_scope!("kson parse");
-            Ok(Some((serde_json::from_reader(reader)?, path)))
-        }
-        "vox" => {
-            let mut data = String::from("");
-            File::open(&path)?.read_to_string(&mut data)?;
-            Ok(Some((kson::Chart::from_vox(&data)?, path)))
-        }
+/// Writes to a temporary file first so a failed save doesn't leave a broken chart behind.
+fn write_chart(
+    path: &Path,
+    chart: &kson::Chart,
+    options: kson::KsonWriteOptions,
+    backup_options: &BackupOptions,
+) -> Result<()> {
+    profile_scope!("Write kson");
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let temp_path = PathBuf::from(temp_path);
+
+    let mut writer = BufWriter::new(File::create(&temp_path)?);
+    chart.to_kson(&mut writer, options)?;
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())?
+        .sync_all()?;
+    if let Err(e) = backup_panel::backup(path, backup_options) {
+        log::warn!("Failed to back up {}: {e}", path.display());
+    }
+    std::fs::rename(&temp_path, path)?;
+    Ok(())
+}
 
-        _ => Ok(None),
+fn open_chart_file(path: PathBuf) -> Result<Option<(kson::Chart, PathBuf)>> {
+    profile_scope!("Chart parse");
+    match kson::Chart::from_file(&path) {
+        Ok(chart) => Ok(Some((chart, path))),
+        Err(kson::Error::UnknownFormat(_)) => Ok(None),
+        Err(e) => Err(e.into()),
     }
 }
 
@@ -1512,16 +2683,18 @@ fn open_chart() -> Result<Option<(kson::Chart, PathBuf)>> {
     }
 }
 
-fn save_chart_as(chart: &kson::Chart) -> Result<Option<PathBuf>> {
+fn save_chart_as(
+    chart: &kson::Chart,
+    options: kson::KsonWriteOptions,
+    backup_options: &BackupOptions,
+) -> Result<Option<PathBuf>> {
     let dialog_result = nfd::open_save_dialog(Some("kson"), None)?;
 
     match dialog_result {
         nfd::Response::Okay(file_path) => {
             let mut path = PathBuf::from(&file_path);
             path.set_extension("kson");
-            let mut file = File::create(&path)?;
-            profile_scope!("Write kson");
-            file.write_all(serde_json::to_string(&chart)?.as_bytes())?;
+            write_chart(&path, chart, options, backup_options)?;
             Ok(Some(path))
         }
         _ => Ok(None),
@@ -0,0 +1,76 @@
+use eframe::egui::{self, RichText};
+use kson::{
+    validate::{ChartIssue, ChartIssueKind},
+    Track,
+};
+
+use crate::{
+    chart_editor::MainState,
+    i18n::{self, fl},
+};
+
+fn lane_name(track: Track) -> String {
+    match track {
+        Track::BT(lane) => format!("BT {:?}", lane),
+        Track::FX(side) => format!("FX {:?}", side),
+        Track::Laser(side) => fl!("laser_lane", side = format!("{:?}", side)),
+    }
+}
+
+fn issue_text(issue: &ChartIssue) -> String {
+    match issue.kind {
+        ChartIssueKind::OverlappingNotes(track) => {
+            fl!("issue_overlapping_notes", lane = lane_name(track))
+        }
+        ChartIssueKind::LaserOutOfRange(side) => {
+            fl!(
+                "issue_laser_out_of_range",
+                lane = lane_name(Track::Laser(side))
+            )
+        }
+        ChartIssueKind::EmptyLaserSection(side) => {
+            fl!(
+                "issue_empty_laser_section",
+                lane = lane_name(Track::Laser(side))
+            )
+        }
+        ChartIssueKind::ZeroBpm => fl!("issue_zero_bpm"),
+        ChartIssueKind::MissingTimeSignature => fl!("issue_missing_time_signature"),
+        ChartIssueKind::NotePastAudioEnd(track) => {
+            fl!("issue_note_past_audio_end", lane = lane_name(track))
+        }
+    }
+}
+
+/// Lists problems found in the chart, see [`kson::Chart::validate`].
+pub fn issues_panel(state: &mut MainState) -> impl egui::Widget + '_ {
+    move |ui: &mut egui::Ui| {
+        let heading = ui.heading(i18n::fl!("chart_issues"));
+        let audio_length = state.audio_length.as_ref().and_then(|x| x.1);
+        let issues = state.chart.validate(audio_length);
+
+        if issues.is_empty() {
+            ui.label(RichText::new(fl!("no_chart_issues")).weak());
+            return heading;
+        }
+
+        let mut jump_to = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for issue in &issues {
+                ui.separator();
+                let measure = state.chart.tick_to_measure(issue.y) + 1;
+                ui.strong(fl!("playing_measure", measure = measure));
+                ui.label(issue_text(issue));
+                if ui.button(fl!("go_to")).clicked() {
+                    jump_to = Some(issue.y);
+                }
+            }
+        });
+
+        if let Some(tick) = jump_to {
+            state.go_to_tick(tick);
+        }
+
+        heading
+    }
+}
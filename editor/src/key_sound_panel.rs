@@ -0,0 +1,187 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use eframe::egui::{self, ComboBox, Slider};
+use kson::{Chart, KeySoundInvokeFX};
+use rodio::{OutputStream, OutputStreamHandle, Source};
+
+use crate::{chart_editor::MainState, i18n::fl, DialogShortcuts};
+
+/// The sample played by an FX chip being edited.
+pub struct KeySoundEdit {
+    pub lane: usize,
+    pub y: u32,
+    /// Filename relative to the chart folder
+    pub filename: String,
+    pub vol: f64,
+    preview: Option<(OutputStream, OutputStreamHandle)>,
+}
+
+impl KeySoundEdit {
+    /// Starts from the sample currently assigned to the chip, if any.
+    pub fn new(chart: &Chart, lane: usize, y: u32) -> Self {
+        let assigned = chart
+            .audio
+            .key_sound
+            .fx
+            .chip_event
+            .iter()
+            .find_map(|(filename, lanes)| {
+                lanes[lane]
+                    .iter()
+                    .find(|x| x.0 == y)
+                    .map(|x| (filename.clone(), x.1.vol))
+            });
+
+        let (filename, vol) = assigned.unwrap_or_else(|| (String::new(), 1.0));
+
+        Self {
+            lane,
+            y,
+            filename,
+            vol,
+            preview: None,
+        }
+    }
+
+    fn play_preview(&mut self, chart_folder: &Path) -> Result<()> {
+        self.preview = Some(play_audio(&chart_folder.join(&self.filename), self.vol)?);
+        Ok(())
+    }
+}
+
+/// Plays an audio file on the default output until the returned stream is dropped.
+pub(crate) fn play_audio(path: &Path, vol: f64) -> Result<(OutputStream, OutputStreamHandle)> {
+    let file = File::open(path)?;
+    let source = rodio::Decoder::new(BufReader::new(file))?;
+    let (stream, handle) = OutputStream::try_default()?;
+    handle.play_raw(source.convert_samples().amplify(vol as f32))?;
+    Ok((stream, handle))
+}
+
+/// Copies `source` into the chart folder unless it is already there and returns its
+/// filename relative to the chart.
+pub(crate) fn import_sample(chart_folder: &Path, source: &Path) -> Result<String> {
+    let filename = source
+        .file_name()
+        .and_then(|x| x.to_str())
+        .ok_or(anyhow!("Invalid sample filename"))?;
+
+    let target = chart_folder.join(filename);
+    if source.parent() != Some(chart_folder) && !target.exists() {
+        std::fs::copy(source, target)?;
+    }
+
+    Ok(filename.to_string())
+}
+
+fn set_key_sound(c: &mut Chart, lane: usize, y: u32, sample: Option<(&str, f64)>) {
+    let chip_event = &mut c.audio.key_sound.fx.chip_event;
+    for lanes in chip_event.values_mut() {
+        lanes[lane].retain(|x| x.0 != y);
+    }
+
+    if let Some((filename, vol)) = sample {
+        let events = &mut chip_event.entry(filename.to_string()).or_default()[lane];
+        let i = events.partition_point(|x| x.0 < y);
+        events.insert(i, (y, KeySoundInvokeFX { vol }));
+    }
+
+    chip_event.retain(|_, lanes| lanes.iter().any(|x| !x.is_empty()));
+}
+
+/// Shows the dialog for assigning a sample to an FX chip if one is being edited.
+pub fn key_sound_dialog(
+    state: &mut MainState,
+    ctx: &egui::Context,
+    shortcuts: &mut DialogShortcuts,
+) {
+    let Some(mut edit) = state.key_sound_edit.take() else {
+        return;
+    };
+    let (confirm, cancel) = shortcuts.take();
+
+    let chart_folder: Option<PathBuf> = state
+        .save_path
+        .as_ref()
+        .and_then(|x| x.parent())
+        .map(Path::to_path_buf);
+
+    let mut samples: Vec<&String> = state.chart.audio.key_sound.fx.chip_event.keys().collect();
+    samples.sort();
+
+    let mut open = true;
+    let mut done = false;
+    let mut remove = false;
+    egui::Window::new(fl!("chip_sound"))
+        .id(egui::Id::new("key_sound_dialog"))
+        .open(&mut open)
+        .show(ctx, |ui| {
+            let Some(chart_folder) = &chart_folder else {
+                ui.label(fl!("save_chart_first"));
+                return;
+            };
+
+            ui.horizontal(|ui| {
+                ComboBox::from_id_source("chip_sound_sample")
+                    .selected_text(&edit.filename)
+                    .show_ui(ui, |ui| {
+                        for sample in samples {
+                            ui.selectable_value(&mut edit.filename, sample.clone(), sample);
+                        }
+                    });
+
+                if ui.button("...").clicked() {
+                    if let Ok(nfd::Response::Okay(picked)) =
+                        nfd::open_file_dialog(Some("wav,ogg,mp3,flac"), None)
+                    {
+                        match import_sample(chart_folder, Path::new(&picked)) {
+                            Ok(filename) => edit.filename = filename,
+                            Err(e) => log::warn!("Failed to import sample: {e}"),
+                        }
+                    }
+                }
+            });
+
+            ui.add(Slider::new(&mut edit.vol, 0.0..=1.0).text(fl!("volume")));
+
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(!edit.filename.is_empty(), egui::Button::new(fl!("preview")))
+                    .clicked()
+                {
+                    if let Err(e) = edit.play_preview(chart_folder) {
+                        log::warn!("Failed to play sample: {e}");
+                    }
+                }
+                remove = ui.button(fl!("remove_chip_sound")).clicked();
+            });
+
+            ui.add_space(10.0);
+            done = (ui.button(fl!("ok")).clicked() || confirm) && !edit.filename.is_empty();
+        });
+
+    let (lane, y) = (edit.lane, edit.y);
+
+    if remove {
+        state
+            .actions
+            .new_action(fl!("remove_chip_sound"), move |c: &mut Chart| {
+                set_key_sound(c, lane, y, None);
+                Ok(())
+            });
+    } else if done {
+        let filename = edit.filename.clone();
+        let vol = edit.vol;
+        state
+            .actions
+            .new_action(fl!("chip_sound"), move |c: &mut Chart| {
+                set_key_sound(c, lane, y, Some((&filename, vol)));
+                Ok(())
+            });
+    } else if open && !cancel {
+        state.key_sound_edit = Some(edit);
+    }
+}
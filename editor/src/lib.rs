@@ -1,33 +1,53 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use chart_editor::MainState;
+use chart_editor::{FollowMode, MainState};
 
+use annotation_panel::{annotation_dialog, annotation_panel};
+use backup_panel::{backup_dialog, find_autosave, list_backups, recovery_dialog, BackupOptions};
 use effect_panel::effect_panel;
 use eframe::egui::{
     self, menu, warn_if_debug_build, Button, Color32, ComboBox, DragValue, Frame, Grid, Key, Label,
-    Layout, Pos2, Rect, Response, RichText, Sense, Slider, Ui, Vec2, ViewportCommand, Visuals,
+    Layout, Pos2, Rect, Response, RichText, Sense, Slider, Stroke, Ui, Vec2, ViewportCommand,
+    Visuals,
 };
 use eframe::App;
 use i18n::fl;
 use i18n_embed::unic_langid::LanguageIdentifier;
+use issues_panel::issues_panel;
+use key_sound_panel::{import_sample, key_sound_dialog, play_audio};
 use kson::{BgmInfo, Chart, MetaInfo};
+use kson_music_playback::ChartSounds;
+use midi_import::{midi_import_dialog, MidiLaneMapping};
+use pattern_library::{pattern_panel, save_pattern_dialog};
+use project::project_panel;
 use puffin::profile_scope;
 use serde::{Deserialize, Serialize};
+use stats_panel::stats_panel;
+use tools::laser_curve_dialog;
 
 mod action_stack;
+mod annotation_panel;
 mod assets;
+mod backup_panel;
 mod camera_widget;
 mod chart_camera;
 mod chart_editor;
 mod effect_editor;
 mod effect_panel;
 mod i18n;
+mod issues_panel;
+mod key_sound_panel;
+mod midi_import;
 mod param_input;
+mod pattern_library;
+mod project;
+mod stats_panel;
 mod tools;
+mod waveform;
 
 pub trait Widget {
     fn ui(self, ui: &mut Ui) -> Response;
@@ -42,17 +62,46 @@ pub struct NewChartOptions {
     destination: Option<PathBuf>,
 }
 
+/// Gives keyboard focus to the first field of a dialog when nothing else has it.
+fn focus_if_unfocused(ui: &Ui, response: &Response) {
+    if ui.memory(|m| m.focused().is_none()) {
+        response.request_focus();
+    }
+}
+
+/// The dialog shortcuts pressed this frame, Ctrl+Enter and Escape. Only the first open dialog
+/// gets them so one key press doesn't confirm or close every dialog at once.
+pub struct DialogShortcuts(Option<(bool, bool)>);
+
+impl DialogShortcuts {
+    fn read(ctx: &egui::Context) -> Self {
+        Self(Some(ctx.input(|i| {
+            (
+                i.modifiers.command && i.key_pressed(Key::Enter),
+                i.key_pressed(Key::Escape),
+            )
+        })))
+    }
+
+    /// Returns `(confirm, cancel)` for an open dialog, dialogs after the first get neither.
+    pub fn take(&mut self) -> (bool, bool) {
+        self.0.take().unwrap_or_default()
+    }
+}
+
 impl Widget for &mut kson::MetaInfo {
     fn ui(self, ui: &mut Ui) -> Response {
         let edit_row = |ui: &mut Ui, label: &str, data: &mut String| {
             ui.label(label);
-            ui.text_edit_singleline(data);
+            let response = ui.text_edit_singleline(data);
             ui.end_row();
+            response
         };
 
         egui::Grid::new("metadata_editor")
             .show(ui, |ui| {
-                edit_row(ui, &i18n::fl!("title"), &mut self.title);
+                let title = edit_row(ui, &i18n::fl!("title"), &mut self.title);
+                focus_if_unfocused(ui, &title);
                 edit_row(ui, &i18n::fl!("artist"), &mut self.artist);
                 edit_row(ui, &i18n::fl!("effector"), &mut self.chart_author);
                 edit_row(ui, &i18n::fl!("jacket"), &mut self.jacket_filename);
@@ -72,11 +121,18 @@ impl Widget for &mut kson::MetaInfo {
     }
 }
 
+impl NewChartOptions {
+    fn is_valid(&self) -> bool {
+        !self.audio.is_empty() && !self.filename.is_empty()
+    }
+}
+
 impl Widget for &mut NewChartOptions {
     fn ui(self, ui: &mut Ui) -> Response {
         ui.horizontal(|ui| {
             ui.label(i18n::fl!("filename"));
-            ui.text_edit_singleline(&mut self.filename);
+            let filename = ui.text_edit_singleline(&mut self.filename);
+            focus_if_unfocused(ui, &filename);
         });
 
         ui.separator();
@@ -108,38 +164,143 @@ impl Widget for &mut NewChartOptions {
         }
         ui.separator();
 
-        ui.add_enabled(
-            !self.audio.is_empty() && !self.filename.is_empty(),
-            Button::new(i18n::fl!("ok")),
-        )
+        ui.add_enabled(self.is_valid(), Button::new(i18n::fl!("ok")))
     }
 }
 
-impl Widget for &mut kson::BgmInfo {
-    fn ui(self, ui: &mut Ui) -> Response {
+/// Music info with the offset field disabled while timing is locked.
+struct BgmInfoEdit<'a> {
+    bgm: &'a mut kson::BgmInfo,
+    timing_locked: bool,
+    /// Audio files are imported to and previewed from here, `None` if the chart isn't saved
+    chart_folder: Option<&'a Path>,
+    preview: &'a mut Option<(rodio::OutputStream, rodio::OutputStreamHandle)>,
+}
+
+impl BgmInfoEdit<'_> {
+    fn preview_button(&mut self, ui: &mut Ui, filename: &str) {
+        let Some(chart_folder) = self.chart_folder else {
+            return;
+        };
+
+        if ui
+            .add_enabled(!filename.is_empty(), Button::new("▶"))
+            .on_hover_text(i18n::fl!("preview"))
+            .clicked()
+        {
+            match play_audio(&chart_folder.join(filename), self.bgm.vol) {
+                Ok(preview) => *self.preview = Some(preview),
+                Err(e) => log::warn!("Failed to play {filename}: {e}"),
+            }
+        }
+    }
+
+    /// Copies the picked files to the chart folder, the first becomes the bgm if there is
+    /// none and the rest are added as FX tracks.
+    fn import_audio_files(&mut self) {
+        let Some(chart_folder) = self.chart_folder else {
+            return;
+        };
+
+        let picked = match nfd::open_file_multiple_dialog(Some("mp3,flac,wav,ogg"), None) {
+            Ok(nfd::Response::Okay(file)) => vec![file],
+            Ok(nfd::Response::OkayMultiple(files)) => files,
+            _ => return,
+        };
+
+        for file in picked {
+            let filename = match import_sample(chart_folder, Path::new(&file)) {
+                Ok(filename) => filename,
+                Err(e) => {
+                    log::warn!("Failed to import {file}: {e}");
+                    continue;
+                }
+            };
+
+            let bgm = &mut *self.bgm;
+            if bgm.filename.is_empty() {
+                bgm.filename = filename;
+            } else if bgm.filename != filename && !bgm.legacy.fp_filenames.contains(&filename) {
+                bgm.legacy.fp_filenames.push(filename);
+            }
+        }
+    }
+}
+
+impl Widget for BgmInfoEdit<'_> {
+    fn ui(mut self, ui: &mut Ui) -> Response {
         Grid::new("bgm_info")
             .show(ui, |ui| {
                 ui.label(i18n::fl!("audio_file"));
-                ui.text_edit_singleline(&mut self.filename);
+                ui.horizontal(|ui| {
+                    let filename = ui.text_edit_singleline(&mut self.bgm.filename);
+                    focus_if_unfocused(ui, &filename);
+                    let filename = self.bgm.filename.clone();
+                    self.preview_button(ui, &filename);
+                });
+                ui.end_row();
+
+                ui.label(i18n::fl!("fx_audio_files"))
+                    .on_hover_text(i18n::fl!("fx_audio_files_hover"));
+                ui.vertical(|ui| {
+                    let mut remove = None;
+                    for i in 0..self.bgm.legacy.fp_filenames.len() {
+                        ui.horizontal(|ui| {
+                            ui.text_edit_singleline(&mut self.bgm.legacy.fp_filenames[i]);
+                            let filename = self.bgm.legacy.fp_filenames[i].clone();
+                            self.preview_button(ui, &filename);
+                            if ui.button("🗑").clicked() {
+                                remove = Some(i);
+                            }
+                        });
+                    }
+                    if let Some(i) = remove {
+                        self.bgm.legacy.fp_filenames.remove(i);
+                    }
+
+                    if ui
+                        .add_enabled(
+                            self.chart_folder.is_some(),
+                            Button::new(i18n::fl!("import_audio_files")),
+                        )
+                        .on_disabled_hover_text(i18n::fl!("save_chart_first"))
+                        .clicked()
+                    {
+                        self.import_audio_files();
+                    }
+                });
                 ui.end_row();
 
+                let bgm = &mut *self.bgm;
+
                 ui.label(i18n::fl!("offset"));
-                ui.add(DragValue::new(&mut self.offset).suffix("ms"));
+                ui.add_enabled(
+                    !self.timing_locked,
+                    DragValue::new(&mut bgm.offset).suffix("ms"),
+                )
+                .on_disabled_hover_text(i18n::fl!("timing_locked"));
                 ui.end_row();
 
                 ui.label(i18n::fl!("volume"));
-                ui.add(Slider::new(&mut self.vol, 0.0..=1.0).clamp_to_range(true));
+                ui.add(Slider::new(&mut bgm.vol, 0.0..=1.0).clamp_to_range(true));
                 ui.end_row();
 
                 ui.separator();
                 ui.end_row();
 
+                ui.label(i18n::fl!("preview_file"));
+                let mut preview_file = bgm.preview.preview_filename.clone().unwrap_or_default();
+                if ui.text_edit_singleline(&mut preview_file).changed() {
+                    bgm.preview.preview_filename = Some(preview_file).filter(|x| !x.is_empty());
+                }
+                ui.end_row();
+
                 ui.label(i18n::fl!("preview_offset"));
-                ui.add(DragValue::new(&mut self.preview.offset).suffix("ms"));
+                ui.add(DragValue::new(&mut bgm.preview.offset).suffix("ms"));
                 ui.end_row();
 
                 ui.label(i18n::fl!("preview_duration"));
-                ui.add(DragValue::new(&mut self.preview.duration).suffix("ms"));
+                ui.add(DragValue::new(&mut bgm.preview.duration).suffix("ms"));
                 ui.end_row();
             })
             .response
@@ -165,7 +326,28 @@ pub enum GuiEvent {
     Next,
     Previous,
     ExportKsh,
+    /// Exports a KSH that loads in KSM 1.60
+    ExportKshLegacy,
     Preferences,
+    CycleFollowMode,
+    EffectDefinitions,
+    Comments,
+    ImportTiming,
+    ImportMidi,
+    AppendChart,
+    ToggleTimingLock,
+    ToggleVisualMetronome,
+    ToggleWaveform,
+    Copy,
+    Cut,
+    Paste,
+    DeleteSelection,
+    Mirror,
+    /// Saves the selection to the pattern library
+    SavePattern,
+    /// Opens the chart in the game, paused at the cursor
+    Playtest,
+    ToggleSplitView,
 }
 
 impl std::fmt::Display for GuiEvent {
@@ -188,6 +370,7 @@ pub enum ChartTool {
     BPM,
     TimeSig,
     Camera,
+    Select,
 }
 
 #[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, Clone)]
@@ -212,9 +395,18 @@ struct AppState {
     new_chart: Option<NewChartOptions>,
     meta_edit: Option<MetaInfo>,
     bgm_edit: Option<BgmInfo>,
+    /// Audio file being previewed from the music info dialog
+    bgm_preview: Option<(rodio::OutputStream, rodio::OutputStreamHandle)>,
     exiting: bool,
     language: LanguageIdentifier,
     show_fx_def: bool,
+    show_comments: bool,
+    show_issues: bool,
+    show_stats: bool,
+    show_project: bool,
+    show_patterns: bool,
+    font_scale: f32,
+    window_title: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -223,6 +415,60 @@ struct Config {
     track_width: f32,
     beats_per_column: u32,
     language: LanguageIdentifier,
+    #[serde(default)]
+    follow_mode: chart_editor::FollowMode,
+    #[serde(default = "default_font_scale")]
+    font_scale: f32,
+    #[serde(default)]
+    pretty_kson: bool,
+    #[serde(default)]
+    chart_states: HashMap<PathBuf, chart_editor::ChartViewState>,
+    /// Name written to new comments
+    #[serde(default)]
+    author_name: String,
+    #[serde(default)]
+    backups: BackupOptions,
+    #[serde(default)]
+    visual_metronome: bool,
+    #[serde(default = "default_show_waveform")]
+    show_waveform: bool,
+    /// Game executable charts are playtested in
+    #[serde(default)]
+    game_path: Option<PathBuf>,
+    /// MIDI keys placed on each lane by MIDI imports
+    #[serde(default)]
+    midi_mapping: MidiLaneMapping,
+    /// Volumes of the metronome and hit sounds during playback
+    #[serde(default)]
+    chart_sounds: ChartSounds,
+}
+
+fn default_font_scale() -> f32 {
+    1.0
+}
+
+fn default_show_waveform() -> bool {
+    true
+}
+
+/// Scales all text styles relative to egui's defaults, this is applied on top of the UI zoom.
+fn apply_font_scale(ctx: &egui::Context, font_scale: f32) {
+    let defaults = egui::Style::default().text_styles;
+    let mut style = (*ctx.style()).clone();
+    for (text_style, font) in style.text_styles.iter_mut() {
+        if let Some(default) = defaults.get(text_style) {
+            font.size = default.size * font_scale;
+        }
+    }
+    ctx.set_style(style);
+}
+
+/// Dark visuals with a thicker, colored outline on the focused widget so keyboard
+/// navigation is easy to follow.
+fn editor_visuals() -> Visuals {
+    let mut visuals = Visuals::dark();
+    visuals.widgets.active.bg_stroke = Stroke::new(2.0, visuals.selection.stroke.color);
+    visuals
 }
 
 //TODO: ehhhhhhhhh
@@ -419,6 +665,10 @@ impl Default for Config {
                 KeyCombo::new(Key::Num7, nomod),
                 GuiEvent::ToolChanged(ChartTool::Camera),
             );
+            default_bindings.insert(
+                KeyCombo::new(Key::Num8, nomod),
+                GuiEvent::ToolChanged(ChartTool::Select),
+            );
         }
 
         default_bindings.insert(KeyCombo::new(Key::Space, nomod), GuiEvent::Play);
@@ -426,12 +676,34 @@ impl Default for Config {
         default_bindings.insert(KeyCombo::new(Key::End, nomod), GuiEvent::End);
         default_bindings.insert(KeyCombo::new(Key::PageDown, nomod), GuiEvent::Next);
         default_bindings.insert(KeyCombo::new(Key::PageUp, nomod), GuiEvent::Previous);
+        default_bindings.insert(KeyCombo::new(Key::F, nomod), GuiEvent::CycleFollowMode);
+        default_bindings.insert(KeyCombo::new(Key::Delete, nomod), GuiEvent::DeleteSelection);
+        default_bindings.insert(KeyCombo::new(Key::F5, nomod), GuiEvent::Playtest);
+        default_bindings.insert(
+            KeyCombo::new(Key::E, Modifiers::new().ctrl()),
+            GuiEvent::EffectDefinitions,
+        );
+        default_bindings.insert(
+            KeyCombo::new(Key::K, Modifiers::new().ctrl()),
+            GuiEvent::Comments,
+        );
 
         Self {
             key_bindings: default_bindings,
             track_width: 72.0,
             beats_per_column: 16,
             language: "en".parse().expect("Bad default language"),
+            follow_mode: Default::default(),
+            font_scale: default_font_scale(),
+            pretty_kson: false,
+            chart_states: HashMap::new(),
+            author_name: String::new(),
+            backups: BackupOptions::default(),
+            visual_metronome: false,
+            show_waveform: true,
+            game_path: None,
+            midi_mapping: MidiLaneMapping::default(),
+            chart_sounds: ChartSounds::default(),
         }
     }
 }
@@ -451,18 +723,41 @@ pub fn rect_xy_wh(rect: [f32; 4]) -> Rect {
     Rect::from_x_y_ranges(x..=x + w, y..=y + h)
 }
 
-const TOOLS: [(&str, ChartTool); 6] = [
+const TOOLS: [(&str, ChartTool); 7] = [
     ("BT", ChartTool::BT),
     ("FX", ChartTool::FX),
     ("LL", ChartTool::LLaser),
     ("RL", ChartTool::RLaser),
     ("BPM", ChartTool::BPM),
     ("TS", ChartTool::TimeSig),
+    ("SEL", ChartTool::Select),
 ];
 
 impl AppState {
+    fn window_title(&self) -> String {
+        let chart_name = self
+            .editor
+            .save_path
+            .as_ref()
+            .and_then(|x| x.file_name())
+            .map(|x| x.to_string_lossy().to_string())
+            .unwrap_or_else(|| i18n::fl!("untitled"));
+        let dirty = if self.editor.actions.saved() { "" } else { "*" };
+
+        if self.editor.audio_playback.is_playing() {
+            let tick = self.editor.audio_playback.get_tick(&self.editor.chart) as u32;
+            let measure = self.editor.chart.tick_to_measure(tick) + 1;
+            format!(
+                "{chart_name}{dirty} - {} - KSON Editor",
+                i18n::fl!("playing_measure", measure = measure)
+            )
+        } else {
+            format!("{chart_name}{dirty} - KSON Editor")
+        }
+    }
+
     fn saved_changes(&mut self) -> bool {
-        let at_save = self.editor.actions.saved();
+        let at_save = self.editor.all_saved();
         if !at_save {
             self.exiting = true;
         }
@@ -485,6 +780,85 @@ impl AppState {
                 .text(i18n::fl!("beats_per_col")),
         );
 
+        ComboBox::new("follow_mode", i18n::fl!("follow_mode"))
+            .selected_text(follow_mode_name(self.editor.screen.follow_mode))
+            .show_ui(ui, |ui| {
+                for mode in [FollowMode::None, FollowMode::Page, FollowMode::Centered] {
+                    ui.selectable_value(
+                        &mut self.editor.screen.follow_mode,
+                        mode,
+                        follow_mode_name(mode),
+                    );
+                }
+            });
+
+        ui.horizontal(|ui| {
+            ui.label(i18n::fl!("author_name"));
+            ui.text_edit_singleline(&mut self.editor.author_name);
+        });
+
+        ui.checkbox(
+            &mut self.editor.kson_options.pretty,
+            i18n::fl!("pretty_kson"),
+        );
+
+        ui.horizontal(|ui| {
+            ui.label(i18n::fl!("backup_count"));
+            ui.add(DragValue::new(&mut self.editor.backup_options.count).clamp_range(0..=100));
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(i18n::fl!("backup_folder"));
+            let folder = &mut self.editor.backup_options.folder;
+            let text = folder
+                .as_ref()
+                .map(|x| x.display().to_string())
+                .unwrap_or_else(|| i18n::fl!("backup_folder_default"));
+            if ui.button(text).clicked() {
+                if let Ok(nfd::Response::Okay(picked)) = nfd::open_pick_folder(None) {
+                    *folder = Some(PathBuf::from(picked));
+                }
+            }
+            if ui
+                .add_enabled(folder.is_some(), Button::new(i18n::fl!("reset_to_default")))
+                .clicked()
+            {
+                *folder = None;
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(i18n::fl!("game_path"));
+            let game_path = &mut self.editor.game_path;
+            let text = game_path
+                .as_ref()
+                .map(|x| x.display().to_string())
+                .unwrap_or_else(|| i18n::fl!("game_path_unset"));
+            if ui.button(text).clicked() {
+                if let Ok(nfd::Response::Okay(picked)) = nfd::open_file_dialog(None, None) {
+                    *game_path = Some(PathBuf::from(picked));
+                }
+            }
+        });
+
+        ui.separator();
+        ui.label(i18n::fl!("playback_sounds"))
+            .on_hover_text(i18n::fl!("playback_sounds_hover"));
+        let mut sounds = self.editor.audio_playback.chart_sounds();
+        for (volume, label) in [
+            (&mut sounds.metronome, i18n::fl!("metronome_volume")),
+            (&mut sounds.chip, i18n::fl!("chip_volume")),
+            (&mut sounds.slam, i18n::fl!("slam_volume")),
+        ] {
+            ui.add(
+                Slider::new(volume, 0.0..=1.0)
+                    .clamp_to_range(true)
+                    .text(label),
+            );
+        }
+        self.editor.audio_playback.set_chart_sounds(sounds);
+        ui.separator();
+
         let mut zoom = ui.ctx().zoom_factor();
 
         ComboBox::new("zoom_edit", i18n::fl!("ui_scale"))
@@ -497,6 +871,18 @@ impl AppState {
 
         ui.ctx().set_zoom_factor(zoom);
 
+        if ui
+            .add(
+                Slider::new(&mut self.font_scale, 0.5..=3.0)
+                    .clamp_to_range(true)
+                    .step_by(0.1)
+                    .text(i18n::fl!("font_scale")),
+            )
+            .changed()
+        {
+            apply_font_scale(ui.ctx(), self.font_scale);
+        }
+
         let selected = ComboBox::new("lang_select", "Language")
             .selected_text(self.language.language.to_string())
             .show_ui(ui, |ui| {
@@ -542,8 +928,33 @@ impl AppState {
     }
 }
 
+fn follow_mode_name(mode: FollowMode) -> String {
+    match mode {
+        FollowMode::None => i18n::fl!("follow_none"),
+        FollowMode::Page => i18n::fl!("follow_page"),
+        FollowMode::Centered => i18n::fl!("follow_centered"),
+    }
+}
+
 const CONFIG_KEY: &str = "CONFIG_2";
 
+/// Mute toggle and volume slider for one part of the playback mix.
+fn volume_control(ui: &mut Ui, label: String, hover: String, volume: &mut f32, muted: &mut bool) {
+    if ui
+        .selectable_label(*muted, format!("🔇 {label}"))
+        .on_hover_text(hover)
+        .clicked()
+    {
+        *muted = !*muted;
+    }
+    ui.add_enabled(
+        !*muted,
+        Slider::new(volume, 0.0..=1.0)
+            .clamp_to_range(true)
+            .show_value(false),
+    );
+}
+
 fn menu_ui(ui: &mut Ui, title: impl ToString, min_width: f32, add_contents: impl FnOnce(&mut Ui)) {
     menu::menu_button(ui, title.to_string(), |ui| {
         ui.with_layout(Layout::top_down_justified(egui::Align::Min), |ui| {
@@ -555,11 +966,25 @@ fn menu_ui(ui: &mut Ui, title: impl ToString, min_width: f32, add_contents: impl
 
 impl App for AppState {
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.editor.store_view_state();
+        self.editor.chart_states.retain(|path, _| path.exists());
+
         let new_config = Config {
             key_bindings: self.key_bindings.clone(),
             beats_per_column: self.editor.screen.beats_per_col,
             track_width: self.editor.screen.track_width,
             language: self.language.clone(),
+            follow_mode: self.editor.screen.follow_mode,
+            font_scale: self.font_scale,
+            pretty_kson: self.editor.kson_options.pretty,
+            chart_states: self.editor.chart_states.clone(),
+            author_name: self.editor.author_name.clone(),
+            backups: self.editor.backup_options.clone(),
+            visual_metronome: self.editor.visual_metronome,
+            show_waveform: self.editor.show_waveform,
+            game_path: self.editor.game_path.clone(),
+            midi_mapping: self.editor.midi_mapping,
+            chart_sounds: self.editor.audio_playback.chart_sounds(),
         };
 
         eframe::set_value(storage, CONFIG_KEY, &new_config)
@@ -575,8 +1000,19 @@ impl App for AppState {
         let events = { ctx.input(|x| x.events.clone()) };
         for e in events {
             match e {
-                egui::Event::Copy => {}
-                egui::Event::Cut => {}
+                egui::Event::Copy if !ctx.wants_keyboard_input() => {
+                    self.editor.gui_event_queue.push_back(GuiEvent::Copy)
+                }
+                egui::Event::Cut if !ctx.wants_keyboard_input() => {
+                    self.editor.gui_event_queue.push_back(GuiEvent::Cut)
+                }
+                egui::Event::Paste(text) if !ctx.wants_keyboard_input() => {
+                    // Charts copied from another editor window arrive as KSON text
+                    if let Ok(clip) = kson::Chart::from_kson(&text) {
+                        self.editor.clipboard = Some(clip);
+                    }
+                    self.editor.gui_event_queue.push_back(GuiEvent::Paste)
+                }
                 egui::Event::Key {
                     key,
                     pressed,
@@ -602,12 +1038,20 @@ impl App for AppState {
                             Some(GuiEvent::MusicInfo) => {
                                 self.bgm_edit = Some(self.editor.chart.audio.bgm.clone())
                             }
+                            Some(GuiEvent::EffectDefinitions) => {
+                                self.show_fx_def = !self.show_fx_def
+                            }
+                            Some(GuiEvent::Comments) => self.show_comments = !self.show_comments,
 
                             Some(action) => self.editor.gui_event_queue.push_back(action.clone()),
                             None => (),
                         }
                     }
                 }
+                egui::Event::PointerMoved(pos) if self.editor.split_view_contains(pos) => {
+                    self.editor
+                        .in_split_view(|editor| editor.mouse_motion_event(pos));
+                }
                 egui::Event::PointerMoved(pos) => self.editor.mouse_motion_event(pos),
 
                 _ => {}
@@ -639,6 +1083,41 @@ impl App for AppState {
                         if ui.button(i18n::fl!("export_ksh")).clicked() {
                             self.editor.gui_event_queue.push_back(GuiEvent::ExportKsh)
                         }
+                        if ui.button(i18n::fl!("export_ksh_legacy")).clicked() {
+                            self.editor
+                                .gui_event_queue
+                                .push_back(GuiEvent::ExportKshLegacy)
+                        }
+                        if ui
+                            .add_enabled(
+                                !self.editor.timing_locked,
+                                Button::new(i18n::fl!("import_timing_menu")),
+                            )
+                            .clicked()
+                        {
+                            self.editor
+                                .gui_event_queue
+                                .push_back(GuiEvent::ImportTiming)
+                        }
+                        if ui.button(i18n::fl!("append_chart_menu")).clicked() {
+                            self.editor.gui_event_queue.push_back(GuiEvent::AppendChart)
+                        }
+                        if ui.button(i18n::fl!("import_midi_menu")).clicked() {
+                            self.editor.gui_event_queue.push_back(GuiEvent::ImportMidi)
+                        }
+                        if ui
+                            .add_enabled(
+                                self.editor.save_path.is_some(),
+                                Button::new(i18n::fl!("restore_backup_menu")),
+                            )
+                            .clicked()
+                        {
+                            self.editor.backup_restore = self
+                                .editor
+                                .save_path
+                                .as_ref()
+                                .map(|path| list_backups(path, &self.editor.backup_options));
+                        }
                         ui.separator();
                         if ui.button(i18n::fl!("preferences")).clicked() {
                             self.show_preferences = true;
@@ -677,6 +1156,36 @@ impl App for AppState {
                             self.editor.gui_event_queue.push_back(GuiEvent::Redo);
                         }
 
+                        ui.separator();
+                        let has_selection = self
+                            .editor
+                            .cursor_object
+                            .as_ref()
+                            .is_some_and(|x| x.selection().is_some());
+                        for (event, label) in [
+                            (GuiEvent::Copy, fl!("copy")),
+                            (GuiEvent::Cut, fl!("cut")),
+                            (GuiEvent::DeleteSelection, fl!("delete_selection")),
+                            (GuiEvent::SavePattern, fl!("save_pattern")),
+                        ] {
+                            if ui.add_enabled(has_selection, Button::new(label)).clicked() {
+                                self.editor.gui_event_queue.push_back(event);
+                            }
+                        }
+                        if ui
+                            .add_enabled(self.editor.clipboard.is_some(), Button::new(fl!("paste")))
+                            .clicked()
+                        {
+                            self.editor.gui_event_queue.push_back(GuiEvent::Paste);
+                        }
+                        if ui
+                            .button(fl!("mirror"))
+                            .on_hover_text(fl!("mirror_hover"))
+                            .clicked()
+                        {
+                            self.editor.gui_event_queue.push_back(GuiEvent::Mirror);
+                        }
+
                         ui.separator();
                         if ui.button(i18n::fl!("metadata")).clicked() && self.meta_edit.is_none() {
                             self.meta_edit = Some(self.editor.chart.meta.clone());
@@ -686,6 +1195,15 @@ impl App for AppState {
                             self.bgm_edit = Some(self.editor.chart.audio.bgm.clone());
                         }
                         ui.checkbox(&mut self.show_fx_def, fl!("effect_definitions"));
+                        ui.checkbox(&mut self.show_comments, fl!("comments"));
+                        ui.checkbox(&mut self.show_issues, fl!("chart_issues"))
+                            .on_hover_text(fl!("chart_issues_hover"));
+                        ui.checkbox(&mut self.show_stats, fl!("chart_stats"))
+                            .on_hover_text(fl!("chart_stats_hover"));
+                        ui.checkbox(&mut self.show_project, fl!("difficulties"))
+                            .on_hover_text(fl!("difficulties_hover"));
+                        ui.checkbox(&mut self.show_patterns, fl!("patterns"))
+                            .on_hover_text(fl!("patterns_hover"));
 
                         let mut is_fullscreen =
                             ctx.input(|x| x.viewport().fullscreen.is_some_and(|x| x));
@@ -708,8 +1226,17 @@ impl App for AppState {
                 ui.separator();
                 menu::bar(ui, |ui| {
                     for (name, tool) in &TOOLS {
+                        let locked = self.editor.timing_locked
+                            && matches!(tool, ChartTool::BPM | ChartTool::TimeSig);
                         if ui
-                            .selectable_label(self.editor.current_tool == *tool, *name)
+                            .add_enabled(
+                                !locked,
+                                egui::SelectableLabel::new(
+                                    self.editor.current_tool == *tool,
+                                    *name,
+                                ),
+                            )
+                            .on_disabled_hover_text(fl!("timing_locked"))
                             .clicked()
                         {
                             if *tool == self.editor.current_tool {
@@ -723,13 +1250,129 @@ impl App for AppState {
                             }
                         }
                     }
+
+                    ui.separator();
+                    if ui
+                        .selectable_label(self.editor.timing_locked, fl!("lock_timing"))
+                        .on_hover_text(fl!("lock_timing_hover"))
+                        .clicked()
+                    {
+                        self.editor
+                            .gui_event_queue
+                            .push_back(GuiEvent::ToggleTimingLock);
+                    }
+
+                    if ui
+                        .selectable_label(self.editor.visual_metronome, fl!("visual_metronome"))
+                        .on_hover_text(fl!("visual_metronome_hover"))
+                        .clicked()
+                    {
+                        self.editor
+                            .gui_event_queue
+                            .push_back(GuiEvent::ToggleVisualMetronome);
+                    }
+
+                    if ui
+                        .selectable_label(self.editor.show_waveform, fl!("waveform"))
+                        .on_hover_text(fl!("waveform_hover"))
+                        .clicked()
+                    {
+                        self.editor
+                            .gui_event_queue
+                            .push_back(GuiEvent::ToggleWaveform);
+                    }
+
+                    if ui
+                        .selectable_label(self.editor.split_screen.is_some(), fl!("split_view"))
+                        .on_hover_text(fl!("split_view_hover"))
+                        .clicked()
+                    {
+                        self.editor
+                            .gui_event_queue
+                            .push_back(GuiEvent::ToggleSplitView);
+                    }
+
+                    let playtest_hint = if self.editor.game_path.is_none() {
+                        Some(fl!("playtest_no_game"))
+                    } else if self.editor.save_path.is_none() {
+                        Some(fl!("playtest_not_saved"))
+                    } else {
+                        None
+                    };
+                    if ui
+                        .add_enabled(playtest_hint.is_none(), Button::new(fl!("playtest")))
+                        .on_hover_text(fl!("playtest_hover"))
+                        .on_disabled_hover_text(playtest_hint.unwrap_or_default())
+                        .clicked()
+                    {
+                        self.editor.gui_event_queue.push_back(GuiEvent::Playtest);
+                    }
+
+                    ui.separator();
+                    let playing = self.editor.audio_playback.is_playing();
+                    ui.add_enabled_ui(!playing, |ui| {
+                        let mut rate = self.editor.audio_playback.rate();
+                        ComboBox::new("playback_rate", fl!("playback_rate"))
+                            .selected_text(format!("{:.0}%", rate * 100.0))
+                            .show_ui(ui, |ui| {
+                                for option in [0.25, 0.5, 0.75, 1.0] {
+                                    ui.selectable_value(
+                                        &mut rate,
+                                        option,
+                                        format!("{:.0}%", option * 100.0),
+                                    );
+                                }
+                            })
+                            .response
+                            .on_hover_text(fl!("playback_rate_hover"));
+                        self.editor.audio_playback.set_rate(rate);
+                    });
+
+                    ui.separator();
+                    let playback = &mut self.editor.audio_playback;
+                    let (mut volume, mut muted) = (playback.bgm_volume(), playback.bgm_muted());
+                    volume_control(
+                        ui,
+                        fl!("bgm_volume"),
+                        fl!("bgm_volume_hover"),
+                        &mut volume,
+                        &mut muted,
+                    );
+                    playback.set_bgm_volume(volume);
+                    playback.set_bgm_muted(muted);
+
+                    let (mut volume, mut muted) = (playback.fx_volume(), playback.fx_muted());
+                    volume_control(
+                        ui,
+                        fl!("fx_volume"),
+                        fl!("fx_volume_hover"),
+                        &mut volume,
+                        &mut muted,
+                    );
+                    playback.set_fx_volume(volume);
+                    playback.set_fx_muted(muted);
+
+                    let outside_audio = self.editor.notes_outside_audio();
+                    if outside_audio > 0 {
+                        ui.with_layout(Layout::right_to_left(emath::Align::Center), |ui| {
+                            ui.label(
+                                RichText::new(fl!("notes_outside_audio", count = outside_audio))
+                                    .color(Color32::BLACK)
+                                    .background_color(Color32::YELLOW),
+                            )
+                            .on_hover_text(fl!("notes_outside_audio_hover"));
+                        });
+                    }
                 })
             });
         }
 
+        let mut shortcuts = DialogShortcuts::read(ctx);
+
         //stuff
         {
-            let mut open = self.show_preferences;
+            let cancel = self.show_preferences && shortcuts.take().1;
+            let mut open = self.show_preferences && !cancel;
             egui::Window::new(i18n::fl!("preferences"))
                 .open(&mut open)
                 .show(ctx, |ui| {
@@ -741,12 +1384,14 @@ impl App for AppState {
 
             //New chart dialog
             if let Some(new_chart) = &mut self.new_chart {
+                let (confirm, cancel) = shortcuts.take();
                 let mut open = true;
                 let mut event = None;
                 egui::Window::new(i18n::fl!("new"))
                     .open(&mut open)
                     .show(ctx, |ui| {
-                        if new_chart.ui(ui).clicked() {
+                        let ok = new_chart.ui(ui);
+                        if ok.clicked() || (confirm && new_chart.is_valid()) {
                             event = Some(GuiEvent::NewChart(new_chart.clone()));
                         }
                     });
@@ -756,20 +1401,21 @@ impl App for AppState {
                     self.new_chart = None;
                 }
 
-                if !open {
+                if !open || cancel {
                     self.new_chart = None;
                 }
             }
 
             //Metadata dialog
             if let Some(mut meta_edit) = self.meta_edit.take() {
+                let (confirm, cancel) = shortcuts.take();
                 let mut open = true;
                 egui::Window::new(i18n::fl!("metadata"))
                     .open(&mut open)
                     .show(ctx, |ui| {
                         meta_edit.ui(ui);
                         ui.add_space(10.0);
-                        if ui.button(i18n::fl!("ok")).clicked() {
+                        if ui.button(i18n::fl!("ok")).clicked() || confirm {
                             self.editor.actions.new_action(
                                 i18n::fl!("update_metadata"),
                                 move |chart: &mut Chart| {
@@ -781,20 +1427,27 @@ impl App for AppState {
                             self.meta_edit = Some(meta_edit)
                         }
                     });
-                if !open {
+                if !open || cancel {
                     self.meta_edit = None;
                 }
             }
 
             //Music data dialog
             self.bgm_edit = if let Some(mut bgm_edit) = self.bgm_edit.take() {
+                let (confirm, cancel) = shortcuts.take();
                 let mut open = true;
                 egui::Window::new(i18n::fl!("music_info"))
                     .open(&mut open)
                     .show(ctx, |ui| {
-                        bgm_edit.ui(ui);
+                        BgmInfoEdit {
+                            bgm: &mut bgm_edit,
+                            timing_locked: self.editor.timing_locked,
+                            chart_folder: self.editor.save_path.as_deref().and_then(Path::parent),
+                            preview: &mut self.bgm_preview,
+                        }
+                        .ui(ui);
                         ui.add_space(10.0);
-                        if ui.button(i18n::fl!("ok")).clicked() {
+                        if ui.button(i18n::fl!("ok")).clicked() || confirm {
                             let new_bgm = bgm_edit.clone();
                             self.editor.actions.new_action(
                                 i18n::fl!("update_music_info"),
@@ -805,13 +1458,94 @@ impl App for AppState {
                             );
                         }
                     });
-                if open {
+                if open && !cancel {
                     Some(bgm_edit)
                 } else {
+                    self.bgm_preview = None;
                     None
                 }
             } else {
                 None
+            };
+
+            annotation_dialog(&mut self.editor, ctx, &mut shortcuts);
+            key_sound_dialog(&mut self.editor, ctx, &mut shortcuts);
+            midi_import_dialog(&mut self.editor, ctx, &mut shortcuts);
+            save_pattern_dialog(&mut self.editor, ctx, &mut shortcuts);
+            laser_curve_dialog(&mut self.editor, ctx, &mut shortcuts);
+            backup_dialog(&mut self.editor, ctx, &mut shortcuts);
+            recovery_dialog(&mut self.editor, ctx, &mut shortcuts);
+
+            //Background save failure
+            if let Some(error) = self.editor.save_error.clone() {
+                egui::Window::new(i18n::fl!("save_failed"))
+                    .collapsible(false)
+                    .resizable(false)
+                    .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -20.0))
+                    .show(ctx, |ui| {
+                        ui.label(RichText::new(error).color(Color32::LIGHT_RED));
+                        if ui.button(i18n::fl!("ok")).clicked() {
+                            self.editor.save_error = None;
+                        }
+                    });
+            }
+
+            //Timing import warning
+            if let Some(import) = self.editor.timing_import.take() {
+                let (confirm, cancel) = shortcuts.take();
+                let mut apply = false;
+                let mut keep = !cancel;
+                egui::Window::new(i18n::fl!("import_timing"))
+                    .collapsible(false)
+                    .resizable(false)
+                    .show(ctx, |ui| {
+                        ui.label(i18n::fl!(
+                            "import_timing_warning",
+                            count = import.shifted_notes
+                        ));
+                        ui.horizontal(|ui| {
+                            apply = ui.button(i18n::fl!("ok")).clicked() || confirm;
+                            if ui.button(i18n::fl!("cancel")).clicked() {
+                                keep = false;
+                            }
+                        });
+                    });
+
+                if apply {
+                    import.apply(&mut self.editor.actions);
+                } else if keep {
+                    self.editor.timing_import = Some(import);
+                }
+            }
+
+            //Paste over existing notes
+            if let Some(paste) = self.editor.paste_conflict.take() {
+                let (confirm, cancel) = shortcuts.take();
+                let mut resolve = None;
+                let mut keep = !cancel;
+                egui::Window::new(i18n::fl!("paste_conflict"))
+                    .collapsible(false)
+                    .resizable(false)
+                    .show(ctx, |ui| {
+                        ui.label(i18n::fl!("paste_conflict_warning", count = paste.conflicts));
+                        ui.horizontal(|ui| {
+                            if ui.button(i18n::fl!("paste_overwrite")).clicked() || confirm {
+                                resolve = Some(kson::PasteConflict::Overwrite);
+                            }
+                            if ui.button(i18n::fl!("paste_keep_existing")).clicked() {
+                                resolve = Some(kson::PasteConflict::KeepExisting);
+                            }
+                            if ui.button(i18n::fl!("cancel")).clicked() {
+                                keep = false;
+                            }
+                        });
+                    });
+
+                if let Some(conflict) = resolve {
+                    paste.apply(&mut self.editor.actions, conflict);
+                } else if keep {
+                    self.editor.paste_conflict = Some(paste);
+                }
             }
         };
 
@@ -838,44 +1572,43 @@ impl App for AppState {
                     .show(ctx, |ui| ui.add(effect_panel(&mut self.editor)));
             }
 
-            let main_response = egui::CentralPanel::default()
-                .frame(main_frame)
-                .show(ctx, |ui| self.editor.draw(ui))
-                .inner;
+            if self.show_comments {
+                egui::SidePanel::right("annotation_panel")
+                    .show(ctx, |ui| ui.add(annotation_panel(&mut self.editor)));
+            }
 
-            match main_response {
-                Ok(response) => {
-                    let pos = ctx.pointer_hover_pos().unwrap_or(Pos2::ZERO);
-                    if response.hovered() && ctx.input(|x| x.raw_scroll_delta) != Vec2::ZERO {
-                        self.editor
-                            .mouse_wheel_event(ctx.input(|x| x.raw_scroll_delta.y));
-                    }
+            if self.show_issues {
+                egui::SidePanel::right("issues_panel")
+                    .show(ctx, |ui| ui.add(issues_panel(&mut self.editor)));
+            }
 
-                    if response.clicked() {
-                        self.editor.primary_clicked(pos)
-                    }
+            if self.show_stats {
+                egui::SidePanel::right("stats_panel")
+                    .show(ctx, |ui| ui.add(stats_panel(&mut self.editor)));
+            }
 
-                    if response.middle_clicked() {
-                        self.editor.middle_clicked(pos)
-                    }
+            if self.show_project {
+                egui::SidePanel::right("project_panel")
+                    .show(ctx, |ui| ui.add(project_panel(&mut self.editor)));
+            }
 
-                    if response.drag_started()
-                        && ctx.input(|x| x.pointer.button_down(egui::PointerButton::Primary))
-                    {
-                        self.editor.drag_start(
-                            egui::PointerButton::Primary,
-                            pos.x,
-                            pos.y,
-                            &Modifiers::from(ctx.input(|x| x.modifiers)),
-                        )
-                    }
+            if self.show_patterns {
+                egui::SidePanel::right("pattern_panel")
+                    .show(ctx, |ui| ui.add(pattern_panel(&mut self.editor)));
+            }
 
-                    if response.drag_stopped() {
+            let main_response = egui::CentralPanel::default()
+                .frame(main_frame)
+                .show(ctx, |ui| self.editor.draw_views(ui))
+                .inner;
+
+            match main_response {
+                Ok((response, split_response)) => {
+                    chart_view_input(&mut self.editor, ctx, &response);
+                    if let Some(response) = split_response {
                         self.editor
-                            .drag_end(egui::PointerButton::Primary, pos.x, pos.y)
+                            .in_split_view(|editor| chart_view_input(editor, ctx, &response));
                     }
-
-                    response.context_menu(|ui| self.editor.context_menu(ui, ui.min_rect().min));
                 }
                 Err(e) => panic!("{}", e),
             }
@@ -883,23 +1616,25 @@ impl App for AppState {
         //exiting
         {
             if self.exiting {
+                let (confirm, cancel) = shortcuts.take();
                 egui::Window::new(i18n::fl!("unsaved_changes_alert"))
                     .collapsible(false)
                     .resizable(false)
                     .show(ctx, |ui| {
                         ui.horizontal(|ui| {
-                            if ui.button(i18n::fl!("yes")).clicked() {
+                            if ui.button(i18n::fl!("yes")).clicked() || confirm {
                                 self.exiting = false;
-                                if matches!(self.editor.save(), Ok(true)) {
+                                if matches!(self.editor.save_all(), Ok(true)) {
                                     ctx.send_viewport_cmd(ViewportCommand::Close)
                                 }
                             }
                             if ui.button(i18n::fl!("no")).clicked() {
                                 self.exiting = false;
                                 self.editor.actions.save(); //marks as saved but doesn't actually save
+                                self.editor.project = None;
                                 ctx.send_viewport_cmd(ViewportCommand::Close)
                             }
-                            if ui.button(i18n::fl!("cancel")).clicked() {
+                            if ui.button(i18n::fl!("cancel")).clicked() || cancel {
                                 self.exiting = false;
                             }
                         });
@@ -913,7 +1648,45 @@ impl App for AppState {
                 ctx.send_viewport_cmd(ViewportCommand::CancelClose)
             }
         }
+
+        let window_title = self.window_title();
+        if window_title != self.window_title {
+            ctx.send_viewport_cmd(ViewportCommand::Title(window_title.clone()));
+            self.window_title = window_title;
+        }
+    }
+}
+
+/// Passes clicks, drags and scrolling on a view of the chart to the editor.
+fn chart_view_input(editor: &mut MainState, ctx: &egui::Context, response: &Response) {
+    let pos = ctx.pointer_hover_pos().unwrap_or(Pos2::ZERO);
+    if response.hovered() && ctx.input(|x| x.raw_scroll_delta) != Vec2::ZERO {
+        editor.mouse_wheel_event(ctx.input(|x| x.raw_scroll_delta.y));
+    }
+
+    if response.clicked() {
+        editor.primary_clicked(pos)
+    }
+
+    if response.middle_clicked() {
+        editor.middle_clicked(pos)
     }
+
+    if response.drag_started() && ctx.input(|x| x.pointer.button_down(egui::PointerButton::Primary))
+    {
+        editor.drag_start(
+            egui::PointerButton::Primary,
+            pos.x,
+            pos.y,
+            &Modifiers::from(ctx.input(|x| x.modifiers)),
+        )
+    }
+
+    if response.drag_stopped() {
+        editor.drag_end(egui::PointerButton::Primary, pos.x, pos.y)
+    }
+
+    response.context_menu(|ui| editor.context_menu(ui, ui.min_rect().min));
 }
 
 pub fn main() -> eframe::Result<()> {
@@ -948,15 +1721,37 @@ pub fn main() -> eframe::Result<()> {
                 new_chart: None,
                 meta_edit: None,
                 bgm_edit: None,
+                bgm_preview: None,
                 exiting: false,
                 language: config.language,
                 show_fx_def: false,
+                show_comments: false,
+                show_issues: false,
+                show_stats: false,
+                show_project: false,
+                show_patterns: false,
+                font_scale: config.font_scale,
+                window_title: String::new(),
             };
 
             app.key_bindings = config.key_bindings;
             app.editor.screen.track_width = config.track_width;
             app.editor.screen.beats_per_col = config.beats_per_column;
-            cc.egui_ctx.set_visuals(Visuals::dark());
+            app.editor.screen.follow_mode = config.follow_mode;
+            app.editor.kson_options.pretty = config.pretty_kson;
+            app.editor.chart_states = config.chart_states;
+            app.editor.author_name = config.author_name;
+            app.editor.backup_options = config.backups;
+            app.editor.visual_metronome = config.visual_metronome;
+            app.editor.show_waveform = config.show_waveform;
+            app.editor.game_path = config.game_path;
+            app.editor.midi_mapping = config.midi_mapping;
+            app.editor
+                .audio_playback
+                .set_chart_sounds(config.chart_sounds);
+            app.editor.autosave_recovery = find_autosave(app.editor.save_path.as_deref());
+            cc.egui_ctx.set_visuals(editor_visuals());
+            apply_font_scale(&cc.egui_ctx, app.font_scale);
 
             Box::new(app)
         }),
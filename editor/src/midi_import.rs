@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{bail, ensure, Result};
+use eframe::egui::{self, ComboBox, DragValue, Grid};
+use kson::{Chart, Interval, KSON_RESOLUTION};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chart_editor::{MainState, PendingPaste},
+    i18n::fl,
+    DialogShortcuts,
+};
+
+/// Grid divisions per measure imported notes can be snapped to
+pub const QUANTIZE_DIVISIONS: [u32; 8] = [4, 8, 12, 16, 24, 32, 48, 64];
+
+/// MIDI note numbers placed on each lane, defaults to the General MIDI drum kit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MidiLaneMapping {
+    pub bt: [u8; 4],
+    pub fx: [u8; 2],
+}
+
+impl Default for MidiLaneMapping {
+    fn default() -> Self {
+        Self {
+            // Kick, snare, closed hi-hat, low tom
+            bt: [36, 38, 42, 45],
+            // Crash, ride
+            fx: [49, 51],
+        }
+    }
+}
+
+/// A note read from a MIDI file, times in ms from the start of the file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MidiNote {
+    pub key: u8,
+    pub start: f64,
+    pub end: f64,
+}
+
+fn read_u16(data: &[u8]) -> Result<u16> {
+    ensure!(data.len() >= 2, "Unexpected end of MIDI file");
+    Ok(u16::from_be_bytes([data[0], data[1]]))
+}
+
+fn read_u32(data: &[u8]) -> Result<u32> {
+    ensure!(data.len() >= 4, "Unexpected end of MIDI file");
+    Ok(u32::from_be_bytes([data[0], data[1], data[2], data[3]]))
+}
+
+/// Reads a variable length quantity, returning it and the bytes it took up.
+fn read_vlq(data: &[u8]) -> Result<(u32, usize)> {
+    let mut value = 0u32;
+    for (i, byte) in data.iter().take(4).enumerate() {
+        value = (value << 7) | (byte & 0x7f) as u32;
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    bail!("Invalid variable length value in MIDI file")
+}
+
+/// Events of a track the imported notes depend on.
+enum TrackEvent {
+    NoteOn {
+        channel: u8,
+        key: u8,
+    },
+    NoteOff {
+        channel: u8,
+        key: u8,
+    },
+    /// Microseconds per quarter note
+    Tempo(u32),
+}
+
+fn read_track(mut data: &[u8]) -> Result<Vec<(u64, TrackEvent)>> {
+    let mut events = vec![];
+    let mut tick = 0u64;
+    let mut running_status = None;
+
+    while !data.is_empty() {
+        let (delta, len) = read_vlq(data)?;
+        data = &data[len..];
+        tick += delta as u64;
+
+        ensure!(!data.is_empty(), "Unexpected end of MIDI track");
+        let status = if data[0] & 0x80 != 0 {
+            let status = data[0];
+            data = &data[1..];
+            status
+        } else {
+            running_status.ok_or_else(|| anyhow::anyhow!("MIDI data without status"))?
+        };
+
+        match status {
+            0xff => {
+                ensure!(!data.is_empty(), "Unexpected end of MIDI track");
+                let kind = data[0];
+                let (len, vlq_len) = read_vlq(&data[1..])?;
+                let start = 1 + vlq_len;
+                let end = start + len as usize;
+                ensure!(data.len() >= end, "Unexpected end of MIDI track");
+                if kind == 0x51 && len == 3 {
+                    let tempo =
+                        u32::from_be_bytes([0, data[start], data[start + 1], data[start + 2]]);
+                    events.push((tick, TrackEvent::Tempo(tempo)));
+                }
+                data = &data[end..];
+                if kind == 0x2f {
+                    break;
+                }
+            }
+            0xf0 | 0xf7 => {
+                let (len, vlq_len) = read_vlq(data)?;
+                let end = vlq_len + len as usize;
+                ensure!(data.len() >= end, "Unexpected end of MIDI track");
+                data = &data[end..];
+            }
+            _ => {
+                running_status = Some(status);
+                let data_len = match status & 0xf0 {
+                    0xc0 | 0xd0 => 1,
+                    _ => 2,
+                };
+                ensure!(data.len() >= data_len, "Unexpected end of MIDI track");
+                let channel = status & 0x0f;
+                let key = data[0];
+                match status & 0xf0 {
+                    0x90 if data[1] > 0 => events.push((tick, TrackEvent::NoteOn { channel, key })),
+                    0x80 | 0x90 => events.push((tick, TrackEvent::NoteOff { channel, key })),
+                    _ => {}
+                }
+                data = &data[data_len..];
+            }
+        }
+    }
+
+    Ok(events)
+}
+
+/// Reads the notes of all tracks in a standard MIDI file.
+pub fn read_midi(data: &[u8]) -> Result<Vec<MidiNote>> {
+    ensure!(data.starts_with(b"MThd"), "Not a MIDI file");
+    let header_len = read_u32(&data[4..])? as usize;
+    let header = data
+        .get(8..8 + header_len)
+        .ok_or_else(|| anyhow::anyhow!("Unexpected end of MIDI file"))?;
+    let division = read_u16(header.get(4..).unwrap_or_default())?;
+    ensure!(
+        division & 0x8000 == 0,
+        "SMPTE timed MIDI files are not supported"
+    );
+    let ticks_per_quarter = division.max(1) as f64;
+
+    let mut events = vec![];
+    let mut rest = &data[8 + header_len..];
+    while rest.len() >= 8 {
+        let len = read_u32(&rest[4..])? as usize;
+        let track = rest
+            .get(8..8 + len)
+            .ok_or_else(|| anyhow::anyhow!("Unexpected end of MIDI file"))?;
+        if rest.starts_with(b"MTrk") {
+            events.extend(read_track(track)?);
+        }
+        rest = &rest[8 + len..];
+    }
+    // Stable, so events at the same tick stay in track order
+    events.sort_by_key(|(tick, _)| *tick);
+
+    let mut notes = vec![];
+    let mut held: HashMap<(u8, u8), f64> = HashMap::new();
+    let (mut tempo_tick, mut tempo_ms) = (0u64, 0.0);
+    let mut ms_per_tick = 500.0 / ticks_per_quarter;
+
+    for (tick, event) in events {
+        let ms = tempo_ms + (tick - tempo_tick) as f64 * ms_per_tick;
+        match event {
+            TrackEvent::Tempo(tempo) => {
+                tempo_tick = tick;
+                tempo_ms = ms;
+                ms_per_tick = tempo as f64 / 1000.0 / ticks_per_quarter;
+            }
+            TrackEvent::NoteOn { channel, key } => {
+                held.entry((channel, key)).or_insert(ms);
+            }
+            TrackEvent::NoteOff { channel, key } => {
+                if let Some(start) = held.remove(&(channel, key)) {
+                    notes.push(MidiNote {
+                        key,
+                        start,
+                        end: ms,
+                    });
+                }
+            }
+        }
+    }
+
+    notes.sort_by(|a, b| a.start.total_cmp(&b.start));
+    Ok(notes)
+}
+
+/// Notes read from a MIDI file waiting to be placed in the chart.
+pub struct MidiImport {
+    pub notes: Vec<MidiNote>,
+    pub filename: String,
+    /// Grid divisions per measure the notes are snapped to
+    pub division: u32,
+}
+
+impl MidiImport {
+    pub fn open(path: &Path) -> Result<Self> {
+        let notes = read_midi(&std::fs::read(path)?)?;
+        Ok(Self {
+            notes,
+            filename: path
+                .file_name()
+                .map(|x| x.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            division: 16,
+        })
+    }
+
+    /// The notes on mapped keys as a clip to paste at tick 0, with the start of the MIDI file at
+    /// the start of the audio. Notes longer than a grid step become holds.
+    pub fn clip(&self, chart: &Chart, mapping: &MidiLaneMapping) -> Chart {
+        let grid = (KSON_RESOLUTION * 4 / self.division).max(1);
+        let offset = chart.audio.bgm.offset as f64;
+        let snap = |ms: f64| {
+            let tick = chart.ms_to_tick(ms + offset);
+            (tick + grid / 2) / grid * grid
+        };
+
+        let mut clip = Chart::new();
+        let lanes = clip
+            .note
+            .bt
+            .iter_mut()
+            .zip(mapping.bt)
+            .chain(clip.note.fx.iter_mut().zip(mapping.fx));
+        for (lane, key) in lanes {
+            let mut notes: Vec<Interval> = self
+                .notes
+                .iter()
+                .filter(|x| x.key == key)
+                .map(|x| {
+                    let y = snap(x.start);
+                    let l = snap(x.end).saturating_sub(y);
+                    Interval {
+                        y,
+                        l: if l > grid { l } else { 0 },
+                    }
+                })
+                .collect();
+            notes.sort_by_key(|x| x.y);
+            notes.dedup_by_key(|x| x.y);
+
+            // Holds are cut short at the next note on the lane
+            for i in 1..notes.len() {
+                let next = notes[i].y;
+                let prev = &mut notes[i - 1];
+                if prev.y + prev.l > next {
+                    prev.l = next - prev.y;
+                }
+            }
+            *lane = notes;
+        }
+        clip
+    }
+
+    /// Notes that are not on a mapped key.
+    pub fn unmapped(&self, mapping: &MidiLaneMapping) -> usize {
+        self.notes
+            .iter()
+            .filter(|x| !mapping.bt.contains(&x.key) && !mapping.fx.contains(&x.key))
+            .count()
+    }
+}
+
+/// Shows the lane mapping dialog of a MIDI import if one is open.
+pub fn midi_import_dialog(
+    state: &mut MainState,
+    ctx: &egui::Context,
+    shortcuts: &mut DialogShortcuts,
+) {
+    let Some(mut import) = state.midi_import.take() else {
+        return;
+    };
+    let (confirm, cancel) = shortcuts.take();
+
+    let mut open = true;
+    let mut done = false;
+    let mut cancelled = cancel;
+    egui::Window::new(fl!("import_midi"))
+        .id(egui::Id::new("midi_import_dialog"))
+        .open(&mut open)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label(&import.filename);
+            Grid::new("midi_lane_mapping").show(ui, |ui| {
+                let mapping = &mut state.midi_mapping;
+                for (i, key) in mapping.bt.iter_mut().enumerate() {
+                    ui.label(format!("BT {}", (b'A' + i as u8) as char));
+                    ui.add(DragValue::new(key).clamp_range(0..=127));
+                    ui.end_row();
+                }
+                for (i, key) in mapping.fx.iter_mut().enumerate() {
+                    ui.label(format!("FX {}", if i == 0 { 'L' } else { 'R' }));
+                    ui.add(DragValue::new(key).clamp_range(0..=127));
+                    ui.end_row();
+                }
+
+                ui.label(fl!("midi_quantize"));
+                ComboBox::from_id_source("midi_quantize")
+                    .selected_text(format!("1/{}", import.division))
+                    .show_ui(ui, |ui| {
+                        for division in QUANTIZE_DIVISIONS {
+                            ui.selectable_value(
+                                &mut import.division,
+                                division,
+                                format!("1/{}", division),
+                            );
+                        }
+                    });
+                ui.end_row();
+            });
+
+            ui.label(fl!(
+                "midi_notes",
+                count = import.notes.len(),
+                unmapped = import.unmapped(&state.midi_mapping)
+            ));
+
+            ui.add_space(10.0);
+            ui.horizontal(|ui| {
+                done = ui.button(fl!("ok")).clicked() || confirm;
+                if ui.button(fl!("cancel")).clicked() {
+                    cancelled = true;
+                }
+            });
+        });
+
+    if done {
+        let clip = import.clip(&state.chart, &state.midi_mapping);
+        let conflicts = state.chart.paste_conflicts(&clip, 0);
+        if conflicts == 0 {
+            state
+                .actions
+                .new_action(fl!("import_midi"), move |c: &mut Chart| {
+                    c.paste(&clip, 0);
+                    Ok(())
+                });
+        } else {
+            state.paste_conflict = Some(PendingPaste {
+                clip,
+                tick: 0,
+                conflicts,
+                action: fl!("import_midi"),
+            });
+        }
+    } else if open && !cancelled {
+        state.midi_import = Some(import);
+    }
+}
@@ -1,38 +1,30 @@
-use std::str::FromStr;
-
 use eframe::egui::{self, Widget};
-use kson::parameter::EffectParameter;
 
-type GetSetValue<'a, T> = Box<dyn 'a + FnMut(Option<EffectParameter<T>>) -> EffectParameter<T>>;
+type GetSetValue<'a> = Box<dyn 'a + FnMut(Option<&str>) -> String>;
 
-pub struct ParamEditor<'a, T> {
-    get_set_value: GetSetValue<'a, T>,
+/// Text field for a parameter in its chart notation, the value is only applied once the
+/// field loses focus and the text parses.
+pub struct ParamEditor<'a> {
+    get_set_value: GetSetValue<'a>,
 }
 
-impl<'a, T: Clone> ParamEditor<'a, T> {
-    pub fn new(value: &'a mut EffectParameter<T>, _allow_filename: bool) -> Self {
+impl<'a> ParamEditor<'a> {
+    pub fn new(
+        mut get: impl 'a + FnMut() -> String,
+        mut set: impl 'a + FnMut(&str) -> bool,
+    ) -> Self {
         Self {
-            get_set_value: Box::new(move |v: Option<EffectParameter<T>>| {
-                //TOOO: Check for filename
+            get_set_value: Box::new(move |v: Option<&str>| {
                 if let Some(v) = v {
-                    *value = v;
+                    set(v);
                 }
-                value.clone()
+                get()
             }),
         }
     }
 }
 
-#[allow(unused)]
-fn is_filename<T>(v: &EffectParameter<T>) -> bool {
-    matches!(
-        (&v.off, &v.on),
-        (kson::parameter::EffectParameterValue::Filename(_), _)
-            | (_, Some(kson::parameter::EffectParameterValue::Filename(_)))
-    )
-}
-
-impl<'a, T: Default + 'static> Widget for ParamEditor<'a, T> {
+impl<'a> Widget for ParamEditor<'a> {
     fn ui(self, ui: &mut eframe::egui::Ui) -> eframe::egui::Response {
         let Self { mut get_set_value } = self;
 
@@ -41,29 +33,29 @@ impl<'a, T: Default + 'static> Widget for ParamEditor<'a, T> {
         let old_value = get_set_value(None);
         let mut value_text = ui
             .data_mut(|x| x.remove_temp::<String>(id))
-            .unwrap_or_else(|| old_value.to_string());
+            .unwrap_or_else(|| old_value.clone());
         let response = ui.text_edit_singleline(&mut value_text);
 
         ui.data_mut(|d| d.insert_temp(id, value_text));
 
         if response.lost_focus() {
             if let Some(value) = ui.data_mut(|d| d.remove_temp::<String>(id)) {
-                get_set_value(EffectParameter::<T>::from_str(&value).ok());
+                get_set_value(Some(&value));
             }
         }
 
         if !response.has_focus() {
-            ui.data_mut(|d| d.insert_temp(id, old_value.to_string()));
+            ui.data_mut(|d| d.insert_temp(id, old_value));
         }
 
         response
     }
 }
 
-pub fn param_editor<T: Clone + Default + 'static>(
-    param: &mut EffectParameter<T>,
-    allow_filename: bool,
-) -> impl egui::Widget + '_ {
-    let editor = ParamEditor::new(param, allow_filename);
+pub fn param_editor<'a>(
+    get: impl 'a + FnMut() -> String,
+    set: impl 'a + FnMut(&str) -> bool,
+) -> impl egui::Widget + 'a {
+    let editor = ParamEditor::new(get, set);
     move |ui: &mut egui::Ui| ui.add(editor)
 }
@@ -0,0 +1,337 @@
+//! Note and laser patterns saved from a selection, kept in the app data folder so they can be
+//! inserted into any chart.
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use eframe::egui::{
+    self, pos2, vec2, Button, Color32, ComboBox, Rect, Response, RichText, Sense, Shape, Stroke,
+};
+use kson::{Chart, Interval, KSON_RESOLUTION};
+use serde::{Deserialize, Serialize};
+
+use crate::{chart_editor::MainState, i18n::fl, midi_import::QUANTIZE_DIVISIONS, DialogShortcuts};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Pattern {
+    pub name: String,
+    pub tags: Vec<String>,
+    /// Ticks from the start of the selection to its end
+    pub length: u32,
+    /// Notes and lasers, starting at tick 0
+    pub clip: Chart,
+}
+
+impl Pattern {
+    fn matches(&self, filter: &str) -> bool {
+        let filter = filter.to_lowercase();
+        filter.split_whitespace().all(|word| {
+            self.name.to_lowercase().contains(word)
+                || self.tags.iter().any(|x| x.to_lowercase().contains(word))
+        })
+    }
+
+    /// The clip with notes and lasers snapped to `division` steps per measure.
+    pub fn quantized(&self, division: u32) -> Chart {
+        let grid = (KSON_RESOLUTION * 4 / division).max(1);
+        let snap = |tick: u32| (tick + grid / 2) / grid * grid;
+
+        let mut clip = self.clip.clone();
+        for lane in clip.note.bt.iter_mut().chain(clip.note.fx.iter_mut()) {
+            for note in lane.iter_mut() {
+                let y = snap(note.y);
+                let end = snap(note.y + note.l);
+                *note = Interval {
+                    y,
+                    l: if note.l == 0 { 0 } else { (end - y).max(grid) },
+                };
+            }
+            lane.dedup_by_key(|x| x.y);
+            // Holds are cut short at the next note on the lane
+            for i in 1..lane.len() {
+                let next = lane[i].y;
+                let prev = &mut lane[i - 1];
+                if prev.y + prev.l > next {
+                    prev.l = next - prev.y;
+                }
+            }
+        }
+        for lane in &mut clip.note.laser {
+            for section in lane.iter_mut() {
+                let y = snap(section.0);
+                for point in &mut section.1 {
+                    point.ry = snap(section.0 + point.ry).saturating_sub(y);
+                }
+                section.1.dedup_by_key(|x| x.ry);
+                section.0 = y;
+            }
+            lane.retain(|x| !x.1.is_empty());
+            lane.dedup_by_key(|x| x.0);
+        }
+        clip
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct PatternLibrary {
+    pub patterns: Vec<Pattern>,
+}
+
+impl PatternLibrary {
+    fn path() -> Option<PathBuf> {
+        directories_next::ProjectDirs::from("", "", "KSON Editor")
+            .map(|x| x.data_dir().join("patterns.json"))
+    }
+
+    pub fn load() -> Self {
+        let Some(path) = Self::path().filter(|x| x.exists()) else {
+            return Self::default();
+        };
+        match std::fs::read(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|x| Ok(serde_json::from_slice(&x)?))
+        {
+            Ok(library) => library,
+            Err(e) => {
+                log::warn!("Failed to read pattern library {}: {e}", path.display());
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if let Some(folder) = path.parent() {
+            std::fs::create_dir_all(folder)?;
+        }
+        std::fs::write(path, serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+}
+
+/// A selection being saved as a pattern.
+pub struct NewPattern {
+    pub name: String,
+    /// Separated by commas
+    pub tags: String,
+    pub length: u32,
+    pub clip: Chart,
+}
+
+impl NewPattern {
+    /// Keeps the notes and lasers of a copied selection.
+    pub fn new(selection: Chart, length: u32) -> Self {
+        let mut clip = Chart::new();
+        clip.note = selection.note;
+        Self {
+            name: String::new(),
+            tags: String::new(),
+            length,
+            clip,
+        }
+    }
+}
+
+pub struct PatternPanel {
+    pub library: PatternLibrary,
+    pub filter: String,
+    /// Grid divisions per measure inserted patterns are snapped to
+    pub division: u32,
+    pub new_pattern: Option<NewPattern>,
+}
+
+impl Default for PatternPanel {
+    fn default() -> Self {
+        Self {
+            library: PatternLibrary::load(),
+            filter: String::new(),
+            division: 16,
+            new_pattern: None,
+        }
+    }
+}
+
+fn pattern_preview(ui: &mut egui::Ui, pattern: &Pattern, laser_colors: [Color32; 2]) -> Response {
+    let (rect, response) = ui.allocate_exact_size(vec2(36.0, 64.0), Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 0.0, Color32::BLACK);
+
+    let length = pattern.length.max(1) as f32;
+    let y = |tick: u32| rect.bottom() - tick as f32 / length * rect.height();
+    let lane_width = rect.width() / 6.0;
+    let note_rect = |x: f32, w: f32, note: &Interval| {
+        let bottom = y(note.y);
+        Rect::from_x_y_ranges(x..=x + w, y(note.y + note.l).min(bottom - 2.0)..=bottom)
+    };
+
+    let note = &pattern.clip.note;
+    for (i, lane) in note.fx.iter().enumerate() {
+        let x = rect.left() + lane_width * (1.0 + 2.0 * i as f32);
+        for n in lane {
+            painter.rect_filled(
+                note_rect(x, lane_width * 2.0 - 1.0, n),
+                0.0,
+                Color32::from_rgb(255, 77, 0),
+            );
+        }
+    }
+    for (i, lane) in note.bt.iter().enumerate() {
+        let x = rect.left() + lane_width * (1.0 + i as f32);
+        for n in lane {
+            painter.rect_filled(note_rect(x, lane_width - 1.0, n), 0.0, Color32::WHITE);
+        }
+    }
+    for (lane, color) in note.laser.iter().zip(laser_colors) {
+        for section in lane {
+            let points = section
+                .1
+                .iter()
+                .flat_map(|p| {
+                    let y = y(section.0 + p.ry);
+                    let x = |v: f64| rect.left() + v as f32 * rect.width();
+                    [pos2(x(p.v), y), pos2(x(p.vf.unwrap_or(p.v)), y)]
+                })
+                .collect();
+            painter.add(Shape::line(points, Stroke::new(1.5, color)));
+        }
+    }
+
+    response
+}
+
+/// Lists the saved patterns and inserts them at the cursor.
+pub fn pattern_panel(state: &mut MainState) -> impl egui::Widget + '_ {
+    move |ui: &mut egui::Ui| {
+        let heading = ui.heading(fl!("patterns"));
+        ui.horizontal(|ui| {
+            ui.label(fl!("filter"));
+            ui.text_edit_singleline(&mut state.patterns.filter);
+        });
+        ComboBox::from_label(fl!("pattern_quantize"))
+            .selected_text(format!("1/{}", state.patterns.division))
+            .show_ui(ui, |ui| {
+                for division in QUANTIZE_DIVISIONS {
+                    ui.selectable_value(
+                        &mut state.patterns.division,
+                        division,
+                        format!("1/{}", division),
+                    );
+                }
+            });
+        ui.separator();
+
+        let mut insert = None;
+        let mut remove = None;
+        let panel = &state.patterns;
+        if panel.library.patterns.is_empty() {
+            ui.label(RichText::new(fl!("no_patterns")).weak());
+        }
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for (i, pattern) in panel.library.patterns.iter().enumerate() {
+                if !pattern.matches(&panel.filter) {
+                    continue;
+                }
+                ui.horizontal(|ui| {
+                    pattern_preview(ui, pattern, state.laser_colors);
+                    ui.vertical(|ui| {
+                        ui.label(&pattern.name);
+                        ui.label(
+                            RichText::new(fl!(
+                                "pattern_length",
+                                beats = pattern.length as f64 / KSON_RESOLUTION as f64
+                            ))
+                            .weak(),
+                        );
+                        if !pattern.tags.is_empty() {
+                            ui.label(RichText::new(pattern.tags.join(", ")).weak());
+                        }
+                        ui.horizontal(|ui| {
+                            if ui.button(fl!("insert")).clicked() {
+                                insert = Some(pattern.quantized(panel.division));
+                            }
+                            if ui.button(fl!("remove")).clicked() {
+                                remove = Some(i);
+                            }
+                        });
+                    });
+                });
+                ui.separator();
+            }
+        });
+
+        if let Some(clip) = insert {
+            state.paste_at_cursor(clip, fl!("insert_pattern"));
+        }
+        if let Some(i) = remove {
+            state.patterns.library.patterns.remove(i);
+            if let Err(e) = state.patterns.library.save() {
+                log::warn!("Failed to save pattern library: {e}");
+            }
+        }
+
+        heading
+    }
+}
+
+/// Shows the name and tags dialog of a selection being saved as a pattern.
+pub fn save_pattern_dialog(
+    state: &mut MainState,
+    ctx: &egui::Context,
+    shortcuts: &mut DialogShortcuts,
+) {
+    let Some(mut new_pattern) = state.patterns.new_pattern.take() else {
+        return;
+    };
+    let (confirm, cancel) = shortcuts.take();
+
+    let mut open = true;
+    let mut done = false;
+    egui::Window::new(fl!("save_pattern"))
+        .id(egui::Id::new("save_pattern_dialog"))
+        .open(&mut open)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            egui::Grid::new("save_pattern").show(ui, |ui| {
+                ui.label(fl!("pattern_name"));
+                let name = ui.text_edit_singleline(&mut new_pattern.name);
+                crate::focus_if_unfocused(ui, &name);
+                ui.end_row();
+                ui.label(fl!("pattern_tags"))
+                    .on_hover_text(fl!("pattern_tags_hover"));
+                ui.text_edit_singleline(&mut new_pattern.tags);
+                ui.end_row();
+            });
+            ui.add_space(10.0);
+            let valid = !new_pattern.name.trim().is_empty();
+            done = (ui.add_enabled(valid, Button::new(fl!("ok"))).clicked() || confirm) && valid;
+        });
+
+    if done {
+        let NewPattern {
+            name,
+            tags,
+            length,
+            clip,
+        } = new_pattern;
+        let library = &mut state.patterns.library;
+        library.patterns.push(Pattern {
+            name: name.trim().to_string(),
+            tags: tags
+                .split(',')
+                .map(str::trim)
+                .filter(|x| !x.is_empty())
+                .map(String::from)
+                .collect(),
+            length,
+            clip,
+        });
+        library.patterns.sort_by_key(|x| x.name.to_lowercase());
+        if let Err(e) = library.save() {
+            log::warn!("Failed to save pattern library: {e}");
+        }
+    } else if open && !cancel {
+        state.patterns.new_pattern = Some(new_pattern);
+    }
+}
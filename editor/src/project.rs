@@ -0,0 +1,166 @@
+//! The difficulties of a song are the charts in the same folder. Each keeps its own undo history
+//! while switching between them, until a chart from another folder is opened.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use eframe::egui::{self, Button, RichText};
+use kson::{Chart, MetaInfo};
+
+use crate::{action_stack::ActionStack, chart_editor::MainState, i18n::fl};
+
+const DIFFICULTY_NAMES: [&str; 4] = ["Light", "Challenge", "Extended", "Infinite"];
+
+pub struct ProjectChart {
+    pub path: PathBuf,
+    pub difficulty: u8,
+    pub level: u8,
+    /// Edits of the difficulty, `None` until it has been opened
+    pub actions: Option<ActionStack<Chart>>,
+}
+
+impl ProjectChart {
+    /// The edits of the difficulty, reading it from its file the first time.
+    pub fn actions(&mut self) -> Result<&mut ActionStack<Chart>> {
+        let actions = match self.actions.take() {
+            Some(actions) => actions,
+            None => ActionStack::new(Chart::from_file(&self.path)?),
+        };
+        Ok(self.actions.insert(actions))
+    }
+
+    fn saved(&self) -> bool {
+        self.actions.as_ref().is_none_or(|x| x.saved())
+    }
+}
+
+pub struct Project {
+    pub folder: PathBuf,
+    /// Sorted by difficulty and level
+    pub charts: Vec<ProjectChart>,
+}
+
+impl Project {
+    /// Lists the charts in the folder of `chart_path`. KSH charts with a KSON chart of the same
+    /// name next to them are left out, unless it is the open one.
+    pub fn scan(chart_path: &Path) -> Option<Self> {
+        let folder = chart_path.parent()?;
+        let paths: Vec<PathBuf> = std::fs::read_dir(folder)
+            .ok()?
+            .filter_map(|x| x.ok())
+            .map(|x| x.path())
+            .filter(|x| {
+                x.extension().and_then(|x| x.to_str()).is_some_and(|x| {
+                    x.eq_ignore_ascii_case("kson") || x.eq_ignore_ascii_case("ksh")
+                })
+            })
+            .collect();
+
+        let mut charts: Vec<ProjectChart> = paths
+            .iter()
+            .filter(|x| {
+                *x == chart_path
+                    || x.extension().is_some_and(|x| x == "kson")
+                    || !paths.contains(&x.with_extension("kson"))
+            })
+            .filter_map(|path| match Chart::from_file(path) {
+                Ok(chart) => Some(ProjectChart {
+                    path: path.clone(),
+                    difficulty: chart.meta.difficulty,
+                    level: chart.meta.level,
+                    actions: None,
+                }),
+                Err(e) => {
+                    log::warn!("Failed to read {}: {e}", path.display());
+                    None
+                }
+            })
+            .collect();
+        charts.sort_by(|a, b| {
+            (a.difficulty, a.level, &a.path).cmp(&(b.difficulty, b.level, &b.path))
+        });
+
+        Some(Self {
+            folder: folder.to_path_buf(),
+            charts,
+        })
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.charts.iter().any(|x| x.path == path)
+    }
+
+    /// Whether a difficulty other than the open one has unsaved changes.
+    pub fn unsaved(&self) -> bool {
+        !self.charts.iter().all(ProjectChart::saved)
+    }
+}
+
+/// Copies the fields describing the song rather than the chart.
+pub fn copy_song_meta(from: &MetaInfo, to: &mut MetaInfo) {
+    to.title = from.title.clone();
+    to.title_img_filename = from.title_img_filename.clone();
+    to.subtitle = from.subtitle.clone();
+    to.artist = from.artist.clone();
+    to.artist_img_filename = from.artist_img_filename.clone();
+    to.disp_bpm = from.disp_bpm.clone();
+    to.std_bpm = from.std_bpm;
+}
+
+/// Lists the difficulties of the song and switches between them.
+pub fn project_panel(state: &mut MainState) -> impl egui::Widget + '_ {
+    move |ui: &mut egui::Ui| {
+        let heading = ui.heading(fl!("difficulties"));
+        let Some(project) = &state.project else {
+            ui.label(RichText::new(fl!("difficulties_unsaved")).weak());
+            return heading;
+        };
+
+        let mut switch_to = None;
+        for chart in &project.charts {
+            let open = state.save_path.as_ref() == Some(&chart.path);
+            let (difficulty, level, saved) = if open {
+                let meta = &state.chart.meta;
+                (meta.difficulty, meta.level, state.actions.saved())
+            } else {
+                (chart.difficulty, chart.level, chart.saved())
+            };
+
+            let mut label = fl!(
+                "difficulty_entry",
+                difficulty = DIFFICULTY_NAMES[(difficulty as usize).min(3)],
+                level = level,
+                file = chart
+                    .path
+                    .file_name()
+                    .map(|x| x.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            );
+            if !saved {
+                label.push('*');
+            }
+            if ui.selectable_label(open, label).clicked() && !open {
+                switch_to = Some(chart.path.clone());
+            }
+        }
+
+        ui.separator();
+        let copy = ui
+            .add_enabled(project.charts.len() > 1, Button::new(fl!("copy_song_info")))
+            .on_hover_text(fl!("copy_song_info_hover"))
+            .clicked();
+
+        let result = if let Some(path) = switch_to {
+            state.switch_difficulty(&path)
+        } else if copy {
+            state.copy_song_info()
+        } else {
+            Ok(())
+        };
+        if let Err(e) = result {
+            log::warn!("{e}");
+        }
+
+        heading
+    }
+}
@@ -0,0 +1,84 @@
+use eframe::egui::{self, RichText};
+
+use crate::{
+    chart_editor::MainState,
+    i18n::{self, fl},
+};
+
+fn percent(value: f64) -> String {
+    format!("{:.1}%", value * 100.0)
+}
+
+/// Shows playability statistics and the max chain for the chart, see [`kson::Chart::stats`].
+pub fn stats_panel(state: &mut MainState) -> impl egui::Widget + '_ {
+    move |ui: &mut egui::Ui| {
+        let heading = ui.heading(i18n::fl!("chart_stats"));
+        let stats = state.chart.stats();
+        let score = kson::score_ticks::ScoreCalculator::new(&state.chart);
+
+        egui::Grid::new("chart_stats")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label(fl!("max_chain"));
+                ui.label(score.max_chain().to_string());
+                ui.end_row();
+
+                ui.label(fl!("max_ex_score"));
+                ui.label(score.max_ex_score().to_string());
+                ui.end_row();
+
+                ui.label(fl!("hold_uptime"));
+                ui.label(percent(stats.hold_uptime));
+                ui.end_row();
+
+                ui.label(fl!("laser_uptime"));
+                ui.label(percent(stats.laser_uptime));
+                ui.end_row();
+
+                ui.label(fl!("both_hands"))
+                    .on_hover_text(fl!("both_hands_hover"));
+                ui.label(percent(stats.both_hands));
+                ui.end_row();
+
+                ui.label(fl!("hand_balance"));
+                ui.label(fl!(
+                    "hand_balance_value",
+                    left = stats.hand_notes[0],
+                    right = stats.hand_notes[1],
+                    share = percent(stats.hand_balance())
+                ));
+                ui.end_row();
+            });
+
+        ui.separator();
+        ui.strong(fl!("one_hand_trills"));
+        if stats.trills.is_empty() {
+            ui.label(RichText::new(fl!("no_one_hand_trills")).weak());
+            return heading;
+        }
+
+        let mut jump_to = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for trill in &stats.trills {
+                ui.horizontal(|ui| {
+                    let measure = state.chart.tick_to_measure(trill.y) + 1;
+                    ui.label(fl!(
+                        "one_hand_trill",
+                        measure = measure,
+                        side = format!("{:?}", trill.side),
+                        notes = trill.notes
+                    ));
+                    if ui.button(fl!("go_to")).clicked() {
+                        jump_to = Some(trill.y);
+                    }
+                });
+            }
+        });
+
+        if let Some(tick) = jump_to {
+            state.go_to_tick(tick);
+        }
+
+        heading
+    }
+}
@@ -236,7 +236,15 @@ impl CursorObject for CameraTool {
             angle: -45.0 - 14.0 * self.angle,
             fov: 70.0,
             radius: (-self.radius + 3.1) / 2.0,
-            tilt: 0.0,
+            tilt: state
+                .chart
+                .camera
+                .cam
+                .pattern
+                .laser
+                .slam_event
+                .spin_at(cursor_tick)
+                .map_or(0.0, |x| x.roll() as f32),
             track_length: 16.0,
         };
 
@@ -1,12 +1,12 @@
 use crate::i18n;
 use crate::tools::CursorObject;
-use crate::Modifiers;
 use crate::{
     action_stack::ActionStack,
     chart_editor::{MainState, ScreenState},
 };
-use anyhow::Result;
-use eframe::egui::{Painter, Pos2, Rgba, Stroke};
+use crate::{DialogShortcuts, Modifiers};
+use anyhow::{anyhow, Result};
+use eframe::egui::{self, Color32, Painter, Pos2, Rgba, Slider, Stroke};
 use eframe::epaint::Shape;
 use kson::{overlaps::Overlaps, Chart, GraphSectionPoint, LaserSection};
 
@@ -47,9 +47,24 @@ impl LaserTool {
         }
     }
 
+    /// Laser value under the cursor. Wide sections span twice the width of the track so the
+    /// track only covers values from 0.25 to 0.75.
     fn lane_to_pos(lane: f32, wide: u8) -> f64 {
-        let resolution: f64 = 10.0 * wide as f64;
-        math::round::floor(resolution * lane as f64 / 6.0, 0) / resolution
+        let pos = math::round::floor(10.0 * lane as f64 / 6.0, 0) / 10.0;
+        if wide == 2 {
+            pos / 2.0 + 0.25
+        } else {
+            pos
+        }
+    }
+
+    /// Values at the edges of the track for the section being edited
+    fn value_bounds(&self) -> (f32, f32) {
+        if self.section.wide() == 2 {
+            (0.25, 0.75)
+        } else {
+            (0.0, 1.0)
+        }
     }
 
     fn get_second_to_last(&self) -> Option<&GraphSectionPoint> {
@@ -172,7 +187,7 @@ impl CursorObject for LaserTool {
                         if let Some(control_point) = screen.get_control_point_pos_section(
                             points,
                             self.section.tick(),
-                            (0.0, 1.0),
+                            self.value_bounds(),
                             Some((0.5 / 6.0, 5.5 / 6.0)),
                         ) {
                             if control_point.distance(pos) < 5.0 {
@@ -280,6 +295,7 @@ impl CursorObject for LaserTool {
             LaserEditMode::None => {}
             LaserEditMode::Edit(edit_state) => {
                 if let Some(curving_index) = edit_state.curving_index {
+                    let wide = self.section.wide() == 2;
                     let end_point = self.section.1[curving_index + 1];
                     let point = &mut self.section.1[curving_index];
                     let start_tick = (self.section.0 + point.ry) as f64;
@@ -287,10 +303,15 @@ impl CursorObject for LaserTool {
                     point.a = ((tick_f - start_tick) / (end_tick - start_tick)).clamp(0.0, 1.0);
 
                     let start_value = point.vf.unwrap_or(point.v);
-                    let in_value = lane as f64 / 5.0 - 0.5 / 6.0;
-                    let value = (in_value - start_value) / (end_point.v - start_value);
-
-                    self.section.1[curving_index].b = value.clamp(0.0, 1.0);
+                    let mut in_value = lane as f64 / 5.0 - 0.5 / 6.0;
+                    if wide {
+                        in_value = in_value / 2.0 + 0.25;
+                    }
+                    // A flat segment has no value range to curve within
+                    if (end_point.v - start_value).abs() > f64::EPSILON {
+                        let value = (in_value - start_value) / (end_point.v - start_value);
+                        self.section.1[curving_index].b = value.clamp(0.0, 1.0);
+                    }
                 }
             }
         }
@@ -338,7 +359,7 @@ impl CursorObject for LaserTool {
                     if let Some(pos) = state.screen.get_control_point_pos_section(
                         start_end,
                         self.section.tick(),
-                        (0.0, 1.0),
+                        self.value_bounds(),
                         Some((0.5 / 6.0, 5.5 / 6.0)),
                     ) {
                         painter.circle(pos, 5.0, color, Stroke::NONE);
@@ -349,3 +370,126 @@ impl CursorObject for LaserTool {
         Ok(())
     }
 }
+
+/// Curve of a laser segment being set in the curve dialog.
+pub struct LaserCurveEdit {
+    pub side: usize,
+    pub section_index: usize,
+    /// Point starting the segment, its `a` and `b` shape the curve to the next point
+    pub point_index: usize,
+    pub a: f64,
+    pub b: f64,
+}
+
+impl LaserCurveEdit {
+    /// The segment of a laser section at `tick`, `None` for slams and outside the section.
+    pub fn at(
+        section: &LaserSection,
+        side: usize,
+        section_index: usize,
+        tick: f64,
+    ) -> Option<Self> {
+        let point_index = section.1.windows(2).position(|points| {
+            let start = (section.0 + points[0].ry) as f64;
+            let end = (section.0 + points[1].ry) as f64;
+            start <= tick && tick < end
+        })?;
+        let point = section.1[point_index];
+        Some(Self {
+            side,
+            section_index,
+            point_index,
+            a: point.a,
+            b: point.b,
+        })
+    }
+}
+
+/// Shows the curve dialog of a laser segment if one is open, previewing the curve on the track.
+pub fn laser_curve_dialog(
+    state: &mut MainState,
+    ctx: &egui::Context,
+    shortcuts: &mut DialogShortcuts,
+) {
+    let Some(mut edit) = state.laser_curve_edit.take() else {
+        return;
+    };
+    let Some(mut section) = state.chart.note.laser[edit.side]
+        .get(edit.section_index)
+        .filter(|x| edit.point_index + 1 < x.1.len())
+        .cloned()
+    else {
+        return;
+    };
+    let (confirm, cancel) = shortcuts.take();
+
+    let mut open = true;
+    let mut done = false;
+    let mut cancelled = cancel;
+    egui::Window::new(i18n::fl!("laser_curve"))
+        .id(egui::Id::new("laser_curve_dialog"))
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.add(Slider::new(&mut edit.a, 0.0..=1.0).text(i18n::fl!("curve_time")));
+            ui.add(Slider::new(&mut edit.b, 0.0..=1.0).text(i18n::fl!("curve_value")));
+            ui.add_space(10.0);
+            ui.horizontal(|ui| {
+                if ui.button(i18n::fl!("linear_curve")).clicked() {
+                    edit.a = 0.5;
+                    edit.b = 0.5;
+                }
+                done = ui.button(i18n::fl!("ok")).clicked() || confirm;
+                if ui.button(i18n::fl!("cancel")).clicked() {
+                    cancelled = true;
+                }
+            });
+        });
+
+    let point = &mut section.1[edit.point_index];
+    point.a = edit.a;
+    point.b = edit.b;
+    let mut preview = Vec::new();
+    state.screen.draw_laser_section(
+        &section,
+        &mut preview,
+        Color32::from_rgba_unmultiplied(0, 194, 0, 160),
+        false,
+        f32::NAN,
+    );
+    ctx.layer_painter(egui::LayerId::new(
+        egui::Order::Foreground,
+        egui::Id::new("laser_curve_preview"),
+    ))
+    .extend(preview.into_iter().map(Shape::mesh));
+
+    if done {
+        let LaserCurveEdit {
+            side,
+            section_index,
+            point_index,
+            a,
+            b,
+        } = edit;
+        let side_name = if side == 0 {
+            i18n::fl!("left")
+        } else {
+            i18n::fl!("right")
+        };
+        state.actions.new_action(
+            i18n::fl!("adjust_laser_curve", side = side_name),
+            move |c: &mut Chart| {
+                let point = c.note.laser[side]
+                    .get_mut(section_index)
+                    .and_then(|x| x.1.get_mut(point_index))
+                    .ok_or(anyhow!("Laser segment not found"))?;
+                point.a = a;
+                point.b = b;
+                Ok(())
+            },
+        );
+    } else if open && !cancelled {
+        state.laser_curve_edit = Some(edit);
+    }
+}
@@ -12,10 +12,12 @@ mod bpm_ts;
 mod buttons;
 mod camera;
 mod laser;
+mod select;
 pub use bpm_ts::*;
 pub use buttons::*;
 pub use camera::*;
 pub use laser::*;
+pub use select::*;
 
 #[allow(unused)]
 pub trait CursorObject {
@@ -84,4 +86,8 @@ pub trait CursorObject {
     fn update(&mut self, tick: u32, tick_f: f64, lane: f32, pos: Pos2, chart: &Chart);
     fn draw(&self, state: &MainState, painter: &Painter) -> Result<()>;
     fn draw_ui(&mut self, _state: &mut MainState, _ctx: &Context) {}
+    /// Range picked for copying, cutting and deleting
+    fn selection(&self) -> Option<Selection> {
+        None
+    }
 }
@@ -0,0 +1,154 @@
+use crate::tools::CursorObject;
+use crate::Modifiers;
+use crate::{
+    action_stack::ActionStack,
+    chart_editor::{MainState, ScreenState},
+    rect_xy_wh,
+};
+use anyhow::Result;
+use eframe::egui::{Painter, Pos2, Rgba, Shape};
+use kson::{Chart, Interval, LaneSelection};
+
+/// Tick range picked with [`SelectTool`] for the clipboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Selection {
+    pub start: u32,
+    pub end: u32,
+    pub lanes: LaneSelection,
+}
+
+/// Drags out a tick range over the whole track, or only the lanes dragged across while shift
+/// is held.
+#[derive(Default)]
+pub struct SelectTool {
+    pressed: bool,
+    per_lane: bool,
+    start: (u32, f32),
+    end: (u32, f32),
+    selection: Option<Selection>,
+}
+
+/// Lanes 0 and 5 are the space beside the buttons, used to pick the lasers.
+fn lanes_between(a: f32, b: f32) -> LaneSelection {
+    let lo = a.min(b).clamp(0.0, 5.0) as usize;
+    let hi = a.max(b).clamp(0.0, 5.0) as usize;
+    let covers = |first: usize, last: usize| lo <= last && hi >= first;
+
+    LaneSelection {
+        bt: [1, 2, 3, 4].map(|lane| covers(lane, lane)),
+        fx: [covers(1, 2), covers(3, 4)],
+        laser: [lo == 0, hi == 5],
+        timeline: false,
+    }
+}
+
+impl SelectTool {
+    fn pending(&self) -> Option<Selection> {
+        let start = self.start.0.min(self.end.0);
+        let end = self.start.0.max(self.end.0);
+        if start == end {
+            return None;
+        }
+
+        Some(Selection {
+            start,
+            end,
+            lanes: if self.per_lane {
+                lanes_between(self.start.1, self.end.1)
+            } else {
+                LaneSelection::ALL
+            },
+        })
+    }
+}
+
+impl CursorObject for SelectTool {
+    fn drag_start(
+        &mut self,
+        _screen: ScreenState,
+        tick: u32,
+        _tick_f: f64,
+        lane: f32,
+        _chart: &Chart,
+        _actions: &mut ActionStack<Chart>,
+        _pos: Pos2,
+        modifiers: &Modifiers,
+    ) {
+        self.pressed = true;
+        self.per_lane = modifiers.shift;
+        self.start = (tick, lane);
+        self.end = (tick, lane);
+    }
+
+    fn drag_end(
+        &mut self,
+        _screen: ScreenState,
+        tick: u32,
+        _tick_f: f64,
+        lane: f32,
+        _chart: &Chart,
+        _actions: &mut ActionStack<Chart>,
+        _pos: Pos2,
+    ) {
+        if !self.pressed {
+            return;
+        }
+
+        self.end = (tick, lane);
+        self.selection = self.pending();
+        self.pressed = false;
+    }
+
+    fn update(&mut self, tick: u32, _tick_f: f64, lane: f32, _pos: Pos2, _chart: &Chart) {
+        if self.pressed {
+            self.end = (tick, lane);
+        }
+    }
+
+    fn draw(&self, state: &MainState, painter: &Painter) -> Result<()> {
+        let selection = if self.pressed {
+            self.pending()
+        } else {
+            self.selection
+        };
+        let Some(selection) = selection else {
+            return Ok(());
+        };
+
+        let (first, last) = if selection.lanes.timeline {
+            (0.0, 6.0)
+        } else {
+            let lanes = selection.lanes;
+            let first = if lanes.laser[0] {
+                0
+            } else {
+                lanes.bt.iter().position(|x| *x).map_or(1, |x| x + 1)
+            };
+            let last = if lanes.laser[1] {
+                6
+            } else {
+                lanes.bt.iter().rposition(|x| *x).map_or(5, |x| x + 2)
+            };
+            (first as f32, last as f32)
+        };
+
+        let color = Rgba::from_rgba_premultiplied(0.1, 0.3, 0.6, 0.3);
+        let lane_width = state.screen.lane_width();
+        let interval = Interval {
+            y: selection.start,
+            l: selection.end - selection.start,
+        };
+        painter.extend(state.screen.interval_to_ranges(&interval).into_iter().map(
+            |(x, y, h, _)| {
+                let x = x + state.screen.track_width / 2.0 + first * lane_width;
+                let w = (last - first) * lane_width;
+                Shape::rect_filled(rect_xy_wh([x, y, w, h]), 0.0, color)
+            },
+        ));
+        Ok(())
+    }
+
+    fn selection(&self) -> Option<Selection> {
+        self.selection
+    }
+}
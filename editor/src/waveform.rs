@@ -0,0 +1,151 @@
+//! Waveform of the chart's audio drawn behind the track. Decoding a song takes a while, so it is
+//! done in the background and the peaks are cached in a peak file that is read instead the next
+//! time the same audio file is opened.
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+
+use anyhow::Result;
+
+/// Peaks stored per second of audio
+const PEAKS_PER_SECOND: f64 = 200.0;
+/// Start of peak files, changed whenever the format changes
+const PEAK_FILE_MAGIC: &[u8; 4] = b"KPK1";
+
+pub struct Waveform {
+    /// Highest amplitude of each interval, 255 at full scale
+    peaks: Vec<u8>,
+}
+
+impl Waveform {
+    pub fn decode(path: &Path) -> Result<Self> {
+        use rodio::Source;
+
+        let decoder = rodio::Decoder::new(BufReader::new(File::open(path)?))?;
+        let samples_per_peak = (decoder.sample_rate() as f64 * decoder.channels() as f64
+            / PEAKS_PER_SECOND)
+            .round()
+            .max(1.0) as usize;
+
+        let mut peaks = vec![];
+        let mut peak = 0u16;
+        for (i, sample) in decoder.enumerate() {
+            peak = peak.max(sample.unsigned_abs());
+            if (i + 1) % samples_per_peak == 0 {
+                peaks.push((peak >> 7).min(255) as u8);
+                peak = 0;
+            }
+        }
+        peaks.push((peak >> 7).min(255) as u8);
+
+        Ok(Self { peaks })
+    }
+
+    /// Reads the peak file of the audio file, or decodes it and writes one.
+    pub fn load(path: &Path) -> Result<Self> {
+        let peak_path = peak_file_path(path);
+        if let Some(peaks) = peak_path
+            .as_ref()
+            .and_then(|x| std::fs::read(x).ok())
+            .and_then(|x| x.strip_prefix(PEAK_FILE_MAGIC).map(<[u8]>::to_vec))
+        {
+            return Ok(Self { peaks });
+        }
+
+        let waveform = Self::decode(path)?;
+        if let Some(peak_path) = peak_path {
+            let written = peak_path
+                .parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|_| {
+                    std::fs::write(
+                        &peak_path,
+                        [&PEAK_FILE_MAGIC[..], &waveform.peaks[..]].concat(),
+                    )
+                });
+            if let Err(e) = written {
+                log::warn!("Failed to write peak file {}: {e}", peak_path.display());
+            }
+        }
+        Ok(waveform)
+    }
+
+    /// Highest amplitude between two points in the audio, from 0 to 1.
+    pub fn peak(&self, start_ms: f64, end_ms: f64) -> f32 {
+        let index =
+            |ms: f64| ((ms.max(0.0) * PEAKS_PER_SECOND / 1000.0) as usize).min(self.peaks.len());
+        let (start, end) = (index(start_ms), index(end_ms));
+        self.peaks[start..end.max(start)]
+            .iter()
+            .max()
+            .map_or(0.0, |x| *x as f32 / 255.0)
+    }
+}
+
+/// Peak files are named by the path, size and modification time of the audio file so they are
+/// made again when it changes.
+fn peak_file_path(path: &Path) -> Option<PathBuf> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    metadata.len().hash(&mut hasher);
+    metadata.modified().ok().hash(&mut hasher);
+
+    directories_next::ProjectDirs::from("", "", "KSON Editor").map(|x| {
+        x.cache_dir()
+            .join("waveforms")
+            .join(format!("{:016x}.peaks", hasher.finish()))
+    })
+}
+
+/// Waveform of the chart's audio, loaded again when the audio file changes.
+#[derive(Default)]
+pub struct WaveformCache {
+    path: Option<PathBuf>,
+    waveform: Option<Waveform>,
+    loading: Option<Receiver<Result<Waveform>>>,
+}
+
+impl WaveformCache {
+    /// Starts loading the waveform of `path` if it is another file, and picks up the loaded one.
+    pub fn update(&mut self, path: Option<PathBuf>) {
+        if self.path != path {
+            self.waveform = None;
+            self.loading = path.clone().map(|path| {
+                let (tx, rx) = channel();
+                std::thread::spawn(move || {
+                    _ = tx.send(Waveform::load(&path));
+                });
+                rx
+            });
+            self.path = path;
+        }
+
+        let Some(loading) = &self.loading else {
+            return;
+        };
+        match loading.try_recv() {
+            Ok(Ok(waveform)) => self.waveform = Some(waveform),
+            Ok(Err(e)) => {
+                if let Some(path) = &self.path {
+                    log::warn!("Failed to read waveform of {}: {e}", path.display());
+                }
+            }
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {}
+        }
+        self.loading = None;
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.loading.is_some()
+    }
+
+    pub fn get(&self) -> Option<&Waveform> {
+        self.waveform.as_ref()
+    }
+}
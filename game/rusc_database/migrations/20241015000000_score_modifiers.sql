@@ -0,0 +1,3 @@
+ALTER TABLE "Scores" ADD COLUMN "speed_mod_type" INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE "Scores" ADD COLUMN "speed_mod_value" REAL NOT NULL DEFAULT 0;
+ALTER TABLE "Scores" ADD COLUMN "window_variant" INTEGER NOT NULL DEFAULT 1;
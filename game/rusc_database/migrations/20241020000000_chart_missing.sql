@@ -0,0 +1 @@
+ALTER TABLE "Charts" ADD COLUMN "missing" BOOLEAN NOT NULL DEFAULT FALSE;
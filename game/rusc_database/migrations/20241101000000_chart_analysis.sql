@@ -0,0 +1,3 @@
+ALTER TABLE "Charts" ADD COLUMN "density" BLOB;
+ALTER TABLE "Charts" ADD COLUMN "radar" TEXT;
+ALTER TABLE "Charts" ADD COLUMN "preview_skip" INTEGER;
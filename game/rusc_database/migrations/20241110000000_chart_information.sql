@@ -0,0 +1,3 @@
+ALTER TABLE "Charts" ADD COLUMN "information" TEXT;
+-- Analyze existing charts again so their information gets stored
+UPDATE "Charts" SET "density" = NULL;
@@ -0,0 +1,6 @@
+-- Files in each chart archive, so unchanged archives don't have to be read on every start
+CREATE TABLE IF NOT EXISTS "ArchiveListings" (
+    "path" TEXT NOT NULL PRIMARY KEY,
+    "modified" INTEGER NOT NULL,
+    "entries" TEXT NOT NULL
+);
@@ -3,7 +3,9 @@ use std::time::Duration;
 
 use sqlx::migrate::Migrator;
 use sqlx::sqlite::SqliteQueryResult;
-use sqlx::{query, query_as, query_scalar, ConnectOptions, Pool, Row, SqlitePool};
+use sqlx::{
+    query, query_as, query_scalar, ConnectOptions, Pool, QueryBuilder, Row, Sqlite, SqlitePool,
+};
 
 static MIGRATOR: Migrator = sqlx::migrate!("./migrations"); // defaults to "./migrations"
 
@@ -34,6 +36,22 @@ pub struct ChartEntry {
     pub preview_length: i64,
     pub lwt: i64,
     pub custom_offset: i64,
+    /// Note density over the length of the chart
+    pub density: Option<Vec<u8>>,
+    /// Json encoded chart radar
+    pub radar: Option<String>,
+    /// Milliseconds into `preview_file` the preview starts at
+    pub preview_skip: Option<i64>,
+    /// Comment from the charter shown with the chart
+    pub information: Option<String>,
+}
+
+/// A chart still missing analysis or preview loudness.
+pub struct PendingAnalysis {
+    pub hash: String,
+    pub path: String,
+    pub analyzed: bool,
+    pub measured: bool,
 }
 
 pub struct ChallengeEntry {
@@ -75,6 +93,10 @@ pub struct ScoreEntry {
     pub gauge_opt: i64,
     pub mirror: bool,
     pub random: bool,
+    pub speed_mod_type: i64,
+    pub speed_mod_value: f64,
+    /// Hit window preset, 1 = NORMAL, 2 = HARD
+    pub window_variant: i64,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -93,6 +115,52 @@ pub enum SortColumn {
     Score,
 }
 
+/// Additional chart criteria, all of which have to match
+#[derive(Debug, Clone, Default)]
+pub struct ChartFilter {
+    /// Inclusive level range
+    pub level_range: Option<(u8, u8)>,
+    /// Inclusive BPM range, matches charts where any part of their BPM range overlaps it
+    pub bpm_range: Option<(f64, f64)>,
+    pub effector: Option<String>,
+    /// Lowest best clear mark, from 1 for played to 5 for perfect. 0 matches charts without scores.
+    pub clear: Option<u8>,
+    /// Lowest best score
+    pub min_score: Option<u32>,
+    /// Only charts of songs added to this collection
+    pub collection: Option<String>,
+}
+
+const SEARCH_COLUMNS: [&str; 6] = [
+    "artist",
+    "title",
+    "path",
+    "effector",
+    "artist_translit",
+    "title_translit",
+];
+
+// The bpm column holds display strings such as "120" or "100-200"
+const MIN_BPM_EXPR: &str = "CAST(bpm AS REAL)";
+const MAX_BPM_EXPR: &str =
+    "CAST(CASE WHEN instr(bpm, '-') > 0 THEN substr(bpm, instr(bpm, '-') + 1) ELSE bpm END AS REAL)";
+
+/// Clear mark of a row in Scores aliased as `s`, calculated like the game does
+const CLEAR_MARK_EXPR: &str = "CASE \
+    WHEN (s.gauge_type = 1 AND s.gauge <= 0) OR (s.gauge_type <> 1 AND s.gauge < 0.7) THEN 1 \
+    WHEN s.miss = 0 AND s.near = 0 THEN 5 \
+    WHEN s.miss = 0 THEN 4 \
+    WHEN s.gauge_type = 1 THEN 3 \
+    ELSE 2 END";
+
+fn push_condition(query_builder: &mut QueryBuilder<'_, Sqlite>, first: &mut bool) {
+    if std::mem::take(first) {
+        query_builder.push(" WHERE ");
+    } else {
+        query_builder.push(" AND ");
+    }
+}
+
 impl LocalSongsDb {
     pub async fn new(db_path: impl AsRef<Path>) -> Result<Self, sqlx::Error> {
         let options = sqlx::sqlite::SqliteConnectOptions::new()
@@ -138,8 +206,12 @@ impl LocalSongsDb {
             preview_offset,
             preview_length,
             lwt,
-            custom_offset
-         FROM Charts"
+            custom_offset,
+            density,
+            radar,
+            preview_skip,
+            information
+         FROM Charts WHERE missing = 0"
         )
         .fetch_all(&self.sqlite_pool)
         .await
@@ -176,7 +248,11 @@ impl LocalSongsDb {
             preview_offset,
             preview_length,
             lwt,
-            custom_offset
+            custom_offset,
+            density,
+            radar,
+            preview_skip,
+            information
          FROM Charts WHERE rowid = ?",
             id
         )
@@ -189,57 +265,104 @@ impl LocalSongsDb {
         query: &str,
         level: u8,
         folder: Option<String>,
+        filter: &ChartFilter,
         order: (SortColumn, SortDir),
     ) -> std::result::Result<Vec<i64>, sqlx::Error> {
         let base_query = "SELECT DISTINCT folderId FROM Charts";
-        let mut query_builder = sqlx::query_builder::QueryBuilder::new(base_query);
+        let mut query_builder = QueryBuilder::new(base_query);
         if let (SortColumn::Score, _) = order {
             query_builder.push(" LEFT JOIN Scores on Charts.hash = Scores.chart_hash");
         }
-        let mut binds = vec![];
+        let mut first = true;
+        push_condition(&mut query_builder, &mut first);
+        query_builder.push("missing = 0");
+
         if !query.is_empty() {
-            for (i, term) in query.split(' ').enumerate() {
-                if i == 0 {
-                    query_builder.push(" WHERE")
-                } else {
-                    query_builder.push(" AND")
-                };
-
-                query_builder.push(
-                    " (artist LIKE ?
-					 OR title LIKE ?
-					 OR path LIKE ?
-					 OR effector LIKE ?
-					 OR artist_translit LIKE ?
-					 OR title_translit LIKE ?)",
-                );
-
-                for _ in 0..6 {
-                    binds.push(format!("%{term}%"));
+            for term in query.split(' ') {
+                push_condition(&mut query_builder, &mut first);
+                query_builder.push("(");
+                for (i, column) in SEARCH_COLUMNS.iter().enumerate() {
+                    if i > 0 {
+                        query_builder.push(" OR ");
+                    }
+                    query_builder.push(column);
+                    query_builder.push(" LIKE ");
+                    query_builder.push_bind(format!("%{term}%"));
                 }
+                query_builder.push(")");
             }
         }
 
         if level > 0 {
-            if query.is_empty() {
-                query_builder.push(" WHERE");
-            } else {
-                query_builder.push(" AND");
-            }
-
-            query_builder.push(" level = ");
+            push_condition(&mut query_builder, &mut first);
+            query_builder.push("level = ");
             query_builder.push_bind(level);
         }
 
-        if let Some(folder) = folder {
-            if query.is_empty() && level == 0 {
-                query_builder.push(" WHERE");
-            } else {
-                query_builder.push(" AND");
+        if let Some((min, max)) = filter.level_range {
+            push_condition(&mut query_builder, &mut first);
+            query_builder.push("level BETWEEN ");
+            query_builder.push_bind(min);
+            query_builder.push(" AND ");
+            query_builder.push_bind(max);
+        }
+
+        if let Some((min, max)) = filter.bpm_range {
+            push_condition(&mut query_builder, &mut first);
+            query_builder.push(MAX_BPM_EXPR);
+            query_builder.push(" >= ");
+            query_builder.push_bind(min);
+            query_builder.push(" AND ");
+            query_builder.push(MIN_BPM_EXPR);
+            query_builder.push(" <= ");
+            query_builder.push_bind(max);
+        }
+
+        if let Some(effector) = &filter.effector {
+            push_condition(&mut query_builder, &mut first);
+            query_builder.push("effector LIKE ");
+            query_builder.push_bind(format!("%{effector}%"));
+        }
+
+        match filter.clear {
+            None => {}
+            Some(0) => {
+                push_condition(&mut query_builder, &mut first);
+                query_builder
+                    .push("NOT EXISTS (SELECT 1 FROM Scores s WHERE s.chart_hash = Charts.hash)");
+            }
+            Some(clear) => {
+                push_condition(&mut query_builder, &mut first);
+                query_builder
+                    .push("EXISTS (SELECT 1 FROM Scores s WHERE s.chart_hash = Charts.hash AND ");
+                query_builder.push(CLEAR_MARK_EXPR);
+                query_builder.push(" >= ");
+                query_builder.push_bind(clear);
+                query_builder.push(")");
             }
+        }
+
+        if let Some(score) = filter.min_score {
+            push_condition(&mut query_builder, &mut first);
+            query_builder.push(
+                "EXISTS (SELECT 1 FROM Scores s WHERE s.chart_hash = Charts.hash AND s.score >= ",
+            );
+            query_builder.push_bind(score);
+            query_builder.push(")");
+        }
+
+        if let Some(collection) = &filter.collection {
+            push_condition(&mut query_builder, &mut first);
+            query_builder
+                .push("Charts.folderid IN (SELECT folderid FROM Collections WHERE collection = ");
+            query_builder.push_bind(collection);
+            query_builder.push(")");
+        }
 
-            query_builder.push(" path LIKE ?");
-            binds.push(format!("{folder}%"));
+        if let Some(folder) = folder {
+            push_condition(&mut query_builder, &mut first);
+            query_builder.push("path LIKE ");
+            query_builder.push_bind(format!("{folder}%"));
         }
 
         query_builder.push(" ORDER BY ");
@@ -257,12 +380,10 @@ impl LocalSongsDb {
             SortDir::Desc => query_builder.push(" DESC"),
         };
 
-        let mut q = query_builder.build_query_scalar();
-        for ele in binds {
-            q = q.bind(ele);
-        }
-
-        q.fetch_all(&self.sqlite_pool).await
+        query_builder
+            .build_query_scalar()
+            .fetch_all(&self.sqlite_pool)
+            .await
     }
 
     pub async fn add_score(
@@ -293,12 +414,15 @@ impl LocalSongsDb {
             gauge_opt,
             mirror,
             random,
+            speed_mod_type,
+            speed_mod_value,
+            window_variant,
         }: ScoreEntry,
     ) -> std::result::Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
         query!("
             INSERT INTO
-			Scores(score,crit,near,early,late,combo,miss,gauge,auto_flags,replay,timestamp,chart_hash,user_name,user_id,local_score,window_perfect,window_good,window_hold,window_miss,window_slam,gauge_type,gauge_opt,mirror,random)
-			VALUES(?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?)",
+			Scores(score,crit,near,early,late,combo,miss,gauge,auto_flags,replay,timestamp,chart_hash,user_name,user_id,local_score,window_perfect,window_good,window_hold,window_miss,window_slam,gauge_type,gauge_opt,mirror,random,speed_mod_type,speed_mod_value,window_variant)
+			VALUES(?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?)",
             score,
             crit,
             near,
@@ -323,6 +447,9 @@ impl LocalSongsDb {
             gauge_opt,
             mirror,
             random,
+            speed_mod_type,
+            speed_mod_value,
+            window_variant,
         ).execute(&self.sqlite_pool).await
     }
 
@@ -353,8 +480,12 @@ impl LocalSongsDb {
         preview_offset,
         preview_length,
         lwt,
-        custom_offset
-     FROM Charts WHERE folderid = ? ORDER BY diff_index DESC",
+        custom_offset,
+        density,
+        radar,
+        preview_skip,
+        information
+     FROM Charts WHERE folderid = ? AND missing = 0 ORDER BY diff_index DESC",
             id
         )
         .fetch_all(&self.sqlite_pool)
@@ -385,13 +516,18 @@ impl LocalSongsDb {
             lwt,
             rowid: _,
             custom_offset: _,
+            density,
+            radar,
+            preview_skip,
+            information,
         }: ChartEntry,
     ) -> std::result::Result<i64, sqlx::Error> {
         query_scalar!(
             "INSERT INTO Charts(
 			folderid,path,title,artist,title_translit,artist_translit,jacket_path,effector,illustrator,
-			diff_name,diff_shortname,bpm,diff_index,level,hash,preview_file,preview_offset,preview_length,lwt,custom_offset)
-			VALUES(?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,0) RETURNING rowid",
+			diff_name,diff_shortname,bpm,diff_index,level,hash,preview_file,preview_offset,preview_length,lwt,custom_offset,
+			density,radar,preview_skip,information)
+			VALUES(?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,0,?,?,?,?) RETURNING rowid",
             folderid,
             path,
             title,
@@ -410,7 +546,11 @@ impl LocalSongsDb {
             preview_file,
             preview_offset,
             preview_length,
-            lwt
+            lwt,
+            density,
+            radar,
+            preview_skip,
+            information
         )
         .fetch_one(&self.sqlite_pool)
         .await
@@ -481,11 +621,16 @@ impl LocalSongsDb {
             lwt,
             rowid: _,
             custom_offset: _,
+            density,
+            radar,
+            preview_skip,
+            information,
         }: ChartEntry,
         id: i32,
     ) -> std::result::Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
         query!("UPDATE Charts SET path=?,title=?,artist=?,title_translit=?,artist_translit=?,jacket_path=?,effector=?,illustrator=?,
-			diff_name=?,diff_shortname=?,bpm=?,diff_index=?,level=?,hash=?,preview_file=?,preview_offset=?,preview_length=?,lwt=? WHERE rowid=?",
+			diff_name=?,diff_shortname=?,bpm=?,diff_index=?,level=?,hash=?,preview_file=?,preview_offset=?,preview_length=?,lwt=?,
+			density=?,radar=?,preview_skip=?,information=? WHERE rowid=?",
             path,
             title,
             artist,
@@ -504,6 +649,10 @@ impl LocalSongsDb {
             preview_offset,
             preview_length,
             lwt,
+            density,
+            radar,
+            preview_skip,
+            information,
             id
 
         ).execute(&self.sqlite_pool).await
@@ -555,6 +704,9 @@ impl LocalSongsDb {
     }
 
     pub async fn remove_folder(&self, id: i64) -> sqlx::Result<()> {
+        query!("DELETE FROM Collections WHERE folderid = ?", id)
+            .execute(&self.sqlite_pool)
+            .await?;
         query!("DELETE FROM Charts WHERE folderid = ?", id)
             .execute(&self.sqlite_pool)
             .await?;
@@ -596,7 +748,10 @@ impl LocalSongsDb {
         gauge_type,
         gauge_opt,
         mirror,
-        random
+        random,
+        speed_mod_type,
+        speed_mod_value,
+        window_variant
         FROM Scores WHERE chart_hash=?",
             chart_hash
         )
@@ -610,6 +765,8 @@ impl LocalSongsDb {
             .await
     }
 
+    /// All scores, with an empty `replay` for scores that have one to avoid loading every replay.
+    /// See [`LocalSongsDb::get_replay`].
     pub async fn get_all_scores(
         &self,
     ) -> std::result::Result<std::vec::Vec<ScoreEntry>, sqlx::Error> {
@@ -626,7 +783,7 @@ impl LocalSongsDb {
         miss,
         gauge,
         auto_flags,
-        replay,
+        CASE WHEN replay IS NULL THEN NULL ELSE '' END AS \"replay?: String\",
         timestamp,
         chart_hash,
         user_name,
@@ -640,13 +797,22 @@ impl LocalSongsDb {
         gauge_type,
         gauge_opt,
         mirror,
-        random
+        random,
+        speed_mod_type,
+        speed_mod_value,
+        window_variant
         FROM Scores",
         )
         .fetch_all(&self.sqlite_pool)
         .await
     }
 
+    pub async fn get_replay(&self, rowid: i64) -> std::result::Result<Option<String>, sqlx::Error> {
+        query_scalar!("SELECT replay FROM Scores WHERE rowid=?", rowid)
+            .fetch_one(&self.sqlite_pool)
+            .await
+    }
+
     pub async fn move_scores(
         &self,
         from: &str,
@@ -696,7 +862,237 @@ impl LocalSongsDb {
             .await
     }
 
+    /// Points an already imported chart to where it was found, in case it has been moved.
+    pub async fn update_chart_location(
+        &self,
+        hash: &str,
+        path: &str,
+        folder_id: i64,
+    ) -> sqlx::Result<SqliteQueryResult> {
+        query!(
+            "UPDATE Charts SET path = ?, folderid = ?, missing = 0 WHERE hash = ?",
+            path,
+            folder_id,
+            hash
+        )
+        .execute(&self.sqlite_pool)
+        .await
+    }
+
+    /// Marks charts whose file no longer exists as missing and restores the ones that have
+    /// reappeared. Missing charts are hidden from queries but keep their rows, so they return
+    /// as they were if the files come back. Returns the number of missing charts.
+    pub async fn reconcile_missing(&self, exists: impl Fn(&Path) -> bool) -> sqlx::Result<usize> {
+        let charts = query!("SELECT rowid, path, missing FROM Charts")
+            .fetch_all(&self.sqlite_pool)
+            .await?;
+
+        let mut missing_count = 0;
+        for chart in charts {
+            let missing = !exists(Path::new(&chart.path));
+            if missing {
+                missing_count += 1;
+            }
+
+            if missing != chart.missing {
+                query!(
+                    "UPDATE Charts SET missing = ? WHERE rowid = ?",
+                    missing,
+                    chart.rowid
+                )
+                .execute(&self.sqlite_pool)
+                .await?;
+            }
+        }
+
+        Ok(missing_count)
+    }
+
+    /// Loudness of the song select preview of a chart in LUFS, `None` until it has been
+    /// measured.
+    pub async fn get_preview_loudness(&self, hash: &str) -> sqlx::Result<Option<f64>> {
+        query_scalar!("SELECT preview_loudness FROM Charts WHERE hash = ?", hash)
+            .fetch_optional(&self.sqlite_pool)
+            .await
+            .map(Option::flatten)
+    }
+
+    pub async fn set_preview_loudness(
+        &self,
+        hash: &str,
+        loudness: f64,
+    ) -> sqlx::Result<SqliteQueryResult> {
+        query!(
+            "UPDATE Charts SET preview_loudness = ? WHERE hash = ?",
+            loudness,
+            hash
+        )
+        .execute(&self.sqlite_pool)
+        .await
+    }
+
+    /// Files in an archive separated by newlines, if it was listed when it was last modified
+    /// at `modified`.
+    pub async fn get_archive_listing(
+        &self,
+        path: &str,
+        modified: i64,
+    ) -> sqlx::Result<Option<String>> {
+        query_scalar!(
+            "SELECT entries FROM ArchiveListings WHERE path = ? AND modified = ?",
+            path,
+            modified
+        )
+        .fetch_optional(&self.sqlite_pool)
+        .await
+    }
+
+    pub async fn set_archive_listing(
+        &self,
+        path: &str,
+        modified: i64,
+        entries: &str,
+    ) -> sqlx::Result<SqliteQueryResult> {
+        query!(
+            "INSERT OR REPLACE INTO ArchiveListings(path, modified, entries) VALUES(?, ?, ?)",
+            path,
+            modified,
+            entries
+        )
+        .execute(&self.sqlite_pool)
+        .await
+    }
+
+    /// Charts scanned before their analysis was stored, and charts whose preview loudness
+    /// hasn't been measured when `loudness` is set.
+    pub async fn get_pending_analysis(&self, loudness: bool) -> sqlx::Result<Vec<PendingAnalysis>> {
+        query_as!(
+            PendingAnalysis,
+            r#"SELECT hash, path, density IS NOT NULL AS "analyzed!: bool",
+            preview_loudness IS NOT NULL AS "measured!: bool"
+            FROM Charts WHERE missing = 0 AND (density IS NULL OR (? AND preview_loudness IS NULL))"#,
+            loudness
+        )
+        .fetch_all(&self.sqlite_pool)
+        .await
+    }
+
+    pub async fn set_chart_analysis(
+        &self,
+        hash: &str,
+        density: &[u8],
+        radar: &str,
+        preview_file: &str,
+        preview_skip: i64,
+        information: Option<&str>,
+    ) -> sqlx::Result<SqliteQueryResult> {
+        query!(
+            "UPDATE Charts SET density = ?, radar = ?, preview_file = ?, preview_skip = ?, information = ? WHERE hash = ?",
+            density,
+            radar,
+            preview_file,
+            preview_skip,
+            information,
+            hash
+        )
+        .execute(&self.sqlite_pool)
+        .await
+    }
+
+    /// Adds the song in folder `folderid` to `collection`, creating the collection if needed.
+    pub async fn add_to_collection(
+        &self,
+        collection: &str,
+        folderid: i64,
+    ) -> sqlx::Result<SqliteQueryResult> {
+        query!(
+            "INSERT OR IGNORE INTO Collections(collection, folderid) VALUES(?, ?)",
+            collection,
+            folderid
+        )
+        .execute(&self.sqlite_pool)
+        .await
+    }
+
+    /// Removes the song in folder `folderid` from `collection`, a collection without songs
+    /// stops existing.
+    pub async fn remove_from_collection(
+        &self,
+        collection: &str,
+        folderid: i64,
+    ) -> sqlx::Result<SqliteQueryResult> {
+        query!(
+            "DELETE FROM Collections WHERE collection = ? AND folderid = ?",
+            collection,
+            folderid
+        )
+        .execute(&self.sqlite_pool)
+        .await
+    }
+
+    pub async fn list_collections(&self) -> sqlx::Result<Vec<String>> {
+        query_scalar!(
+            "SELECT DISTINCT collection FROM Collections ORDER BY collection COLLATE NOCASE"
+        )
+        .fetch_all(&self.sqlite_pool)
+        .await
+    }
+
+    /// Collections the song in folder `folderid` has been added to
+    pub async fn collections_for_folder(&self, folderid: i64) -> sqlx::Result<Vec<String>> {
+        query_scalar!(
+            "SELECT collection FROM Collections WHERE folderid = ? ORDER BY collection COLLATE NOCASE",
+            folderid
+        )
+        .fetch_all(&self.sqlite_pool)
+        .await
+    }
+
+    /// Charts of the songs in `collection`
+    pub async fn charts_in_collection(
+        &self,
+        collection: &str,
+    ) -> std::result::Result<std::vec::Vec<ChartEntry>, sqlx::Error> {
+        query_as!(
+            ChartEntry,
+            "SELECT
+        rowid,
+        folderid,
+        path,
+        title,
+        artist,
+        title_translit,
+        artist_translit,
+        jacket_path,
+        effector,
+        illustrator,
+        diff_name,
+        diff_shortname,
+        bpm,
+        diff_index,
+        level,
+        hash,
+        preview_file,
+        preview_offset,
+        preview_length,
+        lwt,
+        custom_offset,
+        density,
+        radar,
+        preview_skip,
+        information
+     FROM Charts WHERE missing = 0 AND folderid IN (SELECT folderid FROM Collections WHERE collection = ?)
+     ORDER BY folderid, diff_index DESC",
+            collection
+        )
+        .fetch_all(&self.sqlite_pool)
+        .await
+    }
+
     pub async fn remove_empty_folders(&self) -> sqlx::Result<SqliteQueryResult> {
+        query!("DELETE FROM Collections WHERE folderid NOT IN (SELECT folderid FROM Charts)")
+            .execute(&self.sqlite_pool)
+            .await?;
         query!("DELETE FROM Folders WHERE rowid NOT IN (SELECT folderid FROM Charts)")
             .execute(&self.sqlite_pool)
             .await
@@ -368,6 +368,13 @@ function render(deltaTime)
     gfx.ResetTransform()
     
     gfx.Scale(scale, scale)
+
+    -- Only the track is drawn while recording with a clean HUD
+    if gameplay.cleanHud then
+        if gameplay.cleanHudProgress then draw_clean_progress() end
+        return
+    end
+
     local yshift = 0
 
     -- In portrait, we draw a banner across the top
@@ -457,6 +464,14 @@ function render(deltaTime)
     end
 end
 -- -------------------------------------------------------------------------- --
+-- draw_clean_progress:                                                       --
+-- Thin song progress bar along the top edge, the only HUD element drawn      --
+--  while gameplay.cleanHud is set.                                           --
+function draw_clean_progress()
+    FillColor(0, 150, 255, 160)
+    DrawRect(RECT_FILL, 0, 0, desw * gameplay.progress, 4)
+end
+-- -------------------------------------------------------------------------- --
 -- SetUpCritTransform:                                                        --
 -- Utility function which aligns the graphics transform to the center of the  --
 --  crit line on screen, rotation include.                                    --
@@ -1173,7 +1188,7 @@ end
 local real_render = render
 render = function(deltaTime)
     real_render(deltaTime)
-    draw_users(deltaTime)
+    if not gameplay.cleanHud then draw_users(deltaTime) end
 end
 
 -- Update the users in the scoreboard
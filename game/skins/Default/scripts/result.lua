@@ -141,6 +141,13 @@ function getScoreBadgeDesc(s)
     return ""
 end
 
+function getScoreModifiers(s)
+    if s.modifiers ~= nil then
+        return table.concat(s.modifiers, " ")
+    end
+    return ""
+end
+
 result_set = function()
     highScores = { }
     currentAdded = false
@@ -165,6 +172,7 @@ result_set = function()
                 newScore.score = string.format("%08d", result.score)
                 newScore.badge = result.badge
                 newScore.badgeDesc = getScoreBadgeDesc(result)
+                newScore.modifiers = getScoreModifiers(result)
                 newScore.color = {255, 127, 0}
                 newScore.subtext = "Now"
                 newScore.xoff = 0
@@ -175,6 +183,7 @@ result_set = function()
             newScore.score = string.format("%08d", s.score)
             newScore.badge = s.badge
             newScore.badgeDesc = getScoreBadgeDesc(s)
+            newScore.modifiers = getScoreModifiers(s)
             newScore.color = {0, 127, 255}
             newScore.xoff = 0
             if s.timestamp > 0 then
@@ -195,6 +204,7 @@ result_set = function()
             newScore.score = string.format("%08d", result.score)
             newScore.badge = result.badge
             newScore.badgeDesc = getScoreBadgeDesc(result)
+            newScore.modifiers = getScoreModifiers(result)
             newScore.color = {255, 127, 0}
             newScore.subtext = "Now"
             newScore.xoff = 0
@@ -221,6 +231,7 @@ result_set = function()
             newScore.score = string.format("%08d", s.score)
             newScore.badge = s.badge
             newScore.badgeDesc = getScoreBadgeDesc(s)
+            newScore.modifiers = getScoreModifiers(s)
             newScore.subtext = s.name
 
             if highestScore < s.score then
@@ -523,6 +534,14 @@ draw_highscores = function(full)
         gfx.LoadSkinFont("NotoSans-Regular.ttf")
         gfx.FontSize(20)
         gfx.Text(s.subtext, 135, 45)
+
+        if full and s.modifiers ~= "" then
+            gfx.BeginPath()
+            gfx.TextAlign(gfx.TEXT_ALIGN_RIGHT + gfx.TEXT_ALIGN_TOP)
+            gfx.FontSize(14)
+            gfx.FillColor(180, 180, 180)
+            gfx.Text(s.modifiers, 250, 4)
+        end
         gfx.Restore()
     end
 end
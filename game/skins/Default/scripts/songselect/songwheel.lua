@@ -295,14 +295,19 @@ end
 draw_scores = function(difficulty, x, y, w, h)
   if IRData.Active then return draw_scores_ir(difficulty, x, y, w, h) end
 
-  -- draw the top score for this difficulty
+  -- draw the score selected with BT-B/BT-C for this difficulty, the top score by default
   local xOffset = 5
   local height = h / 3 - 10
   local ySpacing = h / 3
   local yOffset = h / 3
+  local scoreIndex = (songwheel.selectedScore or 0) + 1
   gfx.FontSize(30);
   gfx.TextAlign(gfx.TEXT_ALIGN_BOTTOM + gfx.TEXT_ALIGN_CENTER);
-  gfx.FastText("HIGH SCORE", x + (w / 2), y + (h / 2))
+  if scoreIndex > 1 then
+    gfx.FastText(string.format("SCORE %d/%d", scoreIndex, #difficulty.scores), x + (w / 2), y + (h / 2))
+  else
+    gfx.FastText("HIGH SCORE", x + (w / 2), y + (h / 2))
+  end
   gfx.BeginPath()
   gfx.Rect(x + xOffset, y + h / 2, w - (xOffset * 2), h / 2)
   gfx.FillColor(30, 30, 30, 10)
@@ -310,8 +315,8 @@ draw_scores = function(difficulty, x, y, w, h)
   gfx.StrokeWidth(1)
   gfx.Fill()
   gfx.Stroke()
-  if difficulty.scores[1] ~= nil then
-    local highScore = difficulty.scores[1]
+  if difficulty.scores[scoreIndex] ~= nil then
+    local highScore = difficulty.scores[scoreIndex]
     scoreLabel = gfx.CreateLabel(string.format("%08d", highScore.score), 40, 0)
     for i, v in ipairs(grades) do
       if v.max > highScore.score then
@@ -322,14 +327,27 @@ draw_scores = function(difficulty, x, y, w, h)
         break
       end
     end
-    if difficulty.topBadge ~= 0 then
+    local badge = scoreIndex > 1 and highScore.badge or difficulty.topBadge
+    if badge ~= 0 then
       gfx.BeginPath()
-      gfx.ImageRect(x + xOffset + w - h / 2, y + h / 2 + 5, (h / 2 - 10), h / 2 - 10, badges[difficulty.topBadge], 1, 0)
+      gfx.ImageRect(x + xOffset + w - h / 2, y + h / 2 + 5, (h / 2 - 10), h / 2 - 10, badges[badge], 1, 0)
     end
     gfx.FillColor(255, 255, 255)
     gfx.FontSize(40);
     gfx.TextAlign(gfx.TEXT_ALIGN_MIDDLE + gfx.TEXT_ALIGN_CENTER);
     gfx.DrawLabel(scoreLabel, x + (w / 2), y + (h / 4) * 3, w)
+    if highScore.hasReplay then
+      gfx.FillColor(0, 200, 255)
+      gfx.FontSize(16)
+      gfx.TextAlign(gfx.TEXT_ALIGN_TOP + gfx.TEXT_ALIGN_RIGHT)
+      gfx.FastText("[BT-A] REPLAY", x + w - xOffset - 2, y + h / 2 + 2)
+    end
+    if highScore.modifiers ~= nil and #highScore.modifiers > 0 then
+      gfx.FillColor(180, 180, 180)
+      gfx.FontSize(16)
+      gfx.TextAlign(gfx.TEXT_ALIGN_BOTTOM + gfx.TEXT_ALIGN_CENTER)
+      gfx.FastText(table.concat(highScore.modifiers, " "), x + (w / 2), y + h - 2)
+    end
   end
 end
 
@@ -425,6 +443,22 @@ draw_diffs = function(diffs, x, y, w, h)
   draw_cursor(x + w / 2, y + diffHeight / 2, timer * math.pi, diffHeight / 1.5)
 end
 
+-- illustrator and charter comments below the effector, cut to maxLines
+draw_information = function(diff, x, y, w, maxLines)
+  gfx.TextAlign(gfx.TEXT_ALIGN_TOP + gfx.TEXT_ALIGN_LEFT)
+  if diff.illustrator ~= "" then
+    gfx.FastText(string.format("Illustrator: %s", diff.illustrator), x, y)
+    y = y + 25
+  end
+  if diff.information ~= nil and diff.information ~= "" then
+    gfx.FontSize(18)
+    gfx.FillColor(200, 200, 200)
+    gfx.TextBox(x, y, w, diff.information, maxLines)
+    gfx.FillColor(255, 255, 255)
+    gfx.FontSize(20)
+  end
+end
+
 draw_selected = function(song, x, y, w, h)
   check_or_create_cache(song)
   -- set up padding and margins
@@ -496,6 +530,7 @@ draw_selected = function(song, x, y, w, h)
       width - imageSize - 20)
     gfx.FastText(string.format("Effector: %s", diff.effector), xpos + xPadding + imageSize + 3, y + yMargin + yPadding +
       115)
+    draw_information(diff, xpos + xPadding + imageSize + 3, y + yMargin + yPadding + 140, width - imageSize - 20, 2)
   else
     gfx.FontSize(40)
     gfx.TextAlign(gfx.TEXT_ALIGN_TOP + gfx.TEXT_ALIGN_LEFT)
@@ -506,6 +541,7 @@ draw_selected = function(song, x, y, w, h)
     gfx.FontSize(20)
     gfx.DrawLabel(songCache[song.id]["bpm"], xpos + 10, (height / 10) * 6 + 85)
     gfx.FastText(string.format("Effector: %s", diff.effector), xpos + 10, (height / 10) * 6 + 115)
+    draw_information(diff, xpos + 10, (height / 10) * 6 + 140, width - 20, 3)
   end
   if aspectRatio == "PortraitWidescreen" then
     draw_scores(diff, xpos + xPadding + imageSize + 3, (height / 3) * 2, width - imageSize - 20, (height / 3) - yPadding)
@@ -616,6 +652,87 @@ draw_search = function(x, y, w, h)
   gfx.DrawLabel(searchText, xpos + 10, y + (h / 2), w - 20)
 end
 
+draw_filter_summary = function(x, y, w)
+  if songwheel.filterSummary == nil or songwheel.filterSummary == "" then
+    return
+  end
+  gfx.BeginPath()
+  gfx.FillColor(0, 0, 0, 150)
+  gfx.Rect(x, y, w, 26)
+  gfx.Fill()
+  gfx.BeginPath()
+  gfx.FillColor(255, 255, 255)
+  gfx.FontSize(20)
+  gfx.TextAlign(gfx.TEXT_ALIGN_LEFT + gfx.TEXT_ALIGN_MIDDLE)
+  gfx.Text("Filter: " .. songwheel.filterSummary, x + 10, y + 13)
+end
+
+-- Collections the selected song is in, BT-D adds it to or removes it from favourites
+draw_song_collections = function(x, y, w)
+  if songwheel.songs[1] == nil then
+    return
+  end
+  local collections = songwheel.songCollections or {}
+  gfx.BeginPath()
+  gfx.FillColor(0, 0, 0, 150)
+  gfx.Rect(x, y, w, 26)
+  gfx.Fill()
+  gfx.BeginPath()
+  gfx.FontSize(20)
+  gfx.TextAlign(gfx.TEXT_ALIGN_LEFT + gfx.TEXT_ALIGN_MIDDLE)
+  if #collections > 0 then
+    gfx.FillColor(255, 220, 0)
+    gfx.Text("Collections: " .. table.concat(collections, ", "), x + 10, y + 13)
+  end
+  gfx.FillColor(180, 180, 180)
+  gfx.TextAlign(gfx.TEXT_ALIGN_RIGHT + gfx.TEXT_ALIGN_MIDDLE)
+  if songwheel.canDownload then
+    gfx.Text("[BT-D] DOWNLOAD", x + w - 10, y + 13)
+  else
+    gfx.Text("[BT-D] FAVOURITE", x + w - 10, y + 13)
+  end
+end
+
+draw_downloads = function(x, y, w)
+  for _, download in ipairs(songwheel.downloads or {}) do
+    if download.state ~= "done" then
+      local progress = 0
+      local status = "Queued"
+      if download.state == "downloading" then
+        if download.total and download.total > 0 then
+          progress = download.received / download.total
+          status = string.format("%d%%", math.floor(progress * 100))
+        else
+          status = string.format("%.1f MB", download.received / 1048576)
+        end
+      elseif download.state == "extracting" then
+        progress = 1
+        status = "Extracting"
+      elseif download.state == "failed" then
+        status = "Failed"
+      elseif download.attempts > 0 then
+        status = "Retrying"
+      end
+      gfx.BeginPath()
+      gfx.FillColor(0, 0, 0, 150)
+      gfx.Rect(x, y, w, 26)
+      gfx.Fill()
+      gfx.BeginPath()
+      gfx.FillColor(0, 150, 255, 150)
+      gfx.Rect(x, y, w * progress, 26)
+      gfx.Fill()
+      gfx.BeginPath()
+      gfx.FontSize(20)
+      gfx.FillColor(255, 255, 255)
+      gfx.TextAlign(gfx.TEXT_ALIGN_LEFT + gfx.TEXT_ALIGN_MIDDLE)
+      gfx.Text(download.title, x + 10, y + 13)
+      gfx.TextAlign(gfx.TEXT_ALIGN_RIGHT + gfx.TEXT_ALIGN_MIDDLE)
+      gfx.Text(status, x + w - 10, y + 13)
+      y = y + 28
+    end
+  end
+end
+
 render = function(deltaTime)
   timer = (timer + deltaTime)
   timer = timer % 2
@@ -649,8 +766,14 @@ render = function(deltaTime)
   --draw text search
   if aspectRatio == "PortraitWidescreen" then
     draw_search(fifthX * 2, 5, fifthX * 3, fifthY / 5)
+    draw_filter_summary(fifthX * 2, 10 + fifthY / 5, fifthX * 3)
+    draw_song_collections(fifthX * 2, 41 + fifthY / 5, fifthX * 3)
+    draw_downloads(fifthX * 2, 69 + fifthY / 5, fifthX * 3)
   else
     draw_search(fifthX * 2, 5, fifthX * 3, fifthY / 3)
+    draw_filter_summary(fifthX * 2, 10 + fifthY / 3, fifthX * 3)
+    draw_song_collections(fifthX * 2, 41 + fifthY / 3, fifthX * 3)
+    draw_downloads(fifthX * 2, 69 + fifthY / 3, fifthX * 3)
   end
 
   ioffset = ioffset * 0.9
@@ -47,6 +47,14 @@ function render_screen(progress)
     gfx.Text(song.title,0,0)
     gfx.FontSize(55)
     gfx.Text(song.artist,0,80)
+    gfx.FontSize(25)
+    gfx.Text(string.format("Effector: %s", song.effector),0,145)
+    if song.illustrator ~= nil and song.illustrator ~= "" then
+        gfx.Text(string.format("Illustrator: %s", song.illustrator),0,175)
+    end
+    if song.information ~= nil and song.information ~= "" then
+        gfx.TextBox(-resx/4,215,resx/2,song.information,4)
+    end
 end
 
 function reset()
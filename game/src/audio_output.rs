@@ -0,0 +1,85 @@
+//! Output device selection. The host is the audio API, e.g. WASAPI or ASIO on Windows and
+//! ALSA or JACK on Linux, ASIO is only available when built with the `asio` feature.
+
+use std::time::Duration;
+
+use log::warn;
+use rodio::{
+    cpal::{
+        self,
+        traits::{DeviceTrait, HostTrait},
+        SupportedBufferSize,
+    },
+    OutputStream, OutputStreamHandle,
+};
+
+pub fn host_names() -> Vec<&'static str> {
+    cpal::available_hosts()
+        .into_iter()
+        .map(|x| x.name())
+        .collect()
+}
+
+fn host(name: Option<&str>) -> cpal::Host {
+    name.and_then(|name| {
+        cpal::available_hosts()
+            .into_iter()
+            .find(|x| x.name() == name)
+    })
+    .and_then(|id| cpal::host_from_id(id).ok())
+    .unwrap_or_else(cpal::default_host)
+}
+
+fn device(host: &cpal::Host, name: Option<&str>) -> Option<cpal::Device> {
+    name.and_then(|name| {
+        host.output_devices()
+            .ok()?
+            .find(|x| x.name().is_ok_and(|x| x == name))
+    })
+    .or_else(|| host.default_output_device())
+}
+
+pub fn device_names(host_name: Option<&str>) -> Vec<String> {
+    host(host_name)
+        .output_devices()
+        .map(|devices| devices.filter_map(|x| x.name().ok()).collect())
+        .unwrap_or_default()
+}
+
+/// Shortest buffer the device reports, this is the least latency the output can add.
+pub fn buffer_latency(host_name: Option<&str>, device_name: Option<&str>) -> Option<Duration> {
+    let config = device(&host(host_name), device_name)?
+        .default_output_config()
+        .ok()?;
+
+    match config.buffer_size() {
+        SupportedBufferSize::Range { min, .. } => Some(Duration::from_secs_f64(
+            *min as f64 / config.sample_rate().0 as f64,
+        )),
+        SupportedBufferSize::Unknown => None,
+    }
+}
+
+/// Opens the configured output, falling back to the system default if it is unavailable.
+/// Also returns the name of the opened device.
+pub fn open(
+    host_name: Option<&str>,
+    device_name: Option<&str>,
+) -> anyhow::Result<(OutputStream, OutputStreamHandle, Option<String>)> {
+    if host_name.is_some() || device_name.is_some() {
+        let selected = device(&host(host_name), device_name)
+            .map(|device| (OutputStream::try_from_device(&device), device.name().ok()));
+
+        match selected {
+            Some((Ok((stream, handle)), name)) => return Ok((stream, handle, name)),
+            Some((Err(e), _)) => warn!("Failed to open audio output, using default: {e}"),
+            None => warn!("Audio output not found, using default"),
+        }
+    }
+
+    let (stream, handle) = OutputStream::try_default()?;
+    let name = cpal::default_host()
+        .default_output_device()
+        .and_then(|x| x.name().ok());
+    Ok((stream, handle, name))
+}
@@ -1,5 +1,6 @@
 use std::{
     borrow::BorrowMut,
+    cell::RefCell,
     collections::HashMap,
     ops::{AddAssign, SubAssign},
     sync::mpsc::{channel, Receiver, Sender},
@@ -23,6 +24,70 @@ pub struct RuscFilter {
 
 pub struct CustomBindingFilter;
 
+#[derive(Default)]
+struct DebounceState {
+    released: Option<SystemTime>,
+    /// The last press was a bounce, so its release is ignored as well
+    bouncing: bool,
+}
+
+/// Ignores presses that follow the release of the same button too closely, worn switches
+/// tend to bounce and register extra presses.
+pub struct DebounceFilter {
+    input_state: InputState,
+    buttons: RefCell<HashMap<Button, DebounceState>>,
+}
+
+impl DebounceFilter {
+    pub fn new(input_state: InputState) -> Self {
+        Self {
+            input_state,
+            buttons: RefCell::default(),
+        }
+    }
+}
+
+impl FilterFn for DebounceFilter {
+    fn filter(&self, ev: Option<Event>, _gilrs: &mut gilrs::Gilrs) -> Option<Event> {
+        let ev = ev?;
+        let (button, pressed) = match ev.event {
+            gilrs::EventType::ButtonPressed(button, _) => (button, true),
+            gilrs::EventType::ButtonReleased(button, _) => (button, false),
+            _ => return Some(ev),
+        };
+
+        let window = {
+            let config = GameConfig::get();
+            let ms = config
+                .debounce_overrides
+                .get(&button)
+                .copied()
+                .unwrap_or(config.debounce_ms);
+            Duration::from_millis(ms as _)
+        };
+
+        let mut buttons = self.buttons.borrow_mut();
+        let state = buttons.entry(button).or_default();
+        if pressed {
+            state.bouncing = state
+                .released
+                .and_then(|released| ev.time.duration_since(released).ok())
+                .is_some_and(|since| since < window);
+            if state.bouncing {
+                self.input_state.count_debounced(button.into());
+                return Some(Event::dropped());
+            }
+        } else {
+            state.released = Some(ev.time);
+            if std::mem::take(&mut state.bouncing) {
+                return Some(Event::dropped());
+            }
+        }
+
+        Some(ev)
+    }
+}
+
 impl RuscFilter {
     pub fn new(offset: i32) -> (Self, Sender<i32>) {
         let (offset_tx, offset_rx) = channel();
@@ -365,7 +430,7 @@ impl LaserState {
     }
 }
 
-use crate::{companion_interface::ClientEvent, config::GameConfig};
+use crate::{companion_interface::ClientEvent, config::GameConfig, input_state::InputState};
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
 pub struct CustomControlleMap {
     pub buttons: HashMap<Button, Code>,
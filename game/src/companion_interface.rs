@@ -1,5 +1,7 @@
 use std::borrow::Cow;
-use std::sync::{atomic::AtomicBool, Arc};
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::sync::{atomic::AtomicBool, Arc, Mutex};
 
 use crate::button_codes::UscButton;
 use crate::config::GameConfig;
@@ -15,6 +17,7 @@ use specta::{ts, Type};
 use std::net::SocketAddr;
 use tokio::net::TcpListener;
 use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
 
 #[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Type)]
 #[serde(tag = "variant")]
@@ -41,11 +44,78 @@ pub enum ClientEvent {
     SetLevelFilter(u8),
     SetSongFilterType(song_provider::SongFilterType),
     SetSongSort(song_provider::SongSort),
+    /// Pairs a new device using the PIN shown in the game
+    Pair {
+        device: Cow<'static, str>,
+        pin: Cow<'static, str>,
+    },
+    /// Connects as a paired device using the token it got when pairing
+    Authenticate {
+        token: Cow<'static, str>,
+    },
+}
+
+/// Reply to [`ClientEvent::Pair`] and [`ClientEvent::Authenticate`], the first message of
+/// a connection has to be one of these before any game state is sent.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Type)]
+#[serde(tag = "variant")]
+pub enum AuthResult {
+    Paired {
+        token: Cow<'static, str>,
+        permission: CompanionPermission,
+    },
+    Authenticated {
+        permission: CompanionPermission,
+    },
+    Rejected,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, Clone, Copy, Default, PartialEq, Eq, Type)]
+pub enum CompanionPermission {
+    /// Receives the game state but can't send input
+    ViewOnly,
+    #[default]
+    Control,
+}
+
+impl Display for CompanionPermission {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CompanionPermission::ViewOnly => "View only",
+            CompanionPermission::Control => "Control",
+        })
+    }
+}
+
+/// A device that has been paired with the game.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CompanionDevice {
+    pub name: String,
+    pub token: String,
+    pub permission: CompanionPermission,
+}
+
+fn device_permission(token: &str) -> Option<CompanionPermission> {
+    GameConfig::get()
+        .companion_devices
+        .iter()
+        .find(|x| x.token == token)
+        .map(|x| x.permission)
+}
+
+/// Pairing state shared with the connections.
+#[derive(Default)]
+struct Pairing {
+    /// Set while the game is showing a PIN to pair with
+    pin: Mutex<Option<String>>,
+    /// Tokens of the devices currently connected
+    connected: Mutex<HashSet<String>>,
 }
 
 pub struct CompanionServer {
     event_bus: tokio::sync::broadcast::Sender<GameState>,
     pub active: Arc<AtomicBool>,
+    pairing: Arc<Pairing>,
     _listener: poll_promise::Promise<()>,
 }
 
@@ -54,9 +124,10 @@ async fn accept_connection(
     stream: TcpStream,
     event_proxy: winit::event_loop::EventLoopProxy<UscInputEvent>,
     new_events: tokio::sync::broadcast::Receiver<GameState>,
+    pairing: Arc<Pairing>,
 ) {
     use tokio_tungstenite::tungstenite::Error;
-    if let Err(e) = handle_connection(peer, stream, event_proxy, new_events).await {
+    if let Err(e) = handle_connection(peer, stream, event_proxy, new_events, pairing).await {
         match e {
             Error::ConnectionClosed | Error::Protocol(_) | Error::Utf8 => (),
             err => error!("Error processing connection: {}", err),
@@ -69,6 +140,7 @@ async fn handle_connection(
     stream: TcpStream,
     event_proxy: winit::event_loop::EventLoopProxy<UscInputEvent>,
     mut new_events: tokio::sync::broadcast::Receiver<GameState>,
+    pairing: Arc<Pairing>,
 ) -> tokio_tungstenite::tungstenite::Result<()> {
     let ws_stream = tokio_tungstenite::accept_async(stream)
         .await
@@ -77,10 +149,41 @@ async fn handle_connection(
     info!("New WebSocket connection: {}", peer);
 
     let (mut tx, mut rx) = ws_stream.split();
+
+    let (token, result) = loop {
+        let Some(message) = rx.next().await.transpose()? else {
+            return Ok(());
+        };
+        let Message::Text(data) = message else {
+            continue;
+        };
+        break authenticate(&data, &pairing);
+    };
+
+    tx.send(Message::Text(
+        serde_json::to_string(&result).expect("Failed to serialize AuthResult"),
+    ))
+    .await?;
+
+    let Some(token) = token else {
+        warn!("Companion server rejected {}", peer);
+        return Ok(());
+    };
+
+    pairing
+        .connected
+        .lock()
+        .expect("Lock error")
+        .insert(token.clone());
+
     let a = async {
         while let Ok(e) = new_events.recv().await {
+            // Removed devices are disconnected
+            if device_permission(&token).is_none() {
+                break;
+            }
             let res = tx
-                .send(tokio_tungstenite::tungstenite::Message::Text(
+                .send(Message::Text(
                     serde_json::to_string(&e).expect("Failed to serialize GameState"),
                 ))
                 .await;
@@ -92,7 +195,7 @@ async fn handle_connection(
 
     let b = async {
         while let Some(Ok(e)) = rx.next().await {
-            let tokio_tungstenite::tungstenite::Message::Text(data) = e else {
+            let Message::Text(data) = e else {
                 continue;
             };
             let e: ClientEvent =
@@ -101,9 +204,16 @@ async fn handle_connection(
                 warn!("Companion server got an invalid message: {}", m);
             }
 
+            match device_permission(&token) {
+                Some(CompanionPermission::Control) => {}
+                Some(CompanionPermission::ViewOnly) => continue,
+                None => break,
+            }
+
             let events = match e {
                 ClientEvent::Start => button_click_event(UscButton::Start),
                 ClientEvent::Back => button_click_event(UscButton::Back),
+                ClientEvent::Pair { .. } | ClientEvent::Authenticate { .. } => continue,
                 e => vec![UscInputEvent::ClientEvent(e)],
             };
 
@@ -119,13 +229,52 @@ async fn handle_connection(
 
     tokio::join!(a, b);
 
+    pairing.connected.lock().expect("Lock error").remove(&token);
+
     Ok(())
 }
 
+/// Checks the first message of a connection, returning the token of the device if it is
+/// allowed to connect. A wrong PIN ends pairing so it can't be guessed.
+fn authenticate(data: &str, pairing: &Pairing) -> (Option<String>, AuthResult) {
+    match serde_json::from_str(data) {
+        Ok(ClientEvent::Authenticate { token }) => match device_permission(&token) {
+            Some(permission) => (
+                Some(token.into_owned()),
+                AuthResult::Authenticated { permission },
+            ),
+            None => (None, AuthResult::Rejected),
+        },
+        Ok(ClientEvent::Pair { device, pin }) => {
+            let expected = pairing.pin.lock().expect("Lock error").take();
+            if expected.as_deref() != Some(pin.as_ref()) {
+                return (None, AuthResult::Rejected);
+            }
+
+            let device = CompanionDevice {
+                name: device.into_owned(),
+                token: format!("{:032x}", rand::random::<u128>()),
+                permission: CompanionPermission::default(),
+            };
+            info!("Paired companion device: {}", device.name);
+            let result = AuthResult::Paired {
+                token: device.token.clone().into(),
+                permission: device.permission,
+            };
+            let token = device.token.clone();
+            GameConfig::get_mut().companion_devices.push(device);
+            (Some(token), result)
+        }
+        _ => (None, AuthResult::Rejected),
+    }
+}
+
 impl CompanionServer {
     pub fn new(event_proxy: winit::event_loop::EventLoopProxy<UscInputEvent>) -> Self {
         let (event_bus, _) = tokio::sync::broadcast::channel(8);
         let client_bus = event_bus.clone();
+        let pairing = Arc::new(Pairing::default());
+        let client_pairing = pairing.clone();
 
         let _listener = if let Some(addr) = GameConfig::get().companion_address.as_ref() {
             let addr = addr.clone();
@@ -147,6 +296,7 @@ impl CompanionServer {
                             stream,
                             event_proxy.clone(),
                             client_bus.subscribe(),
+                            client_pairing.clone(),
                         ));
                     }
                 }
@@ -158,6 +308,7 @@ impl CompanionServer {
         Self {
             event_bus,
             active: Arc::new(AtomicBool::new(false)),
+            pairing,
             _listener,
         }
     }
@@ -165,6 +316,28 @@ impl CompanionServer {
     pub fn send_state(&self, state: GameState) {
         _ = self.event_bus.send(state);
     }
+
+    /// Shows a new PIN that a device can pair with once.
+    pub fn start_pairing(&self) {
+        let pin = format!("{:06}", rand::random::<u32>() % 1_000_000);
+        *self.pairing.pin.lock().expect("Lock error") = Some(pin);
+    }
+
+    pub fn stop_pairing(&self) {
+        *self.pairing.pin.lock().expect("Lock error") = None;
+    }
+
+    pub fn pairing_pin(&self) -> Option<String> {
+        self.pairing.pin.lock().expect("Lock error").clone()
+    }
+
+    pub fn is_connected(&self, token: &str) -> bool {
+        self.pairing
+            .connected
+            .lock()
+            .expect("Lock error")
+            .contains(token)
+    }
 }
 
 impl WorkerService for CompanionServer {
@@ -180,6 +353,7 @@ impl WorkerService for CompanionServer {
 pub fn print_schema() -> Vec<(&'static str, String)> {
     let server = schema_for!(GameState);
     let client = schema_for!(ClientEvent);
+    let auth = schema_for!(AuthResult);
     vec![
         (
             "server.json",
@@ -189,6 +363,7 @@ pub fn print_schema() -> Vec<(&'static str, String)> {
             "client.json",
             serde_json::to_string_pretty(&client).unwrap(),
         ),
+        ("auth.json", serde_json::to_string_pretty(&auth).unwrap()),
     ]
 }
 
@@ -1,5 +1,11 @@
 use std::{
-    collections::HashMap, fmt::Display, fs::File, io::Read, path::PathBuf, sync::RwLock,
+    borrow::Cow,
+    collections::HashMap,
+    fmt::Display,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::RwLock,
     time::Duration,
 };
 
@@ -12,15 +18,58 @@ use winit::dpi::{PhysicalPosition, PhysicalSize};
 
 use crate::{
     button_codes::{CustomBindings, UscButton},
+    companion_interface::CompanionDevice,
     game::{self, HitWindow},
     skin_settings::{SkinSettingEntry, SkinSettingValue},
     song_provider,
 };
 use serde_with::serde_as;
 
+/// Skin that assets missing from the active skin are taken from
+pub const DEFAULT_SKIN: &str = "Default";
+
+/// Path of `name` in `folder` of `skin`, or of [`DEFAULT_SKIN`] when `skin` doesn't have it.
+pub fn find_skin_asset(
+    game_folder: &Path,
+    skin: &str,
+    folder: &str,
+    name: impl AsRef<Path>,
+) -> Option<PathBuf> {
+    [skin, DEFAULT_SKIN]
+        .into_iter()
+        .map(|skin| {
+            let mut path = game_folder.join("skins");
+            path.push(skin);
+            path.push(folder);
+            path.push(name.as_ref());
+            path
+        })
+        .find(|path| path.exists())
+}
+
 #[derive(Debug, Default, Parser, Clone)]
 pub struct Args {
-    pub chart: Option<String>,
+    /// Charts to play, multiple charts are played one after another
+    pub chart: Vec<String>,
+    /// Play the charts given on the command line with autoplay
+    #[arg(long)]
+    pub autoplay: bool,
+    /// Play the charts given on the command line with perfect inputs without opening a window
+    /// and print the results, for checking the scoring
+    #[arg(long)]
+    pub headless: bool,
+    /// Start the charts given on the command line paused at this measure, starting from 1.
+    /// Start sets the end and then a new start of an A-B loop while playing, FX changes the
+    /// playback rate while paused.
+    #[arg(long, value_name = "MEASURE")]
+    pub practice: Option<u32>,
+    /// Start the charts given on the command line paused at this tick, used by the editor to
+    /// playtest from its cursor
+    #[arg(long, value_name = "TICK", conflicts_with = "practice")]
+    pub practice_tick: Option<u32>,
+    /// Use this MMOD hi-speed without changing the saved setting
+    #[arg(long)]
+    pub speed: Option<f64>,
     #[arg(short, long)]
     pub debug: bool,
     #[arg(short, long)]
@@ -33,6 +82,9 @@ pub struct Args {
     pub camera_test: bool,
     #[arg(long)]
     pub settings: bool,
+    /// Show the first run setup wizard
+    #[arg(long)]
+    pub setup: bool,
     #[arg(long)]
     pub companion_schema: Option<PathBuf>,
 }
@@ -55,6 +107,60 @@ impl Display for ScoreDisplayMode {
     }
 }
 
+/// Lane changes applied to a chart before it is played
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NoteMode {
+    #[default]
+    Normal,
+    /// Swaps the left and right side of the track
+    Mirror,
+    /// Shuffles the BT lanes
+    Random,
+}
+
+impl Display for NoteMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            NoteMode::Normal => "Normal",
+            NoteMode::Mirror => "Mirror",
+            NoteMode::Random => "Random",
+        })
+    }
+}
+
+/// What happens when a gauge that can fail runs out
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AutoRetire {
+    /// Ends the song right away
+    #[default]
+    Instant,
+    /// Keeps playing to the end, the score is still failed
+    EndOfSong,
+    /// Keeps playing to the end for practice
+    Never,
+}
+
+impl AutoRetire {
+    /// Bit set in the score's auto_flags when the gauge ran out with this behavior
+    pub fn auto_flag(self) -> i32 {
+        match self {
+            AutoRetire::Instant => 0,
+            AutoRetire::EndOfSong => 4,
+            AutoRetire::Never => 8,
+        }
+    }
+}
+
+impl Display for AutoRetire {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AutoRetire::Instant => "Instantly",
+            AutoRetire::EndOfSong => "At end of song",
+            AutoRetire::Never => "Never (practice)",
+        })
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
 pub enum ScoreScreenshot {
     #[default]
@@ -80,6 +186,10 @@ pub struct GameConfig {
     #[serde(skip_serializing, skip_deserializing)]
     config_file: PathBuf,
     pub songs_path: PathBuf,
+    /// Folder maps.db is kept in, relative paths are in the game folder
+    pub database_folder: PathBuf,
+    /// Database opened on the last start, copied into `database_folder` when that changes
+    pub database_in_use: Option<PathBuf>,
     pub skin: String,
     pub laser_hues: [f32; 2],
     pub mappings: Vec<String>,
@@ -101,20 +211,65 @@ pub struct GameConfig {
     pub args: Args,
     pub keybinds: Vec<Keybinds>,
     pub controller_binds: CustomBindings,
+    /// Controller button presses within this many ms of the button's last release are ignored
+    pub debounce_ms: u32,
+    /// `debounce_ms` for single buttons
+    pub debounce_overrides: HashMap<gilrs::Button, u32>,
     pub song_select: SongSelectSettings,
     pub graphics: GraphicsSettings,
     #[serde_as(as = "DurationMilliSecondsWithFrac<f64>")]
     pub laser_input_delay: Duration,
     pub distant_button_scale: f32,
     pub master_volume: f32,
+    /// Audio API to output through, `None` for the system default
+    pub audio_host: Option<String>,
+    /// Output device of the audio host, `None` for its default device
+    pub audio_device: Option<String>,
+    /// `global_offset` of each output device the game has started with, by device name
+    pub device_offsets: HashMap<String, i32>,
+    /// Name of the output device in use
+    #[serde(skip_serializing, skip_deserializing)]
+    pub output_device: Option<String>,
     pub hit_window: game::HitWindow,
+    pub hit_feedback: game::HitFeedback,
     pub score_display: ScoreDisplayMode,
     pub fallback_gauge: bool,
     pub start_gauge: game::gauge::GaugeType,
+    pub note_mode: NoteMode,
+    pub auto_retire: AutoRetire,
     pub slam_volume: f32,
+    pub chip_hit_volume: f32,
+    pub miss_volume: f32,
     pub companion_address: Option<String>,
+    /// Devices allowed to connect to the companion server
+    pub companion_devices: Vec<CompanionDevice>,
+    /// Base URL of the USC-IR server scores are submitted to
+    pub ir_url: Option<String>,
+    /// Token identifying the player to the IR server
+    pub ir_token: String,
+    /// Show the song being played in the OS media controls
+    pub media_session: bool,
+    /// Level song select previews to `preview_loudness_target`
+    pub normalize_previews: bool,
+    /// LUFS
+    pub preview_loudness_target: f32,
+    /// Volume of menu sounds and music while a song preview plays
+    pub preview_duck_volume: f32,
+    /// Play the FX track of legacy KSH charts instead of live effects while FX is held
+    pub prerendered_fx_audio: bool,
     pub score_screenshots: ScoreScreenshot,
     pub screenshot_path: PathBuf,
+    /// Toggles the performance overlay
+    pub perf_hud_key: PhysicalKey,
+    /// Asks skins to hide score, gauge and hit ratings, for recording videos
+    pub clean_hud: bool,
+    /// Keep a minimal progress indicator while the HUD is hidden
+    pub clean_hud_progress: bool,
+    /// Toggles `clean_hud` during gameplay
+    pub clean_hud_key: PhysicalKey,
+    /// No config file was found at startup, the setup wizard is shown
+    #[serde(skip_serializing, skip_deserializing)]
+    pub first_run: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -132,12 +287,31 @@ pub enum Fullscreen {
     },
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FramePacing {
+    /// Paces frames to the refresh rate of the monitor the window is on
+    #[default]
+    Auto,
+    /// Limits frames to `target_fps`
+    Manual,
+}
+
+impl Display for FramePacing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            FramePacing::Auto => "Match display",
+            FramePacing::Manual => "Manual",
+        })
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(default)]
 pub struct GraphicsSettings {
     pub fullscreen: Fullscreen,
     pub vsync: bool,
     pub anti_alias: u8,
+    pub frame_pacing: FramePacing,
     pub target_fps: u32,
     pub show_fps: bool,
     pub disable_bg: bool,
@@ -152,6 +326,7 @@ impl Default for GraphicsSettings {
             },
             vsync: true,
             anti_alias: 4,
+            frame_pacing: FramePacing::Auto,
             target_fps: 300,
             show_fps: false,
             disable_bg: false,
@@ -249,7 +424,9 @@ impl Default for GameConfig {
         Self {
             config_file: PathBuf::from_iter([".", "Main.cfg"]),
             songs_path: PathBuf::from_iter([".", "songs"]),
-            skin: "Default".into(),
+            database_folder: PathBuf::from("."),
+            database_in_use: None,
+            skin: DEFAULT_SKIN.into(),
             skin_settings: HashMap::new(),
             skin_definition: vec![],
             mod_speed: 400.0,
@@ -270,19 +447,43 @@ impl Default for GameConfig {
             button_offset: 0,
             laser_offset: 0,
             controller_binds: HashMap::new(),
+            debounce_ms: 0,
+            debounce_overrides: HashMap::new(),
             song_select: SongSelectSettings::default(),
             graphics: GraphicsSettings::default(),
             distant_button_scale: 2.0,
             master_volume: 0.8,
+            audio_host: None,
+            audio_device: None,
+            device_offsets: HashMap::new(),
+            output_device: None,
             hit_window: HitWindow::NORMAL,
+            hit_feedback: game::HitFeedback::STANDARD,
             score_display: ScoreDisplayMode::default(),
             fallback_gauge: false,
             start_gauge: game::gauge::GaugeType::Normal,
+            note_mode: NoteMode::default(),
+            auto_retire: AutoRetire::default(),
             slam_volume: 0.75,
+            chip_hit_volume: 0.0,
+            miss_volume: 0.0,
             laser_input_delay: Duration::from_millis(50),
             companion_address: Some("127.0.0.1:9002".to_string()),
+            companion_devices: vec![],
+            ir_url: None,
+            ir_token: String::new(),
+            media_session: true,
+            normalize_previews: false,
+            preview_loudness_target: -16.0,
+            preview_duck_volume: 0.4,
+            prerendered_fx_audio: true,
             score_screenshots: ScoreScreenshot::default(),
             screenshot_path: PathBuf::from_iter([".", "screenshots"]),
+            perf_hud_key: PhysicalKey::Code(winit::keyboard::KeyCode::F3),
+            clean_hud: false,
+            clean_hud_progress: true,
+            clean_hud_key: PhysicalKey::Code(winit::keyboard::KeyCode::F6),
+            first_run: false,
         }
     }
 }
@@ -303,6 +504,24 @@ impl GameConfig {
             .expect("Tried to get GameConfig before initializing")
     }
 
+    /// Hi-speed to play with, `--speed` overrides the saved value.
+    pub fn play_mod_speed(&self) -> f64 {
+        self.args.speed.unwrap_or(self.mod_speed)
+    }
+
+    /// Resolves paths relative to the game folder
+    pub fn game_path(&self, path: impl AsRef<Path>) -> PathBuf {
+        if path.as_ref().is_absolute() {
+            path.as_ref().to_path_buf()
+        } else {
+            self.game_folder.join(path)
+        }
+    }
+
+    pub fn database_file(&self) -> PathBuf {
+        self.game_path(&self.database_folder).join("maps.db")
+    }
+
     pub fn skin_path(&self) -> PathBuf {
         let mut skin_path = self.game_folder.clone();
         skin_path.push("skins");
@@ -310,6 +529,11 @@ impl GameConfig {
         skin_path
     }
 
+    /// See [`find_skin_asset`]
+    pub fn skin_asset(&self, folder: &str, name: impl AsRef<Path>) -> Option<PathBuf> {
+        find_skin_asset(&self.game_folder, &self.skin, folder, name)
+    }
+
     fn skin_config_path(&self) -> PathBuf {
         let mut skin_config_path = self.config_file.clone();
         skin_config_path.pop();
@@ -405,7 +629,7 @@ impl GameConfig {
                 INSTANCE.set(RwLock::new(GameConfig {
                     config_file: path,
                     songs_path: PathBuf::from_iter([".", "songs"]),
-                    skin: "Default".into(),
+                    skin: DEFAULT_SKIN.into(),
                     args,
                     ..Default::default()
                 }))
@@ -415,8 +639,9 @@ impl GameConfig {
                 INSTANCE.set(RwLock::new(GameConfig {
                     config_file: path,
                     songs_path: PathBuf::from_iter([".", "songs"]),
-                    skin: "Default".into(),
+                    skin: DEFAULT_SKIN.into(),
                     args,
+                    first_run: true,
                     ..Default::default()
                 }))
             }
@@ -429,10 +654,33 @@ impl GameConfig {
         };
     }
 
+    /// Switches `global_offset` to the one stored for the output device in use, a new device
+    /// starts with the current offset.
+    pub fn set_output_device(&mut self, device: Option<String>) {
+        if let Some(device) = &device {
+            self.global_offset = *self
+                .device_offsets
+                .entry(device.clone())
+                .or_insert(self.global_offset);
+        }
+        self.output_device = device;
+    }
+
     pub fn save(&self) {
         info!("Saving config");
 
-        if let Err(e) = toml::to_string_pretty(self)
+        // Changes to the offset of the device in use are only made to `global_offset`
+        let mut config = Cow::Borrowed(self);
+        if let Some(device) = &self.output_device {
+            if self.device_offsets.get(device) != Some(&self.global_offset) {
+                config
+                    .to_mut()
+                    .device_offsets
+                    .insert(device.clone(), self.global_offset);
+            }
+        }
+
+        if let Err(e) = toml::to_string_pretty(&config)
             .map_err(|e| anyhow::anyhow!(e))
             .and_then(|data| {
                 std::fs::write(&self.config_file, data).map_err(|e| anyhow::anyhow!(e))
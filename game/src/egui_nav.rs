@@ -0,0 +1,91 @@
+//! Controller navigation for egui screens. Button presses and knob turns are turned into
+//! the keyboard events egui already uses for focus and widget input, so every egui screen
+//! can be used without a mouse or keyboard.
+
+use egui::{Event, Key, Modifiers};
+use gilrs::Button;
+use kson::Side;
+
+use crate::button_codes::{LaserState, UscButton};
+
+/// Knob rotation, in radians, needed for one step
+const KNOB_STEP: f32 = std::f32::consts::TAU / 12.0;
+
+#[derive(Debug, Default)]
+pub struct EguiNavigation {
+    knob_travel: [f32; 2],
+}
+
+fn key(key: Key, modifiers: Modifiers) -> [Event; 2] {
+    [true, false].map(|pressed| Event::Key {
+        key,
+        physical_key: None,
+        pressed,
+        repeat: false,
+        modifiers,
+    })
+}
+
+fn focus_next() -> [Event; 2] {
+    key(Key::Tab, Modifiers::NONE)
+}
+
+fn focus_previous() -> [Event; 2] {
+    key(Key::Tab, Modifiers::SHIFT)
+}
+
+impl EguiNavigation {
+    /// FX and the d-pad move focus, start activates the focused widget and BT-A/D or
+    /// d-pad left/right adjust it.
+    pub fn button_pressed(&self, button: UscButton) -> Option<[Event; 2]> {
+        Some(match button {
+            UscButton::FX(Side::Left) | UscButton::Other(Button::DPadUp) => focus_previous(),
+            UscButton::FX(Side::Right) | UscButton::Other(Button::DPadDown) => focus_next(),
+            UscButton::Start | UscButton::Other(Button::South) => key(Key::Enter, Modifiers::NONE),
+            UscButton::BT(kson::BtLane::A) | UscButton::Other(Button::DPadLeft) => {
+                key(Key::ArrowLeft, Modifiers::NONE)
+            }
+            UscButton::BT(kson::BtLane::D) | UscButton::Other(Button::DPadRight) => {
+                key(Key::ArrowRight, Modifiers::NONE)
+            }
+            _ => return None,
+        })
+    }
+
+    /// The left knob moves focus and the right knob adjusts the focused widget, one step
+    /// per [`KNOB_STEP`] of rotation.
+    pub fn knobs(&mut self, knobs: LaserState) -> Vec<Event> {
+        let mut events = vec![];
+
+        for (i, side) in [Side::Left, Side::Right].into_iter().enumerate() {
+            let travel = &mut self.knob_travel[i];
+            *travel += knobs.get_axis(side).delta;
+
+            while travel.abs() >= KNOB_STEP {
+                let forward = *travel > 0.0;
+                *travel -= KNOB_STEP.copysign(*travel);
+
+                events.extend(match (side, forward) {
+                    (Side::Left, true) => focus_next(),
+                    (Side::Left, false) => focus_previous(),
+                    (Side::Right, true) => key(Key::ArrowRight, Modifiers::NONE),
+                    (Side::Right, false) => key(Key::ArrowLeft, Modifiers::NONE),
+                });
+            }
+        }
+
+        events
+    }
+
+    pub fn reset(&mut self) {
+        self.knob_travel = [0.0; 2];
+    }
+}
+
+/// Visuals with a clear outline around the focused widget, as there is no mouse cursor
+/// to follow when navigating with a controller.
+pub fn visuals() -> egui::Visuals {
+    let mut visuals = egui::Visuals::dark();
+    visuals.widgets.active.bg_stroke = egui::Stroke::new(2.0, visuals.selection.stroke.color);
+    visuals
+}
@@ -1,12 +1,14 @@
 use crate::{
     button_codes::{UscButton, UscInputEvent},
-    config::{GameConfig, ScoreDisplayMode},
+    config::{AutoRetire, GameConfig, ScoreDisplayMode},
     game_main::AutoPlay,
     input_state::InputState,
     log_result,
     lua_service::LuaProvider,
+    media_session::NowPlaying,
     scene::{Scene, SceneData},
     shaded_mesh::ShadedMesh,
+    song_provider::{ChartAudio, ChartImages},
     songselect::Song,
     vg_ui::Vgfx,
     ControlMessage,
@@ -31,12 +33,12 @@ use kson_rodio_sources::{
 
 use log::{info, warn};
 use puffin::{profile_function, profile_scope};
-use rodio::{dynamic_mixer::DynamicMixerController, source::Buffered, Decoder, Source};
+use rodio::{dynamic_mixer::DynamicMixerController, Source};
 use std::{
     cmp::Ordering,
     collections::{BTreeMap, VecDeque},
     f32::consts::SQRT_2,
-    ops::Sub,
+    ops::{Range, Sub},
     path::PathBuf,
     rc::Rc,
     sync::{mpsc::Sender, Arc},
@@ -52,13 +54,25 @@ pub mod camera;
 use camera::*;
 mod background;
 use background::GameBackground;
+mod chart_background;
+use chart_background::ChartBackground;
+mod hit_sounds;
+use hit_sounds::{HitSound, HitSounds};
+pub mod judge;
+use judge::Score;
+mod practice;
+use practice::PracticeLoop;
+pub mod replay;
+use replay::{Replay, ReplayInput, ReplayPlayer};
 mod lua_data;
-pub use lua_data::HitWindow;
 pub(crate) use lua_data::LuaGameState;
+pub use lua_data::{FadeCurve, HitFeedback, HitWindow};
 pub mod graphics;
 
 const LASER_THRESHOLD: f64 = 1.0 / 12.0;
 const LEADIN: Duration = Duration::from_secs(3);
+/// Time the fail animation plays before moving on to the results
+const FAIL_DURATION: Duration = Duration::from_millis(1500);
 
 pub struct Game {
     view: ChartView,
@@ -77,10 +91,19 @@ pub struct Game {
     hit_window: HitWindow,
     lua: Rc<Lua>,
     intro_done: bool,
+    /// Playback waits for the start button
+    paused: bool,
+    /// Position to start playback from, including lead-in and offsets
+    start_offset: Duration,
     song: Arc<Song>,
     diff_idx: usize,
     control_tx: Option<Sender<ControlMessage>>,
     gauge: Gauges,
+    auto_retire: AutoRetire,
+    /// Set once the gauge has run out
+    retired: Option<AutoRetire>,
+    /// Start of the fail animation
+    failed_at: Option<SystemTime>,
     results_requested: bool,
     closed: bool,
     playback: kson_music_playback::AudioPlayback,
@@ -88,10 +111,8 @@ pub struct Game {
     score_current_max: u64,
     score_summary: ScoreTickSummary,
     score_display: ScoreDisplayMode,
-    real_score: u64,
+    score: Score,
     display_score: u64,
-    combo: u64,
-    max_combo: u64,
     current_tick: u32,
     input_state: InputState,
     laser_cursors: [f64; 2],
@@ -111,22 +132,35 @@ pub struct Game {
     mixer: Arc<DynamicMixerController<f32>>,
     biquad_control: BiquadController,
     source_owner: owned_source::Marker,
-    slam_sample: Option<Buffered<Decoder<std::fs::File>>>,
-    slam_marker: owned_source::Marker,
+    hit_sounds: HitSounds,
     background: Option<GameBackground>,
     foreground: Option<GameBackground>,
+    chart_background: Option<ChartBackground>,
     service_provider: ServiceProvider,
     sync_delta: VecDeque<f64>,
     laser_effects: BTreeMap<u32, AudioEffect>,
     default_laser_effect: AudioEffect,
     autoplay: AutoPlay,
-    slam_volume: f32,
     chip_h: f32,
     laser_buffer: [VecDeque<(SystemTime, f64)>; 2],
     laser_input_delay: Duration,
     laser_offset: f64,
     button_offset: f64,
     global_offset: f64,
+    /// Laser ticks in these ranges are hit automatically, see [`kson::camera::CameraInfo::input_ignore_ranges`]
+    input_ignore_ranges: Vec<Range<u32>>,
+    /// Set while practicing a section, started with `--practice` or `--practice-tick`
+    practice: Option<PracticeLoop>,
+    /// Song time passes this many times faster than real time
+    rate: f64,
+    /// Time a practice loop waiting between attempts starts playing again
+    resume_at: Option<SystemTime>,
+    /// Recording of this play, or the replay being watched
+    replay: Replay,
+    /// Set while watching [`Game::replay`] instead of playing
+    replay_player: Option<ReplayPlayer>,
+    /// Laser cursors as last recorded in the replay
+    recorded_cursors: [f64; 2],
 }
 
 #[derive(Clone, Copy)]
@@ -284,7 +318,14 @@ pub struct GameData {
     chart: kson::Chart,
     skin_folder: PathBuf,
     audio: std::boxed::Box<(dyn rodio::source::Source<Item = f32> + std::marker::Send + 'static)>,
+    /// Pre-rendered FX track of legacy KSH charts
+    fx_audio: Option<Box<dyn Source<Item = f32> + Send>>,
+    /// Background images from the chart folder
+    chart_images: ChartImages,
+    effect_audio: ChartAudio,
     autoplay: AutoPlay,
+    practice_tick: Option<u32>,
+    replay: Option<Replay>,
 }
 
 impl GameData {
@@ -306,9 +347,49 @@ impl GameData {
             diff_idx,
             song,
             audio: Box::new(audio),
+            fx_audio: None,
+            chart_images: ChartImages::new(),
+            effect_audio: ChartAudio::new(),
             autoplay,
+            practice_tick: None,
+            replay: None,
         })
     }
+
+    /// Play `fx_audio` while FX buttons are held instead of the chart's live effects, if the
+    /// player has pre-rendered FX tracks enabled.
+    pub fn with_fx_audio(mut self, fx_audio: Option<Box<dyn Source<Item = f32> + Send>>) -> Self {
+        self.fx_audio = fx_audio.filter(|_| GameConfig::get().prerendered_fx_audio);
+        self
+    }
+
+    pub fn with_chart_images(mut self, chart_images: ChartImages) -> Self {
+        self.chart_images = chart_images;
+        self
+    }
+
+    pub fn with_effect_audio(mut self, effect_audio: ChartAudio) -> Self {
+        self.effect_audio = effect_audio;
+        self
+    }
+
+    /// Start paused at the given measure, counting from 0, and begin playback when start is pressed.
+    pub fn with_practice(mut self, measure: u32) -> Self {
+        self.practice_tick = Some(self.chart.measure_to_tick(measure));
+        self
+    }
+
+    /// Start paused at the given tick, like [`GameData::with_practice`].
+    pub fn with_practice_tick(mut self, tick: u32) -> Self {
+        self.practice_tick = Some(tick);
+        self
+    }
+
+    /// Watch `replay` instead of playing, with the lane changes and gauge it was recorded with.
+    pub fn with_replay(mut self, replay: Option<Replay>) -> Self {
+        self.replay = replay;
+        self
+    }
 }
 
 impl SceneData for GameData {
@@ -317,15 +398,24 @@ impl SceneData for GameData {
         service_provider: ServiceProvider,
     ) -> anyhow::Result<Box<dyn Scene>> {
         let Self {
-            chart,
+            mut chart,
             skin_folder,
             diff_idx,
             song,
             audio,
+            fx_audio,
+            mut chart_images,
+            effect_audio,
             autoplay,
+            practice_tick,
+            replay,
         } = *self;
         profile_function!();
 
+        let watching = replay.is_some();
+        let replay = replay.unwrap_or_else(|| Replay::new(&GameConfig::get()));
+        replay.apply_note_mode(&mut chart);
+
         let context = service_provider
             .get_required::<three_d::Context>()
             .as_ref()
@@ -494,8 +584,9 @@ impl SceneData for GameData {
         let mut playback = kson_music_playback::AudioPlayback::new();
         let (biquad_control, _) = std::sync::mpsc::channel();
         playback
-            .open(audio, "Game", None)
+            .open(audio, "Game", fx_audio)
             .expect("Failed to load audio");
+        playback.set_chart_audio(effect_audio);
         playback.build_effects(&chart);
         playback.stop();
         let laser_effects = chart.laser_effect_queue();
@@ -509,6 +600,7 @@ impl SceneData for GameData {
             .as_ref()
             .and_then(|x| x.layer.as_ref())
             .and_then(|x| x.filename.clone())
+            .filter(|x| !chart_images.contains_key(x))
             .unwrap_or_else(|| "fallback".to_string());
 
         let mut bg_folder = skin_folder.clone();
@@ -517,7 +609,15 @@ impl SceneData for GameData {
 
         let bg_enabled = !GameConfig::get().graphics.disable_bg;
 
-        let background = bg_enabled
+        let chart_background = bg_enabled
+            .then(|| {
+                ChartBackground::new(&context, &chart, &mut chart_images)
+                    .inspect_err(|e| log::warn!("Failed to load chart background: {e}"))
+                    .ok()
+            })
+            .flatten();
+
+        let background = (bg_enabled && !chart_background.as_ref().is_some_and(|x| x.has_image()))
             .then(|| {
                 match GameBackground::new(
                     &context,
@@ -567,7 +667,7 @@ impl SceneData for GameData {
             })
             .flatten();
 
-        Ok(Box::new(Game::new(
+        let mut game = Game::new(
             chart,
             &skin_folder,
             &context,
@@ -589,12 +689,22 @@ impl SceneData for GameData {
             biquad_control,
             background,
             foreground,
+            chart_background,
             service_provider,
             laser_effects,
             autoplay,
             chip_h,
             laser_colors,
-        )?))
+        )?;
+
+        game.replay = replay;
+        if watching {
+            game.replay_player = Some(ReplayPlayer::default());
+        } else if let Some(tick) = practice_tick {
+            game.start_practice(tick);
+        }
+
+        Ok(Box::new(game))
     }
 }
 
@@ -620,29 +730,29 @@ impl Game {
         biquad_control: BiquadController,
         background: Option<GameBackground>,
         foreground: Option<GameBackground>,
+        chart_background: Option<ChartBackground>,
         service_provider: ServiceProvider,
         laser_effects: BTreeMap<u32, AudioEffect>,
         autoplay: AutoPlay,
         chip_h: f32,
         laser_colors: [three_d::Vector4<f32>; 2],
     ) -> Result<Self> {
-        let mut view = ChartView::new(skin_root, td)?;
+        let mut view = ChartView::new(td)?;
         view.build_laser_meshes(&chart);
-        view.hispeed = (GameConfig::get().mod_speed
+        view.hispeed = (GameConfig::get().play_mod_speed()
             / chart
                 .mode_bpm()
                 .ok_or(anyhow!("Failed to calculate Mode BPM"))?) as f32;
         let duration = chart.ms_to_tick(3000.0 + chart.tick_to_ms(chart.get_last_tick()));
-        let mut slam_path = skin_root.clone();
-        slam_path.push("audio");
-        slam_path.push("laser_slam.wav");
-
         let score_ticks = kson::score_ticks::generate_score_ticks(&chart);
+        let input_ignore_ranges = chart.camera.input_ignore_ranges();
 
         let mut res = Self {
             song,
             diff_idx,
             intro_done: false,
+            paused: false,
+            start_offset: Duration::ZERO,
             lua: LuaProvider::new_lua(),
             chart,
             view,
@@ -658,6 +768,9 @@ impl Game {
             camera: ChartCamera::new(),
             lua_game_state: lua_data::LuaGameState::default(),
             control_tx: None,
+            auto_retire: GameConfig::get().auto_retire,
+            retired: None,
+            failed_at: None,
             results_requested: false,
             closed: false,
             playback,
@@ -666,10 +779,8 @@ impl Game {
             score_display: GameConfig::get().score_display,
             score_ticks,
             gauge: Gauges::default(),
-            real_score: 0,
+            score: Score::default(),
             display_score: u64::MAX,
-            combo: 0,
-            max_combo: 0,
             current_tick: 0,
             input_state,
             laser_cursors: [0.0, 1.0],
@@ -694,12 +805,9 @@ impl Game {
             biquad_control,
             background,
             foreground,
+            chart_background,
             source_owner: Default::default(),
-            slam_sample: std::fs::File::open(slam_path)
-                .ok()
-                .and_then(|x| Decoder::new(x).ok())
-                .map(|x| x.buffered()),
-            slam_marker: Default::default(),
+            hit_sounds: HitSounds::new(skin_root),
             service_provider,
             sync_delta: Default::default(),
             laser_wide: [0, 0],
@@ -710,18 +818,117 @@ impl Game {
                 kson::effects::PeakingFilter::default(),
             ),
             autoplay,
-            slam_volume: GameConfig::get().slam_volume,
             chip_h,
             laser_buffer: [VecDeque::new(), VecDeque::new()],
             laser_input_delay: GameConfig::get().laser_input_delay,
             button_offset: -GameConfig::get().button_offset as _,
             global_offset: -GameConfig::get().global_offset as _,
             laser_offset: -GameConfig::get().laser_offset as _,
+            input_ignore_ranges,
+            practice: None,
+            rate: 1.0,
+            resume_at: None,
+            replay: Replay::default(),
+            replay_player: None,
+            recorded_cursors: [0.0, 1.0],
         };
         res.set_track_uniforms();
         Ok(res)
     }
 
+    fn start_paused_at(&mut self, start_tick: u32) {
+        let start_ms = self.chart.tick_to_ms(start_tick)
+            + self.global_offset
+            + self.chart.audio.bgm.offset as f64
+            + self.playback.leadin().as_secs_f64() * 1000.0;
+
+        self.start_offset = Duration::from_secs_f64(start_ms.max(0.0) / 1000.0);
+        self.score_ticks.retain(|x| x.y >= start_tick);
+        self.paused = true;
+    }
+
+    /// Starts paused at `start_tick` with A-B looping and rate changes, the gauge can not fail
+    /// while practicing.
+    fn start_practice(&mut self, start_tick: u32) {
+        self.practice = Some(PracticeLoop::new(start_tick));
+        self.auto_retire = AutoRetire::Never;
+        self.start_paused_at(start_tick);
+    }
+
+    fn set_rate(&mut self, rate: f64) {
+        let rate = (rate * 100.0).round() / 100.0;
+        self.rate = rate.clamp(practice::MIN_RATE, practice::MAX_RATE);
+        self.playback.set_rate(self.rate);
+        info!("Practice rate: {:.0}%", self.rate * 100.0);
+    }
+
+    /// Records the attempt at the practice loop and starts the loop over after a short pause.
+    fn end_practice_attempt(&mut self) {
+        let Some(practice) = &mut self.practice else {
+            return;
+        };
+
+        let first_rating = practice.first_rating.min(self.hit_ratings.len());
+        let summary = HitSummary::from(&self.hit_ratings[first_rating..]);
+        practice.attempts.push(summary);
+        let attempts = practice.attempts.len();
+        let clears = practice.clears();
+        let start = practice.start;
+        info!("Practice attempt {attempts}: {summary:?}");
+
+        if let Ok(end_run) = self.lua.globals().get::<_, Function>("practice_end_run") {
+            let scoring = self.lua.create_table().and_then(|scoring| {
+                scoring.set("score", practice::attempt_score(&summary))?;
+                scoring.set("perfects", summary.crit)?;
+                scoring.set("goods", summary.good)?;
+                scoring.set("misses", summary.miss)?;
+                Ok(scoring)
+            });
+            match scoring {
+                Ok(scoring) => log_result!(end_run.call::<_, ()>((
+                    attempts,
+                    clears,
+                    summary.full_combo(),
+                    scoring
+                ))),
+                Err(e) => warn!("{e}"),
+            }
+        }
+
+        self.restart_practice(start);
+    }
+
+    /// Stops the audio and resets the score to play the practice loop again from `start`.
+    fn restart_practice(&mut self, start: u32) {
+        self.playback.stop();
+        self.source_owner = Default::default();
+        self.playback.build_effects(&self.chart);
+        self.playback.set_leadin(LEADIN);
+
+        self.score_ticks = kson::score_ticks::generate_score_ticks(&self.chart);
+        self.score = Score::default();
+        self.score_current_max = 0;
+        self.hit_ratings.clear();
+        if let Some(practice) = &mut self.practice {
+            practice.first_rating = 0;
+        }
+        self.sync_delta.clear();
+        self.current_tick = start;
+        self.start_paused_at(start);
+        self.resume_at = Some(SystemTime::now() + practice::RESTART_DELAY);
+
+        if let Ok(update_combo) = self.lua.globals().get::<_, Function>("update_combo") {
+            log_result!(update_combo.call::<_, ()>(0));
+        }
+    }
+
+    /// Song time passed between the start of playback and `at`
+    fn song_time(&self, at: SystemTime) -> Duration {
+        at.duration_since(self.zero_time)
+            .unwrap_or(Duration::ZERO)
+            .mul_f64(self.rate)
+    }
+
     fn set_track_uniforms(&mut self) {
         [
             &mut self.track_shader,
@@ -768,6 +975,7 @@ impl Game {
         let track_right = graphics::camera_to_screen(camera, Vec3::unit_x() * 0.5, screen);
         let crit_line = track_right - track_left;
         let rotation = -crit_line.y.atan2(crit_line.x);
+        let config = GameConfig::get();
 
         lua_data::LuaGameState {
             title: self.chart.meta.title.clone(),
@@ -787,7 +995,7 @@ impl Game {
             sudden_cutoff: 0.0,
             hidden_fade: 0.0,
             sudden_fade: 0.0,
-            autoplay: self.autoplay.any(),
+            autoplay: self.autoplay.any() || self.replay_player.is_some(),
             combo_state: 0,
             note_held: [false; 6],
             laser_active: [self.laser_active[0], self.laser_active[1]],
@@ -827,9 +1035,12 @@ impl Game {
                 },
             },
             hit_window,
+            hit_feedback: config.hit_feedback,
             multiplayer: false,
             user_id: "Player".into(),
-            practice_setup: false,
+            practice_setup: self.practice.as_ref().is_some_and(|x| x.end.is_none()),
+            clean_hud: config.clean_hud,
+            clean_hud_progress: config.clean_hud_progress,
         }
     }
 
@@ -849,33 +1060,14 @@ impl Game {
 
     fn on_hit(&mut self, hit_rating: HitRating) {
         self.hit_ratings.push(hit_rating);
+        if self.recording() {
+            let ms = self.current_time().as_secs_f64() * 1000.0;
+            self.replay.add_hit(ms, hit_rating);
+        }
 
-        self.real_score += match hit_rating {
-            HitRating::Crit { .. } => 2,
-            HitRating::Good { .. } => 1,
-            _ => 0,
-        };
-
-        let combo_updated = match hit_rating {
-            HitRating::Crit { .. } | HitRating::Good { .. } => {
-                self.combo += 1;
-                self.max_combo = self.max_combo.max(self.combo);
-                true
-            }
-            HitRating::Miss { .. } => {
-                if self.combo == 0 {
-                    false
-                } else {
-                    self.combo = 0;
-                    true
-                }
-            }
-            HitRating::None => false,
-        };
-
-        if combo_updated {
+        if self.score.add(hit_rating) {
             if let Ok(update_combo) = self.lua.globals().get::<_, Function>("update_combo") {
-                crate::log_result!(update_combo.call::<_, ()>(self.combo));
+                crate::log_result!(update_combo.call::<_, ()>(self.score.combo));
             }
         }
 
@@ -887,6 +1079,7 @@ impl Game {
             } => match tick.tick {
                 ScoreTick::Chip { lane } => {
                     self.beam_colors_current[lane] = self.get_beam_color(lane, 2, delta);
+                    self.hit_sounds.play(HitSound::Chip, &self.mixer);
                 }
                 ScoreTick::Slam { lane, start, end } => {
                     let laser_slam_hit = self.lua.globals().get::<_, Function>("laser_slam_hit");
@@ -934,13 +1127,7 @@ impl Game {
                     //TODO: Does this actually help?
                     self.laser_buffer[lane].clear();
 
-                    if let Some(slam_sample) = self.slam_sample.clone() {
-                        drop(std::mem::take(&mut self.slam_marker));
-                        self.mixer.add(owned_source(
-                            slam_sample.convert_samples().amplify(self.slam_volume),
-                            &self.slam_marker,
-                        )); //TODO: Amplyfy with slam volume
-                    }
+                    self.hit_sounds.play(HitSound::Slam, &self.mixer);
 
                     if let Ok(laser_slam_hit) = laser_slam_hit {
                         log_result!(laser_slam_hit.call::<_, ()>((
@@ -960,6 +1147,7 @@ impl Game {
             } => {
                 if let ScoreTick::Chip { lane } = tick.tick {
                     self.beam_colors_current[lane] = self.get_beam_color(lane, 1, delta);
+                    self.hit_sounds.play(HitSound::Chip, &self.mixer);
                     if let Ok(near_hit) = self.lua.globals().get::<_, Function>("near_hit") {
                         log_result!(near_hit.call::<_, ()>(delta < 0.0));
                     }
@@ -983,6 +1171,7 @@ impl Game {
                     if delta.abs() > f64::EPSILON {
                         self.beam_colors_current[lane] = self.get_beam_color(lane, 0, 0.0);
                     }
+                    self.hit_sounds.play(HitSound::Miss, &self.mixer);
                 }
             }
 
@@ -1011,28 +1200,27 @@ impl Game {
         beam_color
     }
 
-    pub const MAX_SCORE: u64 = 10_000_000_u64;
+    pub const MAX_SCORE: u64 = kson::score_ticks::MAX_SCORE;
     fn actual_display_score(&self) -> u64 {
-        let max = self.score_summary.total as u64 * 2;
-        Self::MAX_SCORE * self.real_score / max
+        self.score.display(self.score_summary.total)
     }
     fn calculate_display_score(&self) -> u64 {
         let max = self.score_summary.total as u64 * 2;
         match self.score_display {
             ScoreDisplayMode::Additive => self.actual_display_score(),
             ScoreDisplayMode::Subtractive => {
-                Self::MAX_SCORE * (max - (self.score_current_max - self.real_score)) / max
+                Self::MAX_SCORE * (max - (self.score_current_max - self.score.real)) / max
             }
             ScoreDisplayMode::Average => {
-                Self::MAX_SCORE * self.real_score / self.score_current_max.max(1)
+                Self::MAX_SCORE * self.score.real / self.score_current_max.max(1)
             }
         }
     }
 
     fn hold_ok(&self, lane: usize, start_tick: u32) -> bool {
-        let is_button_held = &self.input_state.is_button_held((lane as u8).into());
+        let is_button_held = &self.button_held((lane as u8).into());
         let start_ms = self.without_offset(self.chart.tick_to_ms(start_tick));
-        let hold_start = self.zero_time + Duration::from_secs_f64(start_ms / 1000.0);
+        let hold_start = self.zero_time + Duration::from_secs_f64(start_ms / 1000.0 / self.rate);
         let hold_start_thres = hold_start
             .checked_sub(self.hit_window.hold)
             .unwrap_or(hold_start);
@@ -1064,7 +1252,10 @@ impl Game {
                 }
             }
             ScoreTick::Laser { lane, pos } => {
-                if (self.laser_cursors[lane] - pos).abs() < LASER_THRESHOLD || self.auto_lasers() {
+                if (self.laser_cursors[lane] - pos).abs() < LASER_THRESHOLD
+                    || self.auto_lasers()
+                    || self.input_ignored(tick.y)
+                {
                     HitRating::Crit {
                         tick,
                         delta: 0.0,
@@ -1088,9 +1279,7 @@ impl Game {
                 };
                 let delta = ms
                     - self.with_offset(
-                        self.laser_latest_dir_inputs[lane][dir]
-                            .duration_since(self.zero_time)
-                            .unwrap_or(Duration::ZERO)
+                        self.song_time(self.laser_latest_dir_inputs[lane][dir])
                             .as_secs_f64()
                             * 1000.0,
                     );
@@ -1129,12 +1318,10 @@ impl Game {
         }
     }
     fn current_time(&self) -> std::time::Duration {
-        if !self.intro_done {
-            Duration::ZERO
+        if !self.intro_done || self.paused {
+            self.start_offset
         } else {
-            SystemTime::now()
-                .duration_since(self.zero_time)
-                .unwrap_or(Duration::ZERO)
+            self.song_time(SystemTime::now())
         }
     }
 
@@ -1152,14 +1339,25 @@ impl Game {
             + self.playback.leadin().as_secs_f64() * 1000.0
     }
 
-    fn fail_song(&mut self) -> anyhow::Result<()> {
-        //TODO: Enter fail transition state
-        self.transition_to_results()?;
-        Ok(())
+    /// Freezes the chart and stops the audio, the results are shown once the fail
+    /// animation is done.
+    fn fail_song(&mut self) {
+        self.start_offset = self.current_time();
+        self.paused = true;
+        self.failed_at = Some(SystemTime::now());
+        self.playback.stop();
+        self.camera.shakes.push(CameraShake::new(
+            3f32.to_radians(),
+            1.0,
+            12.0,
+            FAIL_DURATION.as_millis() as _,
+        ));
     }
 
     fn transition_to_results(&mut self) -> Result<(), anyhow::Error> {
-        if let AutoPlay::None = self.autoplay {
+        if let (AutoPlay::None, None) = (self.autoplay, &self.replay_player) {
+            // Presses are recorded at their event time and lasers at the tick they were read
+            self.replay.inputs.sort_by(|a, b| a.ms().total_cmp(&b.ms()));
             self.control_tx
                 .as_ref()
                 .ok_or(anyhow!("control_tx not set"))?
@@ -1173,7 +1371,12 @@ impl Game {
                     duration: self.chart.tick_to_ms(self.duration) as i32,
                     hit_window: self.hit_window,
                     manual_exit: false,
-                    max_combo: self.max_combo as _,
+                    max_combo: self.score.max_combo as _,
+                    retired: self.retired,
+                    replay: self
+                        .practice
+                        .is_none()
+                        .then(|| std::mem::take(&mut self.replay)),
                 })
                 .expect("Main loop messaging error");
         } else {
@@ -1182,6 +1385,100 @@ impl Game {
         Ok(())
     }
 
+    /// Whether inputs and judgements are recorded into [`Game::replay`]
+    fn recording(&self) -> bool {
+        self.replay_player.is_none() && !self.autoplay.any() && self.practice.is_none()
+    }
+
+    fn record_input(&mut self, input: ReplayInput) {
+        if self.recording() {
+            self.replay.inputs.push(input);
+        }
+    }
+
+    /// Records the laser cursors if they moved since they were last recorded.
+    fn record_laser_cursors(&mut self, time: Duration) {
+        for side in 0..2 {
+            let pos = (self.laser_cursors[side] * 1000.0).round() / 1000.0;
+            if pos != self.recorded_cursors[side] {
+                self.recorded_cursors[side] = pos;
+                self.record_input(ReplayInput::Laser {
+                    ms: time.as_secs_f64() * 1000.0,
+                    side: side as u8,
+                    pos,
+                });
+            }
+        }
+    }
+
+    /// Applies the inputs and judgements of the watched replay up to `time`.
+    fn watch_replay(&mut self, time: Duration) {
+        let Some(player) = &mut self.replay_player else {
+            return;
+        };
+        let ms = time.as_secs_f64() * 1000.0;
+        let inputs = player.inputs_until(&self.replay, ms);
+        let hits = player.hits_until(&self.replay, ms);
+
+        for input in inputs {
+            match input {
+                ReplayInput::Press { ms, button } => {
+                    let pressed_at =
+                        self.zero_time + Duration::from_secs_f64(ms / 1000.0 / self.rate);
+                    if let Some(held) = self
+                        .replay_player
+                        .as_mut()
+                        .and_then(|x| x.held.get_mut(button as usize))
+                    {
+                        *held = Some(pressed_at);
+                        self.beam_colors_current[button as usize] =
+                            self.get_beam_color(button as usize, 3, 0.0);
+                    }
+                }
+                ReplayInput::Release { button, .. } => {
+                    if let Some(held) = self
+                        .replay_player
+                        .as_mut()
+                        .and_then(|x| x.held.get_mut(button as usize))
+                    {
+                        *held = None;
+                    }
+                }
+                ReplayInput::Laser { side, pos, .. } => {
+                    if let Some(cursor) = self.laser_cursors.get_mut(side as usize) {
+                        *cursor = pos;
+                    }
+                }
+            }
+        }
+
+        for hit in hits {
+            let Some(index) = self
+                .score_ticks
+                .iter()
+                .position(|x| x.y == hit.y && x.tick.global_lane() == hit.lane as usize)
+            else {
+                warn!("No score tick for replay judgement at {}", hit.y);
+                continue;
+            };
+            let tick = self.score_ticks.remove(index);
+            self.on_hit(hit.rating(tick));
+            self.score_current_max += 2;
+        }
+    }
+
+    /// When the button was pressed, by the player or in the watched replay.
+    fn button_held(&self, button: UscButton) -> Option<SystemTime> {
+        match &self.replay_player {
+            Some(player) => player
+                .held
+                .get(Into::<u8>::into(button) as usize)
+                .copied()
+                .flatten(),
+            None => self.input_state.is_button_held(button),
+        }
+    }
+
     fn auto_buttons(&self) -> bool {
         matches!(self.autoplay, AutoPlay::All | AutoPlay::Buttons)
     }
@@ -1190,6 +1487,13 @@ impl Game {
         matches!(self.autoplay, AutoPlay::All | AutoPlay::Lasers)
     }
 
+    fn input_ignored(&self, tick: u32) -> bool {
+        let i = self
+            .input_ignore_ranges
+            .partition_point(|x| x.start <= tick);
+        i > 0 && self.input_ignore_ranges[i - 1].contains(&tick)
+    }
+
     fn take_laser_input(&mut self, index: usize, now: SystemTime) -> bool {
         let Some((time_stamp, delta)) = self.laser_buffer[index].pop_front() else {
             return false;
@@ -1261,13 +1565,10 @@ impl Game {
         button: UscButton,
         button_num: u8,
         timestamp: SystemTime,
-        perfect: Duration,
-        good: Duration,
-        miss: Duration,
     ) -> HitRating {
         let last_tick = self.chart.ms_to_tick(
             self.with_offset(self.current_time().as_secs_f64() * 1000.0)
-                + miss.as_secs_f64() * 1000.0,
+                + self.hit_window.miss.as_secs_f64() * 1000.0,
         ) + 1;
         let mut hittable_ticks = self.score_ticks.iter().take_while(|x| x.y < last_tick);
         let mut hit_rating = HitRating::None;
@@ -1287,26 +1588,10 @@ impl Game {
                     }
                     let tick = *score_tick;
                     let ms = self.chart.tick_to_ms(score_tick.y);
-                    let time = self.with_offset(
-                        timestamp
-                            .duration_since(self.zero_time)
-                            .unwrap_or(Duration::ZERO)
-                            .as_secs_f64()
-                            * 1000.0,
-                    );
+                    let time = self.with_offset(self.song_time(timestamp).as_secs_f64() * 1000.0);
 
                     let delta = ms - time + self.button_offset;
-                    let abs_delta = Duration::from_secs_f64(delta.abs() / 1000.0);
-
-                    hit_rating = if abs_delta <= perfect {
-                        HitRating::Crit { tick, delta, time }
-                    } else if abs_delta <= good {
-                        HitRating::Good { tick, delta, time }
-                    } else if abs_delta <= miss {
-                        HitRating::Miss { tick, delta, time }
-                    } else {
-                        HitRating::None
-                    };
+                    hit_rating = self.hit_window.rate_chip(tick, delta, time);
 
                     match hit_rating {
                         HitRating::None => {}
@@ -1319,6 +1604,9 @@ impl Game {
                 }
             }
             crate::button_codes::UscButton::Back => self.closed = true,
+            crate::button_codes::UscButton::Start if self.paused && self.failed_at.is_none() => {
+                self.paused = false
+            }
             _ => {}
         }
         hit_rating
@@ -1357,7 +1645,8 @@ impl Scene for Game {
         if playback_ms > 0.0 && !self.score_ticks.is_empty() {
             if avg_delta.abs() > 250.0 {
                 self.sync_delta.clear();
-                self.zero_time = SystemTime::now().sub(Duration::from_millis(playback_ms as _));
+                self.zero_time = SystemTime::now()
+                    .sub(Duration::from_millis(playback_ms as _).div_f64(self.rate));
             } else if avg_delta.abs() > 1.0 {
                 if avg_delta > 0.0 {
                     self.zero_time -= Duration::from_nanos(50000);
@@ -1369,10 +1658,29 @@ impl Scene for Game {
             time = self.current_time();
         }
 
-        if self.current_tick >= self.duration && !self.results_requested {
+        let fail_done = self
+            .failed_at
+            .and_then(|x| x.elapsed().ok())
+            .is_some_and(|x| x >= FAIL_DURATION);
+        if (self.current_tick >= self.duration || fail_done) && !self.results_requested {
             self.transition_to_results()?;
             self.results_requested = true;
         }
+        if self.resume_at.is_some_and(|x| sys_time >= x) {
+            self.resume_at = None;
+            self.paused = false;
+        }
+
+        let loop_done = self
+            .practice
+            .as_ref()
+            .and_then(|x| x.end)
+            .is_some_and(|end| self.current_tick >= end);
+        if loop_done && self.resume_at.is_none() {
+            self.end_practice_attempt();
+            return Ok(());
+        }
+
         let missed_chip_tick = self.chart.ms_to_tick(
             self.with_offset(time.saturating_sub(self.hit_window.good).as_secs_f64() * 1000.0),
         );
@@ -1430,29 +1738,13 @@ impl Scene for Game {
             self.biquad_control.send((None, Some(0.0)))
         };
 
-        let keep_laser = match self
-            .chart
-            .camera
-            .tilt
-            .keep
-            .binary_search_by_key(&self.current_tick, |x| x.0)
-        {
-            Ok(i) => self.chart.camera.tilt.keep[i].1,
-            Err(i) => {
-                if i == 0 {
-                    false
-                } else {
-                    self.chart.camera.tilt.keep[i.saturating_sub(1)].1
-                }
-            }
-        };
+        let keep_laser = self.chart.camera.tilt.keep_at(self.current_tick);
 
         self.target_roll = self
             .chart
             .camera
             .tilt
-            .manual
-            .value_at(self.current_tick as f64)
+            .manual_at(self.current_tick as f64)
             .map(TargetRoll::Manual)
             .unwrap_or_else(|| {
                 let current = self.target_roll;
@@ -1510,8 +1802,10 @@ impl Scene for Game {
             *assist_ticks = assist_ticks.saturating_sub(1);
         }
 
+        self.watch_replay(time);
+
         let mut i = 0;
-        while i < self.score_ticks.len() {
+        while i < self.score_ticks.len() && self.replay_player.is_none() {
             if self.score_ticks[i].y > self.current_tick {
                 break;
             }
@@ -1526,15 +1820,11 @@ impl Scene for Game {
             }
         }
 
+        self.record_laser_cursors(time);
+
         self.playback.set_fx_enable(
-            self.input_state
-                .is_button_held(UscButton::FX(kson::Side::Left))
-                .is_some()
-                || self.auto_buttons(),
-            self.input_state
-                .is_button_held(UscButton::FX(kson::Side::Right))
-                .is_some()
-                || self.auto_buttons(),
+            self.button_held(UscButton::FX(kson::Side::Left)).is_some() || self.auto_buttons(),
+            self.button_held(UscButton::FX(kson::Side::Right)).is_some() || self.auto_buttons(),
         );
 
         self.camera.check_spins(self.current_tick);
@@ -1577,8 +1867,11 @@ impl Scene for Game {
             }
         }
 
-        if self.gauge.is_dead() {
-            self.fail_song()?;
+        if self.gauge.is_dead() && self.retired.is_none() {
+            self.retired = Some(self.auto_retire);
+            if self.auto_retire == AutoRetire::Instant {
+                self.fail_song();
+            }
         }
 
         Ok(())
@@ -1604,15 +1897,16 @@ impl Scene for Game {
             (gain, gain / 4.0)
         };
 
-        let config = GameConfig::get();
-        let fallbacks = (config.start_gauge.fallback_supported() && config.fallback_gauge)
+        let Replay {
+            gauge,
+            fallback_gauge,
+            ..
+        } = self.replay;
+        let fallbacks = (gauge.fallback_supported() && fallback_gauge)
             .then(|| GaugeType::Normal.get_gauge(chip_gain, tick_gain))
             .into_iter()
             .collect();
-        self.gauge = Gauges::new(
-            config.start_gauge.get_gauge(chip_gain, tick_gain),
-            fallbacks,
-        );
+        self.gauge = Gauges::new(gauge.get_gauge(chip_gain, tick_gain), fallbacks);
         self.control_tx = Some(app_control_tx);
         lua_provider.register_libraries(self.lua.clone(), "gameplay.lua")?;
         Ok(())
@@ -1639,8 +1933,8 @@ impl Scene for Game {
                         {
                             let new_time = self.chart.tick_to_ms(self.current_tick);
 
-                            self.zero_time =
-                                SystemTime::now().sub(Duration::from_millis(new_time as _))
+                            self.zero_time = SystemTime::now()
+                                .sub(Duration::from_millis(new_time as _).div_f64(self.rate))
                         }
 
                         ui.end_row();
@@ -1759,9 +2053,9 @@ impl Scene for Game {
 
         self.camera
             .update(vec2(viewport.width as f32, viewport.height as f32));
-        if self.intro_done && !self.playback.is_playing() {
+        if self.intro_done && !self.paused && !self.playback.is_playing() {
             info!("Starting playback");
-            self.zero_time = SystemTime::now();
+            self.zero_time = SystemTime::now().sub(self.start_offset.div_f64(self.rate));
             if !self.playback.play() {
                 log::error!("Could not play audio");
                 self.closed = true;
@@ -1772,9 +2066,11 @@ impl Scene for Game {
 
             self.biquad_control = biquad_control;
 
+            let mut source = self.playback.get_source().expect("Audio not loaded");
+            source.skip(self.start_offset);
             self.mixer.add(owned_source(
                 biquad(
-                    self.playback.get_source().expect("Audio not loaded"),
+                    self.playback.stretch(source),
                     BiQuadState::new(BiQuadType::AllPass, SQRT_2, 100.0),
                     Some(biquad_events),
                 ),
@@ -1793,22 +2089,7 @@ impl Scene for Game {
             let max_roll_speed = dt / kson::beat_in_ms(self.chart.bpm_at_tick(self.current_tick));
             self.current_roll = match self.target_roll {
                 TargetRoll::Laser(target_roll) => {
-                    let scale = match self
-                        .chart
-                        .camera
-                        .tilt
-                        .scale
-                        .binary_search_by_key(&self.current_tick, |x| x.0)
-                    {
-                        Ok(i) => self.chart.camera.tilt.scale[i].1,
-                        Err(i) => {
-                            if i == 0 {
-                                1.0
-                            } else {
-                                self.chart.camera.tilt.scale[i.saturating_sub(1)].1
-                            }
-                        }
-                    };
+                    let scale = self.chart.camera.tilt.scale_at(self.current_tick);
                     let target_roll = target_roll * scale;
                     if self.current_roll - target_roll < 0.0 {
                         (self.current_roll + max_roll_speed * 2.0 * scale).min(target_roll)
@@ -1834,6 +2115,11 @@ impl Scene for Game {
                 .iter()
                 .map(|x| x.roll_at(self.current_tick as f32))
                 .sum::<f32>();
+            if let Some(failed_at) = self.failed_at {
+                let progress = failed_at.elapsed().unwrap_or_default().as_secs_f32()
+                    / FAIL_DURATION.as_secs_f32();
+                self.camera.tilt += progress.min(1.0).powi(2) * 30.0;
+            }
 
             self.view.cursor = self.with_offset(time.as_secs_f64() * 1000.0);
 
@@ -1870,6 +2156,16 @@ impl Scene for Game {
                 self.gauge.is_cleared(),
             );
         }
+        if let Some(chart_bg) = self.chart_background.as_mut() {
+            let tilt = self.current_roll as f32 * 12.5;
+            chart_bg.render(
+                td_camera.viewport(),
+                time_ms,
+                self.current_tick,
+                tilt,
+                self.camera.tilt - tilt,
+            );
+        }
 
         self.beam_colors_current
             .iter_mut()
@@ -2044,6 +2340,20 @@ impl Scene for Game {
         &mut self,
         event: &game_loop::winit::event::Event<crate::button_codes::UscInputEvent>,
     ) {
+        if let game_loop::winit::event::Event::WindowEvent {
+            event: game_loop::winit::event::WindowEvent::KeyboardInput { event: key, .. },
+            ..
+        } = event
+        {
+            if key.state == game_loop::winit::event::ElementState::Pressed
+                && !key.repeat
+                && key.physical_key == GameConfig::get().clean_hud_key
+            {
+                let mut config = GameConfig::get_mut();
+                config.clean_hud = !config.clean_hud;
+            }
+        }
+
         if let game_loop::winit::event::Event::UserEvent(UscInputEvent::Laser(ls, timestamp)) =
             event
         {
@@ -2067,7 +2377,7 @@ impl Scene for Game {
                     Ordering::Greater => self.laser_latest_dir_inputs[index][1] = *timestamp,
                 }
 
-                if delta.abs() > 0.0 {
+                if delta.abs() > 0.0 && self.replay_player.is_none() {
                     self.laser_buffer[index].push_back((*timestamp, delta));
                 }
             }
@@ -2075,18 +2385,43 @@ impl Scene for Game {
     }
 
     fn on_button_pressed(&mut self, button: crate::button_codes::UscButton, timestamp: SystemTime) {
-        let HitWindow {
-            variant: _,
-            perfect,
-            good,
-            hold: _,
-            miss,
-            slam: _,
-        } = self.hit_window;
+        if self.replay_player.is_some() {
+            if let UscButton::Back = button {
+                self.closed = true;
+            }
+            return;
+        }
+
+        if self.practice.is_some() {
+            match button {
+                UscButton::Start if !self.paused => {
+                    if let Some(practice) = &mut self.practice {
+                        practice.set_marker(&self.chart, self.current_tick, self.hit_ratings.len());
+                    }
+                    return;
+                }
+                UscButton::FX(side) if self.paused => {
+                    let step = match side {
+                        Side::Left => -practice::RATE_STEP,
+                        Side::Right => practice::RATE_STEP,
+                    };
+                    self.set_rate(self.rate + step);
+                    return;
+                }
+                _ => {}
+            }
+        }
 
         let button_num = Into::<u8>::into(button);
+        if button_num < 6 {
+            let ms = self.song_time(timestamp).as_secs_f64() * 1000.0;
+            self.record_input(ReplayInput::Press {
+                ms,
+                button: button_num,
+            });
+        }
 
-        let hit_rating = self.get_hit_rating(button, button_num, timestamp, perfect, good, miss);
+        let hit_rating = self.get_hit_rating(button, button_num, timestamp);
         if let HitRating::None = hit_rating {
             if (button_num as usize) < self.beam_colors_current.len() {
                 self.beam_colors_current[button_num as usize] =
@@ -2095,7 +2430,34 @@ impl Scene for Game {
         }
     }
 
+    fn on_button_released(&mut self, button: UscButton, timestamp: SystemTime) {
+        let button_num = Into::<u8>::into(button);
+        if button_num < 6 {
+            let ms = self.song_time(timestamp).as_secs_f64() * 1000.0;
+            self.record_input(ReplayInput::Release {
+                ms,
+                button: button_num,
+            });
+        }
+    }
+
     fn name(&self) -> &str {
         "Game"
     }
+
+    fn now_playing(&self) -> Option<NowPlaying> {
+        let jacket = self
+            .song
+            .difficulties
+            .read()
+            .ok()?
+            .get(self.diff_idx)
+            .map(|x| x.jacket_path.clone());
+
+        Some(NowPlaying {
+            title: self.song.title.clone(),
+            artist: self.song.artist.clone(),
+            jacket,
+        })
+    }
 }
@@ -32,32 +32,14 @@ impl CameraSpin {
     }
 
     pub fn roll_at(self, tick: f32) -> f32 {
-        match self {
-            CameraSpin::Half(_) => 0.0,
-            CameraSpin::Full(kson::camera::CamPatternInvokeSpin(y, dir, len)) => {
-                //Reference https://github.com/kshootmania/ksm-v2/blob/master/kshootmania/src/music_game/camera/cam_pattern/cam_pattern_spin.cpp#L52
-                let rate = (tick - y as f32) / len as f32;
-                if !(0.0..=1.0).contains(&rate) {
-                    return 0.0;
-                }
+        let tick = tick as f64;
+        let state = match self {
+            CameraSpin::Half(s) => s.half_at(tick),
+            CameraSpin::Full(s) => s.full_at(tick),
+            CameraSpin::Swing(s) => s.state_at(tick),
+        };
 
-                let abs_degrees = if rate < 360.0 / 675.0 {
-                    (rate / (360.0 / 675.0) * 0.75).sin() / (0.75f32).sin() * 360.0
-                } else if rate < 440.0 / 675.0 {
-                    (((rate * 675.0 - 360.0) * 9.0 / 8.0).to_radians()).sin() * 30.0
-                } else {
-                    (1.0 - ((1.0 - rate) * 90.0 / 235.0 * 675.0)
-                        .to_radians()
-                        .cos()
-                        .powf(2.0))
-                        * 30.0
-                };
-
-                let dir = -(dir as f32).signum();
-                abs_degrees * dir
-            }
-            CameraSpin::Swing(_) => 0.0,
-        }
+        state.map_or(0.0, |x| x.roll() as f32)
     }
 }
 
@@ -0,0 +1,152 @@
+use kson::{KshLayerInfo, KSON_RESOLUTION};
+use log::warn;
+use puffin::profile_function;
+use three_d::Viewport;
+
+use crate::{shaded_mesh::ShadedMesh, song_provider::ChartImages};
+
+const SHADER: &str = r#"
+in vec2 texVp;
+out vec4 target;
+
+uniform ivec2 viewport;
+uniform sampler2D mainTex;
+// Layer animation frames are placed side by side in the texture
+uniform float frames;
+uniform float frame;
+// Radians
+uniform float rotation;
+
+void main()
+{
+    vec2 screen = vec2(viewport);
+    vec2 size = vec2(textureSize(mainTex, 0)) / vec2(frames, 1.0);
+    // Cover the screen, with some margin for rotating
+    float scale = max(screen.x / size.x, screen.y / size.y) * (rotation == 0.0 ? 1.0 : 1.5);
+
+    vec2 pos = texVp - screen * 0.5;
+    float s = sin(rotation);
+    float c = cos(rotation);
+    pos = vec2(pos.x * c - pos.y * s, pos.x * s + pos.y * c);
+
+    vec2 uv = pos / (size * scale) + 0.5;
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {
+        discard;
+    }
+    uv.x = (uv.x + frame) / frames;
+    target = texture(mainTex, uv);
+}
+"#;
+
+/// Tempo synced layers advance one frame per this many measures
+const LAYER_FRAME_MEASURES: f64 = 0.035;
+
+struct Layer {
+    mesh: ShadedMesh,
+    frames: u32,
+    info: KshLayerInfo,
+}
+
+impl Layer {
+    fn frame_at(&self, chart_time: f64, tick: u32) -> u32 {
+        let frame = match self.info.duration {
+            0 => (tick as f64 / (KSON_RESOLUTION * 4) as f64 / LAYER_FRAME_MEASURES) as u32,
+            duration => (chart_time / duration.unsigned_abs() as f64 * self.frames as f64) as u32,
+        } % self.frames;
+
+        if self.info.duration < 0 {
+            self.frames - 1 - frame
+        } else {
+            frame
+        }
+    }
+}
+
+/// Images from the chart folder drawn behind the track. Layer names that are not images in
+/// the chart folder refer to skin backgrounds, those are drawn by
+/// [`GameBackground`](super::background::GameBackground).
+pub struct ChartBackground {
+    image: Option<ShadedMesh>,
+    layer: Option<Layer>,
+}
+
+impl ChartBackground {
+    pub fn new(
+        context: &three_d::Context,
+        chart: &kson::Chart,
+        images: &mut ChartImages,
+    ) -> anyhow::Result<Self> {
+        let legacy = chart.bg.legacy.as_ref();
+
+        if let Some(movie) = legacy.and_then(|x| x.movie.as_ref()) {
+            warn!(
+                "Movie backgrounds are not supported, not playing {:?}",
+                movie.filename
+            );
+        }
+
+        let image_name = chart.bg.filename.iter().chain(
+            legacy
+                .and_then(|x| x.bg.as_ref())
+                .into_iter()
+                .flatten()
+                .map(|x| &x.filename),
+        );
+        let image = image_name
+            .filter_map(|name| Some((name, images.remove(name)?)))
+            .next()
+            .map(|(name, data)| {
+                let mut mesh = ShadedMesh::new_fullscreen(context, SHADER)?;
+                mesh.use_texture_bytes("mainTex", name, data, (false, false), true)?;
+                mesh.set_param("frames", 1.0f32);
+                mesh.set_param("frame", 0.0f32);
+                anyhow::Ok(mesh)
+            })
+            .transpose()?;
+
+        let layer = legacy
+            .and_then(|x| x.layer.clone())
+            .and_then(|info| {
+                let name = info.filename.clone()?;
+                Some((images.remove(&name)?, name, info))
+            })
+            .map(|(data, name, info)| {
+                let mut mesh = ShadedMesh::new_fullscreen(context, SHADER)?;
+                let texture =
+                    mesh.use_texture_bytes("mainTex", &name, data, (false, false), true)?;
+                let frames = (texture.width / texture.height.max(1)).max(1);
+                mesh.set_param("frames", frames as f32);
+                anyhow::Ok(Layer { mesh, frames, info })
+            })
+            .transpose()?;
+
+        Ok(Self { image, layer })
+    }
+
+    /// The chart has its own background image, replacing the skin background
+    pub fn has_image(&self) -> bool {
+        self.image.is_some()
+    }
+
+    pub fn render(&mut self, viewport: Viewport, chart_time: f64, tick: u32, tilt: f32, spin: f32) {
+        profile_function!();
+        if let Some(image) = &self.image {
+            image.draw_fullscreen(viewport);
+        }
+
+        if let Some(layer) = &mut self.layer {
+            let (tilt_rotates, spin_rotates) = layer
+                .info
+                .rotation
+                .as_ref()
+                .map_or((true, true), |x| (x.tilt, x.spin));
+            let rotation =
+                if tilt_rotates { tilt } else { 0.0 } + if spin_rotates { spin } else { 0.0 };
+
+            let frame = layer.frame_at(chart_time, tick);
+            layer.mesh.set_param("frame", frame as f32);
+            layer.mesh.set_param("rotation", rotation.to_radians());
+            layer.mesh.draw_fullscreen(viewport);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::{path::Path, rc::Rc, sync::Arc};
+use std::{rc::Rc, sync::Arc};
 
 use crate::{config::GameConfig, game::HoldState};
 
@@ -20,26 +20,41 @@ use three_d::{
     Vec3,
 };
 use three_d_asset::Srgba;
+
+/// Used for track textures that neither the skin nor the default skin have
+const MISSING_TEXTURE: &[u8] = include_bytes!("../static_assets/missing.png");
 impl ChartView {
     pub const TRACK_LENGTH: f32 = 16.0;
     pub const UP: Vec3 = vec3(0.0, 0.0, -1.0);
     pub const TRACK_DIRECTION: Vec3 = vec3(0.0, 1.0, 0.0);
     pub const Z_NEAR: f32 = 0.01;
 
-    pub fn new(skin_root: impl AsRef<Path>, td: &three_d::Context) -> anyhow::Result<Self> {
+    pub fn new(td: &three_d::Context) -> anyhow::Result<Self> {
         let _indices: [u16; 6] = [0, 1, 2, 0, 2, 3];
-        let mut texure_path = skin_root.as_ref().to_path_buf();
-        texure_path.push("textures");
-        texure_path.push("file.png");
         td.set_depth_test(three_d::DepthTest::Never);
 
-        let mut textures = three_d_asset::io::load(&[
-            texure_path.with_file_name("laser_l.png"),
-            texure_path.with_file_name("laser_r.png"),
-            texure_path.with_file_name("track.png"),
-            texure_path.with_file_name("fxbutton.png"),
-            texure_path.with_file_name("button.png"),
-        ])?;
+        let mut found = vec![];
+        let mut missing = vec![];
+        for file_name in [
+            "laser_l.png",
+            "laser_r.png",
+            "track.png",
+            "fxbutton.png",
+            "button.png",
+        ] {
+            match GameConfig::get().skin_asset("textures", file_name) {
+                Some(path) => found.push(path),
+                None => {
+                    log::warn!("Skin texture \"{file_name}\" not found");
+                    missing.push(file_name);
+                }
+            }
+        }
+
+        let mut textures = three_d_asset::io::load(&found)?;
+        for file_name in missing {
+            textures.insert(file_name, MISSING_TEXTURE.to_vec());
+        }
 
         let _laser_texture = Some(Arc::new(Texture2D::new(
             td,
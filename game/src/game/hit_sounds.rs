@@ -0,0 +1,109 @@
+use std::{fs::File, path::Path, sync::Arc};
+
+use kson_rodio_sources::owned_source::{self, owned_source};
+use rodio::{dynamic_mixer::DynamicMixerController, source::Buffered, Decoder, Source};
+
+use crate::config::GameConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HitSound {
+    Chip,
+    Slam,
+    Miss,
+}
+
+impl HitSound {
+    /// Base file name in the skin `audio` folder, variations are named `<base>_1.wav`, `<base>_2.wav`, etc.
+    const fn file_name(&self) -> &'static str {
+        match self {
+            HitSound::Chip => "chip_hit",
+            HitSound::Slam => "laser_slam",
+            HitSound::Miss => "miss",
+        }
+    }
+
+    fn volume(&self, config: &GameConfig) -> f32 {
+        match self {
+            HitSound::Chip => config.chip_hit_volume,
+            HitSound::Slam => config.slam_volume,
+            HitSound::Miss => config.miss_volume,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Variations {
+    samples: Vec<Buffered<Decoder<File>>>,
+    next: usize,
+    volume: f32,
+    marker: owned_source::Marker,
+}
+
+impl Variations {
+    fn load(audio_folder: &Path, sound: HitSound, volume: f32) -> Self {
+        let name = sound.file_name();
+        let samples = std::iter::once(format!("{name}.wav"))
+            .chain((1..).map(|i| format!("{name}_{i}.wav")))
+            .map_while(|file_name| File::open(audio_folder.join(file_name)).ok())
+            .filter_map(|f| Decoder::new(f).ok())
+            .map(|x| x.buffered())
+            .collect();
+
+        Self {
+            samples,
+            next: 0,
+            volume,
+            marker: Default::default(),
+        }
+    }
+}
+
+/// Skin provided sound effects for hits, preloaded so they can be mixed in without decoding delay.
+pub struct HitSounds {
+    chip: Variations,
+    slam: Variations,
+    miss: Variations,
+}
+
+impl HitSounds {
+    pub fn new(skin_root: &Path) -> Self {
+        let config = GameConfig::get();
+
+        // Variations are taken from the default skin only when the skin has none of them
+        let load = |sound: HitSound| {
+            let audio_folder = config
+                .skin_asset("audio", format!("{}.wav", sound.file_name()))
+                .and_then(|x| x.parent().map(Path::to_path_buf))
+                .unwrap_or_else(|| skin_root.join("audio"));
+            Variations::load(&audio_folder, sound, sound.volume(&config))
+        };
+
+        Self {
+            chip: load(HitSound::Chip),
+            slam: load(HitSound::Slam),
+            miss: load(HitSound::Miss),
+        }
+    }
+
+    pub fn play(&mut self, sound: HitSound, mixer: &Arc<DynamicMixerController<f32>>) {
+        let variations = match sound {
+            HitSound::Chip => &mut self.chip,
+            HitSound::Slam => &mut self.slam,
+            HitSound::Miss => &mut self.miss,
+        };
+
+        if variations.samples.is_empty() || variations.volume <= 0.0 {
+            return;
+        }
+
+        let sample = variations.samples[variations.next % variations.samples.len()].clone();
+        variations.next = variations.next.wrapping_add(1);
+
+        //Cut off the previous instance of the same sound so rapid hits don't pile up
+        drop(std::mem::take(&mut variations.marker));
+        mixer.add(owned_source(
+            sample.convert_samples().amplify(variations.volume),
+            &variations.marker,
+        ));
+    }
+}
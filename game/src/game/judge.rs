@@ -0,0 +1,386 @@
+//! Judging of score ticks without a window, audio or skin. [`Playthrough`] plays a chart with
+//! scripted inputs so the scoring pipeline can be tested.
+
+use std::{cmp::Ordering, fmt::Display, time::Duration};
+
+use kson::{
+    score_ticks::{generate_score_ticks, PlacedScoreTick, ScoreTick, ScoreTicker},
+    Chart,
+};
+
+use super::{Game, HitRating, HitSummary, HitWindow, LASER_THRESHOLD};
+
+impl HitWindow {
+    /// Rates a button press `delta` ms away from its chip, [`HitRating::None`] if it is too far
+    /// away to count.
+    pub fn rate_chip(&self, tick: PlacedScoreTick, delta: f64, time: f64) -> HitRating {
+        let abs_delta = Duration::from_secs_f64(delta.abs() / 1000.0);
+        if abs_delta <= self.perfect {
+            HitRating::Crit { tick, delta, time }
+        } else if abs_delta <= self.good {
+            HitRating::Good { tick, delta, time }
+        } else if abs_delta <= self.miss {
+            HitRating::Miss { tick, delta, time }
+        } else {
+            HitRating::None
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Score {
+    /// 2 for each critical and 1 for each near
+    pub real: u64,
+    pub combo: u64,
+    pub max_combo: u64,
+}
+
+impl Score {
+    /// Counts a judgement, returns true if the combo changed.
+    pub fn add(&mut self, hit_rating: HitRating) -> bool {
+        self.real += match hit_rating {
+            HitRating::Crit { .. } => 2,
+            HitRating::Good { .. } => 1,
+            _ => 0,
+        };
+
+        match hit_rating {
+            HitRating::Crit { .. } | HitRating::Good { .. } => {
+                self.combo += 1;
+                self.max_combo = self.max_combo.max(self.combo);
+                true
+            }
+            HitRating::Miss { .. } if self.combo > 0 => {
+                self.combo = 0;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Score out of [`Game::MAX_SCORE`] for a chart with `total` score ticks.
+    pub fn display(&self, total: u32) -> u64 {
+        let max = (total as u64 * 2).max(1);
+        Game::MAX_SCORE * self.real / max
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScriptedInput {
+    /// BT A to D are 0 to 3, FX L and R are 4 and 5
+    Press(usize),
+    Release(usize),
+    /// Moves a laser cursor to a position from 0 to 1
+    Knob {
+        side: usize,
+        pos: f64,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PlaythroughResult {
+    pub score: u64,
+    pub summary: HitSummary,
+    pub max_combo: u64,
+}
+
+impl Display for PlaythroughResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({} critical, {} near, {} error, max combo {})",
+            self.score, self.summary.crit, self.summary.good, self.summary.miss, self.max_combo
+        )
+    }
+}
+
+/// Plays a chart with inputs at chart times in ms, judged the same way as in [`Game`] but
+/// without audio sync, offsets or laser assist.
+pub struct Playthrough {
+    chart: Chart,
+    hit_window: HitWindow,
+    score_ticks: Vec<PlacedScoreTick>,
+    total: u32,
+    /// Time each button has been held since
+    held: [Option<f64>; 6],
+    laser_cursors: [f64; 2],
+    /// Time of the latest left and right turn of each knob
+    knob_turns: [[f64; 2]; 2],
+    hit_ratings: Vec<HitRating>,
+    score: Score,
+}
+
+impl Playthrough {
+    pub fn new(chart: Chart, hit_window: HitWindow) -> Self {
+        let score_ticks = generate_score_ticks(&chart);
+        Self {
+            total: score_ticks.summary().total,
+            chart,
+            hit_window,
+            score_ticks,
+            held: [None; 6],
+            laser_cursors: [0.0, 1.0],
+            knob_turns: [[f64::NEG_INFINITY; 2]; 2],
+            hit_ratings: vec![],
+            score: Score::default(),
+        }
+    }
+
+    /// Inputs that hit every note of the chart on time.
+    pub fn perfect_inputs(chart: &Chart) -> Vec<(f64, ScriptedInput)> {
+        let mut inputs = vec![];
+        let lanes = chart.note.bt.iter().chain(chart.note.fx.iter());
+        for (lane, notes) in lanes.enumerate() {
+            for note in notes {
+                inputs.push((chart.tick_to_ms(note.y), ScriptedInput::Press(lane)));
+                inputs.push((
+                    chart.tick_to_ms(note.y + note.l),
+                    ScriptedInput::Release(lane),
+                ));
+            }
+        }
+
+        // Knobs are moved a little early as ticks are judged before the inputs at the same time
+        for tick in generate_score_ticks(chart) {
+            let ms = chart.tick_to_ms(tick.y);
+            match tick.tick {
+                ScoreTick::Laser { lane, pos } => {
+                    inputs.push((ms - 0.5, ScriptedInput::Knob { side: lane, pos }))
+                }
+                ScoreTick::Slam { lane, start, end } => {
+                    inputs.push((
+                        ms - 0.5,
+                        ScriptedInput::Knob {
+                            side: lane,
+                            pos: start,
+                        },
+                    ));
+                    inputs.push((
+                        ms,
+                        ScriptedInput::Knob {
+                            side: lane,
+                            pos: end,
+                        },
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        inputs.sort_by(|a, b| a.0.total_cmp(&b.0));
+        inputs
+    }
+
+    /// Plays the inputs, which have to be sorted by time, then the rest of the chart without
+    /// any input.
+    pub fn run(
+        mut self,
+        inputs: impl IntoIterator<Item = (f64, ScriptedInput)>,
+    ) -> PlaythroughResult {
+        for (time, input) in inputs {
+            self.advance(time);
+            self.input(time, input);
+        }
+
+        let end = self.chart.tick_to_ms(self.chart.get_last_tick())
+            + self.hit_window.miss.as_secs_f64() * 1000.0;
+        self.advance(end);
+
+        PlaythroughResult {
+            score: self.score.display(self.total),
+            summary: HitSummary::from(self.hit_ratings.as_slice()),
+            max_combo: self.score.max_combo,
+        }
+    }
+
+    fn on_hit(&mut self, hit_rating: HitRating) {
+        self.hit_ratings.push(hit_rating);
+        self.score.add(hit_rating);
+    }
+
+    /// Judges the ticks passed by `time`, same as the tick loop of [`Game`].
+    fn advance(&mut self, time: f64) {
+        let current_tick = self.chart.ms_to_tick(time);
+        let missed_tick = self
+            .chart
+            .ms_to_tick(time - self.hit_window.good.as_secs_f64() * 1000.0);
+
+        let mut i = 0;
+        while i < self.score_ticks.len() {
+            if self.score_ticks[i].y > current_tick {
+                break;
+            }
+
+            match self.process_tick(self.score_ticks[i], missed_tick, time) {
+                HitRating::None => i += 1,
+                r => {
+                    self.on_hit(r);
+                    self.score_ticks.remove(i);
+                }
+            }
+        }
+    }
+
+    fn process_tick(&mut self, tick: PlacedScoreTick, missed_tick: u32, time: f64) -> HitRating {
+        let crit = HitRating::Crit {
+            tick,
+            delta: 0.0,
+            time,
+        };
+        let miss = HitRating::Miss {
+            tick,
+            delta: 0.0,
+            time,
+        };
+
+        match tick.tick {
+            ScoreTick::Hold { lane, start_tick } => {
+                let hold_start =
+                    self.chart.tick_to_ms(start_tick) - self.hit_window.hold.as_secs_f64() * 1000.0;
+                if self.held[lane].is_some_and(|t| t > hold_start) {
+                    crit
+                } else {
+                    miss
+                }
+            }
+            ScoreTick::Laser { lane, pos } => {
+                if (self.laser_cursors[lane] - pos).abs() < LASER_THRESHOLD {
+                    crit
+                } else {
+                    miss
+                }
+            }
+            ScoreTick::Slam { lane, start, end } => {
+                let dir = match end.total_cmp(&start) {
+                    Ordering::Less => 0,
+                    _ => 1,
+                };
+                let delta = self.chart.tick_to_ms(tick.y) - self.knob_turns[lane][dir];
+                if tick.y < missed_tick {
+                    HitRating::Miss { tick, delta, time }
+                } else if delta.abs() < self.hit_window.slam.as_secs_f64() * 1000.0 {
+                    self.laser_cursors[lane] = end;
+                    HitRating::Crit { tick, delta, time }
+                } else {
+                    HitRating::None
+                }
+            }
+            ScoreTick::Chip { .. } if tick.y < missed_tick => miss,
+            ScoreTick::Chip { .. } => HitRating::None,
+        }
+    }
+
+    fn input(&mut self, time: f64, input: ScriptedInput) {
+        match input {
+            ScriptedInput::Press(lane) => {
+                self.held[lane] = Some(time);
+
+                let last_tick = self
+                    .chart
+                    .ms_to_tick(time + self.hit_window.miss.as_secs_f64() * 1000.0)
+                    + 1;
+                let next = self
+                    .score_ticks
+                    .iter()
+                    .take_while(|x| x.y < last_tick)
+                    .position(|x| match x.tick {
+                        ScoreTick::Chip { lane: l } | ScoreTick::Hold { lane: l, .. } => l == lane,
+                        _ => false,
+                    });
+
+                let Some(index) = next else {
+                    return;
+                };
+                let tick = self.score_ticks[index];
+                if let ScoreTick::Hold { .. } = tick.tick {
+                    return;
+                }
+
+                let delta = self.chart.tick_to_ms(tick.y) - time;
+                let hit_rating = self.hit_window.rate_chip(tick, delta, time);
+                if !matches!(hit_rating, HitRating::None) {
+                    self.on_hit(hit_rating);
+                    self.score_ticks.remove(index);
+                }
+            }
+            ScriptedInput::Release(lane) => self.held[lane] = None,
+            ScriptedInput::Knob { side, pos } => {
+                match pos.total_cmp(&self.laser_cursors[side]) {
+                    Ordering::Less => self.knob_turns[side][0] = time,
+                    Ordering::Greater => self.knob_turns[side][1] = time,
+                    Ordering::Equal => {}
+                }
+                self.laser_cursors[side] = pos.clamp(0.0, 1.0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kson::{GraphSectionPoint, Interval, LaserSection};
+
+    use super::*;
+
+    fn test_chart() -> Chart {
+        let mut chart = Chart::new();
+        chart.beat.bpm = vec![(0, 120.0)];
+        chart.note.bt[0] = vec![Interval { y: 0, l: 0 }, Interval { y: 480, l: 0 }];
+        chart.note.bt[2] = vec![Interval { y: 240, l: 480 }];
+        chart.note.fx[1] = vec![Interval { y: 960, l: 0 }];
+
+        let mut slam = GraphSectionPoint::new(480, 0.5);
+        slam.vf = Some(1.0);
+        chart.note.laser[0] = vec![LaserSection(
+            0,
+            vec![
+                GraphSectionPoint::new(0, 0.5),
+                slam,
+                GraphSectionPoint::new(960, 1.0),
+            ],
+            1,
+        )];
+        chart
+    }
+
+    #[test]
+    fn perfect_play() {
+        let chart = test_chart();
+        let total = generate_score_ticks(&chart).len() as u32;
+        let inputs = Playthrough::perfect_inputs(&chart);
+        let result = Playthrough::new(chart, HitWindow::NORMAL).run(inputs);
+
+        assert_eq!(result.score, Game::MAX_SCORE);
+        assert_eq!(result.summary.crit, total);
+        assert_eq!(result.summary.miss, 0);
+        assert_eq!(result.max_combo, total as u64);
+    }
+
+    #[test]
+    fn no_input() {
+        let chart = test_chart();
+        let total = generate_score_ticks(&chart).len() as u32;
+        let result = Playthrough::new(chart, HitWindow::NORMAL).run([]);
+
+        assert_eq!(result.score, 0);
+        assert_eq!(result.summary.miss, total);
+        assert_eq!(result.max_combo, 0);
+    }
+
+    #[test]
+    fn chip_timing() {
+        let mut chart = Chart::new();
+        chart.beat.bpm = vec![(0, 120.0)];
+        chart.note.bt[0] = [0, 480, 960, 1440].map(|y| Interval { y, l: 0 }).to_vec();
+
+        // 0 is on time, 480 is 100ms late, 960 is 200ms early and 1440 is not hit
+        let inputs = [0.0, 1100.0, 1800.0].map(|t| (t, ScriptedInput::Press(0)));
+        let result = Playthrough::new(chart, HitWindow::NORMAL).run(inputs);
+
+        assert_eq!(result.summary.crit, 1);
+        assert_eq!(result.summary.good, 1);
+        assert_eq!(result.summary.miss, 2);
+        assert_eq!(result.score, Game::MAX_SCORE * 3 / 8);
+        assert_eq!(result.max_combo, 2);
+    }
+}
@@ -40,9 +40,12 @@ pub(crate) struct LuaGameState {
     pub(crate) score_replays: Vec<ScoreReplay>, //Array of previous scores for the current song
     pub(crate) crit_line: CritLine,     // info about crit line and everything attached to it
     pub(crate) hit_window: HitWindow, // This may be absent (== nil) for the default timing window (46 / 92 / 138 / 250ms)
+    pub(crate) hit_feedback: HitFeedback,
     pub(crate) multiplayer: bool,
     pub(crate) user_id: String,
     pub(crate) practice_setup: bool, // true: it's the setup, false: practicing n
+    pub(crate) clean_hud: bool, // true: only the track should be drawn, hide score, gauge and hit ratings
+    pub(crate) clean_hud_progress: bool, // true: keep a minimal progress indicator while cleanHud is set
 }
 
 #[derive(Debug, Serialize, Default, Deserialize, Clone, PartialEq, ToLuaLsType)]
@@ -120,6 +123,107 @@ impl HitWindow {
     }
 }
 
+#[derive(Debug, Serialize, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum FadeCurve {
+    /// Disappears at once when the duration is over
+    None,
+    #[default]
+    Linear,
+    /// Starts fading slowly and speeds up towards the end
+    EaseIn,
+}
+
+impl std::fmt::Display for FadeCurve {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            FadeCurve::None => "None",
+            FadeCurve::Linear => "Linear",
+            FadeCurve::EaseIn => "Ease in",
+        })
+    }
+}
+
+/// How long skins should show hit ratings for, a duration of zero hides that rating.
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HitFeedback {
+    #[serde_as(as = "DurationMilliSecondsWithFrac<f64>")]
+    pub crit: Duration,
+    #[serde_as(as = "DurationMilliSecondsWithFrac<f64>")]
+    pub near: Duration,
+    #[serde_as(as = "DurationMilliSecondsWithFrac<f64>")]
+    pub miss: Duration,
+    /// Time at the end of the duration spent fading out
+    #[serde_as(as = "DurationMilliSecondsWithFrac<f64>")]
+    pub fade: Duration,
+    pub fade_curve: FadeCurve,
+}
+
+impl ToLuaLsType for HitFeedback {
+    fn lua_ls_type() -> luals_gen::LuaLsType {
+        LuaLsType::Named(
+            "HitFeedback".into(),
+            luals_gen::LuaLsTypeDef::TableLiteral(vec![
+                ("crit".into(), LuaLsType::Primitive("number".into())),
+                ("near".into(), LuaLsType::Primitive("number".into())),
+                ("miss".into(), LuaLsType::Primitive("number".into())),
+                ("fade".into(), LuaLsType::Primitive("number".into())),
+                ("fadeCurve".into(), LuaLsType::Primitive("string".into())),
+            ]),
+        )
+    }
+}
+
+impl Default for HitFeedback {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
+
+impl HitFeedback {
+    pub const STANDARD: Self = Self {
+        crit: Duration::from_millis(300),
+        near: Duration::from_millis(300),
+        miss: Duration::from_millis(400),
+        fade: Duration::from_millis(150),
+        fade_curve: FadeCurve::Linear,
+    };
+
+    pub const SHORT: Self = Self {
+        crit: Duration::from_millis(150),
+        near: Duration::from_millis(150),
+        miss: Duration::from_millis(250),
+        fade: Duration::from_millis(75),
+        fade_curve: FadeCurve::Linear,
+    };
+
+    /// Only shows the ratings that need attention
+    pub const MINIMAL: Self = Self {
+        crit: Duration::ZERO,
+        near: Duration::from_millis(200),
+        miss: Duration::from_millis(300),
+        fade: Duration::from_millis(100),
+        fade_curve: FadeCurve::EaseIn,
+    };
+
+    pub const LONG: Self = Self {
+        crit: Duration::from_millis(500),
+        near: Duration::from_millis(500),
+        miss: Duration::from_millis(700),
+        fade: Duration::from_millis(250),
+        fade_curve: FadeCurve::Linear,
+    };
+
+    pub const PRESETS: [(&'static str, Self); 4] = [
+        ("Standard", Self::STANDARD),
+        ("Short", Self::SHORT),
+        ("Minimal", Self::MINIMAL),
+        ("Long", Self::LONG),
+    ];
+}
+
 #[derive(Debug, Serialize, Default, Deserialize, Clone, PartialEq, ToLuaLsType)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct CritLine {
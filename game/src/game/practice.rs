@@ -0,0 +1,62 @@
+//! A-B looping of a chart section with a slower or faster playback rate for practicing.
+
+use std::time::Duration;
+
+use kson::{score_ticks::MAX_SCORE, Chart};
+
+use super::HitSummary;
+
+pub const MIN_RATE: f64 = 0.25;
+pub const MAX_RATE: f64 = 1.5;
+/// Rate change for each FX press while paused
+pub const RATE_STEP: f64 = 0.05;
+/// Pause between the end of an attempt and the loop starting over
+pub const RESTART_DELAY: Duration = Duration::from_millis(1500);
+
+/// Section being practiced and the judgements of each attempt at it.
+#[derive(Debug, Default)]
+pub struct PracticeLoop {
+    /// Start of the loop, on a measure line
+    pub start: u32,
+    /// End of the loop, on a measure line. Playback continues past the start until it is set.
+    pub end: Option<u32>,
+    /// Index in the hit ratings where the current attempt begins
+    pub first_rating: usize,
+    pub attempts: Vec<HitSummary>,
+}
+
+impl PracticeLoop {
+    pub fn new(start: u32) -> Self {
+        Self {
+            start,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the next marker for a start press at `tick`. Without an end the loop ends at the
+    /// end of the current measure, otherwise a new loop starts at the start of the current
+    /// measure and the attempts so far are cleared.
+    pub fn set_marker(&mut self, chart: &Chart, tick: u32, rating_count: usize) {
+        let measure = chart.tick_to_measure(tick);
+        if self.end.is_none() {
+            self.end = Some(chart.measure_to_tick(measure + 1).max(self.start + 1));
+        } else {
+            *self = Self {
+                start: chart.measure_to_tick(measure),
+                first_rating: rating_count,
+                ..Default::default()
+            };
+        }
+    }
+
+    /// Attempts without any misses
+    pub fn clears(&self) -> usize {
+        self.attempts.iter().filter(|x| x.full_combo()).count()
+    }
+}
+
+/// Score out of [`MAX_SCORE`] for the judgements of one attempt.
+pub fn attempt_score(summary: &HitSummary) -> u64 {
+    let judged = (summary.crit + summary.good + summary.miss) as u64;
+    MAX_SCORE * (summary.crit as u64 * 2 + summary.good as u64) / (judged * 2).max(1)
+}
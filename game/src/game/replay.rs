@@ -0,0 +1,190 @@
+//! Recording of the inputs and judgements of a play, stored compressed with its score so the
+//! play can be watched again from song select.
+
+use std::{
+    io::{Read, Write},
+    time::SystemTime,
+};
+
+use anyhow::{ensure, Result};
+use base64::Engine;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use kson::{score_ticks::PlacedScoreTick, Chart};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+use super::{gauge::GaugeType, HitRating};
+use crate::config::{GameConfig, NoteMode};
+
+const REPLAY_VERSION: u32 = 1;
+
+/// Input at a song time in milliseconds
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum ReplayInput {
+    Press {
+        ms: f64,
+        button: u8,
+    },
+    Release {
+        ms: f64,
+        button: u8,
+    },
+    /// Laser cursor position after it moved
+    Laser {
+        ms: f64,
+        side: u8,
+        pos: f64,
+    },
+}
+
+impl ReplayInput {
+    pub fn ms(&self) -> f64 {
+        match self {
+            ReplayInput::Press { ms, .. }
+            | ReplayInput::Release { ms, .. }
+            | ReplayInput::Laser { ms, .. } => *ms,
+        }
+    }
+}
+
+/// A judged score tick
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ReplayHit {
+    /// Song time the judgement was made at in milliseconds
+    pub ms: f64,
+    /// 0 = miss, 1 = good, 2 = crit
+    pub rating: u8,
+    pub y: u32,
+    /// See [`kson::score_ticks::ScoreTick::global_lane`]
+    pub lane: u8,
+    pub delta: f64,
+    pub time: f64,
+}
+
+impl ReplayHit {
+    /// The rating for the recorded judgement of `tick`.
+    pub fn rating(&self, tick: PlacedScoreTick) -> HitRating {
+        let Self { delta, time, .. } = *self;
+        match self.rating {
+            2 => HitRating::Crit { tick, delta, time },
+            1 => HitRating::Good { tick, delta, time },
+            _ => HitRating::Miss { tick, delta, time },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub mirror: bool,
+    /// Chart lane each BT lane was taken from when the notes were randomized
+    pub bt_order: [u8; 4],
+    pub gauge: GaugeType,
+    pub fallback_gauge: bool,
+    pub inputs: Vec<ReplayInput>,
+    pub hits: Vec<ReplayHit>,
+}
+
+impl Default for Replay {
+    fn default() -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            mirror: false,
+            bt_order: [0, 1, 2, 3],
+            gauge: GaugeType::default(),
+            fallback_gauge: false,
+            inputs: vec![],
+            hits: vec![],
+        }
+    }
+}
+
+impl Replay {
+    /// An empty replay with the lane changes and gauge of a new play.
+    pub fn new(config: &GameConfig) -> Self {
+        let mut replay = Self {
+            gauge: config.start_gauge,
+            fallback_gauge: config.fallback_gauge,
+            ..Default::default()
+        };
+        match config.note_mode {
+            NoteMode::Normal => {}
+            NoteMode::Mirror => replay.mirror = true,
+            NoteMode::Random => replay.bt_order.shuffle(&mut rand::thread_rng()),
+        }
+        replay
+    }
+
+    /// Applies the lane changes the replay was recorded with to `chart`.
+    pub fn apply_note_mode(&self, chart: &mut Chart) {
+        if self.mirror {
+            chart.mirror(0, u32::MAX);
+        }
+        let mut bt = std::mem::take(&mut chart.note.bt);
+        chart.note.bt = self.bt_order.map(|i| std::mem::take(&mut bt[i as usize]));
+    }
+
+    pub fn add_hit(&mut self, ms: f64, hit_rating: HitRating) {
+        let (rating, tick, delta, time) = match hit_rating {
+            HitRating::None => return,
+            HitRating::Crit { tick, delta, time } => (2, tick, delta, time),
+            HitRating::Good { tick, delta, time } => (1, tick, delta, time),
+            HitRating::Miss { tick, delta, time } => (0, tick, delta, time),
+        };
+
+        self.hits.push(ReplayHit {
+            ms,
+            rating,
+            y: tick.y,
+            lane: tick.tick.global_lane() as u8,
+            delta,
+            time,
+        });
+    }
+
+    /// Gzipped json in base64, as stored in the scores database.
+    pub fn encode(&self) -> Result<String> {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        serde_json::to_writer(&mut encoder, self)?;
+        encoder.flush()?;
+        Ok(base64::engine::general_purpose::STANDARD.encode(encoder.finish()?))
+    }
+
+    pub fn decode(data: &str) -> Result<Self> {
+        let compressed = base64::engine::general_purpose::STANDARD.decode(data)?;
+        let mut json = vec![];
+        GzDecoder::new(compressed.as_slice()).read_to_end(&mut json)?;
+        let replay: Self = serde_json::from_slice(&json)?;
+        ensure!(
+            replay.version == REPLAY_VERSION,
+            "Unsupported replay version {}",
+            replay.version
+        );
+        Ok(replay)
+    }
+}
+
+/// Position in a [`Replay`] being watched.
+#[derive(Debug, Default)]
+pub struct ReplayPlayer {
+    next_input: usize,
+    next_hit: usize,
+    /// Press times of the held buttons
+    pub held: [Option<SystemTime>; 6],
+}
+
+impl ReplayPlayer {
+    /// Inputs of `replay` made up to `ms` since the last call.
+    pub fn inputs_until(&mut self, replay: &Replay, ms: f64) -> Vec<ReplayInput> {
+        let start = self.next_input;
+        self.next_input += replay.inputs[start..].partition_point(|x| x.ms() <= ms);
+        replay.inputs[start..self.next_input].to_vec()
+    }
+
+    /// Judgements of `replay` made up to `ms` since the last call.
+    pub fn hits_until(&mut self, replay: &Replay, ms: f64) -> Vec<ReplayHit> {
+        let start = self.next_hit;
+        self.next_hit += replay.hits[start..].partition_point(|x| x.ms <= ms);
+        replay.hits[start..self.next_hit].to_vec()
+    }
+}
@@ -7,6 +7,7 @@ use std::{
 
 use di::{Activator, InjectBuilder, Injectable};
 use egui::epaint::Hsva;
+use kson_rodio_sources::ducking::{ducked, Ducking};
 use log::warn;
 use puffin::{ProfilerScope, ThreadProfiler};
 use rodio::Source;
@@ -148,17 +149,16 @@ impl TealData for GameData {
                 }
                 let config = GameConfig::get();
 
-                let mut folder = config.game_folder.clone();
-                folder.push("skins");
-                folder.push(&config.skin);
-                folder.push("audio");
-                folder.push(&name);
-                if folder.extension().is_none() {
-                    folder.set_extension("wav");
+                let mut file_name = PathBuf::from(&name);
+                if file_name.extension().is_none() {
+                    file_name.set_extension("wav");
                 }
+                let Some(path) = config.skin_asset("audio", &file_name) else {
+                    warn!("Skin sample \"{}\" not found", &name);
+                    return Ok(());
+                };
 
-                let file =
-                    std::fs::File::open(&folder).map_err(tealr::mlu::mlua::Error::external)?;
+                let file = std::fs::File::open(&path).map_err(tealr::mlu::mlua::Error::external)?;
 
                 let decoder = rodio::Decoder::new(file)
                     .map_err(tealr::mlu::mlua::Error::external)?
@@ -190,6 +190,9 @@ impl TealData for GameData {
                 let mixer: AppDataRef<RuscMixer> = lua
                     .app_data_ref()
                     .ok_or(mlua::Error::external("Mixer app data not set"))?;
+                let ducking: AppDataRef<Arc<Ducking>> = lua
+                    .app_data_ref()
+                    .ok_or(mlua::Error::external("Ducking app data not set"))?;
 
                 let play_control = Arc::new(AtomicUsize::new(1));
                 let prev = game_data
@@ -201,11 +204,11 @@ impl TealData for GameData {
                 }
 
                 let to_play = sample.clone();
+                // Dipped while a song preview plays
+                let ducking = (*ducking).clone();
                 if do_loop {
                     mixer.add(
-                        to_play
-                            .convert_samples()
-                            .repeat_infinite()
+                        ducked(to_play.convert_samples().repeat_infinite(), ducking)
                             .stoppable()
                             .periodic_access(Duration::from_millis(10), move |x| {
                                 if play_control.load(std::sync::atomic::Ordering::SeqCst) == 0 {
@@ -216,14 +219,13 @@ impl TealData for GameData {
                 } else {
                     let done_control = play_control.clone();
                     mixer.add(rodio::source::Done::new(
-                        to_play.convert_samples().stoppable().periodic_access(
-                            Duration::from_millis(10),
-                            move |x| {
+                        ducked(to_play.convert_samples(), ducking)
+                            .stoppable()
+                            .periodic_access(Duration::from_millis(10), move |x| {
                                 if play_control.load(std::sync::atomic::Ordering::SeqCst) == 0 {
                                     x.stop()
                                 }
-                            },
-                        ),
+                            }),
                         done_control,
                     ))
                 }
@@ -1,12 +1,14 @@
 use std::{
+    collections::VecDeque,
     num::NonZeroU32,
     ops::{Add, Sub},
+    path::PathBuf,
     rc::Rc,
     sync::{
         mpsc::{channel, Receiver, Sender},
         Arc, RwLock,
     },
-    time::{Duration, SystemTime},
+    time::{Duration, Instant, SystemTime},
 };
 
 use di::{RefMut, ServiceProvider};
@@ -36,14 +38,18 @@ use three_d as td;
 use crate::{
     button_codes::{LaserState, UscInputEvent},
     companion_interface::{self},
-    config::{Fullscreen, GameConfig},
-    game::{gauge::Gauge, HitRating},
+    config::{AutoRetire, FramePacing, Fullscreen, GameConfig},
+    egui_nav::{self, EguiNavigation},
+    game::{gauge::Gauge, replay::Replay, HitRating},
     game_data::GameData,
     help,
     input_state::InputState,
+    ir::LuaIr,
     lua_http::LuaHttp,
     lua_service::LuaProvider,
     main_menu::MainMenuButton,
+    media_session::MediaSession,
+    perf_hud::PerfHud,
     scene,
     settings_screen::SettingsScreen,
     song_provider, songselect,
@@ -77,6 +83,8 @@ pub enum ControlMessage {
         diff: usize,
         loader: song_provider::LoadSongFn,
         autoplay: AutoPlay,
+        /// Watch this replay instead of playing
+        replay: Option<Replay>,
     },
     TransitionComplete(Box<dyn scene::Scene>),
     Result {
@@ -90,6 +98,10 @@ pub enum ControlMessage {
         max_combo: i32,
         duration: i32,
         manual_exit: bool,
+        /// How play continued after the gauge ran out, `None` if it never did
+        retired: Option<AutoRetire>,
+        /// Recording of the play, `None` when it was not recorded
+        replay: Option<Replay>,
     },
 
     ApplySettings,
@@ -127,16 +139,35 @@ pub struct GameMain {
     modifiers: Modifiers,
     service_provider: ServiceProvider,
     show_fps: bool,
-    frame_end: std::time::SystemTime,
+    frame_end: Instant,
     frame_duration: Duration,
+    playlist: VecDeque<PathBuf>,
+    media_session: MediaSession,
+    perf_hud: PerfHud,
+    egui_nav: EguiNavigation,
+    /// Last time the skin scripts were checked for changes
+    skin_check: Instant,
 }
 
-fn get_frame_duration(settings: &GameConfig) -> Duration {
-    let target_fps = settings.graphics.target_fps as u64;
-    if target_fps == 0 {
-        Duration::from_nanos(1)
-    } else {
-        Duration::from_nanos(1_000_000_000 / target_fps.max(30))
+fn get_frame_duration(settings: &GameConfig, window: &Window) -> Duration {
+    let refresh_rate = window
+        .current_monitor()
+        .and_then(|m| m.refresh_rate_millihertz());
+
+    match (settings.graphics.frame_pacing, refresh_rate) {
+        // Swapping buffers already waits for the display
+        (FramePacing::Auto, _) if settings.graphics.vsync => Duration::ZERO,
+        (FramePacing::Auto, Some(millihertz)) if millihertz > 0 => {
+            Duration::from_nanos(1_000_000_000_000 / millihertz as u64)
+        }
+        _ => {
+            let target_fps = settings.graphics.target_fps as u64;
+            if target_fps == 0 {
+                Duration::ZERO
+            } else {
+                Duration::from_nanos(1_000_000_000 / target_fps.max(30))
+            }
+        }
     }
 }
 
@@ -147,8 +178,12 @@ impl GameMain {
         gui: EguiGlow,
         show_debug_ui: bool,
         service_provider: ServiceProvider,
+        playlist: VecDeque<PathBuf>,
+        media_session: MediaSession,
+        window: &Window,
     ) -> Self {
         let (control_tx, control_rx) = channel();
+        gui.egui_ctx.set_visuals(egui_nav::visuals());
 
         Self {
             lua_arena: service_provider.get_required(),
@@ -176,13 +211,21 @@ impl GameMain {
             service_provider,
             show_fps: GameConfig::get().graphics.show_fps,
             companion_update: 0,
-            frame_end: SystemTime::UNIX_EPOCH,
-            frame_duration: get_frame_duration(&GameConfig::get()),
+            frame_end: Instant::now(),
+            frame_duration: get_frame_duration(&GameConfig::get(), window),
+            playlist,
+            media_session,
+            perf_hud: PerfHud::default(),
+            egui_nav: EguiNavigation::default(),
+            skin_check: Instant::now(),
         }
     }
 
     const KEYBOARD_LASER_SENS: f32 = 2.0 / 240.0;
+    /// How often the skin scripts are checked for changes to reload them
+    const SKIN_CHECK_INTERVAL: Duration = Duration::from_secs(1);
     pub fn update(&mut self) {
+        let update_start = Instant::now();
         self.scenes
             .tick(1000.0 / 240.0, self.knob_state, self.control_tx.clone());
 
@@ -207,6 +250,13 @@ impl GameMain {
                 server.send_state(state);
             }
 
+            let now_playing = if GameConfig::get().media_session {
+                self.scenes.active.last().and_then(|x| x.now_playing())
+            } else {
+                None
+            };
+            self.media_session.update(now_playing);
+
             self.companion_update = 30; // every 125ms
         }
 
@@ -239,6 +289,8 @@ impl GameMain {
                 )))
             });
         }
+
+        self.perf_hud.record_update(update_start.elapsed());
     }
     pub fn render(
         &mut self,
@@ -247,6 +299,7 @@ impl GameMain {
         surface: &glutin::surface::Surface<glutin::surface::WindowSurface>,
         gl_context: &PossiblyCurrentContext,
     ) -> FrameOutput {
+        let render_start = Instant::now();
         let GameMain {
             lua_arena,
             scenes,
@@ -275,6 +328,11 @@ impl GameMain {
             companion_update: _,
             frame_end,
             frame_duration,
+            playlist,
+            media_session: _,
+            perf_hud,
+            egui_nav: _,
+            skin_check,
         } = self;
 
         knob_state.zero_deltas();
@@ -319,6 +377,11 @@ impl GameMain {
             *frame_count += 1;
         }
 
+        if skin_check.elapsed() >= Self::SKIN_CHECK_INTERVAL {
+            *skin_check = Instant::now();
+            lua_provider.reload_changed_scripts();
+        }
+
         while let Ok(control_msg) = control_rx.try_recv() {
             match control_msg {
                 ControlMessage::None => {}
@@ -355,6 +418,7 @@ impl GameMain {
                     loader,
                     song,
                     autoplay,
+                    replay,
                 } => {
                     if let Ok(_arena) = lua_arena.read() {
                         let transition_lua = transition_song_lua.clone();
@@ -365,6 +429,7 @@ impl GameMain {
                                 loader,
                                 song,
                                 autoplay,
+                                replay,
                             },
                             control_tx.clone(),
                             vgfx.clone(),
@@ -386,6 +451,8 @@ impl GameMain {
                     max_combo,
                     duration,
                     manual_exit,
+                    retired,
+                    replay,
                 } => {
                     if let Ok(_arena) = lua_arena.read() {
                         let transition_lua = transition_lua.clone();
@@ -402,6 +469,8 @@ impl GameMain {
                                 max_combo,
                                 duration,
                                 manual_exit,
+                                retired,
+                                replay,
                             },
                             control_tx.clone(),
                             vgfx.clone(),
@@ -425,7 +494,7 @@ impl GameMain {
 
                     *show_fps = settings.graphics.show_fps;
 
-                    *frame_duration = get_frame_duration(&settings);
+                    *frame_duration = get_frame_duration(&settings, window);
 
                     window.set_fullscreen(match settings.graphics.fullscreen {
                         Fullscreen::Windowed { .. } => None,
@@ -475,24 +544,59 @@ impl GameMain {
             if *show_debug_ui {
                 Self::debug_ui(ctx, scenes, &vgfx);
             }
+
+            perf_hud.show(ctx);
         });
         gui.paint(window);
 
+        let gc_start = Instant::now();
         Self::run_lua_gc(lua_arena, &mut vgfx.write().expect("Lock error"));
+        let gc_duration = gc_start.elapsed();
+
+        if perf_hud.visible {
+            let lua_memory = lua_arena
+                .read()
+                .expect("Lock error")
+                .0
+                .iter()
+                .map(|lua| lua.used_memory())
+                .sum();
+            perf_hud.set_lua_memory(lua_memory);
+        }
 
         if let Ok(mut a) = game_data.write() {
             a.profile_stack.clear()
         }
 
-        let exit = scenes.is_empty();
+        if scenes.is_empty() {
+            if let Some(next_chart) = playlist.pop_front() {
+                let skin_folder = vgfx.read().expect("Lock error").skin_folder();
+                match crate::load_chart_scene(
+                    &next_chart,
+                    skin_folder,
+                    service_provider.create_scope(),
+                ) {
+                    Ok(scene) => scenes.loaded.push(scene),
+                    Err(e) => log::error!("Failed to load {}: {}", next_chart.display(), e),
+                }
+            }
+        }
+
+        let exit = scenes.is_empty() && playlist.is_empty();
         if exit {
             GameConfig::get().save()
         }
 
+        perf_hud.record_frame(
+            frame_input.elapsed_time,
+            render_start.elapsed(),
+            gc_duration,
+        );
+
         {
             profile_scope!("Wait on FPS limiter");
             crate::help::wait_until(*frame_end);
-            *frame_end = SystemTime::now() + *frame_duration;
+            *frame_end = crate::help::next_frame_end(*frame_end, *frame_duration);
         }
         FrameOutput {
             exit,
@@ -534,6 +638,7 @@ impl GameMain {
         match event {
             Event::UserEvent(e) => {
                 self.input_state.update(e);
+                self.navigate_egui(e);
                 match e {
                     UscInputEvent::Laser(ls, _time) => self.knob_state = *ls,
                     UscInputEvent::Button(b, s, time) => match s {
@@ -561,6 +666,8 @@ impl GameMain {
                 window_id: _,
                 event: WindowEvent::Moved(physical_pos),
             } => {
+                // The window may have moved to a monitor with a different refresh rate
+                self.frame_duration = get_frame_duration(&GameConfig::get(), window);
                 let windowed = &mut GameConfig::get_mut().graphics.fullscreen;
                 if let Fullscreen::Windowed { pos, .. } = windowed {
                     *pos = *physical_pos;
@@ -600,6 +707,16 @@ impl GameMain {
             {
                 self.show_debug_ui = !self.show_debug_ui
             }
+            Event::WindowEvent {
+                event: WindowEvent::KeyboardInput { event: key, .. },
+                ..
+            } if key.state == ElementState::Pressed
+                && !key.repeat
+                && key.physical_key == GameConfig::get().perf_hud_key
+                && !text_input_active =>
+            {
+                self.perf_hud.visible = !self.perf_hud.visible
+            }
             Event::WindowEvent {
                 event:
                     WindowEvent::KeyboardInput {
@@ -694,12 +811,33 @@ impl GameMain {
             .for_each(|x| x.on_event(transformed_event.as_ref().unwrap_or(event)));
     }
 
+    /// Feeds controller input to egui when the top scene is an egui screen.
+    fn navigate_egui(&mut self, event: &UscInputEvent) {
+        if !self.scenes.active.last().is_some_and(|x| x.has_egui()) {
+            self.egui_nav.reset();
+            return;
+        }
+
+        let events = match event {
+            UscInputEvent::Laser(ls, _) => self.egui_nav.knobs(*ls),
+            UscInputEvent::Button(button, event::ElementState::Pressed, _) => self
+                .egui_nav
+                .button_pressed(*button)
+                .map(Vec::from)
+                .unwrap_or_default(),
+            _ => return,
+        };
+
+        self.gui.egui_winit.egui_input_mut().events.extend(events);
+    }
+
     fn run_lua_gc(lua_arena: &mut RefMut<LuaArena>, vgfx: &mut Vgfx) {
         profile_scope!("Garbage collect");
         lua_arena.write().expect("Lock error").0.retain(|lua| {
             //lua.gc_collect();
             if Rc::strong_count(lua) > 1 {
                 LuaHttp::poll(lua);
+                LuaIr::poll(lua);
                 true
             } else {
                 vgfx.drop_assets(lua_address(lua));
@@ -1,7 +1,7 @@
 use std::{
     path::PathBuf,
     sync::{Arc, Mutex, RwLock},
-    time::{Duration, SystemTime},
+    time::{Duration, Instant, SystemTime},
 };
 
 use anyhow::anyhow;
@@ -263,17 +263,30 @@ pub fn take_screenshot(
         .unwrap_or(path))
 }
 
-pub fn wait_until(frame_end: SystemTime) {
-    let mut now = SystemTime::now();
+pub fn wait_until(frame_end: Instant) {
+    let mut now = Instant::now();
     if now > frame_end {
         return;
     }
     let ms = Duration::from_millis(1);
     while now < frame_end {
-        let wait = frame_end.duration_since(now).unwrap_or(Duration::ZERO);
+        let wait = frame_end.duration_since(now);
         if wait > ms {
             std::thread::sleep(wait - ms);
         }
-        now = SystemTime::now();
+        now = Instant::now();
     }
 }
+
+/// The deadline after `frame_end`, staying on the same grid of frame deadlines so frames
+/// line up with the display refresh. Deadlines that have already passed are skipped.
+pub fn next_frame_end(frame_end: Instant, frame_duration: Duration) -> Instant {
+    let now = Instant::now();
+    let next = frame_end + frame_duration;
+    if next >= now || frame_duration.is_zero() {
+        return next.max(now);
+    }
+
+    let missed = (now - next).as_nanos() / frame_duration.as_nanos() + 1;
+    next + frame_duration * missed as u32
+}
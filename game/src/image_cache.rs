@@ -0,0 +1,187 @@
+//! Background loading for images requested by skins with `gfx.LoadImageJob`, these are mostly
+//! song jackets. Only a few images decode at a time with the latest requests first, and images
+//! loaded at a fixed size are also stored on disk as thumbnails so the full size file only has
+//! to be decoded once.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+use image::DynamicImage;
+use poll_promise::Promise;
+
+use crate::cache_dir;
+
+/// Images decoding at the same time
+const MAX_RUNNING: usize = 4;
+/// Requests beyond this are dropped, oldest first. Skins request visible images every frame
+/// so anything still needed is requested again.
+const MAX_QUEUED: usize = 64;
+/// Decoded images waiting to be picked up, prefetched images nobody asked for are dropped
+/// oldest first past this
+const MAX_FINISHED: usize = 32;
+/// Images that failed to load are tried again after this, they may still be downloading
+const RETRY_FAILED: Duration = Duration::from_secs(10);
+
+type Size = Option<(u32, u32)>;
+
+#[derive(Default)]
+pub struct ImageJobs {
+    queue: VecDeque<(String, Size)>,
+    running: HashMap<String, Promise<DynamicImage>>,
+    finished: VecDeque<(String, DynamicImage)>,
+    failed: HashMap<String, Instant>,
+    /// Images uploaded for each lua state, they stay until the state is dropped since the
+    /// skin keeps using the id it got
+    loaded: HashSet<(usize, String)>,
+    /// Last size a skin asked for, used for prefetching
+    last_size: Size,
+}
+
+impl ImageJobs {
+    /// Queues `path` ahead of earlier requests unless it is already loading.
+    pub fn request(&mut self, path: &str, size: Size) {
+        if size.is_some() {
+            self.last_size = size;
+        }
+
+        if self.failed(path) || self.is_pending(path) {
+            return;
+        }
+
+        self.queue.retain(|(x, _)| x != path);
+        self.queue.push_front((path.to_string(), size));
+        self.queue.truncate(MAX_QUEUED);
+        self.start_jobs();
+    }
+
+    /// Queues `path` behind everything requested so far, at the size last requested by the skin.
+    pub fn prefetch(&mut self, path: &str) {
+        if self.failed(path)
+            || self.is_pending(path)
+            || self.queue.iter().any(|(x, _)| x == path)
+            || self.loaded.iter().any(|(_, x)| x == path)
+            || self.queue.len() >= MAX_QUEUED
+        {
+            return;
+        }
+
+        self.queue.push_back((path.to_string(), self.last_size));
+        self.start_jobs();
+    }
+
+    fn is_pending(&self, path: &str) -> bool {
+        self.running.contains_key(path) || self.finished.iter().any(|(x, _)| x == path)
+    }
+
+    fn start_jobs(&mut self) {
+        let finished: Vec<String> = self
+            .running
+            .iter()
+            .filter(|(_, job)| job.ready().is_some())
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        for path in finished {
+            let Some(Ok(image)) = self.running.remove(&path).map(Promise::try_take) else {
+                continue;
+            };
+
+            if image.width() == 0 {
+                self.failed.insert(path, Instant::now());
+            } else {
+                self.failed.remove(&path);
+                self.finished.push_back((path, image));
+            }
+        }
+
+        while self.finished.len() > MAX_FINISHED {
+            self.finished.pop_front();
+        }
+
+        while self.running.len() < MAX_RUNNING {
+            let Some((path, size)) = self.queue.pop_front() else {
+                break;
+            };
+
+            let job_path = path.clone();
+            self.running.insert(
+                path,
+                Promise::spawn_thread("load image", move || load(&job_path, size)),
+            );
+        }
+    }
+
+    /// Takes the decoded image for `path` if it is done loading.
+    pub fn take(&mut self, path: &str) -> Option<DynamicImage> {
+        self.start_jobs();
+        let i = self.finished.iter().position(|(x, _)| x == path)?;
+        self.finished.remove(i).map(|(_, image)| image)
+    }
+
+    /// Whether `path` failed to load recently, it is requested again after [`RETRY_FAILED`].
+    pub fn failed(&self, path: &str) -> bool {
+        self.failed
+            .get(path)
+            .is_some_and(|x| x.elapsed() < RETRY_FAILED)
+    }
+
+    /// Tracks an image uploaded for a lua state so it is not prefetched again.
+    pub fn loaded(&mut self, lua_index: usize, path: &str) {
+        self.loaded.insert((lua_index, path.to_string()));
+    }
+
+    /// Drops images of lua states that no longer exist.
+    pub fn retain_lua(&mut self, f: impl Fn(usize) -> bool) {
+        self.loaded.retain(|(lua_index, _)| f(*lua_index));
+    }
+}
+
+fn thumbnail_path(path: &Path, (w, h): (u32, u32)) -> Option<PathBuf> {
+    let modified = std::fs::metadata(path)
+        .and_then(|x| x.modified())
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    let key = format!("{}|{modified}|{w}x{h}", path.display());
+
+    let mut thumbnail = cache_dir();
+    thumbnail.push("thumbnails");
+    thumbnail.push(sha1_smol::Sha1::from(key).hexdigest());
+    thumbnail.set_extension("png");
+    Some(thumbnail)
+}
+
+/// Decodes the image, returns an empty image if it can't be loaded.
+fn load(path: &str, size: Size) -> DynamicImage {
+    let path = Path::new(path);
+    let Some(size) = size else {
+        return image::open(path).unwrap_or_default();
+    };
+
+    let thumbnail = thumbnail_path(path, size);
+    if let Some(image) = thumbnail.as_ref().and_then(|x| image::open(x).ok()) {
+        return image;
+    }
+
+    let Ok(image) = image::open(path) else {
+        return DynamicImage::default();
+    };
+
+    let image = image.resize(size.0, size.1, image::imageops::FilterType::CatmullRom);
+    if let Some(thumbnail) = thumbnail {
+        let saved = thumbnail
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| Ok(image.save(&thumbnail)?));
+        if let Err(e) = saved {
+            log::warn!("Failed to cache thumbnail for {}: {e}", path.display());
+        }
+    }
+
+    image
+}
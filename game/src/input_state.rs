@@ -15,6 +15,8 @@ pub struct InputState {
     laser_state: Arc<RwLock<LaserState>>,
     gilrs: Arc<Mutex<gilrs::Gilrs>>,
     buttons_held: Arc<RwLock<HashMap<UscButton, SystemTime>>>,
+    /// Presses ignored by the debounce filter since startup
+    debounced: Arc<RwLock<HashMap<UscButton, u32>>>,
 }
 
 impl InputState {
@@ -24,6 +26,7 @@ impl InputState {
             laser_state: Arc::new(RwLock::new(LaserState::default())),
             gilrs,
             buttons_held: Arc::new(RwLock::new(HashMap::default())),
+            debounced: Arc::new(RwLock::new(HashMap::default())),
         }
     }
 
@@ -59,6 +62,21 @@ impl InputState {
             .and_then(|l| l.get(&button).copied())
     }
 
+    pub fn count_debounced(&self, button: UscButton) {
+        if let Ok(mut debounced) = self.debounced.write() {
+            *debounced.entry(button).or_default() += 1;
+        }
+    }
+
+    /// Number of presses of `button` ignored by the debounce filter
+    pub fn debounced(&self, button: UscButton) -> u32 {
+        self.debounced
+            .read()
+            .ok()
+            .and_then(|l| l.get(&button).copied())
+            .unwrap_or_default()
+    }
+
     pub fn get_axis(&self, side: Side) -> LaserAxis {
         self.laser_state.read().expect("Lock error").get_axis(side)
     }
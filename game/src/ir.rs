@@ -0,0 +1,551 @@
+//! Client for USC-IR internet ranking servers. Scores are submitted in the background one at a
+//! time and kept in a saved queue while the server can't be reached. Skins can query records and
+//! leaderboards through the `IR` lua table.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use base64::Engine;
+use log::{info, warn};
+use poll_promise::Promise;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use tealr::{
+    mlu::{
+        mlua::{Function, Lua, LuaSerdeExt, RegistryKey},
+        ExportInstances, TealData, UserData, UserDataProxy,
+    },
+    ToTypename,
+};
+use uuid::Uuid;
+
+use crate::{
+    config::GameConfig,
+    worker_service::{Backoff, WorkerService},
+};
+
+/// Wait before retrying a score the server didn't receive, multiplied by the failed attempts
+const RETRY_DELAY: Duration = Duration::from_secs(10);
+/// Failed attempts after which the retry delay stops growing
+const MAX_BACKOFF: u32 = 12;
+
+/// Status codes of IR responses, `Pending` and `RequestFailure` are only set by the client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum IrState {
+    Unused = 0,
+    Pending = 1,
+    Success = 20,
+    Accepted = 22,
+    BadRequest = 40,
+    Unauthorized = 41,
+    ChartRefused = 42,
+    Forbidden = 43,
+    NotFound = 44,
+    ServerError = 50,
+    RequestFailure = 60,
+}
+
+const IR_STATES: [IrState; 11] = [
+    IrState::Unused,
+    IrState::Pending,
+    IrState::Success,
+    IrState::Accepted,
+    IrState::BadRequest,
+    IrState::Unauthorized,
+    IrState::ChartRefused,
+    IrState::Forbidden,
+    IrState::NotFound,
+    IrState::ServerError,
+    IrState::RequestFailure,
+];
+
+impl IrState {
+    fn from_code(code: i32) -> Self {
+        IR_STATES
+            .into_iter()
+            .find(|x| *x as i32 == code)
+            .unwrap_or(IrState::ServerError)
+    }
+}
+
+/// The `IRData` lua global.
+pub fn lua_ir_data() -> Value {
+    let states: HashMap<String, i32> = IR_STATES
+        .into_iter()
+        .map(|x| (format!("{x:?}"), x as i32))
+        .collect();
+    json!({
+        "Active": GameConfig::get().ir_url.is_some(),
+        "States": states,
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IrResponse<T> {
+    pub status_code: i32,
+    #[serde(default)]
+    pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<T>,
+}
+
+impl<T> IrResponse<T> {
+    fn failed(error: anyhow::Error) -> Self {
+        Self {
+            status_code: IrState::RequestFailure as i32,
+            description: error.to_string(),
+            body: None,
+        }
+    }
+
+    pub fn state(&self) -> IrState {
+        IrState::from_code(self.status_code)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct IrServer {
+    url: String,
+    token: String,
+}
+
+impl IrServer {
+    fn from_config() -> Option<Self> {
+        let config = GameConfig::get();
+        config.ir_url.as_ref().map(|url| Self {
+            url: url.trim_end_matches('/').to_string(),
+            token: config.ir_token.clone(),
+        })
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<IrResponse<T>> {
+        Ok(reqwest::Client::new()
+            .get(format!("{}/{path}", self.url))
+            .bearer_auth(&self.token)
+            .send()
+            .await?
+            .json()
+            .await?)
+    }
+
+    async fn post<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &impl Serialize,
+    ) -> Result<IrResponse<T>> {
+        Ok(reqwest::Client::new()
+            .post(format!("{}/{path}", self.url))
+            .bearer_auth(&self.token)
+            .json(body)
+            .send()
+            .await?
+            .json()
+            .await?)
+    }
+
+    async fn post_replay(&self, identifier: String, replay: Vec<u8>) -> Result<IrResponse<Value>> {
+        let form = reqwest::multipart::Form::new()
+            .text("identifier", identifier)
+            .part(
+                "replay",
+                reqwest::multipart::Part::bytes(replay).file_name("replay.gz"),
+            );
+        Ok(reqwest::Client::new()
+            .post(format!("{}/replays", self.url))
+            .bearer_auth(&self.token)
+            .multipart(form)
+            .send()
+            .await?
+            .json()
+            .await?)
+    }
+}
+
+/// Chart a score is submitted for, sent so the server can start tracking charts it doesn't know
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IrChart {
+    pub chart_hash: String,
+    pub title: String,
+    pub artist: String,
+    pub effector: String,
+    pub illustrator: String,
+    pub bpm: String,
+    pub level: u8,
+    pub difficulty: u8,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IrScoreOptions {
+    pub gauge_type: u8,
+    pub gauge_opt: i32,
+    pub mirror: bool,
+    pub random: bool,
+    pub auto_flags: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IrScore {
+    pub score: u32,
+    pub gauge: f32,
+    /// Seconds since the unix epoch
+    pub timestamp: i64,
+    pub crit: i32,
+    pub near: i32,
+    pub error: i32,
+    pub early: i32,
+    pub late: i32,
+    pub combo: i32,
+    /// Same as the song wheel badge
+    pub lamp: u8,
+    pub options: IrScoreOptions,
+}
+
+/// Body of a score submission
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IrSubmission {
+    pub chart: IrChart,
+    pub score: IrScore,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SubmitResponse {
+    score: Value,
+    server_record: Value,
+    /// Identifier to upload the replay with when the server wants it
+    send_replay: Option<String>,
+    #[serde(default)]
+    adjacent_above: Vec<Value>,
+    #[serde(default)]
+    adjacent_below: Vec<Value>,
+}
+
+/// Outcome of a submission shown on the results screen.
+#[derive(Debug, Clone)]
+pub struct IrResult {
+    pub state: IrState,
+    pub description: String,
+    /// The server record followed by the scores around the submitted one
+    pub scores: Vec<Value>,
+}
+
+impl IrResult {
+    fn new(state: IrState, description: String) -> Self {
+        Self {
+            state,
+            description,
+            scores: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct QueuedScore {
+    id: Uuid,
+    submission: IrSubmission,
+    /// Encoded replay, uploaded when the server asks for it
+    replay: Option<String>,
+    #[serde(flatten)]
+    retry: Backoff,
+}
+
+/// Kept in the game folder, scores waiting in it are lost if it is deleted
+fn queue_path() -> PathBuf {
+    let mut path = GameConfig::get().game_folder.clone();
+    path.push("ir_queue.json");
+    path
+}
+
+/// Checks that the server tracks the chart, submits the score and uploads the replay if asked
+/// to. Errors when the server couldn't be reached.
+async fn submit(
+    server: IrServer,
+    submission: IrSubmission,
+    replay: Option<String>,
+) -> Result<IrResult> {
+    let tracked: IrResponse<Value> = server
+        .get(&format!("charts/{}", submission.chart.chart_hash))
+        .await?;
+    if tracked.state() == IrState::ChartRefused {
+        return Ok(IrResult::new(tracked.state(), tracked.description));
+    }
+
+    let response: IrResponse<SubmitResponse> = server.post("scores", &submission).await?;
+    let mut result = IrResult::new(response.state(), response.description);
+    let Some(body) = response.body else {
+        return Ok(result);
+    };
+
+    if let (Some(identifier), Some(replay)) = (body.send_replay, replay) {
+        let uploaded = match base64::engine::general_purpose::STANDARD.decode(replay) {
+            Ok(replay) => server.post_replay(identifier, replay).await,
+            Err(e) => Err(e.into()),
+        };
+        match uploaded {
+            Ok(x) if x.state() == IrState::Success => {}
+            Ok(x) => warn!("IR did not accept the replay: {}", x.description),
+            Err(e) => warn!("Failed to upload replay to IR: {e}"),
+        }
+    }
+
+    if body.server_record != body.score {
+        result.scores.push(body.server_record);
+    }
+    result.scores.extend(body.adjacent_above);
+    result.scores.push(body.score);
+    result.scores.extend(body.adjacent_below);
+    Ok(result)
+}
+
+pub struct InternetRanking {
+    queue: Vec<QueuedScore>,
+    active: Option<(Uuid, Promise<Result<IrResult>>)>,
+    /// Latest outcome of the scores submitted since the game started
+    results: HashMap<Uuid, IrResult>,
+    /// Increased whenever a result changes
+    version: u64,
+}
+
+impl InternetRanking {
+    pub fn new() -> Self {
+        let queue: Vec<QueuedScore> = std::fs::read_to_string(queue_path())
+            .ok()
+            .and_then(|x| serde_json::from_str(&x).ok())
+            .unwrap_or_default();
+        if !queue.is_empty() {
+            info!("{} scores waiting to be sent to IR", queue.len());
+        }
+
+        Self {
+            queue,
+            active: None,
+            results: HashMap::new(),
+            version: 0,
+        }
+    }
+
+    /// Queues a score to be sent to the IR server, returns the id to look up its result with.
+    pub fn submit(&mut self, submission: IrSubmission, replay: Option<String>) -> Uuid {
+        let id = Uuid::new_v4();
+        self.queue.push(QueuedScore {
+            id,
+            submission,
+            replay,
+            retry: Backoff::default(),
+        });
+        self.results
+            .insert(id, IrResult::new(IrState::Pending, String::new()));
+        self.version += 1;
+        self.save();
+        id
+    }
+
+    pub fn result(&self, id: Uuid) -> Option<&IrResult> {
+        self.results.get(&id)
+    }
+
+    /// Changes whenever a submission result changes.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    fn save(&self) {
+        let saved = serde_json::to_string(&self.queue)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| {
+                let path = queue_path();
+                if let Some(folder) = path.parent() {
+                    std::fs::create_dir_all(folder)?;
+                }
+                Ok(std::fs::write(path, json)?)
+            });
+        if let Err(e) = saved {
+            warn!("Could not save IR queue: {e}");
+        }
+    }
+
+    fn finished(&mut self, id: Uuid, result: Result<IrResult>) {
+        let Some(i) = self.queue.iter().position(|x| x.id == id) else {
+            return;
+        };
+
+        let result = match result {
+            Ok(result) if result.state != IrState::ServerError => {
+                info!(
+                    "IR score submitted: {:?} {}",
+                    result.state, result.description
+                );
+                self.queue.remove(i);
+                result
+            }
+            failed => {
+                self.queue[i].retry.failed(RETRY_DELAY, MAX_BACKOFF);
+                let error = match failed {
+                    Ok(result) => result.description,
+                    Err(e) => e.to_string(),
+                };
+                warn!("Could not submit score to IR, retrying later: {error}");
+                IrResult::new(
+                    IrState::RequestFailure,
+                    format!("The score will be sent when the server can be reached ({error})"),
+                )
+            }
+        };
+
+        self.results.insert(id, result);
+        self.version += 1;
+        self.save();
+    }
+
+    fn start_next(&mut self) {
+        let Some(server) = IrServer::from_config() else {
+            return;
+        };
+        let now = Instant::now();
+        let Some(next) = self.queue.iter().find(|x| x.retry.ready(now)) else {
+            return;
+        };
+
+        self.active = Some((
+            next.id,
+            Promise::spawn_async(submit(server, next.submission.clone(), next.replay.clone())),
+        ));
+    }
+}
+
+impl WorkerService for InternetRanking {
+    fn update(&mut self) {
+        if let Some((id, active)) = self.active.take() {
+            match active.try_take() {
+                Ok(result) => self.finished(id, result),
+                Err(active) => self.active = Some((id, active)),
+            }
+        }
+
+        if self.active.is_none() {
+            self.start_next();
+        }
+    }
+}
+
+/// IR requests made by a lua state, with the callbacks to call with their responses.
+#[derive(Default)]
+pub struct LuaIr {
+    calls: Vec<(RegistryKey, Promise<IrResponse<Value>>)>,
+}
+
+impl LuaIr {
+    /// Calls the callbacks of the finished requests.
+    pub fn poll(lua: &Lua) {
+        let calls = match lua.app_data_mut::<LuaIr>() {
+            Some(mut ir) => std::mem::take(&mut ir.calls),
+            None => return,
+        };
+
+        let mut remaining = vec![];
+        for (callback, call) in calls {
+            match call.try_take() {
+                Ok(response) => {
+                    if let Ok(callback) = lua.registry_value::<Function>(&callback) {
+                        crate::log_result!(lua
+                            .to_value(&response)
+                            .and_then(|x| callback.call::<_, ()>(x)));
+                    }
+                    _ = lua.remove_registry_value(callback);
+                }
+                Err(call) => remaining.push((callback, call)),
+            }
+        }
+
+        if let Some(mut ir) = lua.app_data_mut::<LuaIr>() {
+            ir.calls.append(&mut remaining);
+        }
+    }
+
+    fn request<F>(
+        lua: &Lua,
+        callback: Function,
+        request: impl FnOnce(IrServer) -> F,
+    ) -> tealr::mlu::mlua::Result<()>
+    where
+        F: Future<Output = Result<IrResponse<Value>>> + Send + 'static,
+    {
+        let call = match IrServer::from_config() {
+            Some(server) => {
+                let request = request(server);
+                Promise::spawn_async(
+                    async move { request.await.unwrap_or_else(IrResponse::failed) },
+                )
+            }
+            None => Promise::from_ready(IrResponse::failed(anyhow::anyhow!("No IR server set"))),
+        };
+
+        let key = lua.create_registry_value(callback)?;
+        if let Some(mut ir) = lua.app_data_mut::<LuaIr>() {
+            ir.calls.push((key, call));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default, ToTypename, UserData)]
+pub struct ExportIr;
+
+impl TealData for ExportIr {
+    fn add_methods<'lua, T: tealr::mlu::TealDataMethods<'lua, Self>>(methods: &mut T) {
+        methods.add_function("Heartbeat", |lua, callback: Function<'lua>| {
+            LuaIr::request(lua, callback, |server| async move { server.get("").await })
+        });
+
+        methods.add_function(
+            "ChartTracked",
+            |lua, (hash, callback): (String, Function<'lua>)| {
+                LuaIr::request(lua, callback, |server| async move {
+                    server.get(&format!("charts/{hash}")).await
+                })
+            },
+        );
+
+        methods.add_function(
+            "Record",
+            |lua, (hash, callback): (String, Function<'lua>)| {
+                LuaIr::request(lua, callback, |server| async move {
+                    server.get(&format!("charts/{hash}/record")).await
+                })
+            },
+        );
+
+        methods.add_function(
+            "Leaderboard",
+            |lua, (hash, mode, n, callback): (String, String, u32, Function<'lua>)| {
+                LuaIr::request(lua, callback, |server| async move {
+                    server
+                        .get(&format!("charts/{hash}/leaderboard?mode={mode}&n={n}"))
+                        .await
+                })
+            },
+        );
+    }
+
+    fn add_fields<'lua, F: tealr::mlu::TealDataFields<'lua, Self>>(_fields: &mut F) {}
+}
+
+impl ExportInstances for ExportIr {
+    fn add_instances<'lua, T: tealr::mlu::InstanceCollector<'lua>>(
+        self,
+        instance_collector: &mut T,
+    ) -> tealr::mlu::mlua::Result<()> {
+        instance_collector.add_instance("IR", UserDataProxy::<ExportIr>::new)?;
+        Ok(())
+    }
+}
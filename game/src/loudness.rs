@@ -0,0 +1,127 @@
+//! Integrated loudness as described in ITU-R BS.1770, used to level song previews.
+
+use rodio::Source;
+
+/// Loudness given to previews without any audible blocks
+pub const SILENCE: f64 = -70.0;
+
+/// Gain applied to previews is limited to this many dB either way
+const MAX_GAIN_DB: f64 = 12.0;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+/// The two stage K-weighting filter, coefficients are derived for any sample rate the
+/// same way as libebur128 does.
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let fs = sample_rate as f64;
+
+    let shelf = {
+        let f0 = 1681.974450955533;
+        let gain = 3.999843853973347;
+        let q = 0.7071752369554196;
+        let k = (std::f64::consts::PI * f0 / fs).tan();
+        let vh = 10f64.powf(gain / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        Biquad {
+            b: [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            ..Default::default()
+        }
+    };
+
+    let high_pass = {
+        let f0 = 38.13547087602444;
+        let q = 0.5003270373238773;
+        let k = (std::f64::consts::PI * f0 / fs).tan();
+        let a0 = 1.0 + k / q + k * k;
+        Biquad {
+            b: [1.0, -2.0, 1.0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            ..Default::default()
+        }
+    };
+
+    [shelf, high_pass]
+}
+
+/// Integrated loudness of `source` in LUFS, [`SILENCE`] if nothing is loud enough to measure.
+pub fn measure(source: impl Source<Item = f32>) -> f64 {
+    let channels = source.channels().max(1) as usize;
+    let sample_rate = source.sample_rate();
+    // 400ms blocks overlapping by 75%
+    let step = (sample_rate / 10).max(1) as usize;
+
+    let mut filters = vec![k_weighting(sample_rate); channels];
+    let mut steps: Vec<f64> = vec![];
+    let mut sum = 0.0;
+    let mut count = 0;
+
+    for (i, sample) in source.enumerate() {
+        let [shelf, high_pass] = &mut filters[i % channels];
+        let weighted = high_pass.process(shelf.process(sample as f64));
+        sum += weighted * weighted;
+
+        if i % channels == channels - 1 {
+            count += 1;
+            if count == step {
+                steps.push(sum / step as f64);
+                sum = 0.0;
+                count = 0;
+            }
+        }
+    }
+
+    let blocks: Vec<f64> = steps
+        .windows(4)
+        .map(|x| x.iter().sum::<f64>() / 4.0)
+        .collect();
+    let loudness = |power: f64| -0.691 + 10.0 * power.log10();
+    let gated_mean = |threshold: f64| {
+        let gated = blocks
+            .iter()
+            .copied()
+            .filter(|x| loudness(*x) > threshold)
+            .collect::<Vec<_>>();
+        (!gated.is_empty()).then(|| gated.iter().sum::<f64>() / gated.len() as f64)
+    };
+
+    let Some(absolute) = gated_mean(SILENCE) else {
+        return SILENCE;
+    };
+
+    gated_mean(loudness(absolute) - 10.0)
+        .map(loudness)
+        .unwrap_or(SILENCE)
+}
+
+/// Linear gain bringing audio measured at `loudness` to `target` LUFS.
+pub fn gain(loudness: f64, target: f64) -> f32 {
+    if loudness <= SILENCE {
+        return 1.0;
+    }
+
+    let db = (target - loudness).clamp(-MAX_GAIN_DB, MAX_GAIN_DB);
+    10f64.powf(db / 20.0) as f32
+}
@@ -1,8 +1,13 @@
-use std::rc::Rc;
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::SystemTime,
+};
 
 use crate::{
-    config::GameConfig,
+    config::{GameConfig, DEFAULT_SKIN},
     game_data::{self, ExportGame, LuaPath},
+    ir::{lua_ir_data, ExportIr, LuaIr},
     lua_http::{ExportLuaHttp, LuaHttp},
     util::lua_address,
     vg_ui::{ExportVgfx, Vgfx},
@@ -10,11 +15,25 @@ use crate::{
 };
 use anyhow::Result;
 use di::{injectable, Ref, RefMut};
-use log::info;
+use kson_rodio_sources::ducking::Ducking;
+use log::{info, warn};
 use puffin::profile_scope;
-use serde_json::json;
-use tealr::mlu::mlua::Lua;
 use tealr::mlu::mlua::LuaSerdeExt;
+use tealr::mlu::mlua::{Lua, Table, Value};
+
+/// Skin scripts a Lua state was loaded from, checked so the state can be reloaded when they
+/// change
+struct LoadedScripts {
+    script_path: String,
+    /// Watched files and their modification times when they were loaded
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    /// Skin modules loaded with `require`, unloaded before reloading so they are loaded again
+    modules: Vec<String>,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|x| x.modified()).ok()
+}
 
 //TODO: Used expanded macro because of wrong dependencies, use macro when fixed
 #[injectable]
@@ -23,6 +42,7 @@ pub struct LuaProvider {
     vgfx: RefMut<Vgfx>,
     context: Ref<three_d::core::Context>,
     mixer: Ref<InnerRuscMixer>,
+    ducking: Ref<Ducking>,
     game_data: RefMut<game_data::GameData>,
 }
 
@@ -33,7 +53,14 @@ impl LuaProvider {
 
     pub fn register_libraries(&self, lua: Rc<Lua>, script_path: impl AsRef<str>) -> Result<()> {
         //Set path for 'require' (https://stackoverflow.com/questions/4125971/setting-the-global-lua-path-variable-from-c-c?lq=1)
-        let mut real_script_path = GameConfig::get().skin_path();
+        // Modules missing from the skin are looked up in the default skin
+        let skin_paths = {
+            let config = GameConfig::get();
+            [
+                config.skin_path(),
+                config.game_folder.join("skins").join(DEFAULT_SKIN),
+            ]
+        };
         let arena = self.arena.clone();
         let vgfx = self.vgfx.clone();
         let game_data = self.game_data.clone();
@@ -42,12 +69,8 @@ impl LuaProvider {
         tealr::mlu::set_global_env(ExportGame, &lua)?;
         tealr::mlu::set_global_env(LuaPath, &lua)?;
         tealr::mlu::set_global_env(ExportLuaHttp, &lua)?;
-        lua.globals().set(
-            "IRData",
-            lua.to_value(&json!({
-                "Active": false
-            }))?,
-        )?;
+        tealr::mlu::set_global_env(ExportIr, &lua)?;
+        lua.globals().set("IRData", lua.to_value(&lua_ir_data())?)?;
         arena
             .write()
             .expect("Could not get lock to lua arena")
@@ -65,7 +88,9 @@ impl LuaProvider {
             lua.set_app_data(game_data.clone());
             lua.set_app_data(self.context.clone());
             lua.set_app_data(self.mixer.clone());
+            lua.set_app_data(self.ducking.clone());
             lua.set_app_data(LuaHttp::default());
+            lua.set_app_data(LuaIr::default());
             //lua.gc_stop();
         }
 
@@ -73,36 +98,109 @@ impl LuaProvider {
             let package: tealr::mlu::mlua::Table = lua.globals().get("package")?;
             let old_path: String = package.get("path")?;
 
-            let package_path = format!(
-                "{0};{1}/scripts/?.lua;{1}/scripts/?",
-                old_path,
-                real_script_path.as_os_str().to_string_lossy()
-            );
+            let package_path = skin_paths.iter().fold(old_path, |package_path, skin_path| {
+                format!(
+                    "{0};{1}/scripts/?.lua;{1}/scripts/?",
+                    package_path,
+                    skin_path.as_os_str().to_string_lossy()
+                )
+            });
             package.set("path", package_path)?;
 
             lua.globals().set("package", package)?;
         }
 
-        real_script_path.push("scripts");
+        Self::load_scripts(&lua, script_path.as_ref())
+    }
+
+    /// Evaluates `common.lua` and the script at `script_path` in the skin `scripts` folder, or
+    /// in the default skin when the skin doesn't have them.
+    fn load_scripts(lua: &Lua, script_path: &str) -> Result<()> {
+        let (common_path, real_script_path) = {
+            let config = GameConfig::get();
+            (
+                config.skin_asset("scripts", "common.lua"),
+                config
+                    .skin_asset("scripts", script_path)
+                    .unwrap_or_else(|| config.skin_path().join("scripts").join(script_path)),
+            )
+        };
 
-        real_script_path.push("common.lua");
-        if real_script_path.exists() {
-            info!("Loading: {:?}", &real_script_path);
-            let test_code = std::fs::read_to_string(&real_script_path)?;
+        let mut files = vec![];
+        if let Some(common_path) = common_path {
+            info!("Loading: {:?}", &common_path);
+            files.push((common_path.clone(), modified(&common_path)));
+            let test_code = std::fs::read_to_string(&common_path)?;
             lua.load(&test_code).set_name("common.lua").eval::<()>()?;
         }
 
-        real_script_path.pop();
-
-        real_script_path.push(script_path.as_ref());
         info!("Loading: {:?}", &real_script_path);
+        files.push((real_script_path.clone(), modified(&real_script_path)));
+        // Set before evaluating so a script with errors gets reloaded once it is fixed
+        lua.set_app_data(LoadedScripts {
+            script_path: script_path.to_string(),
+            files,
+            modules: vec![],
+        });
+
         let test_code = std::fs::read_to_string(real_script_path)?;
-        {
+        let result = {
             profile_scope!("evaluate lua file");
-            lua.load(&test_code)
-                .set_name(script_path.as_ref())
-                .eval::<()>()?;
+            lua.load(&test_code).set_name(script_path).eval::<()>()
+        };
+
+        let loaded: Table = lua.globals().get::<_, Table>("package")?.get("loaded")?;
+        let modules: Vec<(String, PathBuf)> = loaded
+            .pairs::<String, Value>()
+            .filter_map(|x| x.ok())
+            .filter_map(|(name, _)| {
+                let path = GameConfig::get()
+                    .skin_asset("scripts", format!("{}.lua", name.replace('.', "/")))?;
+                Some((name, path))
+            })
+            .collect();
+
+        if let Some(mut scripts) = lua.app_data_mut::<LoadedScripts>() {
+            for (name, path) in modules {
+                scripts.files.push((path.clone(), modified(&path)));
+                scripts.modules.push(name);
+            }
+        }
+
+        Ok(result?)
+    }
+
+    /// Evaluates the scripts of Lua states again when their files have changed. Globals set by
+    /// the game are kept, so the scene using the state continues where it was.
+    pub fn reload_changed_scripts(&self) {
+        profile_scope!("Check skin scripts");
+        let arena = self.arena.read().expect("Lock error");
+        for lua in arena.0.iter() {
+            let Some((script_path, modules)) = lua.app_data_ref::<LoadedScripts>().and_then(|x| {
+                x.files
+                    .iter()
+                    .any(|(path, time)| modified(path) != *time)
+                    .then(|| (x.script_path.clone(), x.modules.clone()))
+            }) else {
+                continue;
+            };
+
+            info!("Reloading skin script: {script_path}");
+            let reloaded = lua
+                .globals()
+                .get::<_, Table>("package")
+                .and_then(|x| x.get::<_, Table>("loaded"))
+                .and_then(|loaded| {
+                    modules
+                        .iter()
+                        .try_for_each(|name| loaded.set(name.as_str(), Value::Nil))
+                })
+                .map_err(anyhow::Error::from)
+                .and_then(|_| Self::load_scripts(lua, &script_path));
+
+            if let Err(e) = reloaded {
+                warn!("Failed to reload {script_path}: {e}");
+            }
         }
-        Ok(())
     }
 }
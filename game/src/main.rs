@@ -1,4 +1,5 @@
 use std::{
+    collections::VecDeque,
     path::{Path, PathBuf},
     rc::Rc,
     sync::{mpsc::channel, Arc, Mutex, RwLock},
@@ -6,7 +7,7 @@ use std::{
 };
 
 use crate::{
-    button_codes::{LaserState, RuscFilter},
+    button_codes::{DebounceFilter, LaserState, RuscFilter},
     config::Args,
     config::GameConfig,
     game_main::GameMain,
@@ -28,9 +29,9 @@ use game_main::ControlMessage;
 
 use glutin_winit::GlWindow;
 use help::ServiceHelper;
-use kson::Ksh;
 use log::*;
 
+use kson_rodio_sources::ducking::Ducking;
 use lua_service::LuaProvider;
 use luals_gen::LuaLsGen;
 use puffin::profile_function;
@@ -49,18 +50,25 @@ use glutin::prelude::*;
 mod animation;
 mod async_service;
 mod audio;
+mod audio_output;
 mod audio_test;
 mod button_codes;
 mod companion_interface;
 mod config;
+mod egui_nav;
 mod game;
 mod game_data;
 mod game_main;
 mod help;
+mod image_cache;
 mod input_state;
+mod ir;
+mod loudness;
 mod lua_http;
 mod lua_service;
 mod main_menu;
+mod media_session;
+mod perf_hud;
 mod results;
 mod scene;
 mod settings_dialog;
@@ -91,9 +99,32 @@ macro_rules! block_on {
 pub type InnerRuscMixer = DynamicMixerController<f32>;
 pub type RuscMixer = Arc<InnerRuscMixer>;
 
-//TODO: Move to platform files
-#[cfg(all(target_os = "windows", not(feature = "portable")))]
+/// Folder the executable is in
+fn exe_dir() -> PathBuf {
+    let mut exe_dir = std::env::current_exe().expect("Could not get exe path");
+    exe_dir.pop();
+    exe_dir
+}
+
+/// Portable mode keeps all game data next to the executable, for running the game from a
+/// removable drive. Enabled by the `portable` feature or by a file named `portable` next to
+/// the executable.
+pub fn is_portable() -> bool {
+    cfg!(feature = "portable") || exe_dir().join("portable").exists()
+}
+
 pub fn default_game_dir() -> PathBuf {
+    if is_portable() {
+        exe_dir()
+    } else {
+        user_game_dir()
+    }
+}
+
+/// Game folder outside of portable mode
+//TODO: Move to platform files
+#[cfg(target_os = "windows")]
+fn user_game_dir() -> PathBuf {
     let mut game_dir = directories::UserDirs::new()
         .expect("Failed to get directories")
         .document_dir()
@@ -103,15 +134,8 @@ pub fn default_game_dir() -> PathBuf {
     game_dir
 }
 
-#[cfg(all(target_os = "windows", feature = "portable"))]
-pub fn default_game_dir() -> PathBuf {
-    let mut game_dir = std::env::current_exe().expect("Could not get exe path");
-    game_dir.pop();
-    game_dir
-}
-
 #[cfg(not(target_os = "windows"))]
-pub fn default_game_dir() -> PathBuf {
+fn user_game_dir() -> PathBuf {
     let mut game_dir = directories::UserDirs::new()
         .expect("Failed to get directories")
         .home_dir()
@@ -121,9 +145,8 @@ pub fn default_game_dir() -> PathBuf {
 }
 
 pub fn init_game_dir(game_dir: impl AsRef<Path>) -> anyhow::Result<()> {
-    #[cfg(feature = "portable")]
-    {
-        return Ok(());
+    if is_portable() {
+        return migrate_to_portable(game_dir);
     }
 
     let cargo_dir = std::env::var("CARGO_MANIFEST_DIR");
@@ -213,10 +236,39 @@ pub fn init_game_dir(game_dir: impl AsRef<Path>) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Copies the config and database from the user game folder the first time the game runs
+/// in portable mode, existing files in the portable folder are kept.
+fn migrate_to_portable(game_dir: impl AsRef<Path>) -> anyhow::Result<()> {
+    let user_dir = user_game_dir();
+    if user_dir == game_dir.as_ref() {
+        return Ok(());
+    }
+
+    for file in ["Main.cfg", "maps.db"] {
+        let source = user_dir.join(file);
+        let target = game_dir.as_ref().join(file);
+        if source.exists() && !target.exists() {
+            info!("Copying {source:?} -> {target:?}");
+            std::fs::copy(&source, &target)?;
+        }
+    }
+
+    Ok(())
+}
+
 pub fn project_dirs() -> ProjectDirs {
     directories::ProjectDirs::from("", "Drewol", "USC").expect("Failed to get project dirs")
 }
 
+/// Folder for downloads and generated files that can be deleted at any time
+pub fn cache_dir() -> PathBuf {
+    if is_portable() {
+        default_game_dir().join("cache")
+    } else {
+        project_dirs().cache_dir().to_path_buf()
+    }
+}
+
 pub struct Scenes {
     pub active: Vec<Box<dyn Scene>>,
     pub loaded: Vec<Box<dyn Scene>>,
@@ -445,7 +497,16 @@ fn main() -> anyhow::Result<()> {
         info!("Running anyway");
     };
     GameConfig::init(config_path, args);
-    let (_output_stream, output_stream_handle) = rodio::OutputStream::try_default()?;
+    if GameConfig::get().args.headless {
+        let charts = GameConfig::get().args.chart.clone();
+        return play_headless(&charts);
+    }
+
+    let (_output_stream, output_stream_handle, output_device) = {
+        let config = GameConfig::get();
+        audio_output::open(config.audio_host.as_deref(), config.audio_device.as_deref())?
+    };
+    GameConfig::get_mut().set_output_device(output_device);
     let sink = rodio::Sink::try_new(&output_stream_handle)?;
     let (mixer_controls, mixer) = rodio::dynamic_mixer::mixer::<f32>(2, 44100);
     mixer_controls.add(rodio::source::Zero::new(2, 44100));
@@ -504,7 +565,17 @@ fn main() -> anyhow::Result<()> {
             RefMut::new(block_on!(song_provider::FileSongProvider::new()).into())
         }))
         .add(singleton_factory(|x| {
-            RefMut::new(song_provider::NauticaSongProvider::new(x.get_required_mut()).into())
+            let downloads = x
+                .get_required_mut::<song_provider::DownloadQueue>()
+                .read()
+                .expect("Lock error")
+                .requests();
+            RefMut::new(
+                song_provider::NauticaSongProvider::new(x.get_required_mut(), downloads).into(),
+            )
+        }))
+        .add(singleton_factory(|x| {
+            RefMut::new(song_provider::DownloadQueue::new(x.get_required_mut()).into())
         }))
         .add(transient_factory::<
             RwLock<dyn song_provider::SongProvider>,
@@ -524,8 +595,16 @@ fn main() -> anyhow::Result<()> {
         }))
         .add_worker::<FileSongProvider>()
         .add_worker::<NauticaSongProvider>()
+        .add_worker::<song_provider::DownloadQueue>()
+        .add(singleton_factory(|_| {
+            RefMut::new(ir::InternetRanking::new().into())
+        }))
+        .add_worker::<ir::InternetRanking>()
         .add_worker::<companion_interface::CompanionServer>()
         .add(singleton_factory(move |_| mixer_controls.clone()))
+        .add(singleton_factory(|_| {
+            Ducking::new(Duration::from_millis(300))
+        }))
         .add(Vgfx::singleton().as_mut())
         .add(singleton_factory(|_| {
             RefMut::new(LuaArena(Vec::new()).into())
@@ -543,6 +622,7 @@ fn main() -> anyhow::Result<()> {
     let vgfx = services.get_required_mut::<Vgfx>();
     let event_proxy = eventloop.create_proxy();
     let (mut rusc_filter, offset_tx) = RuscFilter::new(GameConfig::get().global_offset as _);
+    let debounce_filter = DebounceFilter::new(InputState::clone(&services.get_required()));
 
     let _input_thread = poll_promise::Promise::spawn_thread("gilrs", move || {
         let mut knob_state = LaserState::default();
@@ -558,6 +638,7 @@ fn main() -> anyhow::Result<()> {
                         .next_event()
                         .filter_ev(&rusc_filter, &mut input)
                         .filter_ev(&binding_filter, &mut input)
+                        .filter_ev(&debounce_filter, &mut input)
                 } else {
                     None
                 }
@@ -614,7 +695,14 @@ fn main() -> anyhow::Result<()> {
 
     let mut scenes = Scenes::new();
 
-    if GameConfig::get().args.chart.as_ref().is_none() {
+    let mut playlist: VecDeque<PathBuf> = GameConfig::get()
+        .args
+        .chart
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+
+    if playlist.is_empty() {
         let mut title = Box::new(main_menu::MainMenu::new(services.create_scope()));
         title.suspend();
         scenes.loaded.push(title);
@@ -625,51 +713,24 @@ fn main() -> anyhow::Result<()> {
             ));
             scenes.loaded.push(songsel);
         }
-    }
-
-    if let Some(chart_path) = GameConfig::get().args.chart.as_ref() {
-        let chart_path = PathBuf::from(chart_path);
-        let chart =
-            kson::Chart::from_ksh(&std::io::read_to_string(std::fs::File::open(&chart_path)?)?)?;
-
-        let song = Song {
-            title: chart.meta.title.clone(),
-            artist: chart.meta.artist.clone(),
-            bpm: chart.meta.disp_bpm.clone(),
-            id: SongId::default(),
-            difficulties: Arc::new(
-                vec![Difficulty {
-                    jacket_path: chart_path.with_file_name(&chart.meta.jacket_filename),
-                    level: chart.meta.level,
-                    difficulty: chart.meta.difficulty,
-                    id: DiffId::default(),
-                    effector: chart.meta.chart_author.clone(),
-                    top_badge: 0,
-                    hash: None,
-                    scores: vec![],
-                    illustrator: String::new(),
-                }]
-                .into(),
-            ),
-        };
 
-        let audio = rodio::Decoder::new(std::fs::File::open(
-            chart_path.with_file_name(chart.audio.bgm.filename.clone()),
-        )?)?;
+        let config = GameConfig::get();
+        if config.first_run || config.args.setup {
+            scenes
+                .loaded
+                .push(Box::new(settings_screen::setup_wizard::SetupWizard::new(
+                    services.create_scope(),
+                )));
+        }
+    }
 
+    if let Some(chart_path) = playlist.pop_front() {
         let skin_folder = { vgfx.read().expect("Lock error").skin_folder() };
-
-        scenes.loaded.push(
-            Box::new(game::GameData::new(
-                Arc::new(song),
-                0,
-                chart,
-                skin_folder,
-                Box::new(audio.convert_samples()),
-                game_main::AutoPlay::None,
-            )?)
-            .make_scene(services.create_scope())?,
-        );
+        scenes.loaded.push(load_chart_scene(
+            &chart_path,
+            skin_folder,
+            services.create_scope(),
+        )?);
     }
 
     if GameConfig::get().args.sound_test {
@@ -696,7 +757,16 @@ fn main() -> anyhow::Result<()> {
             )));
     }
 
-    let game = GameMain::new(scenes, fps_paint, gui, show_debug_ui, services);
+    let game = GameMain::new(
+        scenes,
+        fps_paint,
+        gui,
+        show_debug_ui,
+        services,
+        playlist,
+        media_session::MediaSession::new(&window),
+        &window,
+    );
 
     let mut last_offset = { GameConfig::get().global_offset };
 
@@ -754,6 +824,115 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Plays charts with perfect inputs without a window or audio and prints the results.
+fn play_headless(charts: &[String]) -> anyhow::Result<()> {
+    let hit_window = GameConfig::get().hit_window;
+    for path in charts {
+        let chart = kson::Chart::from_file(Path::new(path))?;
+        let inputs = game::judge::Playthrough::perfect_inputs(&chart);
+        let result = game::judge::Playthrough::new(chart, hit_window).run(inputs);
+        println!("{path}: {result}");
+    }
+    Ok(())
+}
+
+/// Loads a chart given on the command line straight into a game scene, applying the
+/// `--autoplay`, `--practice` and `--practice-tick` arguments.
+pub fn load_chart_scene(
+    chart_path: &Path,
+    skin_folder: PathBuf,
+    service_provider: ServiceProvider,
+) -> anyhow::Result<Box<dyn Scene>> {
+    let chart = kson::Chart::from_file(chart_path)?;
+
+    let song = Song {
+        title: chart.meta.title.clone(),
+        artist: chart.meta.artist.clone(),
+        bpm: chart.meta.disp_bpm.clone(),
+        id: SongId::default(),
+        difficulties: Arc::new(
+            vec![Difficulty {
+                jacket_path: chart_path.with_file_name(&chart.meta.jacket_filename),
+                level: chart.meta.level,
+                difficulty: chart.meta.difficulty,
+                id: DiffId::default(),
+                effector: chart.meta.chart_author.clone(),
+                top_badge: 0,
+                hash: None,
+                scores: vec![],
+                illustrator: chart.meta.jacket_author.clone(),
+                density: vec![],
+                radar: None,
+                information: chart.meta.information.clone(),
+            }]
+            .into(),
+        ),
+    };
+
+    let audio = rodio::Decoder::new(std::fs::File::open(
+        chart_path.with_file_name(chart.audio.bgm.filename.clone()),
+    )?)?;
+    let fx_audio = chart
+        .audio
+        .bgm
+        .fx_filename()
+        .and_then(|x| std::fs::File::open(chart_path.with_file_name(x)).ok())
+        .and_then(|x| rodio::Decoder::new(x).ok())
+        .map(|x| Box::new(x.convert_samples()) as Box<dyn Source<Item = f32> + Send>);
+    let chart_images = chart
+        .bg
+        .image_filenames()
+        .into_iter()
+        .filter_map(|x| {
+            Some((
+                x.to_string(),
+                std::fs::read(chart_path.with_file_name(x)).ok()?,
+            ))
+        })
+        .collect();
+    let effect_audio = chart
+        .audio
+        .effect_filenames()
+        .into_iter()
+        .filter_map(|x| {
+            Some((
+                x.to_string(),
+                std::fs::read(chart_path.with_file_name(x)).ok()?,
+            ))
+        })
+        .collect();
+
+    let (autoplay, practice, practice_tick) = {
+        let args = &GameConfig::get().args;
+        let autoplay = if args.autoplay {
+            game_main::AutoPlay::All
+        } else {
+            game_main::AutoPlay::None
+        };
+        (autoplay, args.practice, args.practice_tick)
+    };
+
+    let mut game_data = game::GameData::new(
+        Arc::new(song),
+        0,
+        chart,
+        skin_folder,
+        Box::new(audio.convert_samples()),
+        autoplay,
+    )?
+    .with_fx_audio(fx_audio)
+    .with_chart_images(chart_images)
+    .with_effect_audio(effect_audio);
+
+    if let Some(measure) = practice {
+        game_data = game_data.with_practice(measure.saturating_sub(1));
+    } else if let Some(tick) = practice_tick {
+        game_data = game_data.with_practice_tick(tick);
+    }
+
+    Box::new(game_data).make_scene(service_provider)
+}
+
 fn export_luals_defs() -> Result<(), anyhow::Error> {
     use std::io::Write;
     let mut path = default_game_dir();
@@ -0,0 +1,98 @@
+//! Publishes the song being played to the OS media session, MPRIS on Linux and SMTC on
+//! Windows, so desktop widgets and stream tools can show what is playing.
+
+use std::path::PathBuf;
+
+use game_loop::winit::window::Window;
+use log::warn;
+use souvlaki::{MediaControls, MediaMetadata, MediaPlayback, PlatformConfig};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NowPlaying {
+    pub title: String,
+    pub artist: String,
+    pub jacket: Option<PathBuf>,
+}
+
+pub struct MediaSession {
+    controls: Option<MediaControls>,
+    current: Option<NowPlaying>,
+}
+
+impl MediaSession {
+    pub fn new(window: &Window) -> Self {
+        #[cfg(target_os = "windows")]
+        let hwnd = {
+            use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+            match window.raw_window_handle() {
+                RawWindowHandle::Win32(handle) => Some(handle.hwnd),
+                _ => None,
+            }
+        };
+
+        #[cfg(not(target_os = "windows"))]
+        let hwnd = {
+            _ = window;
+            None
+        };
+
+        let controls = MediaControls::new(PlatformConfig {
+            dbus_name: "usc_game",
+            display_name: "USC",
+            hwnd,
+        })
+        .and_then(|mut controls| controls.attach(|_| {}).map(|_| controls));
+
+        let controls = match controls {
+            Ok(controls) => Some(controls),
+            Err(e) => {
+                warn!("Failed to create media session: {:?}", e);
+                None
+            }
+        };
+
+        Self {
+            controls,
+            current: None,
+        }
+    }
+
+    pub fn update(&mut self, now_playing: Option<NowPlaying>) {
+        if self.current == now_playing {
+            return;
+        }
+
+        let Some(controls) = self.controls.as_mut() else {
+            return;
+        };
+
+        let result = match &now_playing {
+            Some(NowPlaying {
+                title,
+                artist,
+                jacket,
+            }) => {
+                let cover_url = jacket
+                    .as_ref()
+                    .and_then(|x| x.canonicalize().ok())
+                    .map(|x| format!("file://{}", x.display()));
+
+                controls
+                    .set_metadata(MediaMetadata {
+                        title: Some(title),
+                        artist: Some(artist),
+                        cover_url: cover_url.as_deref(),
+                        ..Default::default()
+                    })
+                    .and_then(|_| controls.set_playback(MediaPlayback::Playing { progress: None }))
+            }
+            None => controls.set_playback(MediaPlayback::Stopped),
+        };
+
+        if let Err(e) = result {
+            warn!("Failed to update media session: {:?}", e);
+        }
+
+        self.current = now_playing;
+    }
+}
@@ -0,0 +1,132 @@
+use std::{collections::VecDeque, time::Duration};
+
+use egui::{Align2, Color32};
+use egui_plot::{Legend, Line, Plot, PlotPoints};
+
+/// Number of frames kept for the graph
+const HISTORY: usize = 240;
+
+/// Rolling samples of a single timing, in milliseconds
+#[derive(Default)]
+struct Samples(VecDeque<f64>);
+
+impl Samples {
+    fn push(&mut self, ms: f64) {
+        if self.0.len() == HISTORY {
+            self.0.pop_front();
+        }
+        self.0.push_back(ms);
+    }
+
+    fn last(&self) -> f64 {
+        self.0.back().copied().unwrap_or_default()
+    }
+
+    fn max(&self) -> f64 {
+        self.0.iter().copied().fold(0.0, f64::max)
+    }
+
+    fn line(&self, name: &str, color: Color32) -> Line {
+        let points: PlotPoints = self
+            .0
+            .iter()
+            .enumerate()
+            .map(|(x, y)| [x as f64, *y])
+            .collect();
+        Line::new(points).name(name).color(color)
+    }
+}
+
+/// Compact performance overlay, toggled with the configured perf HUD key.
+#[derive(Default)]
+pub struct PerfHud {
+    pub visible: bool,
+    frame: Samples,
+    update: Samples,
+    render: Samples,
+    lua_gc: Samples,
+    /// Update time accumulated since the last rendered frame
+    pending_update: Duration,
+    lua_memory: usize,
+}
+
+impl PerfHud {
+    pub fn record_update(&mut self, duration: Duration) {
+        self.pending_update += duration;
+    }
+
+    /// `frame_ms` is the time since the last frame, `render` the time spent rendering it.
+    pub fn record_frame(&mut self, frame_ms: f64, render: Duration, lua_gc: Duration) {
+        self.frame.push(frame_ms);
+        self.render.push(render.as_secs_f64() * 1000.0);
+        self.lua_gc.push(lua_gc.as_secs_f64() * 1000.0);
+        self.update
+            .push(std::mem::take(&mut self.pending_update).as_secs_f64() * 1000.0);
+    }
+
+    pub fn set_lua_memory(&mut self, bytes: usize) {
+        self.lua_memory = bytes;
+    }
+
+    pub fn show(&self, ctx: &egui::Context) {
+        if !self.visible {
+            return;
+        }
+
+        egui::Window::new("Performance")
+            .anchor(Align2::LEFT_TOP, [5.0, 5.0])
+            .resizable(false)
+            .collapsible(false)
+            .title_bar(false)
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("perf_hud_stats").show(ui, |ui| {
+                    let frame = self.frame.last();
+                    ui.label("Frame");
+                    ui.label(format!(
+                        "{:.2} ms ({:.0} FPS), worst {:.2} ms",
+                        frame,
+                        1000.0 / frame.max(f64::EPSILON),
+                        self.frame.max()
+                    ));
+                    ui.end_row();
+
+                    ui.label("Update");
+                    ui.label(format!("{:.2} ms", self.update.last()));
+                    ui.end_row();
+
+                    ui.label("Render");
+                    ui.label(format!("{:.2} ms", self.render.last()));
+                    ui.end_row();
+
+                    ui.label("Lua GC");
+                    ui.label(format!(
+                        "{:.2} ms, worst {:.2} ms, {:.1} MiB",
+                        self.lua_gc.last(),
+                        self.lua_gc.max(),
+                        self.lua_memory as f64 / (1024.0 * 1024.0)
+                    ));
+                    ui.end_row();
+                });
+
+                Plot::new("perf_hud_graph")
+                    .width(300.0)
+                    .height(100.0)
+                    .include_y(0.0)
+                    .include_y(1000.0 / 60.0)
+                    .include_x(0.0)
+                    .include_x(HISTORY as f64)
+                    .show_axes([false, true])
+                    .allow_drag(false)
+                    .allow_zoom(false)
+                    .allow_scroll(false)
+                    .legend(Legend::default())
+                    .show(ui, |plot| {
+                        plot.line(self.frame.line("Frame", Color32::WHITE));
+                        plot.line(self.update.line("Update", Color32::LIGHT_BLUE));
+                        plot.line(self.render.line("Render", Color32::LIGHT_GREEN));
+                        plot.line(self.lua_gc.line("Lua GC", Color32::LIGHT_RED));
+                    });
+            });
+    }
+}
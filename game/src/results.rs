@@ -14,13 +14,15 @@ use serde::Serialize;
 use crate::{
     async_service::AsyncService,
     button_codes::UscButton,
-    config::GameConfig,
+    config::{AutoRetire, GameConfig, NoteMode},
     game::{
         gauge::{Gauge, GaugeType},
+        replay::Replay,
         HitRating, HitSummary, HitWindow,
     },
     game_main::AutoPlay,
     help,
+    ir::{InternetRanking, IrChart, IrScore, IrScoreOptions, IrSubmission},
     lua_service::LuaProvider,
     scene::{Scene, SceneData},
     song_provider::{DiffId, ScoreProvider, SongDiffId, SongId},
@@ -31,11 +33,12 @@ use crate::{
 use serde_with::*;
 use tealr::{
     mlu::{
-        mlua::{Function, Lua, LuaSerdeExt},
+        mlua::{Function, Lua, LuaSerdeExt, Table},
         TealData, UserData,
     },
     ToTypename,
 };
+use uuid::Uuid;
 
 #[derive(Debug, ToTypename, Clone, Serialize, Default, ToLuaLsType)]
 #[serde(rename_all = "camelCase")]
@@ -93,6 +96,11 @@ pub struct SongResultData {
     laser_hit_stats: Vec<HitStat>, // Only when isSelf is true; contains HitStat for lasers
     is_local: bool,               // Whether this score was set locally
     song_id: SongDiffId,
+    modifiers: Vec<String>, // Short labels for the modifiers used, same as Score
+    #[serde(skip)]
+    replay: Option<String>, // Encoded replay to store with the score
+    #[serde(skip)]
+    chart_hash: Option<String>,
 }
 
 #[repr(u8)]
@@ -105,6 +113,27 @@ pub enum ClearMark {
     Perfect,
 }
 
+/// Grades and the lowest score reaching them, from best to worst
+pub const GRADES: [(&str, u32); 10] = [
+    ("S", 99_00000),
+    ("AAA+", 98_00000),
+    ("AAA", 97_00000),
+    ("AA+", 95_00000),
+    ("AA", 93_00000),
+    ("A+", 90_00000),
+    ("A", 87_00000),
+    ("B", 75_00000),
+    ("C", 65_00000),
+    ("D", 0),
+];
+
+pub fn grade(score: u32) -> &'static str {
+    GRADES
+        .iter()
+        .find(|(_, min)| score >= *min)
+        .map_or("D", |(name, _)| name)
+}
+
 pub fn calculate_clear_mark(hits: HitSummary, manual: bool, gauge: &Gauge) -> ClearMark {
     if manual {
         return ClearMark::None;
@@ -141,6 +170,8 @@ impl SongResultData {
         max_combo: i32,
         duration: i32,
         manual_exit: bool,
+        retired: Option<AutoRetire>,
+        replay: Option<Replay>,
     ) -> anyhow::Result<Self> {
         use itertools::Itertools;
         use statrs::statistics::{Data, Median, Statistics};
@@ -152,8 +183,11 @@ impl SongResultData {
             effector,
             top_badge: _,
             scores,
-            hash: _,
+            hash,
             illustrator,
+            density: _,
+            radar: _,
+            information: _,
         } = song.difficulties.read().expect("Lock error")[diff_idx].clone();
 
         let Song {
@@ -164,19 +198,7 @@ impl SongResultData {
             difficulties: _,
         } = (*song).clone();
 
-        let grade = match score {
-            99_00000.. => "S",
-            98_00000.. => "AAA+",
-            97_00000.. => "AAA",
-            95_00000.. => "AA+",
-            93_00000.. => "AA",
-            90_00000.. => "A+",
-            87_00000.. => "A",
-            75_00000.. => "B",
-            65_00000.. => "C",
-            0.. => "D",
-        }
-        .to_string();
+        let grade = grade(score).to_string();
 
         let badge = calculate_clear_mark(
             HitSummary::from(hit_ratings.as_slice()),
@@ -329,11 +351,11 @@ impl SongResultData {
                 AutoPlay::Buttons => 1,
                 AutoPlay::Lasers => 2,
                 AutoPlay::All => 3,
-            },
+            } | retired.map_or(0, AutoRetire::auto_flag),
             autoplay: autoplay.any(),
             gauge_option: 0,
-            mirror: false,
-            random: false,
+            mirror: GameConfig::get().note_mode == NoteMode::Mirror,
+            random: GameConfig::get().note_mode == NoteMode::Random,
             max_combo,
             illustrator,
             duration,
@@ -348,9 +370,62 @@ impl SongResultData {
             mission: String::new(),
             retry_count: 0,
             is_self: true,
-            speed_mod_type: 0,
-            speed_mod_value: GameConfig::get().mod_speed,
+            speed_mod_type: 1,
+            speed_mod_value: GameConfig::get().play_mod_speed(),
             is_local: true,
+            modifiers: vec![],
+            chart_hash: hash,
+            replay: replay.and_then(|x| {
+                x.encode()
+                    .inspect_err(|e| warn!("Failed to encode replay: {e}"))
+                    .ok()
+            }),
+        }
+        .with_modifiers())
+    }
+
+    fn with_modifiers(mut self) -> Self {
+        self.modifiers = Score::from(&self).modifiers;
+        self
+    }
+
+    /// The score to send to the IR server, autoplay and exited plays are not submitted.
+    fn ir_submission(&self) -> Option<IrSubmission> {
+        if self.auto_flags & 3 != 0 || self.badge == ClearMark::None as u8 {
+            return None;
+        }
+        let score = Score::from(self);
+
+        Some(IrSubmission {
+            chart: IrChart {
+                chart_hash: self.chart_hash.clone()?,
+                title: self.real_title.clone(),
+                artist: self.artist.clone(),
+                effector: self.effector.clone(),
+                illustrator: self.illustrator.clone(),
+                bpm: self.bpm.clone(),
+                level: self.level,
+                difficulty: self.difficulty,
+            },
+            score: IrScore {
+                score: self.score,
+                gauge: self.gauge,
+                timestamp: score.timestamp as i64,
+                crit: self.perfects,
+                near: self.goods,
+                error: self.misses,
+                early: self.earlies,
+                late: self.lates,
+                combo: self.max_combo,
+                lamp: self.badge,
+                options: IrScoreOptions {
+                    gauge_type: self.gauge_type,
+                    gauge_opt: self.gauge_option,
+                    mirror: self.mirror,
+                    random: self.random,
+                    auto_flags: self.auto_flags,
+                },
+            },
         })
     }
 }
@@ -365,6 +440,9 @@ impl SceneData for SongResultData {
 
         Ok(Box::new(SongResult {
             score_service: services.get_required(),
+            ir: services.get_required_mut(),
+            ir_submission: None,
+            ir_version: u64::MAX,
             close: false,
             control_tx: None,
             data: *self,
@@ -435,7 +513,8 @@ pub struct Score {
     pub gauge_option: i32,
     pub mirror: bool,
     pub random: bool,
-    /// bits for autoplay settings, 0 = no autoplay
+    /// bits for autoplay settings, 0 = no autoplay. 4 and 8 are set when play continued
+    /// after the gauge ran out, see [`AutoRetire::auto_flag`]
     pub auto_flags: i32,
     pub score: i32,
     pub perfects: i32,
@@ -451,6 +530,54 @@ pub struct Score {
     pub earlies: i32,
     pub lates: i32,
     pub combo: u32,
+    /// 0 = XMOD, 1 = MMOD, 2 = CMOD
+    pub speed_mod_type: i32,
+    pub speed_mod_value: f64,
+    /// Short labels for the modifiers used, e.g. `["M400", "HARD", "MIR"]`
+    pub modifiers: Vec<String>,
+    /// Row of the score in the database, used to load its replay
+    #[serde(skip)]
+    pub id: i64,
+    /// Whether a replay of the play was stored, see [`ScoreProvider::get_replay`]
+    pub has_replay: bool,
+}
+
+impl Score {
+    pub fn with_modifiers(mut self) -> Self {
+        let mut modifiers = vec![];
+
+        match self.speed_mod_type {
+            0 => modifiers.push(format!("x{:.2}", self.speed_mod_value)),
+            1 => modifiers.push(format!("M{:.0}", self.speed_mod_value)),
+            2 => modifiers.push(format!("C{:.0}", self.speed_mod_value)),
+            _ => {}
+        }
+
+        if self.gauge_type == 1 {
+            modifiers.push("HARD".to_string());
+        }
+        if self.mirror {
+            modifiers.push("MIR".to_string());
+        }
+        if self.random {
+            modifiers.push("RAN".to_string());
+        }
+        if self.auto_flags & AutoRetire::Never.auto_flag() != 0 {
+            modifiers.push("NO-FAIL".to_string());
+        }
+        match self.auto_flags & 3 {
+            0 => {}
+            1 => modifiers.push("AUTO-BT".to_string()),
+            2 => modifiers.push("AUTO-LASER".to_string()),
+            _ => modifiers.push("AUTO".to_string()),
+        }
+        if self.hit_window.variant == HitWindow::HARD.variant {
+            modifiers.push("HARD-JUDGE".to_string());
+        }
+
+        self.modifiers = modifiers;
+        self
+    }
 }
 
 impl From<&SongResultData> for Score {
@@ -473,6 +600,8 @@ impl From<&SongResultData> for Score {
             hit_window,
             is_local,
             max_combo,
+            speed_mod_type,
+            speed_mod_value,
             ..
         } = val;
         Score {
@@ -497,7 +626,13 @@ impl From<&SongResultData> for Score {
             earlies: *earlies,
             lates: *lates,
             combo: *max_combo as _,
+            speed_mod_type: *speed_mod_type,
+            speed_mod_value: *speed_mod_value,
+            modifiers: vec![],
+            id: 0,
+            has_replay: false,
         }
+        .with_modifiers()
     }
 }
 
@@ -517,14 +652,57 @@ pub struct SongResult {
     close: bool,
     score_service: RefMut<dyn ScoreProvider>,
     screenshot_state: ScreenshotState,
+    ir: RefMut<InternetRanking>,
+    /// Id of the score sent to the IR server
+    ir_submission: Option<Uuid>,
+    /// [`InternetRanking::version`] of the IR result shown
+    ir_version: u64,
+}
+
+impl SongResult {
+    /// Shows the response of the IR server when it has changed.
+    fn update_ir(&mut self) -> anyhow::Result<()> {
+        let Some(id) = self.ir_submission else {
+            return Ok(());
+        };
+        let ir = self.ir.read().expect("Lock error");
+        if ir.version() == self.ir_version {
+            return Ok(());
+        }
+        self.ir_version = ir.version();
+        let Some(ir_result) = ir.result(id) else {
+            return Ok(());
+        };
+
+        let result: Table = self.lua.globals().get("result")?;
+        result.set("irState", ir_result.state as i32)?;
+        result.set("irDescription", ir_result.description.as_str())?;
+        result.set("irScores", self.lua.to_value(&ir_result.scores)?)?;
+        Ok(())
+    }
 }
 
 impl Scene for SongResult {
     fn init(&mut self, app_control_tx: Sender<ControlMessage>) -> anyhow::Result<()> {
+        if GameConfig::get().ir_url.is_some() {
+            if let Some(submission) = self.data.ir_submission() {
+                self.ir_submission = Some(
+                    self.ir
+                        .write()
+                        .expect("Lock error")
+                        .submit(submission, self.data.replay.clone()),
+                );
+            }
+        }
+
         self.score_service
             .write()
             .expect("Lock error")
-            .insert_score(&self.data.song_id, Score::from(&self.data))?;
+            .insert_score(
+                &self.data.song_id,
+                Score::from(&self.data),
+                self.data.replay.take(),
+            )?;
 
         self.services
             .get_required::<LuaProvider>()
@@ -533,6 +711,7 @@ impl Scene for SongResult {
         self.lua
             .globals()
             .set("result", self.lua.to_value(&self.data)?)?;
+        self.update_ir()?;
 
         if let Ok(result_set) = self.lua.globals().get::<_, Function>("result_set") {
             result_set.call::<_, ()>(())?;
@@ -542,6 +721,7 @@ impl Scene for SongResult {
     }
 
     fn render_ui(&mut self, dt: f64) -> anyhow::Result<()> {
+        crate::log_result!(self.update_ir());
         let render_fn: Function = self.lua.globals().get("render")?;
         render_fn.call(dt / 1000.0)?;
 
@@ -8,6 +8,7 @@ use three_d::{RenderTarget, Viewport};
 use crate::{
     button_codes::{LaserState, UscButton, UscInputEvent},
     companion_interface::GameState,
+    media_session::NowPlaying,
     ControlMessage,
 };
 
@@ -46,6 +47,10 @@ pub trait Scene {
     fn game_state(&self) -> GameState {
         GameState::None
     }
+    /// Song to publish to the OS media session while this is the top scene
+    fn now_playing(&self) -> Option<NowPlaying> {
+        None
+    }
 }
 
 pub trait SceneData: Send {
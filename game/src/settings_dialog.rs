@@ -1,8 +1,8 @@
 use std::{
     rc::Rc,
     sync::{
-        atomic::{AtomicI32, AtomicU32},
-        Arc,
+        atomic::{AtomicI32, AtomicU32, AtomicUsize, Ordering},
+        Arc, Mutex,
     },
 };
 
@@ -14,15 +14,20 @@ use tealr::mlu::mlua::{Function, IntoLua, Lua, LuaSerdeExt};
 use crate::{
     async_service::AsyncService,
     button_codes::{UscButton, UscInputEvent},
-    config::{GameConfig, ScoreDisplayMode},
+    config::{GameConfig, NoteMode, ScoreDisplayMode},
     game::HitWindow,
     game_main::AutoPlay,
     input_state::InputState,
     lua_service::LuaProvider,
+    results::GRADES,
     settings_screen::HitFrames,
+    song_provider::{SongFilterCriteria, CLEAR_FILTER_NAMES},
     songselect::KNOB_NAV_THRESHOLD,
 };
 
+const MAX_FILTER_LEVEL: i32 = 20;
+const MAX_FILTER_BPM: i32 = 1000;
+
 type Setter<T> = Box<dyn Fn(T) + Send>;
 type Getter<T> = Box<dyn Fn() -> T + Send>;
 
@@ -199,6 +204,104 @@ impl SettingsDialogTab {
         }
     }
 
+    /// Song select filters, a range covering every value turns that filter off
+    pub fn song_filters(criteria: Arc<Mutex<SongFilterCriteria>>, effectors: Vec<String>) -> Self {
+        let mut effector_options = vec!["Any".to_string()];
+        effector_options.extend(effectors.iter().cloned());
+        let effector_names = effectors.clone();
+
+        Self::new(
+            "Filters",
+            vec![
+                (
+                    "Min Level".into(),
+                    criteria_int(
+                        &criteria,
+                        |c| level_range(c).0,
+                        |c, x| set_level_range(c, (x, level_range(c).1.max(x))),
+                        (1, MAX_FILTER_LEVEL, 1),
+                    ),
+                ),
+                (
+                    "Max Level".into(),
+                    criteria_int(
+                        &criteria,
+                        |c| level_range(c).1,
+                        |c, x| set_level_range(c, (level_range(c).0.min(x), x)),
+                        (1, MAX_FILTER_LEVEL, 1),
+                    ),
+                ),
+                (
+                    "Min BPM".into(),
+                    criteria_int(
+                        &criteria,
+                        |c| bpm_range(c).0,
+                        |c, x| set_bpm_range(c, (x, bpm_range(c).1.max(x))),
+                        (0, MAX_FILTER_BPM, 10),
+                    ),
+                ),
+                (
+                    "Max BPM".into(),
+                    criteria_int(
+                        &criteria,
+                        |c| bpm_range(c).1,
+                        |c, x| set_bpm_range(c, (bpm_range(c).0.min(x), x)),
+                        (0, MAX_FILTER_BPM, 10),
+                    ),
+                ),
+                (
+                    "Effector".into(),
+                    SettingsDialogSetting::options(
+                        {
+                            let criteria = criteria.clone();
+                            move || {
+                                let c = criteria.lock().expect("Lock error");
+                                c.effector
+                                    .as_ref()
+                                    .and_then(|e| effectors.iter().position(|x| x == e))
+                                    .map_or(0, |i| i + 1)
+                            }
+                        },
+                        move |i| {
+                            criteria.lock().expect("Lock error").effector = i
+                                .checked_sub(1)
+                                .and_then(|i| effector_names.get(i))
+                                .cloned()
+                        },
+                        effector_options,
+                    ),
+                ),
+                (
+                    "Clear".into(),
+                    criteria_options(
+                        &criteria,
+                        |c| c.clear.map_or(0, |x| x as usize + 1),
+                        |c, i| c.clear = i.checked_sub(1).map(|x| x as u8),
+                        CLEAR_FILTER_NAMES,
+                    ),
+                ),
+                (
+                    "Grade".into(),
+                    criteria_options(
+                        &criteria,
+                        |c| {
+                            c.min_score
+                                .and_then(|s| GRADES.iter().position(|(_, min)| *min == s))
+                                .map_or(0, |i| i + 1)
+                        },
+                        |c, i| {
+                            c.min_score = i
+                                .checked_sub(1)
+                                .and_then(|i| GRADES.get(i))
+                                .map(|(_, min)| *min)
+                        },
+                        GRADES.map(|(name, _)| name),
+                    ),
+                ),
+            ],
+        )
+    }
+
     fn change_setting(&self, steps: i32) {
         let setting = &self.settings[self.current_setting].1;
 
@@ -220,6 +323,59 @@ impl SettingsDialogTab {
     }
 }
 
+fn level_range(c: &SongFilterCriteria) -> (i32, i32) {
+    c.level_range
+        .map(|(min, max)| (min as i32, max as i32))
+        .unwrap_or((1, MAX_FILTER_LEVEL))
+}
+
+fn set_level_range(c: &mut SongFilterCriteria, (min, max): (i32, i32)) {
+    c.level_range = ((min, max) != (1, MAX_FILTER_LEVEL)).then_some((min as u8, max as u8));
+}
+
+fn bpm_range(c: &SongFilterCriteria) -> (i32, i32) {
+    c.bpm_range
+        .map(|(min, max)| (min as i32, max as i32))
+        .unwrap_or((0, MAX_FILTER_BPM))
+}
+
+fn set_bpm_range(c: &mut SongFilterCriteria, (min, max): (i32, i32)) {
+    c.bpm_range = ((min, max) != (0, MAX_FILTER_BPM)).then_some((min as f64, max as f64));
+}
+
+fn criteria_options<const N: usize>(
+    criteria: &Arc<Mutex<SongFilterCriteria>>,
+    get: impl Fn(&SongFilterCriteria) -> usize + Send + 'static,
+    set: impl Fn(&mut SongFilterCriteria, usize) + Send + 'static,
+    names: [&str; N],
+) -> SettingsDialogSetting {
+    let (get_criteria, set_criteria) = (criteria.clone(), criteria.clone());
+    let mut options = vec!["Any".to_string()];
+    options.extend(names.iter().map(ToString::to_string));
+    SettingsDialogSetting::options(
+        move || get(&get_criteria.lock().expect("Lock error")),
+        move |i| set(&mut set_criteria.lock().expect("Lock error"), i),
+        options,
+    )
+}
+
+fn criteria_int(
+    criteria: &Arc<Mutex<SongFilterCriteria>>,
+    get: impl Fn(&SongFilterCriteria) -> i32 + Send + 'static,
+    set: impl Fn(&mut SongFilterCriteria, i32) + Send + 'static,
+    (min, max, step): (i32, i32, i32),
+) -> SettingsDialogSetting {
+    let (get_criteria, set_criteria) = (criteria.clone(), criteria.clone());
+    SettingsDialogSetting::int(
+        move || get(&get_criteria.lock().expect("Lock error")),
+        move |x| set(&mut set_criteria.lock().expect("Lock error"), x),
+        min,
+        max,
+        step,
+        1,
+    )
+}
+
 pub struct SettingsDialog {
     pub show: bool,
     tabs: Vec<SettingsDialogTab>,
@@ -268,6 +424,11 @@ impl SettingsDialog {
         }
     }
 
+    pub fn with_tab(mut self, tab: SettingsDialogTab) -> Self {
+        self.tabs.push(tab);
+        self
+    }
+
     pub fn on_button_press(&mut self, button: UscButton) {
         match button {
             UscButton::BT(l) => self.tabs[self.current_tab].change_setting(match l {
@@ -397,6 +558,8 @@ impl SettingsDialog {
         input_state: InputState,
         services: di::ServiceProvider,
         autoplay_tx: Sender<AutoPlay>,
+        sort_index: Arc<AtomicUsize>,
+        sorts: Vec<String>,
     ) -> Self {
         let tx = Arc::new(AtomicU32::new(0));
         let rx = tx.clone();
@@ -404,8 +567,85 @@ impl SettingsDialog {
         let itx = Arc::new(AtomicI32::new(0));
         let irx = itx.clone();
 
+        let mut quick = vec![
+            (
+                "Hi-Speed".into(),
+                SettingsDialogSetting::int(
+                    || GameConfig::get().mod_speed.round() as i32,
+                    |x| GameConfig::get_mut().mod_speed = x as f64,
+                    50,
+                    2000,
+                    5,
+                    1,
+                ),
+            ),
+            (
+                "Gauge".into(),
+                SettingsDialogSetting::options(
+                    || match GameConfig::get().start_gauge {
+                        crate::game::gauge::GaugeType::Normal => 0,
+                        crate::game::gauge::GaugeType::Hard => 1,
+                    },
+                    |x| {
+                        GameConfig::get_mut().start_gauge = match x {
+                            1 => crate::game::gauge::GaugeType::Hard,
+                            _ => crate::game::gauge::GaugeType::Normal,
+                        }
+                    },
+                    vec!["Normal".into(), "Hard".into()],
+                ),
+            ),
+            (
+                "Notes".into(),
+                SettingsDialogSetting::options(
+                    || match GameConfig::get().note_mode {
+                        NoteMode::Normal => 0,
+                        NoteMode::Mirror => 1,
+                        NoteMode::Random => 2,
+                    },
+                    |x| {
+                        GameConfig::get_mut().note_mode = match x {
+                            1 => NoteMode::Mirror,
+                            2 => NoteMode::Random,
+                            _ => NoteMode::Normal,
+                        }
+                    },
+                    vec![
+                        NoteMode::Normal.to_string(),
+                        NoteMode::Mirror.to_string(),
+                        NoteMode::Random.to_string(),
+                    ],
+                ),
+            ),
+            (
+                "Global Offset".into(),
+                SettingsDialogSetting::int(
+                    || GameConfig::get().global_offset,
+                    |x| GameConfig::get_mut().global_offset = x,
+                    -100,
+                    100,
+                    1,
+                    1,
+                ),
+            ),
+        ];
+        if !sorts.is_empty() {
+            quick.push((
+                "Sort".into(),
+                SettingsDialogSetting::options(
+                    {
+                        let sort_index = sort_index.clone();
+                        move || sort_index.load(Ordering::Relaxed)
+                    },
+                    move |x| sort_index.store(x, Ordering::Relaxed),
+                    sorts,
+                ),
+            ));
+        }
+
         Self::new(
             vec![
+                SettingsDialogTab::new("Quick", quick),
                 SettingsDialogTab::new(
                     "Offsets",
                     vec![
@@ -447,22 +687,6 @@ impl SettingsDialog {
                 SettingsDialogTab::new(
                     "Game",
                     vec![
-                        (
-                            "Gauge".into(),
-                            SettingsDialogSetting::options(
-                                || match GameConfig::get().start_gauge {
-                                    crate::game::gauge::GaugeType::Normal => 0,
-                                    crate::game::gauge::GaugeType::Hard => 1,
-                                },
-                                |x| {
-                                    GameConfig::get_mut().start_gauge = match x {
-                                        1 => crate::game::gauge::GaugeType::Hard,
-                                        _ => crate::game::gauge::GaugeType::Normal,
-                                    }
-                                },
-                                vec!["Normal".into(), "Hard".into()],
-                            ),
-                        ),
                         (
                             "Backup Gauge".into(),
                             SettingsDialogSetting::bool(
@@ -37,6 +37,11 @@ impl BindingUi {
         }
     }
 
+    /// Waiting for a controller input to bind
+    pub fn is_binding(&self) -> bool {
+        self.currently_binding != ActiveBinding::None
+    }
+
     pub fn run_checks(&mut self, settings: &mut GameConfig) {
         let lock_gilrs = self.input_state.lock_gilrs();
         let gamepad = lock_gilrs.gamepad(self.controller);
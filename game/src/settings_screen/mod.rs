@@ -1,12 +1,19 @@
 mod controller_binding;
+pub mod setup_wizard;
 pub mod skin_select;
 
-use std::{collections::HashMap, path::PathBuf, sync::mpsc::Sender, time::Duration};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::mpsc::{channel, Receiver, Sender},
+    time::Duration,
+};
 
 use di::ServiceProvider;
 use egui::{CollapsingResponse, InnerResponse, RichText, Separator, Slider, TextEdit, Ui};
 use gilrs::GamepadId;
 use itertools::Itertools;
+use kson::{BtLane, Side};
 use skin_select::SkinMeta;
 use winit::{
     dpi::{PhysicalPosition, PhysicalSize},
@@ -14,13 +21,17 @@ use winit::{
 };
 
 use crate::{
-    config::{Fullscreen, GameConfig, ScoreDisplayMode, ScoreScreenshot},
-    game::HitWindow,
+    audio_output,
+    button_codes::UscButton,
+    companion_interface::{CompanionPermission, CompanionServer},
+    config::{AutoRetire, FramePacing, Fullscreen, GameConfig, ScoreDisplayMode, ScoreScreenshot},
+    game::{FadeCurve, HitFeedback, HitWindow},
     game_main::ControlMessage,
     help::AsyncPicker,
     input_state::InputState,
     scene::Scene,
     skin_settings::SkinSettingValue,
+    test_scenes::latency_test::{LatencyTest, SuggestedOffset},
 };
 
 use self::controller_binding::BindingUi;
@@ -36,6 +47,13 @@ pub struct SettingsScreen {
     primary_monitor: Option<MonitorHandle>,
     tx: Sender<ControlMessage>,
     skins: Vec<(SkinMeta, PathBuf)>,
+    services: ServiceProvider,
+    suspended: bool,
+    offset_tx: Sender<SuggestedOffset>,
+    offset_rx: Receiver<SuggestedOffset>,
+    audio_hosts: Vec<&'static str>,
+    audio_devices: Vec<String>,
+    buffer_latency: Option<Duration>,
 }
 
 impl SettingsScreen {
@@ -56,32 +74,11 @@ impl SettingsScreen {
         let monitors = window.available_monitors().collect_vec();
         let primary_monitor = window.current_monitor();
 
-        let mut skins_folder = crate::default_game_dir();
-        skins_folder.push("skins");
-        let skins = skins_folder
-            .read_dir()
-            .ok()
-            .into_iter()
-            .flatten()
-            .filter_map(|x| x.ok())
-            .filter(|x| x.file_type().is_ok_and(|x| x.is_dir()))
-            .map(|x| x.path())
-            .map(|mut p| {
-                p.push("meta.json");
-                if let Ok(Ok(m)) = std::fs::File::open(&p).map(serde_json::from_reader) {
-                    p.pop();
-                    (m, p)
-                } else {
-                    p.pop();
-                    (
-                        SkinMeta::named(p.file_name().and_then(|x| x.to_str()).unwrap_or("unk")),
-                        p,
-                    )
-                }
-            })
-            .collect();
+        let skins = skin_select::installed_skins();
 
-        Self {
+        let (offset_tx, offset_rx) = channel();
+
+        let mut screen = Self {
             altered_settings: GameConfig::get().clone(),
             close: false,
             input_state,
@@ -92,12 +89,262 @@ impl SettingsScreen {
             primary_monitor,
             tx,
             skins,
+            services,
+            suspended: false,
+            offset_tx,
+            offset_rx,
+            audio_hosts: audio_output::host_names(),
+            audio_devices: vec![],
+            buffer_latency: None,
+        };
+        screen.refresh_audio_devices();
+        screen
+    }
+
+    fn refresh_audio_devices(&mut self) {
+        let host = self.altered_settings.audio_host.as_deref();
+        self.audio_devices = audio_output::device_names(host);
+        self.buffer_latency =
+            audio_output::buffer_latency(host, self.altered_settings.audio_device.as_deref());
+    }
+
+    fn audio_output_ui(&mut self, ui: &mut Ui) {
+        let mut changed = false;
+        let settings = &mut self.altered_settings;
+
+        egui::ComboBox::from_label("Audio host")
+            .selected_text(settings.audio_host.as_deref().unwrap_or("Default"))
+            .show_ui(ui, |ui| {
+                changed |= ui
+                    .selectable_value(&mut settings.audio_host, None, "Default")
+                    .changed();
+                for host in &self.audio_hosts {
+                    changed |= ui
+                        .selectable_value(&mut settings.audio_host, Some(host.to_string()), *host)
+                        .changed();
+                }
+            });
+
+        if changed {
+            settings.audio_device = None;
+        }
+
+        egui::ComboBox::from_label("Audio device")
+            .selected_text(settings.audio_device.as_deref().unwrap_or("Default"))
+            .show_ui(ui, |ui| {
+                changed |= ui
+                    .selectable_value(&mut settings.audio_device, None, "Default")
+                    .changed();
+                for device in &self.audio_devices {
+                    changed |= ui
+                        .selectable_value(&mut settings.audio_device, Some(device.clone()), device)
+                        .changed();
+                }
+            });
+        ui.end_row();
+
+        match self.buffer_latency {
+            Some(latency) => ui.label(format!(
+                "Output buffer latency: {:.1} ms",
+                latency.as_secs_f64() * 1000.0
+            )),
+            None => ui.label("Output buffer latency: Unknown"),
+        };
+        ui.label("Audio output changes apply after a restart, use the latency test to measure the full latency");
+        ui.end_row();
+
+        let settings = &mut self.altered_settings;
+        if !settings.device_offsets.is_empty() {
+            ui.label("Global offset of each output device, switched when the game starts with it");
+            ui.end_row();
+            let mut removed = None;
+            egui::Grid::new("device_offsets")
+                .num_columns(3)
+                .show(ui, |ui| {
+                    let mut devices: Vec<_> = settings.device_offsets.iter_mut().collect();
+                    devices.sort_by(|a, b| a.0.cmp(b.0));
+                    for (device, offset) in devices {
+                        ui.label(device.as_str());
+                        if settings.output_device.as_ref() == Some(device) {
+                            ui.label(format!("{} ms", settings.global_offset));
+                            ui.label("In use");
+                        } else {
+                            ui.add(egui::DragValue::new(offset).suffix(" ms"));
+                            if ui.button("Remove").clicked() {
+                                removed = Some(device.clone());
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+            if let Some(device) = removed {
+                settings.device_offsets.remove(&device);
+            }
+        }
+
+        if changed {
+            self.refresh_audio_devices();
+        }
+    }
+
+    fn hit_feedback_ui(&mut self, ui: &mut Ui) {
+        let feedback = &mut self.altered_settings.hit_feedback;
+
+        ui.label("Hit rating display (ms, 0 hides)");
+        ui.end_row();
+        egui::Grid::new("hit_feedback")
+            .num_columns(4)
+            .show(ui, |ui| {
+                ui.label("Crit");
+                ui.label("Near");
+                ui.label("Miss");
+                ui.label("Fade");
+                ui.end_row();
+
+                for duration in [
+                    &mut feedback.crit,
+                    &mut feedback.near,
+                    &mut feedback.miss,
+                    &mut feedback.fade,
+                ] {
+                    let mut ms = duration.as_millis() as u64;
+                    if ui
+                        .add(egui::DragValue::new(&mut ms).clamp_range(0..=2000))
+                        .changed()
+                    {
+                        *duration = Duration::from_millis(ms);
+                    }
+                }
+            });
+        ui.end_row();
+
+        egui::ComboBox::new("hit_feedback_fade", "Fade curve")
+            .selected_text(feedback.fade_curve.to_string())
+            .show_ui(ui, |ui| {
+                for curve in [FadeCurve::None, FadeCurve::Linear, FadeCurve::EaseIn] {
+                    ui.selectable_value(&mut feedback.fade_curve, curve, curve.to_string());
+                }
+            });
+        ui.end_row();
+
+        ui.horizontal(|ui| {
+            for (name, preset) in HitFeedback::PRESETS {
+                if ui.button(format!("Set {name}")).clicked() {
+                    *feedback = preset;
+                }
+            }
+        });
+        ui.end_row();
+    }
+
+    /// Debounce time for all buttons with per button overrides, next to the presses ignored so far.
+    fn debounce_ui(&mut self, ui: &mut Ui) {
+        ui.label("Debounce (ms)")
+            .on_hover_text("Ignores presses right after a release, for switches that double press");
+        ui.add(egui::DragValue::new(&mut self.altered_settings.debounce_ms).clamp_range(0..=100));
+        ui.end_row();
+
+        egui::Grid::new("debounce_overrides")
+            .num_columns(3)
+            .show(ui, |ui| {
+                ui.label("Button");
+                ui.label("Override");
+                ui.label("Ignored presses");
+                ui.end_row();
+
+                for button in [
+                    UscButton::BT(BtLane::A),
+                    UscButton::BT(BtLane::B),
+                    UscButton::BT(BtLane::C),
+                    UscButton::BT(BtLane::D),
+                    UscButton::FX(Side::Left),
+                    UscButton::FX(Side::Right),
+                    UscButton::Start,
+                ] {
+                    ui.label(button.as_str());
+
+                    let key = gilrs::Button::from(button);
+                    let overrides = &mut self.altered_settings.debounce_overrides;
+                    let default = self.altered_settings.debounce_ms;
+                    let mut ms = overrides.get(&key).copied();
+                    ui.horizontal(|ui| {
+                        let mut enabled = ms.is_some();
+                        if ui.checkbox(&mut enabled, "").changed() {
+                            ms = enabled.then_some(default);
+                        }
+                        if let Some(ms) = ms.as_mut() {
+                            ui.add(egui::DragValue::new(ms).clamp_range(0..=100));
+                        }
+                    });
+                    match ms {
+                        Some(ms) => overrides.insert(key, ms),
+                        None => overrides.remove(&key),
+                    };
+
+                    ui.label(self.input_state.debounced(button).to_string());
+                    ui.end_row();
+                }
+            });
+    }
+
+    /// Paired devices are changed in the current config right away, as devices can pair
+    /// while the settings are open.
+    fn companion_ui(&mut self, ui: &mut Ui) {
+        let server: di::RefMut<CompanionServer> = self.services.get_required();
+        let server = server.read().expect("Lock error");
+
+        if let Some(pin) = server.pairing_pin() {
+            ui.label(RichText::new(format!("Pairing PIN: {pin}")).heading());
+            if ui.button("Cancel pairing").clicked() {
+                server.stop_pairing();
+            }
+        } else if ui.button("Pair new device").clicked() {
+            server.start_pairing();
+        }
+        ui.end_row();
+
+        let mut config = GameConfig::get_mut();
+        let mut remove = None;
+        egui::Grid::new("companion_devices")
+            .num_columns(4)
+            .show(ui, |ui| {
+                for (i, device) in config.companion_devices.iter_mut().enumerate() {
+                    ui.label(&device.name);
+                    if server.is_connected(&device.token) {
+                        ui.label("Connected");
+                    } else {
+                        ui.label("");
+                    }
+                    egui::ComboBox::new(("companion_permission", i), "")
+                        .selected_text(device.permission.to_string())
+                        .show_ui(ui, |ui| {
+                            for permission in
+                                [CompanionPermission::ViewOnly, CompanionPermission::Control]
+                            {
+                                ui.selectable_value(
+                                    &mut device.permission,
+                                    permission,
+                                    permission.to_string(),
+                                );
+                            }
+                        });
+                    if ui.button("Remove").clicked() {
+                        remove = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+
+        if let Some(i) = remove {
+            config.companion_devices.remove(i);
         }
     }
 
     fn apply(&self) {
         let mut c = GameConfig::get_mut();
+        let companion_devices = std::mem::take(&mut c.companion_devices);
         *c = self.altered_settings.clone();
+        c.companion_devices = companion_devices;
         _ = self.tx.send(ControlMessage::ApplySettings);
     }
 }
@@ -121,7 +368,15 @@ impl Scene for SettingsScreen {
     }
 
     fn is_suspended(&self) -> bool {
-        false
+        self.suspended
+    }
+
+    fn suspend(&mut self) {
+        self.suspended = true;
+    }
+
+    fn resume(&mut self) {
+        self.suspended = false;
     }
 
     fn debug_ui(&mut self, _ctx: &egui::Context) -> anyhow::Result<()> {
@@ -136,6 +391,13 @@ impl Scene for SettingsScreen {
         "Settings"
     }
 
+    fn on_button_pressed(&mut self, button: UscButton, _timestamp: std::time::SystemTime) {
+        let binding = self.binding_ui.as_ref().is_some_and(|x| x.is_binding());
+        if button == UscButton::Back && !binding {
+            self.close = true;
+        }
+    }
+
     fn tick(
         &mut self,
         _dt: f64,
@@ -153,6 +415,13 @@ impl Scene for SettingsScreen {
     }
 
     fn render_egui(&mut self, ctx: &egui::Context) -> anyhow::Result<()> {
+        while let Ok(offset) = self.offset_rx.try_recv() {
+            match offset {
+                SuggestedOffset::Global(x) => self.altered_settings.global_offset = x,
+                SuggestedOffset::Button(x) => self.altered_settings.button_offset = x,
+            }
+        }
+
         egui::panel::TopBottomPanel::bottom("settings_buttons").show(ctx, |ui| {
             if ui.button("Cancel").clicked() {
                 self.close = true;
@@ -168,10 +437,20 @@ impl Scene for SettingsScreen {
             egui::ScrollArea::vertical().show(ui, |ui| {
                 settings_section("Input", ui, |ui| {
                     ui.label("Offset");
-                    ui.add(Slider::new(
-                        &mut self.altered_settings.global_offset,
-                        -100..=100,
-                    ));
+                    ui.horizontal(|ui| {
+                        ui.add(Slider::new(
+                            &mut self.altered_settings.global_offset,
+                            -200..=200,
+                        ));
+                        if ui.button("Latency test").clicked() {
+                            _ = self.tx.send(ControlMessage::TransitionComplete(Box::new(
+                                LatencyTest::new(
+                                    self.services.create_scope(),
+                                    self.offset_tx.clone(),
+                                ),
+                            )));
+                        }
+                    });
                     ui.end_row();
                     ui.checkbox(
                         &mut self.altered_settings.keyboard_buttons,
@@ -215,6 +494,8 @@ impl Scene for SettingsScreen {
                     if let Some(binding_ui) = self.binding_ui.as_mut() {
                         binding_ui.ui(ui, &mut self.altered_settings);
                     }
+                    ui.end_row();
+                    self.debounce_ui(ui);
                 });
 
                 settings_section("Game", ui, |ui| {
@@ -274,6 +555,21 @@ impl Scene for SettingsScreen {
                         self.altered_settings.hit_window = HitWindow::HARD;
                     }
 
+                    ui.end_row();
+                    self.hit_feedback_ui(ui);
+                    ui.end_row();
+
+                    ui.checkbox(&mut self.altered_settings.clean_hud, "Clean HUD")
+                        .on_hover_text(
+                            "Hides score, gauge and hit ratings for recording, F6 toggles it in game",
+                        );
+                    ui.add_enabled(
+                        self.altered_settings.clean_hud,
+                        egui::Checkbox::new(
+                            &mut self.altered_settings.clean_hud_progress,
+                            "Keep progress bar",
+                        ),
+                    );
                     ui.end_row();
 
                     let mut songs_path = self
@@ -283,13 +579,33 @@ impl Scene for SettingsScreen {
                         .unwrap_or("")
                         .to_string();
 
-                    ui.label("Songs path");
+                    ui.label("Songs path").on_hover_text(
+                        "Chart packs in .zip archives are read without extracting them, other archive formats like .rar have to be extracted",
+                    );
                     AsyncPicker::new()
                         .folder()
                         .show("song_folder".into(), &mut songs_path, ui);
 
                     self.altered_settings.songs_path = PathBuf::from(songs_path);
 
+                    ui.end_row();
+
+                    let mut database_folder = self
+                        .altered_settings
+                        .database_folder
+                        .to_str()
+                        .unwrap_or("")
+                        .to_string();
+
+                    ui.label("Database folder").on_hover_text(
+                        "The current database is copied to the new folder on the next start",
+                    );
+                    AsyncPicker::new()
+                        .folder()
+                        .show("database_folder".into(), &mut database_folder, ui);
+
+                    self.altered_settings.database_folder = PathBuf::from(database_folder);
+
                     ui.end_row();
                     egui::ComboBox::new("score_display_mode", "Score display mode")
                         .selected_text(self.altered_settings.score_display.to_string())
@@ -313,6 +629,23 @@ impl Scene for SettingsScreen {
 
                     ui.end_row();
 
+                    egui::ComboBox::new("auto_retire", "Fail on empty hard gauge")
+                        .selected_text(self.altered_settings.auto_retire.to_string())
+                        .show_ui(ui, |ui| {
+                            for behavior in [
+                                AutoRetire::Instant,
+                                AutoRetire::EndOfSong,
+                                AutoRetire::Never,
+                            ] {
+                                ui.selectable_value(
+                                    &mut self.altered_settings.auto_retire,
+                                    behavior,
+                                    behavior.to_string(),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
                     egui::ComboBox::new("auto_screenshot_score", "Score screenshot")
                         .selected_text(self.altered_settings.score_screenshots.to_string())
                         .show_ui(ui, |ui| {
@@ -356,8 +689,18 @@ impl Scene for SettingsScreen {
                     ui.end_row();
                     ui.checkbox(&mut self.altered_settings.graphics.show_fps, "Show FPS");
                     ui.end_row();
+                    let frame_pacing = &mut self.altered_settings.graphics.frame_pacing;
+                    egui::ComboBox::new("frame_pacing", "Frame pacing")
+                        .selected_text(frame_pacing.to_string())
+                        .show_ui(ui, |ui| {
+                            for pacing in [FramePacing::Auto, FramePacing::Manual] {
+                                ui.selectable_value(frame_pacing, pacing, pacing.to_string());
+                            }
+                        });
+                    ui.end_row();
                     ui.label("Target FPS");
-                    ui.add(
+                    ui.add_enabled(
+                        self.altered_settings.graphics.frame_pacing == FramePacing::Manual,
                         egui::DragValue::new(&mut self.altered_settings.graphics.target_fps)
                             .clamp_range(0..=1000),
                     );
@@ -533,35 +876,86 @@ impl Scene for SettingsScreen {
                         Slider::new(&mut self.altered_settings.slam_volume, 0.0..=1.0)
                             .custom_formatter(|x, _| format!("{:.0}%", x * 100.0))
                             .custom_parser(|x| x.trim_matches('%').trim().parse().ok()),
+                    );
+
+                    ui.label("Hit sound volume");
+                    ui.add(
+                        Slider::new(&mut self.altered_settings.chip_hit_volume, 0.0..=1.0)
+                            .custom_formatter(|x, _| format!("{:.0}%", x * 100.0))
+                            .custom_parser(|x| x.trim_matches('%').trim().parse().ok()),
+                    );
+
+                    ui.label("Miss sound volume");
+                    ui.add(
+                        Slider::new(&mut self.altered_settings.miss_volume, 0.0..=1.0)
+                            .custom_formatter(|x, _| format!("{:.0}%", x * 100.0))
+                            .custom_parser(|x| x.trim_matches('%').trim().parse().ok()),
+                    );
+                    ui.end_row();
+
+                    ui.checkbox(
+                        &mut self.altered_settings.media_session,
+                        "Show now playing in OS media controls",
+                    );
+                    ui.end_row();
+
+                    ui.checkbox(
+                        &mut self.altered_settings.normalize_previews,
+                        "Level preview loudness",
+                    );
+                    ui.add_enabled(
+                        self.altered_settings.normalize_previews,
+                        egui::DragValue::new(&mut self.altered_settings.preview_loudness_target)
+                            .clamp_range(-30.0..=-5.0)
+                            .speed(0.1)
+                            .suffix(" LUFS"),
+                    );
+                    ui.end_row();
+
+                    ui.label("Menu volume during previews").on_hover_text(
+                        "Menu sounds and music are turned down while a song preview plays",
+                    );
+                    ui.add(
+                        Slider::new(&mut self.altered_settings.preview_duck_volume, 0.0..=1.0)
+                            .custom_formatter(|x, _| format!("{:.0}%", x * 100.0))
+                            .custom_parser(|x| x.trim_matches('%').trim().parse().ok()),
+                    );
+                    ui.end_row();
+
+                    ui.checkbox(
+                        &mut self.altered_settings.prerendered_fx_audio,
+                        "Use pre-rendered FX tracks",
                     )
+                    .on_hover_text("Some older charts come with an FX track that plays in place of live effects");
+                    ui.end_row();
+
+                    self.audio_output_ui(ui);
                 });
 
-                settings_section("Skin", ui, |ui| {
-                    let current_skin = self
-                        .skins
-                        .iter()
-                        .find(|x| x.1.ends_with(&self.altered_settings.skin))
-                        .map(|x| x.0.name.clone())
-                        .unwrap_or_default();
+                settings_section("Companion", ui, |ui| {
+                    self.companion_ui(ui);
+                });
 
-                    egui::ComboBox::new("skin_select", "Selected skin")
-                        .selected_text(&current_skin)
-                        .show_ui(ui, |ui| {
-                            for (meta, path) in self.skins.iter() {
-                                if ui
-                                    .selectable_label(path.ends_with(&current_skin), &meta.name)
-                                    .clicked()
-                                {
-                                    if let Some(v) = path
-                                        .file_name()
-                                        .and_then(|x| x.to_str())
-                                        .map(|x| x.to_string())
-                                    {
-                                        self.altered_settings.skin = v;
-                                    }
-                                }
-                            }
-                        });
+                settings_section("Internet Ranking", ui, |ui| {
+                    let mut ir_url = self.altered_settings.ir_url.clone().unwrap_or_default();
+                    ui.label("Server URL");
+                    ui.text_edit_singleline(&mut ir_url);
+                    let ir_url = ir_url.trim();
+                    self.altered_settings.ir_url = (!ir_url.is_empty()).then(|| ir_url.to_string());
+                    ui.end_row();
+
+                    ui.label("Token");
+                    ui.add(TextEdit::singleline(&mut self.altered_settings.ir_token).password(true))
+                        .on_hover_text("Identifies you to the server");
+                    ui.end_row();
+                });
+
+                settings_section("Skin", ui, |ui| {
+                    skin_select::skin_combo_box(
+                        ui,
+                        &self.skins,
+                        &mut self.altered_settings.skin,
+                    );
 
                     ui.end_row();
                     ui.separator();
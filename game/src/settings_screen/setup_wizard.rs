@@ -0,0 +1,322 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::mpsc::{channel, Receiver, Sender},
+};
+
+use di::ServiceProvider;
+use egui::{RichText, Slider};
+use gilrs::GamepadId;
+
+use crate::{
+    button_codes::UscButton,
+    config::GameConfig,
+    game_main::ControlMessage,
+    help::AsyncPicker,
+    input_state::InputState,
+    scene::Scene,
+    test_scenes::latency_test::{LatencyTest, SuggestedOffset},
+};
+
+use super::{
+    controller_binding::BindingUi,
+    skin_select::{self, SkinMeta},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Step {
+    SongsFolder,
+    Input,
+    Offset,
+    Skin,
+}
+
+impl Step {
+    const ALL: [Step; 4] = [Step::SongsFolder, Step::Input, Step::Offset, Step::Skin];
+
+    fn title(&self) -> &'static str {
+        match self {
+            Step::SongsFolder => "Songs folder",
+            Step::Input => "Input",
+            Step::Offset => "Offset",
+            Step::Skin => "Skin",
+        }
+    }
+
+    fn next(self) -> Option<Self> {
+        Self::ALL.get(self as usize + 1).copied()
+    }
+
+    fn prev(self) -> Option<Self> {
+        Self::ALL.get((self as usize).checked_sub(1)?).copied()
+    }
+}
+
+/// Walks new players through the settings needed to start playing, the settings are only
+/// written to the config once the wizard is finished.
+pub struct SetupWizard {
+    altered_settings: GameConfig,
+    step: Step,
+    close: bool,
+    suspended: bool,
+    tx: Option<Sender<ControlMessage>>,
+    services: ServiceProvider,
+    input_state: InputState,
+    controllers: HashMap<GamepadId, String>,
+    selected_controller: Option<GamepadId>,
+    binding_ui: Option<BindingUi>,
+    skins: Vec<(SkinMeta, PathBuf)>,
+    offset_tx: Sender<SuggestedOffset>,
+    offset_rx: Receiver<SuggestedOffset>,
+}
+
+impl SetupWizard {
+    pub fn new(services: ServiceProvider) -> Self {
+        let input_state = InputState::clone(&services.get_required());
+        let controllers = {
+            let lock_gilrs = input_state.lock_gilrs();
+            lock_gilrs
+                .gamepads()
+                .map(|(id, pad)| (id, pad.name().to_string()))
+                .collect()
+        };
+        let (offset_tx, offset_rx) = channel();
+
+        Self {
+            altered_settings: GameConfig::get().clone(),
+            step: Step::SongsFolder,
+            close: false,
+            suspended: false,
+            tx: None,
+            services,
+            input_state,
+            controllers,
+            selected_controller: None,
+            binding_ui: None,
+            skins: skin_select::installed_skins(),
+            offset_tx,
+            offset_rx,
+        }
+    }
+
+    fn finish(&mut self) {
+        {
+            let mut config = GameConfig::get_mut();
+            let companion_devices = std::mem::take(&mut config.companion_devices);
+            *config = self.altered_settings.clone();
+            config.companion_devices = companion_devices;
+            config.first_run = false;
+            config.save();
+        }
+        if let Some(tx) = &self.tx {
+            _ = tx.send(ControlMessage::ApplySettings);
+        }
+        self.close = true;
+    }
+
+    fn songs_folder_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Pick the folder your charts are in, each song should be in its own folder.");
+        let mut songs_path = self
+            .altered_settings
+            .songs_path
+            .to_str()
+            .unwrap_or("")
+            .to_string();
+
+        ui.horizontal(|ui| {
+            ui.label("Songs path");
+            AsyncPicker::new()
+                .folder()
+                .show("song_folder".into(), &mut songs_path, ui);
+        });
+
+        self.altered_settings.songs_path = PathBuf::from(songs_path);
+    }
+
+    fn input_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Play with the keyboard, or pick a controller and bind its buttons and knobs.");
+        ui.checkbox(
+            &mut self.altered_settings.keyboard_buttons,
+            "Keyboard buttons",
+        );
+        ui.checkbox(&mut self.altered_settings.keyboard_knobs, "Keyboard knobs");
+        ui.checkbox(&mut self.altered_settings.mouse_knobs, "Mouse knobs");
+
+        egui::ComboBox::from_label("Controller")
+            .selected_text(
+                self.selected_controller
+                    .and_then(|id| self.controllers.get(&id))
+                    .map(String::as_str)
+                    .unwrap_or("None"),
+            )
+            .show_ui(ui, |ui| {
+                if ui
+                    .selectable_value(&mut self.selected_controller, None, "None")
+                    .clicked()
+                {
+                    self.binding_ui = None;
+                }
+
+                for (id, name) in self.controllers.iter() {
+                    if ui
+                        .selectable_value(&mut self.selected_controller, Some(*id), name)
+                        .clicked()
+                    {
+                        self.binding_ui = Some(BindingUi::new(*id, self.input_state.clone()));
+                    }
+                }
+            });
+
+        if let Some(binding_ui) = self.binding_ui.as_mut() {
+            ui.horizontal_wrapped(|ui| binding_ui.ui(ui, &mut self.altered_settings));
+        }
+    }
+
+    fn offset_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Run the latency test and tap along to the beat to find your offset.");
+        ui.horizontal(|ui| {
+            ui.label("Offset");
+            ui.add(Slider::new(
+                &mut self.altered_settings.global_offset,
+                -200..=200,
+            ));
+            if ui.button("Latency test").clicked() {
+                if let Some(tx) = &self.tx {
+                    _ = tx.send(ControlMessage::TransitionComplete(Box::new(
+                        LatencyTest::new(self.services.create_scope(), self.offset_tx.clone()),
+                    )));
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Button offset");
+            ui.add(Slider::new(
+                &mut self.altered_settings.button_offset,
+                -200..=200,
+            ));
+        });
+    }
+
+    fn skin_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Pick a skin, more skins can be added to the skins folder later.");
+        skin_select::skin_combo_box(ui, &self.skins, &mut self.altered_settings.skin);
+    }
+}
+
+impl Scene for SetupWizard {
+    fn init(&mut self, app_control_tx: Sender<ControlMessage>) -> anyhow::Result<()> {
+        self.tx = Some(app_control_tx);
+        Ok(())
+    }
+
+    fn render_ui(&mut self, _dt: f64) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn is_suspended(&self) -> bool {
+        self.suspended
+    }
+
+    fn suspend(&mut self) {
+        self.suspended = true;
+    }
+
+    fn resume(&mut self) {
+        self.suspended = false;
+    }
+
+    fn debug_ui(&mut self, _ctx: &egui::Context) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn closed(&self) -> bool {
+        self.close
+    }
+
+    fn name(&self) -> &str {
+        "Setup"
+    }
+
+    fn on_button_pressed(&mut self, button: UscButton, _timestamp: std::time::SystemTime) {
+        let binding = self.binding_ui.as_ref().is_some_and(|x| x.is_binding());
+        if button == UscButton::Back && !binding {
+            if let Some(prev) = self.step.prev() {
+                self.step = prev;
+            }
+        }
+    }
+
+    fn tick(
+        &mut self,
+        _dt: f64,
+        _knob_state: crate::button_codes::LaserState,
+    ) -> anyhow::Result<()> {
+        if let Some(binding_ui) = self.binding_ui.as_mut() {
+            binding_ui.run_checks(&mut self.altered_settings)
+        }
+
+        Ok(())
+    }
+
+    fn has_egui(&self) -> bool {
+        true
+    }
+
+    fn render_egui(&mut self, ctx: &egui::Context) -> anyhow::Result<()> {
+        while let Ok(offset) = self.offset_rx.try_recv() {
+            match offset {
+                SuggestedOffset::Global(x) => self.altered_settings.global_offset = x,
+                SuggestedOffset::Button(x) => self.altered_settings.button_offset = x,
+            }
+        }
+
+        egui::panel::TopBottomPanel::bottom("setup_buttons").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Skip setup").clicked() {
+                    self.close = true;
+                }
+                if let Some(prev) = self.step.prev() {
+                    if ui.button("Back").clicked() {
+                        self.step = prev;
+                    }
+                }
+                match self.step.next() {
+                    Some(next) => {
+                        if ui.button("Next").clicked() {
+                            self.step = next;
+                        }
+                    }
+                    None => {
+                        if ui.button("Finish").clicked() {
+                            self.finish();
+                        }
+                    }
+                }
+            });
+        });
+
+        egui::panel::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Welcome to USC");
+            ui.horizontal(|ui| {
+                for step in Step::ALL {
+                    let text = RichText::new(step.title());
+                    ui.label(if step == self.step {
+                        text.strong()
+                    } else {
+                        text.weak()
+                    });
+                }
+            });
+            ui.separator();
+
+            egui::ScrollArea::vertical().show(ui, |ui| match self.step {
+                Step::SongsFolder => self.songs_folder_ui(ui),
+                Step::Input => self.input_ui(ui),
+                Step::Offset => self.offset_ui(ui),
+                Step::Skin => self.skin_ui(ui),
+            });
+        });
+
+        Ok(())
+    }
+}
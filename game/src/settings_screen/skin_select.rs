@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -19,3 +21,59 @@ impl SkinMeta {
         }
     }
 }
+
+/// Skins in the game folder along with their metadata, falling back to the folder name for
+/// skins without a `meta.json`
+pub fn installed_skins() -> Vec<(SkinMeta, PathBuf)> {
+    let mut skins_folder = crate::default_game_dir();
+    skins_folder.push("skins");
+    skins_folder
+        .read_dir()
+        .ok()
+        .into_iter()
+        .flatten()
+        .filter_map(|x| x.ok())
+        .filter(|x| x.file_type().is_ok_and(|x| x.is_dir()))
+        .map(|x| x.path())
+        .map(|mut p| {
+            p.push("meta.json");
+            if let Ok(Ok(m)) = std::fs::File::open(&p).map(serde_json::from_reader) {
+                p.pop();
+                (m, p)
+            } else {
+                p.pop();
+                (
+                    SkinMeta::named(p.file_name().and_then(|x| x.to_str()).unwrap_or("unk")),
+                    p,
+                )
+            }
+        })
+        .collect()
+}
+
+pub fn skin_combo_box(ui: &mut egui::Ui, skins: &[(SkinMeta, PathBuf)], skin: &mut String) {
+    let current_skin = skins
+        .iter()
+        .find(|x| x.1.ends_with(skin.as_str()))
+        .map(|x| x.0.name.clone())
+        .unwrap_or_default();
+
+    egui::ComboBox::new("skin_select", "Selected skin")
+        .selected_text(&current_skin)
+        .show_ui(ui, |ui| {
+            for (meta, path) in skins.iter() {
+                if ui
+                    .selectable_label(path.ends_with(&current_skin), &meta.name)
+                    .clicked()
+                {
+                    if let Some(v) = path
+                        .file_name()
+                        .and_then(|x| x.to_str())
+                        .map(|x| x.to_string())
+                    {
+                        *skin = v;
+                    }
+                }
+            }
+        });
+}
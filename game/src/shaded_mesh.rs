@@ -278,9 +278,34 @@ impl ShadedMesh {
         flip_y: bool,
     ) -> anyhow::Result<CpuTexture> {
         profile_function!();
-        let name = name.into();
-        let mut cpu_texture: CpuTexture = three_d_asset::io::load_and_deserialize(path)?;
+        let cpu_texture: CpuTexture = three_d_asset::io::load_and_deserialize(path)?;
+        self.use_cpu_texture(name.into(), cpu_texture, wrap_xy, flip_y)
+    }
 
+    /// Like [`ShadedMesh::use_texture`] for an image that is already read, the format is
+    /// taken from the extension of `file_name`.
+    pub fn use_texture_bytes(
+        &mut self,
+        name: impl Into<String>,
+        file_name: &str,
+        data: Vec<u8>,
+        wrap_xy: (bool, bool),
+        flip_y: bool,
+    ) -> anyhow::Result<CpuTexture> {
+        profile_function!();
+        let mut raw = three_d_asset::io::RawAssets::new();
+        raw.insert(file_name, data);
+        let cpu_texture: CpuTexture = raw.deserialize(file_name)?;
+        self.use_cpu_texture(name.into(), cpu_texture, wrap_xy, flip_y)
+    }
+
+    fn use_cpu_texture(
+        &mut self,
+        name: String,
+        mut cpu_texture: CpuTexture,
+        wrap_xy: (bool, bool),
+        flip_y: bool,
+    ) -> anyhow::Result<CpuTexture> {
         log::info!("{}", &cpu_texture.name);
         cpu_texture.data = match cpu_texture.data {
             three_d::TextureData::RU8(luma) => {
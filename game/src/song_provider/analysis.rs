@@ -0,0 +1,134 @@
+//! Chart statistics shown in song select. These are computed when a chart is scanned and
+//! stored in the database so browsing never has to parse chart files.
+
+use kson::Chart;
+use serde::{Deserialize, Serialize};
+
+/// Segments in the density strip
+const DENSITY_SEGMENTS: usize = 64;
+/// Window the peak density is counted over
+const PEAK_WINDOW_MS: f64 = 2000.0;
+
+// Values that max out the radar
+const NOTES_NPS: f32 = 12.0;
+const PEAK_NPS: f32 = 25.0;
+const TRICKY_PER_MINUTE: f32 = 12.0;
+const ONE_HAND_SHARE: f32 = 0.5;
+const HAND_TRIP_SHARE: f32 = 0.2;
+
+/// Chart characteristics scaled from 0 to 1
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Radar {
+    /// Average notes per second
+    pub notes: f32,
+    /// Notes per second in the densest part of the chart
+    pub peak: f32,
+    /// How much of the chart has lasers
+    pub tsumami: f32,
+    /// BPM, time signature and scroll speed changes per minute
+    pub tricky: f32,
+    /// Notes hit while a laser is held
+    pub one_hand: f32,
+    /// Notes on the same side as a held laser, which have to be hit with the other hand
+    pub hand_trip: f32,
+}
+
+pub struct ChartAnalysis {
+    /// Notes over the length of the chart, scaled so the densest segment is 255
+    pub density: Vec<u8>,
+    pub radar: Radar,
+}
+
+impl ChartAnalysis {
+    pub fn new(chart: &Chart) -> Self {
+        // (tick, side) where side 0 is the left half of the lanes
+        let notes: Vec<(u32, usize)> = chart
+            .note
+            .bt
+            .iter()
+            .enumerate()
+            .flat_map(|(lane, notes)| notes.iter().map(move |x| (x.y, lane / 2)))
+            .chain(
+                chart
+                    .note
+                    .fx
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(lane, notes)| notes.iter().map(move |x| (x.y, lane))),
+            )
+            .collect();
+
+        let lasers = [0, 1].map(|side| {
+            chart.note.laser[side]
+                .iter()
+                .map(|x| {
+                    (
+                        x.tick(),
+                        x.tick() + x.last().map(|x| x.ry).unwrap_or_default(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        });
+        let laser_held = |side: usize, tick: u32| {
+            let i = lasers[side].partition_point(|x| x.0 <= tick);
+            i > 0 && lasers[side][i - 1].1 > tick
+        };
+
+        let length_ms = chart.tick_to_ms(chart.get_last_tick()).max(1.0);
+        let mut times: Vec<f64> = notes.iter().map(|x| chart.tick_to_ms(x.0)).collect();
+        times.sort_by(f64::total_cmp);
+
+        let mut segments = [0u32; DENSITY_SEGMENTS];
+        for time in &times {
+            let segment = (time / length_ms * DENSITY_SEGMENTS as f64) as usize;
+            segments[segment.min(DENSITY_SEGMENTS - 1)] += 1;
+        }
+        let densest = segments.iter().copied().max().unwrap_or_default().max(1);
+        let density = segments.iter().map(|x| (x * 255 / densest) as u8).collect();
+
+        let mut peak = 0;
+        let mut window_start = 0;
+        for (i, time) in times.iter().enumerate() {
+            while times[window_start] < time - PEAK_WINDOW_MS {
+                window_start += 1;
+            }
+            peak = peak.max(i + 1 - window_start);
+        }
+
+        let laser_ms: f64 = lasers
+            .iter()
+            .flatten()
+            .map(|(start, end)| chart.tick_to_ms(*end) - chart.tick_to_ms(*start))
+            .sum();
+
+        let changes = chart.beat.bpm.len().saturating_sub(1)
+            + chart.beat.time_sig.len().saturating_sub(1)
+            + chart.beat.scroll_speed.len().saturating_sub(1);
+
+        let one_hand = notes
+            .iter()
+            .filter(|(tick, _)| laser_held(0, *tick) || laser_held(1, *tick))
+            .count();
+        let hand_trip = notes
+            .iter()
+            .filter(|(tick, side)| laser_held(*side, *tick))
+            .count();
+
+        let note_count = notes.len().max(1) as f32;
+        let length_s = (length_ms / 1000.0) as f32;
+        let scale = |value: f32, max: f32| (value / max).clamp(0.0, 1.0);
+
+        Self {
+            density,
+            radar: Radar {
+                notes: scale(notes.len() as f32 / length_s, NOTES_NPS),
+                peak: scale(peak as f32 / (PEAK_WINDOW_MS / 1000.0) as f32, PEAK_NPS),
+                tsumami: scale((laser_ms / length_ms) as f32, 1.0),
+                tricky: scale(changes as f32 / (length_s / 60.0), TRICKY_PER_MINUTE),
+                one_hand: scale(one_hand as f32 / note_count, ONE_HAND_SHARE),
+                hand_trip: scale(hand_trip as f32 / note_count, HAND_TRIP_SHARE),
+            },
+        }
+    }
+}
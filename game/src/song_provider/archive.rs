@@ -0,0 +1,210 @@
+//! Read-only access to chart packs stored as `.zip` archives. Other archive formats such as
+//! `.rar` are not supported and have to be extracted.
+//!
+//! Files inside an archive are addressed with regular paths that pass through the
+//! archive file, e.g. `songs/pack.zip/Song/exh.ksh`. This lets the rest of the file
+//! provider keep using `with_file_name` to find jackets and audio next to a chart.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Cursor, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::cache_dir;
+
+pub fn is_archive(p: &Path) -> bool {
+    p.extension()
+        .and_then(|x| x.to_str())
+        .is_some_and(|x| x.eq_ignore_ascii_case("zip"))
+}
+
+/// Splits a path into the archive file it points into and the entry name inside it.
+pub fn split_archive_path(p: &Path) -> Option<(PathBuf, String)> {
+    let archive = p
+        .ancestors()
+        .skip(1)
+        .find(|a| is_archive(a) && a.is_file())?;
+    let entry = p
+        .strip_prefix(archive)
+        .ok()?
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+
+    Some((archive.to_path_buf(), entry))
+}
+
+/// Whether a file exists, either on disk or inside an archive.
+pub fn exists(p: &Path) -> bool {
+    p.is_file()
+        || split_archive_path(p).is_some_and(|(archive, entry)| {
+            list_files(&archive).is_ok_and(|files| files.contains(&entry))
+        })
+}
+
+struct CachedListing {
+    modified: SystemTime,
+    entries: Arc<Vec<String>>,
+}
+
+fn listing_cache() -> &'static Mutex<HashMap<PathBuf, CachedListing>> {
+    static CACHE: OnceLock<Mutex<HashMap<PathBuf, CachedListing>>> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+/// Returns the names of all files in an archive, reusing the previous listing if the
+/// archive has not been modified since it was last read.
+pub fn list_files(archive: &Path) -> Result<Arc<Vec<String>>> {
+    let modified = std::fs::metadata(archive)?.modified()?;
+    let mut cache = listing_cache().lock().map_err(|_| anyhow!("Lock error"))?;
+
+    if let Some(cached) = cache.get(archive).filter(|x| x.modified == modified) {
+        return Ok(cached.entries.clone());
+    }
+
+    let zip = zip::ZipArchive::new(BufReader::new(File::open(archive)?))?;
+    let entries = Arc::new(
+        zip.file_names()
+            .filter(|x| !x.ends_with('/'))
+            .map(str::to_string)
+            .collect::<Vec<_>>(),
+    );
+
+    cache.insert(
+        archive.to_path_buf(),
+        CachedListing {
+            modified,
+            entries: entries.clone(),
+        },
+    );
+
+    Ok(entries)
+}
+
+/// Stores a listing of an archive read from somewhere else, such as the database.
+pub fn cache_listing(
+    archive: &Path,
+    modified: SystemTime,
+    entries: Vec<String>,
+) -> Arc<Vec<String>> {
+    let entries = Arc::new(entries);
+    if let Ok(mut cache) = listing_cache().lock() {
+        cache.insert(
+            archive.to_path_buf(),
+            CachedListing {
+                modified,
+                entries: entries.clone(),
+            },
+        );
+    }
+    entries
+}
+
+/// Reads a file from disk, or from inside an archive if the path passes through one.
+pub fn read(p: &Path) -> Result<Vec<u8>> {
+    let Some((archive, entry)) = split_archive_path(p) else {
+        return Ok(std::fs::read(p)?);
+    };
+
+    let mut zip = zip::ZipArchive::new(BufReader::new(File::open(archive)?))?;
+    let mut file = zip.by_name(&entry)?;
+    let mut data = Vec::with_capacity(file.size() as usize);
+    file.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+pub trait ReadSeek: Read + Seek + Send + Sync {}
+impl<T: Read + Seek + Send + Sync> ReadSeek for T {}
+
+/// A window into a stored (uncompressed) archive entry.
+struct EntryReader {
+    file: File,
+    start: u64,
+    len: u64,
+    pos: u64,
+}
+
+impl Read for EntryReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.len.saturating_sub(self.pos) as usize;
+        let max = buf.len().min(remaining);
+        if max == 0 {
+            return Ok(0);
+        }
+        self.file.seek(SeekFrom::Start(self.start + self.pos))?;
+        let n = self.file.read(&mut buf[..max])?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for EntryReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(p) => p as i64,
+            SeekFrom::End(p) => self.len as i64 + p,
+            SeekFrom::Current(p) => self.pos as i64 + p,
+        };
+        if new_pos < 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Seek before start of entry",
+            ));
+        }
+        self.pos = new_pos as u64;
+        Ok(self.pos)
+    }
+}
+
+/// Opens a file for streaming. Stored archive entries are read directly from the
+/// archive file, compressed entries have to be inflated into memory first.
+pub fn open(p: &Path) -> Result<Box<dyn ReadSeek>> {
+    let Some((archive, entry)) = split_archive_path(p) else {
+        return Ok(Box::new(BufReader::new(File::open(p)?)));
+    };
+
+    let mut zip = zip::ZipArchive::new(BufReader::new(File::open(&archive)?))?;
+    let mut file = zip.by_name(&entry)?;
+
+    if file.compression() == zip::CompressionMethod::Stored {
+        let start = file.data_start();
+        let len = file.size();
+        return Ok(Box::new(BufReader::new(EntryReader {
+            file: File::open(&archive)?,
+            start,
+            len,
+            pos: 0,
+        })));
+    }
+
+    let mut data = Vec::with_capacity(file.size() as usize);
+    file.read_to_end(&mut data)?;
+    Ok(Box::new(Cursor::new(data)))
+}
+
+/// Copies an archive entry into the cache folder so it can be loaded by path, used
+/// for jackets. Files outside of archives are returned as is.
+pub fn extract_to_cache(p: &Path, hash: &str) -> Result<PathBuf> {
+    if split_archive_path(p).is_none() {
+        return Ok(p.to_path_buf());
+    }
+
+    let mut cache_path = cache_dir();
+    cache_path.push("archives");
+    cache_path.push(hash);
+    std::fs::create_dir_all(&cache_path)?;
+    cache_path.push(p.file_name().ok_or(anyhow!("No file name"))?);
+
+    if !cache_path.exists() {
+        std::fs::write(&cache_path, read(p)?)?;
+    }
+
+    Ok(cache_path)
+}
@@ -0,0 +1,331 @@
+//! Nautica songs downloaded into the songs folder in the background, one at a time. Partial
+//! downloads are kept so failed or interrupted downloads continue where they stopped, and the
+//! queue is saved so it continues after a restart.
+
+use std::{
+    fs::File,
+    io::BufReader,
+    path::PathBuf,
+    sync::mpsc::{channel, Receiver, Sender},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use di::RefMut;
+use log::{info, warn};
+use poll_promise::Promise;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use super::{files::songs_path, nautica::NauticaSong, FileSongProvider, SongProvider};
+use crate::{
+    cache_dir,
+    worker_service::{Backoff, WorkerService},
+};
+
+/// Failed attempts before a download is given up on
+const MAX_ATTEMPTS: u32 = 5;
+/// Wait before retrying a failed download, multiplied by the failed attempts
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "camelCase")]
+pub enum DownloadState {
+    Queued,
+    /// Received bytes, `total` is unknown when the server doesn't send the size
+    Downloading {
+        received: u64,
+        total: Option<u64>,
+    },
+    Extracting,
+    Done,
+    Failed {
+        error: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Download {
+    pub id: Uuid,
+    pub title: String,
+    pub artist: String,
+    #[serde(flatten)]
+    pub state: DownloadState,
+    #[serde(flatten)]
+    pub retry: Backoff,
+}
+
+impl Download {
+    pub fn new(id: Uuid, title: String, artist: String) -> Self {
+        Self {
+            id,
+            title,
+            artist,
+            state: DownloadState::Queued,
+            retry: Backoff::default(),
+        }
+    }
+}
+
+pub struct DownloadQueue {
+    downloads: Vec<Download>,
+    active: Option<(Uuid, Promise<Result<()>>)>,
+    requests: (Sender<Download>, Receiver<Download>),
+    progress: (
+        Sender<(Uuid, DownloadState)>,
+        Receiver<(Uuid, DownloadState)>,
+    ),
+    file_songs: RefMut<FileSongProvider>,
+    /// Increased whenever a download changes
+    version: u64,
+}
+
+fn queue_path() -> PathBuf {
+    let mut path = cache_dir();
+    path.push("nautica_downloads.json");
+    path
+}
+
+/// Folder in the songs folder a song is extracted to.
+fn folder_name(title: &str, id: Uuid) -> String {
+    let name: String = title
+        .chars()
+        .filter(|c| !c.is_control() && !r#"<>:"/\|?*"#.contains(*c))
+        .collect();
+    let name = name.trim().trim_end_matches('.');
+    if name.is_empty() {
+        id.hyphenated().to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Downloads the song archive next to the one streamed songs use, continuing a partial download,
+/// and extracts it into the songs folder.
+async fn download(id: Uuid, title: String, progress: Sender<(Uuid, DownloadState)>) -> Result<()> {
+    let mut zip_path = cache_dir();
+    zip_path.push(id.hyphenated().to_string());
+    tokio::fs::create_dir_all(&zip_path).await?;
+    zip_path.push("data.zip");
+
+    if !zip_path.exists() {
+        let part_path = zip_path.with_extension("zip.part");
+        let NauticaSong { data: nautica } =
+            reqwest::get(format!("https://ksm.dev/app/songs/{}", id.as_hyphenated()))
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+        let mut received = tokio::fs::metadata(&part_path)
+            .await
+            .map(|x| x.len())
+            .unwrap_or_default();
+        let mut request = reqwest::Client::new().get(&nautica.cdn_download_url);
+        if received > 0 {
+            info!("Resuming download of {title} at {received} bytes");
+            request = request.header(reqwest::header::RANGE, format!("bytes={received}-"));
+        }
+
+        let mut response = request.send().await?;
+        if response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+            tokio::fs::remove_file(&part_path).await?;
+            bail!("Partial download doesn't match, starting over");
+        }
+        response = response.error_for_status()?;
+        if response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+            // Sent from the start
+            received = 0;
+        }
+
+        let total = response.content_length().map(|x| x + received);
+        let mut file = if received > 0 {
+            tokio::fs::OpenOptions::new()
+                .append(true)
+                .open(&part_path)
+                .await?
+        } else {
+            tokio::fs::File::create(&part_path).await?
+        };
+
+        while let Some(chunk) = response.chunk().await? {
+            file.write_all(&chunk).await?;
+            received += chunk.len() as u64;
+            _ = progress.send((id, DownloadState::Downloading { received, total }));
+        }
+        file.flush().await?;
+        drop(file);
+        tokio::fs::rename(&part_path, &zip_path).await?;
+    }
+
+    _ = progress.send((id, DownloadState::Extracting));
+    let target = songs_path().join("nautica").join(folder_name(&title, id));
+    tokio::task::spawn_blocking(move || {
+        let extracted = File::open(&zip_path)
+            .map_err(anyhow::Error::from)
+            .and_then(|f| Ok(zip::ZipArchive::new(BufReader::new(f))?.extract(&target)?));
+        if extracted.is_err() {
+            // Downloaded again on the next attempt
+            _ = std::fs::remove_file(&zip_path);
+        }
+        extracted
+    })
+    .await?
+}
+
+impl DownloadQueue {
+    pub fn new(file_songs: RefMut<FileSongProvider>) -> Self {
+        let downloads = std::fs::read_to_string(queue_path())
+            .ok()
+            .and_then(|x| serde_json::from_str::<Vec<Download>>(&x).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|mut x| {
+                if matches!(
+                    x.state,
+                    DownloadState::Downloading { .. } | DownloadState::Extracting
+                ) {
+                    x.state = DownloadState::Queued;
+                }
+                x
+            })
+            .collect();
+
+        Self {
+            downloads,
+            active: None,
+            requests: channel(),
+            progress: channel(),
+            file_songs,
+            version: 0,
+        }
+    }
+
+    /// Sends downloads to add to the queue.
+    pub fn requests(&self) -> Sender<Download> {
+        self.requests.0.clone()
+    }
+
+    pub fn downloads(&self) -> &[Download] {
+        &self.downloads
+    }
+
+    /// Changes whenever a download changes, for updating progress displays.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Saves the unfinished downloads.
+    fn save(&self) {
+        let unfinished = self
+            .downloads
+            .iter()
+            .filter(|x| x.state != DownloadState::Done)
+            .collect::<Vec<_>>();
+        let saved = serde_json::to_string(&unfinished)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| Ok(std::fs::write(queue_path(), json)?));
+        if let Err(e) = saved {
+            warn!("Could not save nautica downloads: {e}");
+        }
+    }
+
+    fn finished(&mut self, id: Uuid, result: Result<()>) {
+        let Some(download) = self.downloads.iter_mut().find(|x| x.id == id) else {
+            return;
+        };
+
+        match result {
+            Ok(()) => {
+                info!("Downloaded {}", download.title);
+                download.state = DownloadState::Done;
+                self.file_songs.write().expect("Lock error").refresh();
+            }
+            Err(e) => {
+                download.retry.failed(RETRY_DELAY, MAX_ATTEMPTS);
+                if download.retry.attempts < MAX_ATTEMPTS {
+                    warn!("Download of {} failed, retrying: {e}", download.title);
+                    download.state = DownloadState::Queued;
+                } else {
+                    warn!("Download of {} failed: {e}", download.title);
+                    download.state = DownloadState::Failed {
+                        error: e.to_string(),
+                    };
+                }
+            }
+        }
+    }
+
+    fn start_next(&mut self) {
+        let now = Instant::now();
+        let Some(next) = self
+            .downloads
+            .iter_mut()
+            .find(|x| x.state == DownloadState::Queued && x.retry.ready(now))
+        else {
+            return;
+        };
+
+        next.state = DownloadState::Downloading {
+            received: 0,
+            total: None,
+        };
+        self.version += 1;
+        self.active = Some((
+            next.id,
+            Promise::spawn_async(download(
+                next.id,
+                next.title.clone(),
+                self.progress.0.clone(),
+            )),
+        ));
+    }
+}
+
+impl WorkerService for DownloadQueue {
+    fn update(&mut self) {
+        let mut changed = false;
+        while let Ok(download) = self.requests.1.try_recv() {
+            match self.downloads.iter_mut().find(|x| x.id == download.id) {
+                // Queued again to retry
+                Some(existing) if matches!(existing.state, DownloadState::Failed { .. }) => {
+                    *existing = download;
+                }
+                Some(_) => continue,
+                None => {
+                    info!("Queued download of {}", download.title);
+                    self.downloads.push(download);
+                }
+            }
+            changed = true;
+        }
+
+        while let Ok((id, state)) = self.progress.1.try_recv() {
+            if let Some(download) = self.downloads.iter_mut().find(|x| x.id == id) {
+                download.state = state;
+                self.version += 1;
+            }
+        }
+
+        if let Some((id, active)) = self.active.take() {
+            match active.try_take() {
+                Ok(result) => {
+                    self.finished(id, result);
+                    changed = true;
+                }
+                Err(active) => self.active = Some((id, active)),
+            }
+        }
+
+        if self.active.is_none() {
+            self.start_next();
+        }
+
+        if changed {
+            self.version += 1;
+            self.save();
+        }
+    }
+}
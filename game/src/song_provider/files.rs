@@ -1,18 +1,19 @@
 use std::{
     collections::{HashMap, HashSet},
+    io::{BufRead, BufReader, Read},
     path::{Path, PathBuf},
     sync::{
         mpsc::{channel, Receiver, Sender, TryRecvError},
         Arc, RwLock,
     },
-    time::{Duration, SystemTime},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use crate::{
     block_on,
     config::{GameConfig, SongSelectSettings},
-    game::{gauge::Gauge, HitSummary, HitWindow},
-    log_result,
+    game::{gauge::Gauge, replay::Replay, HitSummary, HitWindow},
+    log_result, loudness,
     results::{calculate_clear_mark, Score},
     song_provider::SongFilterType,
     songselect::{Difficulty, Song},
@@ -20,10 +21,11 @@ use crate::{
 };
 
 use super::{
-    DiffId, LoadSongFn, ScoreProvider, ScoreProviderEvent, SongDiffId, SongFilter, SongId,
-    SongProvider, SongProviderEvent, SongSort,
+    analysis::ChartAnalysis, archive, AudioSource, DiffId, LoadSongFn, LoadedSong, PreviewResult,
+    ScoreProvider, ScoreProviderEvent, SongDiffId, SongFilter, SongId, SongProvider,
+    SongProviderEvent, SongSort,
 };
-use anyhow::{anyhow, bail, ensure};
+use anyhow::{anyhow, bail, ensure, Context};
 
 use futures::{executor::block_on, AsyncReadExt, StreamExt};
 use itertools::Itertools;
@@ -118,7 +120,7 @@ impl From<ScoreEntry> for Score {
             player_name: value.user_name,
             is_local: value.local_score,
             hit_window: HitWindow::new(
-                1,
+                value.window_variant as _,
                 value.window_perfect as _,
                 value.window_good as _,
                 value.window_hold as _,
@@ -127,14 +129,33 @@ impl From<ScoreEntry> for Score {
             earlies: value.early as _,
             lates: value.late as _,
             combo: value.combo as _,
+            speed_mod_type: value.speed_mod_type as _,
+            speed_mod_value: value.speed_mod_value,
+            modifiers: vec![],
+            id: value.rowid,
+            has_replay: value.replay.is_some(),
         }
+        .with_modifiers()
     }
 }
 
 impl FileSongProvider {
     pub async fn new() -> Self {
-        let mut db_file = GameConfig::get().game_folder.clone();
-        db_file.push("maps.db");
+        let db_file = {
+            let mut config = GameConfig::get_mut();
+            let mut db_file = config.database_file();
+            if let Some(old) = config.database_in_use.clone() {
+                if let Err(e) = migrate_database(&old, &db_file) {
+                    warn!("Failed to move database, using {}: {e}", old.display());
+                    db_file = old;
+                }
+            }
+            config.database_in_use = Some(db_file.clone());
+            db_file
+        };
+        if let Some(folder) = db_file.parent() {
+            _ = std::fs::create_dir_all(folder);
+        }
 
         let database = LocalSongsDb::new(db_file)
             .await
@@ -170,6 +191,17 @@ impl FileSongProvider {
             last_full_update: SystemTime::now(),
         }
     }
+
+    /// Queries the songs again when browsing the changed collection.
+    fn collection_changed(&mut self, collection: &str) {
+        if matches!(&self.filter.filter_type, SongFilterType::Collection(c) if c == collection) {
+            self.worker_tx.send(WorkerControlMessage::Query(
+                self.query.clone(),
+                self.filter.clone(),
+                self.sort,
+            ));
+        }
+    }
 }
 
 async fn files_worker(
@@ -210,10 +242,29 @@ async fn files_worker(
                         .into_iter()
                         .collect();
 
+                    // Charts that are still on disk but were not found by their hash have
+                    // been modified, charts that are gone are only marked missing so they
+                    // can be restored if they come back
                     for removed in db_hashes.difference(&hashes) {
-                        database.remove_hash(removed).await;
+                        let Ok(Some(id)) = database.get_hash_id(removed).await else {
+                            continue;
+                        };
+                        if database
+                            .get_song(id)
+                            .await
+                            .is_ok_and(|chart| archive::exists(Path::new(&chart.path)))
+                        {
+                            database.remove_hash(removed).await;
+                        }
                     }
 
+                    match database.reconcile_missing(archive::exists).await {
+                        Ok(0) => {}
+                        Ok(missing) => info!("{missing} charts are missing"),
+                        Err(e) => warn!("Failed to check for missing charts: {e}"),
+                    }
+
+                    analyze_charts(&worker_tx, &database).await;
                     database.remove_empty_folders().await;
 
                     worker_tx.send(WorkerEvent::ImporterState(ImporterState::Idle));
@@ -234,6 +285,104 @@ async fn files_worker(
     }
 }
 
+/// Analyzes charts scanned before their analysis was stored in the database, and measures
+/// the loudness of previews that haven't been measured yet so they can be levelled when played.
+/// Charts without any notes are removed from the database.
+async fn analyze_charts(worker_tx: &Sender<WorkerEvent>, database: &LocalSongsDb) {
+    let normalize = GameConfig::get().normalize_previews;
+    let pending = database
+        .get_pending_analysis(normalize)
+        .await
+        .unwrap_or_default();
+    let total = pending.len();
+
+    for (i, chart) in pending.into_iter().enumerate() {
+        worker_tx.send(WorkerEvent::ImporterState(ImporterState::Loading(format!(
+            "Analyzing charts {}/{}",
+            i + 1,
+            total
+        ))));
+
+        let path = PathBuf::from(&chart.path);
+        let measure = normalize && !chart.measured;
+        let analyzed = {
+            let path = path.clone();
+            tokio::task::spawn_blocking(move || {
+                let parsed = parse_chart(&path, &archive::read(&path)?)?;
+                if parsed.get_last_tick() == 0 {
+                    return anyhow::Ok(None);
+                }
+                let issues = parsed.validate(None);
+                if !issues.is_empty() {
+                    warn!(
+                        "Issues in {}: {}",
+                        path.display(),
+                        issues.iter().map(|x| x.to_string()).join("; ")
+                    );
+                }
+                let loudness = if measure {
+                    let (source, skip, duration) = chart_preview(&parsed, &path)?;
+                    Some(loudness::measure(
+                        source.skip_duration(skip).take_duration(duration),
+                    ))
+                } else {
+                    None
+                };
+                let (preview_file, preview_skip) = preview_source(&parsed, &path);
+                anyhow::Ok(Some((
+                    ChartAnalysis::new(&parsed),
+                    preview_file,
+                    preview_skip,
+                    loudness,
+                    parsed.meta.information,
+                )))
+            })
+            .await
+        };
+
+        let (analysis, preview_file, preview_skip, loudness, information) = match analyzed {
+            Ok(Ok(Some(analyzed))) => analyzed,
+            Ok(Ok(None)) => {
+                warn!("Empty chart {}", path.display());
+                if let Err(e) = database.remove_hash(&chart.hash).await {
+                    warn!("Failed to remove empty chart: {e}");
+                }
+                continue;
+            }
+            Ok(Err(e)) => {
+                warn!("Failed to analyze {}: {}", path.display(), e);
+                continue;
+            }
+            Err(e) => {
+                warn!("Failed to analyze {}: {}", path.display(), e);
+                continue;
+            }
+        };
+
+        if !chart.analyzed {
+            if let Err(e) = database
+                .set_chart_analysis(
+                    &chart.hash,
+                    &analysis.density,
+                    &serde_json::to_string(&analysis.radar).unwrap_or_default(),
+                    &preview_file.to_string_lossy(),
+                    preview_skip.as_millis() as _,
+                    information.as_deref(),
+                )
+                .await
+            {
+                warn!("Failed to store chart analysis: {e}");
+            }
+        }
+
+        if let Some(loudness) = loudness {
+            if let Err(e) = database.set_preview_loudness(&chart.hash, loudness).await {
+                warn!("Failed to store preview loudness: {e}");
+            }
+        }
+    }
+}
+
 async fn load_db(database: &LocalSongsDb, worker_tx: &Sender<WorkerEvent>) {
     let mut diffs = database
         .get_songs()
@@ -266,6 +415,9 @@ async fn load_db(database: &LocalSongsDb, worker_tx: &Sender<WorkerEvent>) {
                 scores: Vec::default(), //TODO
                 hash: Some(diff.hash),
                 illustrator: diff.illustrator,
+                density: diff.density.unwrap_or_default(),
+                radar: diff.radar.and_then(|x| serde_json::from_str(&x).ok()),
+                information: diff.information,
             });
             drop(difficulties);
             song
@@ -310,11 +462,78 @@ async fn read_song_dir(
             if let Ok(mut r) = Box::pin(read_song_dir(dir, worker_tx, worker_db)).await {
                 hashes.append(&mut r);
             }
+        } else if archive::is_archive(&p) {
+            let msg = format!("{}", p.display());
+            worker_tx.send(WorkerEvent::ImporterState(ImporterState::Loading(msg)));
+            match read_song_archive(&p, worker_tx, worker_db).await {
+                Ok(mut r) => hashes.append(&mut r),
+                Err(e) => warn!("Failed to read archive {}: {}", p.display(), e),
+            }
         } else if is_chart_file(&p).is_some() {
             chart_files.push(p);
         }
     }
 
+    hashes.append(&mut read_chart_folder(chart_files, worker_tx, worker_db).await?);
+
+    Ok(hashes)
+}
+
+/// Lists the files in an archive, using the listing stored in the database while the archive
+/// is unchanged so archives don't have to be read again on every start.
+async fn archive_listing(
+    path: &Path,
+    worker_db: &LocalSongsDb,
+) -> anyhow::Result<Arc<Vec<String>>> {
+    let modified = std::fs::metadata(path)?.modified()?;
+    let modified_ns = modified.duration_since(UNIX_EPOCH)?.as_nanos() as i64;
+    let key = path.to_string_lossy();
+
+    if let Ok(Some(entries)) = worker_db.get_archive_listing(&key, modified_ns).await {
+        let entries = entries.lines().map(str::to_string).collect();
+        return Ok(archive::cache_listing(path, modified, entries));
+    }
+
+    let listing = {
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || archive::list_files(&path)).await??
+    };
+    if let Err(e) = worker_db
+        .set_archive_listing(&key, modified_ns, &listing.join("\n"))
+        .await
+    {
+        warn!("Failed to store listing of {}: {e}", path.display());
+    }
+    Ok(listing)
+}
+
+async fn read_song_archive(
+    path: &Path,
+    worker_tx: &Sender<WorkerEvent>,
+    worker_db: &LocalSongsDb,
+) -> anyhow::Result<Vec<String>> {
+    let listing = archive_listing(path, worker_db).await?;
+
+    let folders = listing
+        .iter()
+        .map(|entry| path.join(entry))
+        .filter(|p| is_chart_file(p).is_some())
+        .into_group_map_by(|p| p.parent().map(Path::to_path_buf));
+
+    let mut hashes = vec![];
+    for (_, chart_files) in folders {
+        hashes.append(&mut read_chart_folder(chart_files, worker_tx, worker_db).await?);
+    }
+
+    Ok(hashes)
+}
+
+async fn read_chart_folder(
+    chart_files: Vec<PathBuf>,
+    worker_tx: &Sender<WorkerEvent>,
+    worker_db: &LocalSongsDb,
+) -> anyhow::Result<Vec<String>> {
+    let mut hashes = vec![];
     let mut chart_loaders = vec![];
     if !chart_files.is_empty() {
         let folder_id = worker_db
@@ -332,11 +551,6 @@ async fn read_song_dir(
             ));
         }
 
-        let mut song = Song {
-            id: SongId::IntId(folder_id),
-            ..Default::default()
-        };
-
         for (p, t) in chart_loaders {
             match t.await? {
                 Ok(hash) => hashes.push(hash),
@@ -350,7 +564,7 @@ async fn read_song_dir(
     Ok(hashes)
 }
 
-fn is_chart_file(p: &PathBuf) -> Option<String> {
+fn is_chart_file(p: &Path) -> Option<String> {
     p.extension()
         .and_then(|x| x.to_str())
         .map(|x| x.to_lowercase())
@@ -363,34 +577,216 @@ async fn read_chart_file(
     worker_db: LocalSongsDb,
     folder_id: i64,
 ) -> anyhow::Result<String> {
-    let data = tokio::fs::read(&p).await?;
-    let mut hasher = sha1_smol::Sha1::new();
-    hasher.update(&data);
-    let hash = hasher.digest().to_string();
+    let hash = {
+        let p = p.clone();
+        tokio::task::spawn_blocking(move || hash_chart(&p)).await??
+    };
 
     if worker_db.get_hash_id(&hash).await?.is_some() {
-        return Ok(hash); //Already exists
+        //Already exists, but may have been moved
+        worker_db
+            .update_chart_location(&hash, &p.to_string_lossy(), folder_id)
+            .await?;
+        return Ok(hash);
+    }
+    let (chart, analysis) = {
+        let p = p.clone();
+        tokio::task::spawn_blocking(move || scan_chart(&p)).await??
+    };
+
+    let mut entry = chart_to_entry(&chart, &p, folder_id, &hash, analysis);
+    if archive::split_archive_path(&p).is_some() {
+        entry.jacket_path = match archive::extract_to_cache(
+            &p.with_file_name(&chart.meta.jacket_filename),
+            &hash,
+        ) {
+            Ok(jacket) => jacket.to_string_lossy().to_string(),
+            Err(e) => {
+                warn!("Failed to extract jacket for {}: {}", p.display(), e);
+                String::new()
+            }
+        };
+    }
+
+    worker_db.add_chart(entry).await;
+
+    Ok(hash)
+}
+
+/// Hashes a chart file without reading all of it into memory
+fn hash_chart(p: &Path) -> anyhow::Result<String> {
+    let mut reader = archive::open(p)?;
+    let mut hasher = sha1_smol::Sha1::new();
+    let mut buf = [0; 8192];
+    loop {
+        let read = reader.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+    Ok(hasher.digest().to_string())
+}
+
+/// Reads what the song list needs from a chart. KSH charts are only read up to their first
+/// measure, their analysis and the check for empty charts are left for [`analyze_charts`].
+fn scan_chart(p: &Path) -> anyhow::Result<(kson::Chart, Option<ChartAnalysis>)> {
+    if is_chart_file(p).is_some_and(|ext| ext == "ksh") {
+        let reader = DecodedLines::new(BufReader::new(archive::open(p)?));
+        return Ok((kson::Chart::metadata_from_ksh_reader(reader)?, None));
+    }
+
+    let chart = parse_chart(p, &archive::read(p)?)?;
+    ensure!(chart.get_last_tick() > 0, "Empty chart");
+    let analysis = ChartAnalysis::new(&chart);
+    Ok((chart, Some(analysis)))
+}
+
+/// Decodes a KSH file to UTF-8 one line at a time. The encoding is picked from the byte order
+/// mark of the first line, falling back to Shift-JIS.
+struct DecodedLines<R> {
+    inner: R,
+    encoding: Option<encoding::EncodingRef>,
+    raw: Vec<u8>,
+    decoded: String,
+    pos: usize,
+}
+
+impl<R: BufRead> DecodedLines<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            encoding: None,
+            raw: vec![],
+            decoded: String::new(),
+            pos: 0,
+        }
     }
-    let ext = is_chart_file(&p).expect("Got non chart file");
-    let chart: kson::Chart = if ext == "ksh" {
+}
+
+impl<R: BufRead> Read for DecodedLines<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let available = self.fill_buf()?;
+        let read = available.len().min(buf.len());
+        buf[..read].copy_from_slice(&available[..read]);
+        self.consume(read);
+        Ok(read)
+    }
+}
+
+impl<R: BufRead> BufRead for DecodedLines<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        if self.pos >= self.decoded.len() {
+            self.raw.clear();
+            self.inner.read_until(b'\n', &mut self.raw)?;
+
+            let decoded = match self.encoding {
+                Some(encoding) => encoding.decode(&self.raw, encoding::DecoderTrap::Strict),
+                None => {
+                    let (decoded, encoding) = encoding::types::decode(
+                        &self.raw,
+                        encoding::DecoderTrap::Strict,
+                        encoding::all::WINDOWS_31J,
+                    );
+                    self.encoding = Some(encoding);
+                    decoded
+                }
+            };
+            self.decoded =
+                decoded.map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            self.pos = 0;
+        }
+
+        Ok(&self.decoded.as_bytes()[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt;
+    }
+}
+
+fn parse_chart(p: &Path, data: &[u8]) -> anyhow::Result<kson::Chart> {
+    let ext = is_chart_file(p).expect("Got non chart file");
+    Ok(if ext == "ksh" {
         let (c, _) = encoding::types::decode(
-            &data,
+            data,
             encoding::DecoderTrap::Strict,
             encoding::all::WINDOWS_31J,
         );
         let c = c.map_err(|x| anyhow::anyhow!("{x}"))?;
         kson::Chart::from_ksh(&c)?
     } else {
-        serde_json::from_slice(&data)?
+        serde_json::from_slice(data)?
+    })
+}
+
+/// Used when a chart doesn't set a preview duration
+const DEFAULT_PREVIEW_DURATION: Duration = Duration::from_secs(15);
+
+/// Picks the song select preview for a chart, as the audio file and how far into it the
+/// preview starts. A dedicated preview file is played from the start, otherwise the bgm is
+/// played from the preview offset.
+fn preview_source(chart: &kson::Chart, path: &Path) -> (PathBuf, Duration) {
+    let preview = &chart.audio.bgm.preview;
+    preview
+        .preview_filename
+        .as_deref()
+        .map(|x| path.with_file_name(x))
+        .filter(|x| archive::exists(x))
+        .map(|x| (x, Duration::ZERO))
+        .unwrap_or_else(|| {
+            (
+                path.with_file_name(&chart.audio.bgm.filename),
+                Duration::from_millis(preview.offset as u64),
+            )
+        })
+}
+
+/// Opens a preview, if the file can't be opened the preview is silent so the song wheel
+/// behaves the same as with audio.
+fn open_preview(audio_path: &Path, skip: Duration, duration_ms: i64) -> PreviewResult {
+    let duration = match duration_ms {
+        ..=0 => DEFAULT_PREVIEW_DURATION,
+        ms => Duration::from_millis(ms as u64),
     };
 
-    ensure!(chart.get_last_tick() > 0, "Empty chart");
+    match archive::open(audio_path)
+        .and_then(|file| rodio::Decoder::new(file).map_err(anyhow::Error::from))
+    {
+        Ok(source) => Ok((
+            Box::new(source.convert_samples()) as Box<dyn Source<Item = f32> + Send>,
+            skip,
+            duration,
+        )),
+        Err(e) => {
+            warn!("Failed to open preview {}: {}", audio_path.display(), e);
+            Ok((
+                Box::new(rodio::source::Zero::<f32>::new(2, 44100)),
+                Duration::ZERO,
+                duration,
+            ))
+        }
+    }
+}
 
-    worker_db
-        .add_chart(chart_to_entry(&chart, &p, folder_id, &hash))
-        .await;
+fn chart_preview(chart: &kson::Chart, path: &Path) -> PreviewResult {
+    let (audio_path, skip) = preview_source(chart, path);
+    open_preview(&audio_path, skip, chart.audio.bgm.preview.duration as _)
+}
 
-    Ok(hash)
+/// Opens the preview picked when the chart was scanned, `None` if the chart hasn't been
+/// analyzed yet. The file is looked up next to the chart in case the folder has been moved.
+fn cached_preview(entry: &ChartEntry) -> Option<PreviewResult> {
+    let (Some(audio_path), Some(skip)) = (&entry.preview_file, entry.preview_skip) else {
+        return None;
+    };
+    let audio_path = Path::new(&entry.path).with_file_name(Path::new(audio_path).file_name()?);
+
+    Some(open_preview(
+        &audio_path,
+        Duration::from_millis(skip as u64),
+        entry.preview_length,
+    ))
 }
 
 fn chart_to_entry(
@@ -398,8 +794,13 @@ fn chart_to_entry(
     path: impl AsRef<Path>,
     folder_id: i64,
     hash: &str,
+    analysis: Option<ChartAnalysis>,
 ) -> ChartEntry {
     let path = path.as_ref();
+    let (density, radar) = analysis
+        .map(|x| (x.density, serde_json::to_string(&x.radar).ok()))
+        .unzip();
+    let (preview_file, preview_skip) = preview_source(c, path);
     ChartEntry {
         rowid: 0,
         folderid: folder_id,
@@ -420,11 +821,7 @@ fn chart_to_entry(
         diff_index: c.meta.difficulty as _,
         level: c.meta.level as _,
         hash: hash.to_string(),
-        preview_file: Some(
-            path.with_file_name(c.audio.bgm.filename.clone())
-                .to_string_lossy()
-                .to_string(),
-        ),
+        preview_file: Some(preview_file.to_string_lossy().to_string()),
         preview_offset: c.audio.bgm.preview.offset as _,
         preview_length: c.audio.bgm.preview.duration as _,
         lwt: std::fs::metadata(path)
@@ -433,6 +830,10 @@ fn chart_to_entry(
             .map(|x| x.as_secs())
             .unwrap_or_default() as _,
         custom_offset: 0,
+        density,
+        radar: radar.flatten(),
+        preview_skip: Some(preview_skip.as_millis() as _),
+        information: c.meta.information.clone(),
     }
 }
 
@@ -449,8 +850,20 @@ async fn query_songs(
     } else {
         None
     };
+    let chart_filter = rusc_database::ChartFilter {
+        level_range: filter.criteria.level_range,
+        bpm_range: filter.criteria.bpm_range,
+        effector: filter.criteria.effector.clone(),
+        clear: filter.criteria.clear,
+        min_score: filter.criteria.min_score,
+        collection: match &filter.filter_type {
+            SongFilterType::Collection(collection) => Some(collection.clone()),
+            _ => None,
+        },
+    };
+
     let charts = match database
-        .get_folder_ids_query(&q, filter.level, folder, sort.into())
+        .get_folder_ids_query(&q, filter.level, folder, &chart_filter, sort.into())
         .await
     {
         Ok(charts) => charts,
@@ -463,16 +876,24 @@ async fn query_songs(
     Ok(charts.iter().map(|x| SongId::IntId(*x)).collect_vec())
 }
 
-fn songs_path() -> PathBuf {
-    let song_path = crate::config::GameConfig::get().songs_path.clone();
+pub(super) fn songs_path() -> PathBuf {
+    let config = GameConfig::get();
+    config.game_path(&config.songs_path)
+}
 
-    if song_path.is_absolute() {
-        song_path
-    } else {
-        let mut p = GameConfig::get().game_folder.clone();
-        p.push(song_path);
-        p
+/// Copies the database to a new location before it is opened. A database that already exists
+/// at the new location is used as is.
+fn migrate_database(from: &Path, to: &Path) -> anyhow::Result<()> {
+    if from == to || !from.exists() || to.exists() {
+        return Ok(());
+    }
+
+    if let Some(folder) = to.parent() {
+        std::fs::create_dir_all(folder)?;
     }
+    info!("Copying database {} -> {}", from.display(), to.display());
+    std::fs::copy(from, to)?;
+    Ok(())
 }
 
 impl WorkerService for FileSongProvider {
@@ -583,7 +1004,7 @@ impl SongProvider for FileSongProvider {
         let path = PathBuf::from(block_on!(db.get_song(_diff_index as _))?.path);
 
         Ok(Box::new(move || {
-            let data = std::fs::read(&path)?;
+            let data = archive::read(&path)?;
             let data = encoding::decode(
                 &data,
                 encoding::DecoderTrap::Strict,
@@ -594,11 +1015,55 @@ impl SongProvider for FileSongProvider {
 
             let chart = kson::Chart::from_ksh(&data)?;
 
-            let audio = rodio::decoder::Decoder::new(std::fs::File::open(
-                path.with_file_name(&chart.audio.bgm.filename),
+            let audio = rodio::decoder::Decoder::new(archive::open(
+                &path.with_file_name(&chart.audio.bgm.filename),
             )?)?;
 
-            Ok((chart, Box::new(audio.convert_samples())))
+            let fx_audio = chart
+                .audio
+                .bgm
+                .fx_filename()
+                .map(|x| path.with_file_name(x))
+                .filter(|x| archive::exists(x))
+                .and_then(|x| {
+                    archive::open(&x)
+                        .and_then(|file| rodio::Decoder::new(file).map_err(anyhow::Error::from))
+                        .map_err(|e| warn!("Failed to open FX track {}: {}", x.display(), e))
+                        .ok()
+                })
+                .map(|x| Box::new(x.convert_samples()) as AudioSource);
+
+            let images = chart
+                .bg
+                .image_filenames()
+                .into_iter()
+                .filter_map(|name| {
+                    let data = archive::read(&path.with_file_name(name))
+                        .map_err(|e| warn!("Failed to read background image {name}: {e}"))
+                        .ok()?;
+                    Some((name.to_string(), data))
+                })
+                .collect();
+
+            let effect_audio = chart
+                .audio
+                .effect_filenames()
+                .into_iter()
+                .filter_map(|name| {
+                    let data = archive::read(&path.with_file_name(name))
+                        .map_err(|e| warn!("Failed to read effect audio {name}: {e}"))
+                        .ok()?;
+                    Some((name.to_string(), data))
+                })
+                .collect();
+
+            Ok(LoadedSong {
+                audio: Box::new(audio.convert_samples()),
+                fx_audio,
+                images,
+                effect_audio,
+                chart,
+            })
         }))
     }
 
@@ -620,23 +1085,39 @@ impl SongProvider for FileSongProvider {
                 bail!("Unsupported id type")
             };
             let mut charts = block_on(db.get_charts_for_folder(id))?;
-            let Some(mut chart) = charts.pop() else {
+            let Some(entry) = charts.pop() else {
                 bail!("No chart found")
             };
 
-            let Some(path) = chart.preview_file.take() else {
-                bail!("No preview file")
+            let (source, skip, duration) = match cached_preview(&entry) {
+                Some(preview) => preview?,
+                None => {
+                    let path = PathBuf::from(&entry.path);
+                    chart_preview(&parse_chart(&path, &archive::read(&path)?)?, &path)?
+                }
             };
 
-            let source = rodio::Decoder::new(std::fs::File::open(
-                PathBuf::from(&chart.path).with_file_name(path),
-            )?)?
-            .convert_samples();
-            Ok((
-                Box::new(source) as Box<dyn Source<Item = f32> + Send>,
-                Duration::from_millis(chart.preview_offset as u64),
-                Duration::from_millis(chart.preview_length as u64),
-            ))
+            let (normalize, target) = {
+                let config = GameConfig::get();
+                (config.normalize_previews, config.preview_loudness_target)
+            };
+            let loudness = if normalize {
+                db.get_preview_loudness(&entry.hash).await.ok().flatten()
+            } else {
+                None
+            };
+
+            match loudness {
+                Some(loudness) => {
+                    let gain = loudness::gain(loudness, target as f64);
+                    Ok((
+                        Box::new(source.amplify(gain)) as Box<dyn Source<Item = f32> + Send>,
+                        skip,
+                        duration,
+                    ))
+                }
+                None => Ok((source, skip, duration)),
+            }
         })
     }
 
@@ -722,6 +1203,14 @@ impl SongProvider for FileSongProvider {
                 crate::song_provider::SongSortType::Effector,
                 crate::song_provider::SortDir::Desc,
             ),
+            super::SongSort::new(
+                crate::song_provider::SongSortType::Level,
+                crate::song_provider::SortDir::Asc,
+            ),
+            super::SongSort::new(
+                crate::song_provider::SongSortType::Level,
+                crate::song_provider::SortDir::Desc,
+            ),
         ]
     }
 
@@ -757,9 +1246,49 @@ impl SongProvider for FileSongProvider {
                     super::SongFilterType::Folder(x.file_name().to_string_lossy().to_string())
                 }),
         );
+        res.extend(
+            self.list_collections()
+                .into_iter()
+                .map(super::SongFilterType::Collection),
+        );
         res
     }
 
+    fn list_collections(&self) -> Vec<String> {
+        block_on(self.database.list_collections()).unwrap_or_else(|e| {
+            log::warn!("Failed to list collections: {e}");
+            vec![]
+        })
+    }
+
+    fn song_collections(&self, id: &SongId) -> Vec<String> {
+        let SongId::IntId(folderid) = id else {
+            return vec![];
+        };
+        block_on(self.database.collections_for_folder(*folderid)).unwrap_or_else(|e| {
+            log::warn!("Failed to get collections: {e}");
+            vec![]
+        })
+    }
+
+    fn add_to_collection(&mut self, id: &SongId, collection: &str) -> anyhow::Result<()> {
+        let SongId::IntId(folderid) = id else {
+            bail!("Invalid song id: {id}");
+        };
+        block_on(self.database.add_to_collection(collection, *folderid))?;
+        self.collection_changed(collection);
+        Ok(())
+    }
+
+    fn remove_from_collection(&mut self, id: &SongId, collection: &str) -> anyhow::Result<()> {
+        let SongId::IntId(folderid) = id else {
+            bail!("Invalid song id: {id}");
+        };
+        block_on(self.database.remove_from_collection(collection, *folderid))?;
+        self.collection_changed(collection);
+        Ok(())
+    }
+
     fn refresh(&mut self) {
         if let ImporterState::Idle = self.importer_state {
             self.importer_state = ImporterState::Starting;
@@ -773,8 +1302,14 @@ impl ScoreProvider for FileSongProvider {
         todo!()
     }
 
-    fn insert_score(&mut self, id: &SongDiffId, score: Score) -> anyhow::Result<()> {
-        {
+    fn insert_score(
+        &mut self,
+        id: &SongDiffId,
+        mut score: Score,
+        replay: Option<String>,
+    ) -> anyhow::Result<()> {
+        score.has_replay = replay.is_some();
+        score.id = {
             let Score {
                 gauge,
                 gauge_type,
@@ -793,6 +1328,8 @@ impl ScoreProvider for FileSongProvider {
                 earlies,
                 lates,
                 combo,
+                speed_mod_type,
+                speed_mod_value,
                 ..
             } = score;
 
@@ -800,7 +1337,7 @@ impl ScoreProvider for FileSongProvider {
                 bail!("Hash required")
             };
 
-            block_on(self.database.add_score(ScoreEntry {
+            let inserted = block_on(self.database.add_score(ScoreEntry {
                 rowid: 0,
                 score: score as _,
                 crit: perfects as _,
@@ -811,7 +1348,7 @@ impl ScoreProvider for FileSongProvider {
                 miss: misses as _,
                 gauge: gauge as _,
                 auto_flags: auto_flags as _,
-                replay: None,
+                replay,
                 timestamp: timestamp as _,
                 chart_hash: hash.to_string(),
                 user_name: "".to_string(),
@@ -826,8 +1363,12 @@ impl ScoreProvider for FileSongProvider {
                 gauge_opt: 0,
                 mirror,
                 random,
+                speed_mod_type: speed_mod_type as _,
+                speed_mod_value,
+                window_variant: hit_window.variant as _,
             }))?;
-        }
+            inserted.last_insert_rowid()
+        };
 
         self.score_bus
             .broadcast(ScoreProviderEvent::NewScore(id.clone(), score));
@@ -839,6 +1380,12 @@ impl ScoreProvider for FileSongProvider {
         self.score_bus.add_rx()
     }
 
+    fn get_replay(&self, score: &Score) -> anyhow::Result<Replay> {
+        let replay = block_on(self.database.get_replay(score.id))?
+            .context("No replay stored for the score")?;
+        Replay::decode(&replay)
+    }
+
     fn init_scores(&self, songs: &mut dyn Iterator<Item = &Arc<Song>>) -> anyhow::Result<()> {
         let mut scores = block_on(self.database.get_all_scores())?;
 
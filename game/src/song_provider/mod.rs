@@ -1,7 +1,7 @@
 #![allow(unused)]
 
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     default,
     fmt::{format, Debug, Display, Write},
     str::FromStr,
@@ -23,8 +23,11 @@ use tealr::{
     ToTypename, TypeName,
 };
 
-use crate::{results::Score, songselect::Song};
+use crate::{game::replay::Replay, results::Score, songselect::Song};
 use specta::Type;
+mod analysis;
+mod archive;
+mod downloads;
 mod files;
 mod nautica;
 
@@ -82,6 +85,7 @@ pub enum SongSortType {
     Date,
     Artist,
     Effector,
+    Level,
 }
 
 #[derive(
@@ -109,6 +113,7 @@ impl From<SongSort> for (rusc_database::SortColumn, rusc_database::SortDir) {
                 SongSortType::Date => rusc_database::SortColumn::Date,
                 SongSortType::Artist => rusc_database::SortColumn::Artist,
                 SongSortType::Effector => rusc_database::SortColumn::Effector,
+                SongSortType::Level => rusc_database::SortColumn::Level,
             },
             match val.direction {
                 SortDir::Asc => rusc_database::SortDir::Asc,
@@ -135,6 +140,7 @@ impl Display for SongSort {
             SongSortType::Date => formatter.write_str("Date"),
             SongSortType::Artist => formatter.write_str("Artist"),
             SongSortType::Effector => formatter.write_str("Effector"),
+            SongSortType::Level => formatter.write_str("Level"),
         }?;
 
         formatter.write_str(" ")?;
@@ -166,15 +172,72 @@ impl Display for SongFilterType {
     }
 }
 
+/// Names of the clear marks a chart can be filtered by, starting from charts without scores
+pub const CLEAR_FILTER_NAMES: [&str; 6] = [
+    "Not Played",
+    "Played",
+    "Cleared",
+    "Hard Cleared",
+    "Full Combo",
+    "Perfect",
+];
+
+/// Extra song filters set from the song select filter panel, all of which have to match
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default)]
+pub struct SongFilterCriteria {
+    pub level_range: Option<(u8, u8)>,
+    pub bpm_range: Option<(f64, f64)>,
+    pub effector: Option<String>,
+    /// Lowest best clear mark, indexing [`CLEAR_FILTER_NAMES`]
+    pub clear: Option<u8>,
+    /// Lowest best score, from one of the [`crate::results::GRADES`]
+    pub min_score: Option<u32>,
+}
+
+impl Display for SongFilterCriteria {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut parts = vec![];
+        if let Some((min, max)) = self.level_range {
+            parts.push(format!("Level {min}-{max}"));
+        }
+        if let Some((min, max)) = self.bpm_range {
+            parts.push(format!("BPM {min}-{max}"));
+        }
+        if let Some(effector) = &self.effector {
+            parts.push(format!("Effector: {effector}"));
+        }
+        if let Some(name) = self.clear.and_then(|c| CLEAR_FILTER_NAMES.get(c as usize)) {
+            parts.push(format!("Clear: {name}"));
+        }
+        if let Some(min_score) = self.min_score {
+            parts.push(format!("Grade: {}+", crate::results::grade(min_score)));
+        }
+
+        formatter.write_str(&parts.join(", "))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct SongFilter {
     pub filter_type: SongFilterType,
     pub level: u8,
+    #[serde(default)]
+    pub criteria: SongFilterCriteria,
 }
 
 impl SongFilter {
     pub fn new(filter_type: SongFilterType, level: u8) -> Self {
-        Self { filter_type, level }
+        Self {
+            filter_type,
+            level,
+            criteria: Default::default(),
+        }
+    }
+
+    pub fn with_criteria(mut self, criteria: SongFilterCriteria) -> Self {
+        self.criteria = criteria;
+        self
     }
 }
 
@@ -311,8 +374,23 @@ impl SongDiffId {
 
 impl TealData for SongDiffId {}
 pub type PreviewResult = anyhow::Result<(Box<dyn Source<Item = f32> + Send>, Duration, Duration)>;
-pub type LoadSongFn =
-    Box<dyn FnOnce() -> anyhow::Result<(Chart, Box<dyn rodio::Source<Item = f32> + Send>)> + Send>;
+pub type AudioSource = Box<dyn Source<Item = f32> + Send>;
+/// Background images used by a chart, by file name
+pub type ChartImages = HashMap<String, Vec<u8>>;
+pub type ChartAudio = HashMap<String, Vec<u8>>;
+
+/// A chart with everything from its folder needed to play it
+pub struct LoadedSong {
+    pub chart: Chart,
+    pub audio: AudioSource,
+    /// The pre-rendered FX track, for legacy KSH charts that have one
+    pub fx_audio: Option<AudioSource>,
+    pub images: ChartImages,
+    /// Audio files played by SwitchAudio effects and FX chips
+    pub effect_audio: ChartAudio,
+}
+
+pub type LoadSongFn = Box<dyn FnOnce() -> anyhow::Result<LoadedSong> + Send>;
 
 pub trait SongProvider: Send {
     fn subscribe(&mut self) -> bus::BusReader<SongProviderEvent>;
@@ -328,14 +406,44 @@ pub trait SongProvider: Send {
     fn get_preview(&self, id: &SongId) -> Promise<PreviewResult>;
     fn get_all(&self) -> (Vec<Arc<Song>>, Vec<SongId>);
     fn refresh(&mut self) {}
+    /// Names of all collections with songs in them
+    fn list_collections(&self) -> Vec<String> {
+        vec![]
+    }
+    /// Collections the song has been added to
+    fn song_collections(&self, _id: &SongId) -> Vec<String> {
+        vec![]
+    }
+    fn add_to_collection(&mut self, _id: &SongId, _collection: &str) -> anyhow::Result<()> {
+        anyhow::bail!("Collections are not supported by this song provider")
+    }
+    fn remove_from_collection(&mut self, _id: &SongId, _collection: &str) -> anyhow::Result<()> {
+        anyhow::bail!("Collections are not supported by this song provider")
+    }
+    /// Whether songs can be added to the [`DownloadQueue`]
+    fn can_download(&self) -> bool {
+        false
+    }
+    fn download(&mut self, _id: &SongId) -> anyhow::Result<()> {
+        anyhow::bail!("Songs from this provider can't be downloaded")
+    }
 }
 
 pub trait ScoreProvider {
     fn subscribe(&mut self) -> bus::BusReader<ScoreProviderEvent>;
     fn get_scores(&mut self, id: &SongDiffId) -> Vec<Score>;
-    fn insert_score(&mut self, id: &SongDiffId, score: Score) -> anyhow::Result<()>;
+    /// Stores `score` along with an encoded [`Replay`] of the play.
+    fn insert_score(
+        &mut self,
+        id: &SongDiffId,
+        score: Score,
+        replay: Option<String>,
+    ) -> anyhow::Result<()>;
+    fn get_replay(&self, score: &Score) -> anyhow::Result<Replay>;
     fn init_scores(&self, songs: &mut dyn Iterator<Item = &Arc<Song>>) -> anyhow::Result<()>;
 }
 
+pub use analysis::Radar;
+pub use downloads::{Download, DownloadQueue, DownloadState};
 pub use files::FileSongProvider;
 pub use nautica::NauticaSongProvider;
@@ -17,14 +17,17 @@ use rodio::Source;
 
 use crate::{
     async_service::AsyncService,
-    project_dirs,
+    cache_dir,
     results::Score,
     song_provider::SongFilterType,
     songselect::{Difficulty, Song},
     worker_service::WorkerService,
 };
 
-use super::{DiffId, LoadSongFn, SongDiffId, SongFilter, SongId, SongProvider, SongProviderEvent};
+use super::{
+    AudioSource, DiffId, Download, LoadSongFn, LoadedSong, SongDiffId, SongFilter, SongId,
+    SongProvider, SongProviderEvent,
+};
 use anyhow::{anyhow, bail, ensure, Result};
 use kson::Ksh;
 use poll_promise::Promise;
@@ -152,7 +155,7 @@ impl Datum {
             tags: _,
         } = self;
 
-        let mut song_path = project_dirs().cache_dir().to_path_buf();
+        let mut song_path = cache_dir();
         song_path.push(id.as_hyphenated().to_string());
 
         std::fs::create_dir_all(&song_path);
@@ -203,6 +206,9 @@ impl Chart {
             scores: vec![],
             hash: None,
             illustrator: String::new(),
+            density: vec![],
+            radar: None,
+            information: None,
         }
     }
 }
@@ -221,6 +227,7 @@ pub struct NauticaSongProvider {
         std::sync::mpsc::Receiver<Datum>,
     ),
     async_worker: Arc<std::sync::RwLock<AsyncService>>,
+    downloads: Sender<Download>,
 }
 
 impl Debug for NauticaSongProvider {
@@ -234,7 +241,7 @@ async fn next_songs(path: String) -> Result<NauticaSongs> {
     //TODO: Async requests
     let nautica_songs = reqwest::get(&path).await?.json::<NauticaSongs>().await?;
     for x in &nautica_songs.data {
-        let mut song_path = project_dirs().cache_dir().to_path_buf();
+        let mut song_path = cache_dir();
         song_path.push(x.id.hyphenated().to_string());
         std::fs::create_dir_all(&song_path)?;
         if x.jacket_url.ends_with("png") {
@@ -277,7 +284,7 @@ async fn next_songs(path: String) -> Result<NauticaSongs> {
 }
 
 impl NauticaSongProvider {
-    pub fn new(async_worker: RefMut<AsyncService>) -> Self {
+    pub fn new(async_worker: RefMut<AsyncService>, downloads: Sender<Download>) -> Self {
         let local_data = std::fs::read_to_string(cache_path())
             .ok()
             .and_then(|x| serde_json::from_str(&x).ok())
@@ -294,6 +301,7 @@ impl NauticaSongProvider {
             local_data,
             song_loaded: std::sync::mpsc::channel(),
             async_worker,
+            downloads,
         }
     }
 
@@ -379,7 +387,7 @@ impl WorkerService for NauticaSongProvider {
 }
 
 fn cache_path() -> PathBuf {
-    let mut path = project_dirs().cache_dir().to_path_buf();
+    let mut path = cache_dir();
     path.push("nautica_cache.json");
     path
 }
@@ -464,7 +472,7 @@ impl SongProvider for NauticaSongProvider {
 
         let song_uuid = Uuid::parse_str(song_id)?;
 
-        let mut song_path = project_dirs().cache_dir().to_path_buf();
+        let mut song_path = cache_dir();
 
         song_path.push(song_uuid.hyphenated().to_string());
         log::info!("Writing song cache {:?}", &song_path);
@@ -504,7 +512,7 @@ impl SongProvider for NauticaSongProvider {
 
             let song_uuid = Uuid::parse_str(&song_id)?;
 
-            let mut song_path = project_dirs().cache_dir().to_path_buf();
+            let mut song_path = cache_dir();
 
             song_path.push(song_uuid.hyphenated().to_string());
             log::info!("Writing song cache {:?}", &song_path);
@@ -564,11 +572,34 @@ impl SongProvider for NauticaSongProvider {
     fn refresh(&mut self) {
         self.query_changed();
     }
+
+    fn can_download(&self) -> bool {
+        true
+    }
+
+    fn download(&mut self, id: &SongId) -> anyhow::Result<()> {
+        let SongId::StringId(song_id) = id else {
+            bail!("Bad song id")
+        };
+
+        let song = self
+            .all_songs
+            .iter()
+            .find(|x| x.id == *id)
+            .ok_or(anyhow!("song id not in song list"))?;
+
+        self.downloads.send(Download::new(
+            Uuid::parse_str(song_id)?,
+            song.title.clone(),
+            song.artist.clone(),
+        ))?;
+        Ok(())
+    }
 }
 
 fn download_song(id: Uuid, diff: u8, on_loaded: Sender<Datum>) -> anyhow::Result<LoadSongFn> {
     Ok(Box::new(move || {
-        let mut song_path = project_dirs().cache_dir().to_path_buf();
+        let mut song_path = cache_dir();
 
         song_path.push(id.hyphenated().to_string());
         song_path.push("data.zip");
@@ -591,10 +622,7 @@ fn download_song(id: Uuid, diff: u8, on_loaded: Sender<Datum>) -> anyhow::Result
     }))
 }
 
-fn song_from_zip(
-    data: impl std::io::Read + std::io::Seek,
-    diff: u8,
-) -> Result<(kson::Chart, Box<dyn rodio::Source<Item = f32> + Send>)> {
+fn song_from_zip(data: impl std::io::Read + std::io::Seek, diff: u8) -> Result<LoadedSong> {
     let mut archive = zip::read::ZipArchive::new(data)?;
     for i in 0..archive.len() {
         let mut file = archive.by_index(i)?;
@@ -622,11 +650,64 @@ fn song_from_zip(
                 let mut bgm_buf = Vec::new();
                 bgm_entry.read_to_end(&mut bgm_buf)?;
                 let bgm_cursor = std::io::Cursor::new(bgm_buf);
-
-                return Ok((
+                drop(bgm_entry);
+
+                let fx_audio = chart.audio.bgm.fx_filename().and_then(|fx_name| {
+                    let fx_path = file_folder.with_file_name(fx_name);
+                    let fx_path = fx_path.to_str().unwrap_or("").replace('\\', "/");
+                    let mut fx_buf = Vec::new();
+                    archive
+                        .by_name(&fx_path)
+                        .ok()?
+                        .read_to_end(&mut fx_buf)
+                        .ok()?;
+                    let fx_audio = rodio::Decoder::new(std::io::Cursor::new(fx_buf))
+                        .map_err(|e| warn!("Failed to open FX track {fx_path}: {e}"))
+                        .ok()?;
+                    Some(Box::new(fx_audio.convert_samples()) as AudioSource)
+                });
+
+                let images = chart
+                    .bg
+                    .image_filenames()
+                    .into_iter()
+                    .filter_map(|name| {
+                        let image_path = file_folder.with_file_name(name);
+                        let image_path = image_path.to_str().unwrap_or("").replace('\\', "/");
+                        let mut image_buf = Vec::new();
+                        archive
+                            .by_name(&image_path)
+                            .ok()?
+                            .read_to_end(&mut image_buf)
+                            .ok()?;
+                        Some((name.to_string(), image_buf))
+                    })
+                    .collect();
+
+                let effect_audio = chart
+                    .audio
+                    .effect_filenames()
+                    .into_iter()
+                    .filter_map(|name| {
+                        let audio_path = file_folder.with_file_name(name);
+                        let audio_path = audio_path.to_str().unwrap_or("").replace('\\', "/");
+                        let mut audio_buf = Vec::new();
+                        archive
+                            .by_name(&audio_path)
+                            .ok()?
+                            .read_to_end(&mut audio_buf)
+                            .ok()?;
+                        Some((name.to_string(), audio_buf))
+                    })
+                    .collect();
+
+                return Ok(LoadedSong {
+                    audio: Box::new(rodio::Decoder::new(bgm_cursor)?.convert_samples()),
+                    fx_audio,
+                    images,
+                    effect_audio,
                     chart,
-                    Box::new(rodio::Decoder::new(bgm_cursor)?.convert_samples()),
-                ));
+                });
             }
         }
     }
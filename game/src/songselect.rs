@@ -2,25 +2,31 @@ use crate::{
     async_service::AsyncService,
     button_codes::{LaserAxis, LaserState, UscButton, UscInputEvent},
     config::GameConfig,
+    game::replay::Replay,
     game_main::AutoPlay,
     help::await_task,
     input_state::InputState,
     lua_service::LuaProvider,
-    results::Score,
+    results::{Score, GRADES},
     scene::{Scene, SceneData},
-    settings_dialog::SettingsDialog,
+    settings_dialog::{SettingsDialog, SettingsDialogTab},
     song_provider::{
-        self, DiffId, ScoreProvider, ScoreProviderEvent, SongDiffId, SongFilter, SongFilterType,
-        SongId, SongProvider, SongProviderEvent, SongSort,
+        self, DiffId, Download, DownloadQueue, Radar, ScoreProvider, ScoreProviderEvent,
+        SongDiffId, SongFilter, SongFilterCriteria, SongFilterType, SongId, SongProvider,
+        SongProviderEvent, SongSort, CLEAR_FILTER_NAMES,
     },
     take_duration_fade::take_duration_fade,
+    vg_ui::Vgfx,
     ControlMessage, RuscMixer,
 };
 use anyhow::{anyhow, ensure, Result};
 use di::{RefMut, ServiceProvider};
 use game_loop::winit::event::{ElementState, Event, Ime, WindowEvent};
 use itertools::Itertools;
-use kson_rodio_sources::owned_source::{self, owned_source};
+use kson_rodio_sources::{
+    ducking::{ducked, Ducking},
+    owned_source::{self, owned_source},
+};
 use log::warn;
 use puffin::{profile_function, profile_scope};
 use rodio::Source;
@@ -34,7 +40,7 @@ use std::{
     sync::{
         atomic::{AtomicBool, AtomicU64, AtomicUsize},
         mpsc::{self, Receiver, Sender},
-        Arc, RwLock,
+        Arc, Mutex, RwLock,
     },
     time::{Duration, SystemTime},
 };
@@ -65,6 +71,11 @@ pub struct Difficulty {
     pub scores: Vec<Score>, //array of all scores on this diff
     pub hash: Option<String>,
     pub illustrator: String,
+    /// Note density over the length of the chart from 0 to 255, empty when unknown
+    pub density: Vec<u8>,
+    pub radar: Option<Radar>,
+    /// Comment from the charter, see [`kson::MetaInfo::information`]
+    pub information: Option<String>,
 }
 
 impl TealData for Difficulty {
@@ -80,8 +91,12 @@ impl TealData for Difficulty {
         fields.add_field_method_get("difficulty", |_, diff| Ok(diff.difficulty));
         fields.add_field_method_get("id", |_, diff| Ok(diff.id.clone()));
         fields.add_field_method_get("effector", |_, diff| Ok(diff.effector.clone()));
+        fields.add_field_method_get("illustrator", |_, diff| Ok(diff.illustrator.clone()));
+        fields.add_field_method_get("information", |_, diff| Ok(diff.information.clone()));
         fields.add_field_method_get("topBadge", |_, diff| Ok(diff.top_badge));
         fields.add_field_method_get("scores", |_, diff| Ok(diff.scores.clone()));
+        fields.add_field_method_get("density", |_, diff| Ok(diff.density.clone()));
+        fields.add_field_method_get("radar", |lua, diff| lua.to_value(&diff.radar));
     }
 }
 
@@ -114,8 +129,15 @@ pub struct SongSelect {
     search_input_active: bool, //true when the user is currently inputting search text
     search_text: String,       //current string used by the song search
     search_status: String,     //database status
+    filter_summary: String,    //active filter panel criteria
+    filter_criteria: SongFilterCriteria,
+    collections: Vec<String>,      //names of all collections
+    song_collections: Vec<String>, //collections the selected song is in
+    can_download: bool,            //songs are streamed and can be downloaded with BT-D
+    downloads: Vec<Download>,      //queued downloads and their progress
     selected_index: i32,
     selected_diff_index: i32,
+    selected_score: i32, //index in the scores of the selected difficulty
     preview_countdown: f64,
     preview_finished: Arc<AtomicUsize>,
     preview_playing: Arc<AtomicU64>,
@@ -130,6 +152,9 @@ impl TealData for SongSelect {
         fields.add_field_method_get("searchText", |_, songwheel| {
             Ok(songwheel.search_text.clone())
         });
+        fields.add_field_method_get("filterSummary", |_, songwheel| {
+            Ok(songwheel.filter_summary.clone())
+        });
         fields.add_field_method_get(
             "searchStatus",
             |_, _| -> Result<Option<String>, tealr::mlu::mlua::Error> { Ok(None) },
@@ -153,8 +178,15 @@ impl SongSelect {
             search_input_active: false,
             search_text: String::new(),
             search_status: String::new(),
+            filter_summary: String::new(),
+            filter_criteria: SongFilterCriteria::default(),
+            collections: vec![],
+            song_collections: vec![],
+            can_download: false,
+            downloads: vec![],
             selected_index: 0,
             selected_diff_index: 0,
+            selected_score: 0,
             preview_countdown: 1500.0,
             preview_finished: Arc::new(AtomicUsize::new(0)),
             preview_playing: Arc::new(AtomicU64::new(0)),
@@ -171,6 +203,8 @@ impl SceneData for SongSelect {
     }
 }
 pub const KNOB_NAV_THRESHOLD: f32 = std::f32::consts::PI / 3.0;
+/// Collection BT-D adds the selected song to
+const FAVOURITES_COLLECTION: &str = "Favourites";
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum MenuState {
@@ -191,6 +225,8 @@ pub struct SongSelectScene {
     suspended: Arc<AtomicBool>,
     closed: bool,
     mixer: RuscMixer,
+    /// Turns menu sounds down while a preview plays
+    ducking: Arc<Ducking>,
     sample_owner: owned_source::Marker,
     settings_dialog: SettingsDialog,
     settings_closed: SystemTime,
@@ -208,7 +244,17 @@ pub struct SongSelectScene {
     sort_index: usize,
     filters: Vec<song_provider::SongFilterType>,
     sorts: Vec<song_provider::SongSort>,
+    /// Sort picked in the quick options of the settings dialog
+    quick_sort: Arc<AtomicUsize>,
     auto_rx: Receiver<crate::game_main::AutoPlay>,
+    filter_criteria: Arc<Mutex<SongFilterCriteria>>,
+    applied_criteria: SongFilterCriteria,
+    /// Collections to toggle the selected song in, sent from the skin
+    collection_tx: Sender<String>,
+    collection_rx: Receiver<String>,
+    download_queue: RefMut<DownloadQueue>,
+    /// [`DownloadQueue::version`] of the downloads shown
+    downloads_version: u64,
 }
 
 impl SongSelectScene {
@@ -219,13 +265,41 @@ impl SongSelectScene {
         let score_provider: RefMut<dyn ScoreProvider> = services.get_required();
         let score_events = score_provider.write().expect("Lock error").subscribe();
         let song_events = song_provider.write().expect("Lock error").subscribe();
+        song_select.can_download = song_provider.read().expect("Lock error").can_download();
         let (initial_songs, initial_order) = song_provider.write().expect("Lock error").get_all();
         _ = score_provider
             .write()
             .expect("Lock error")
             .init_scores(&mut initial_songs.iter());
+        let effectors = initial_songs
+            .iter()
+            .flat_map(|s| {
+                s.difficulties
+                    .read()
+                    .expect("Lock error")
+                    .iter()
+                    .map(|d| d.effector.clone())
+                    .collect_vec()
+            })
+            .filter(|x| !x.is_empty())
+            .unique()
+            .sorted_by_key(|x| x.to_lowercase())
+            .collect_vec();
         song_select.songs.add(initial_songs, initial_order);
+        let applied_criteria = GameConfig::get().song_select.filter.criteria.clone();
+        song_select.filter_summary = applied_criteria.to_string();
+        song_select.filter_criteria = applied_criteria.clone();
+        let filter_criteria = Arc::new(Mutex::new(applied_criteria.clone()));
         let (auto_tx, auto_rx) = mpsc::channel();
+        let (collection_tx, collection_rx) = mpsc::channel();
+        let quick_sort = Arc::new(AtomicUsize::new(0));
+        let sort_names = song_provider
+            .read()
+            .expect("Lock error")
+            .get_available_sorts()
+            .iter()
+            .map(ToString::to_string)
+            .collect_vec();
         Self {
             filter_lua: LuaProvider::new_lua(),
             sort_lua: LuaProvider::new_lua(),
@@ -238,13 +312,20 @@ impl SongSelectScene {
             suspended: Arc::new(AtomicBool::new(false)),
             closed: false,
             mixer: services.get_required(),
+            ducking: services.get_required(),
             sample_owner,
             input_state: input_state.clone(),
             settings_dialog: SettingsDialog::general_settings(
                 input_state,
                 services.create_scope(),
                 auto_tx,
-            ),
+                quick_sort.clone(),
+                sort_names,
+            )
+            .with_tab(SettingsDialogTab::song_filters(
+                filter_criteria.clone(),
+                effectors,
+            )),
             async_worker: services.get_required(),
             song_events,
             score_events,
@@ -257,11 +338,29 @@ impl SongSelectScene {
             sort_index: 0,
             filters: vec![],
             sorts: vec![],
+            quick_sort,
             settings_closed: SystemTime::UNIX_EPOCH,
             auto_rx,
+            filter_criteria,
+            applied_criteria,
+            collection_tx,
+            collection_rx,
+            download_queue: services.get_required_mut(),
+            downloads_version: u64::MAX,
         }
     }
 
+    fn song_filter(&self) -> SongFilter {
+        SongFilter::new(
+            self.filters
+                .get(self.folder_filter_index)
+                .cloned()
+                .unwrap_or_default(),
+            self.level_filter,
+        )
+        .with_criteria(self.applied_criteria.clone())
+    }
+
     fn on_search(&mut self) {
         _ = self.update_lua();
         self.song_provider
@@ -293,6 +392,8 @@ impl SongSelectScene {
             self.filter_lua.to_value(&json!({
                 "folder": filters.iter().map(|x| x.to_string()).collect_vec(),
                 "level": (0..=20).map(|x| if x == 0 {"All".to_owned()} else {format!("Level: {x}")}).collect_vec(),
+                "clear": CLEAR_FILTER_NAMES,
+                "grade": GRADES.map(|(name, min)| json!({ "name": name, "minScore": min })),
             }))?,
         )?;
 
@@ -303,6 +404,37 @@ impl SongSelectScene {
         Ok((filters, sorts))
     }
 
+    /// Starts loading jackets of the songs around the selected one so they are ready when the
+    /// wheel scrolls to them.
+    fn prefetch_jackets(&self) {
+        const PREFETCH_DISTANCE: i32 = 5;
+        let songs = &self.state.songs;
+        if songs.is_empty() {
+            return;
+        }
+
+        let jackets: Vec<String> = (1..=PREFETCH_DISTANCE)
+            .flat_map(|x| [x, -x])
+            .map(|x| (self.state.selected_index + x).rem_euclid(songs.len() as i32))
+            .unique()
+            .flat_map(|i| {
+                songs[i as usize]
+                    .difficulties
+                    .read()
+                    .expect("Lock error")
+                    .iter()
+                    .map(|x| x.jacket_path.to_string_lossy().to_string())
+                    .collect_vec()
+            })
+            .unique()
+            .collect();
+
+        let vgfx: RefMut<Vgfx> = self.services.get_required();
+        vgfx.write()
+            .expect("Lock error")
+            .prefetch_images(jackets.iter().map(String::as_str));
+    }
+
     fn start_preview(&mut self) {
         let Some(song_id) = self
             .state
@@ -319,6 +451,7 @@ impl SongSelectScene {
         let preview_finished = self.state.preview_finished.clone();
         let owner = self.sample_owner.clone();
         let mixer = self.mixer.clone();
+        let ducking = self.ducking.clone();
 
         if preview_playing.load(std::sync::atomic::Ordering::Relaxed) == song_id.as_u64() {
             return;
@@ -349,11 +482,12 @@ impl SongSelectScene {
                 &owner,
                 song_id.as_u64(),
                 mixer,
+                ducking,
             );
         });
     }
 
-    fn start_song(&mut self, autoplay: AutoPlay) {
+    fn start_song(&mut self, autoplay: AutoPlay, replay: Option<Replay>) {
         let state = &self.state;
         let song = self.state.songs.get(state.selected_index as usize).cloned();
 
@@ -378,6 +512,7 @@ impl SongSelectScene {
                         loader,
                         song: song.clone(),
                         autoplay,
+                        replay,
                     });
                 }
                 Err(err) => {
@@ -387,6 +522,147 @@ impl SongSelectScene {
         }
     }
 
+    fn selected_score(&self) -> Option<Score> {
+        let song = self.state.songs.get(self.state.selected_index as usize)?;
+        let diffs = song.difficulties.read().expect("Lock error");
+        diffs
+            .get(self.state.selected_diff_index as usize)?
+            .scores
+            .get(self.state.selected_score as usize)
+            .cloned()
+    }
+
+    fn set_selected_score(&mut self, index: i32) -> anyhow::Result<()> {
+        if index != self.state.selected_score {
+            self.state.selected_score = index;
+            let raw_state: mlua::Table = self.lua.globals().get("songwheel")?;
+            raw_state.set("selectedScore", index)?;
+        }
+        Ok(())
+    }
+
+    /// Moves the selection in the scores of the selected difficulty.
+    fn advance_score(&mut self, steps: i32) -> anyhow::Result<()> {
+        let score_count = self
+            .state
+            .songs
+            .get(self.state.selected_index as usize)
+            .and_then(|song| {
+                let diffs = song.difficulties.read().expect("Lock error");
+                diffs
+                    .get(self.state.selected_diff_index as usize)
+                    .map(|diff| diff.scores.len())
+            })
+            .unwrap_or_default();
+
+        self.set_selected_score(
+            (self.state.selected_score + steps).clamp(0, score_count.saturating_sub(1) as _),
+        )
+    }
+
+    /// Plays the replay stored with the selected score.
+    fn watch_replay(&mut self) -> anyhow::Result<()> {
+        let Some(score) = self.selected_score() else {
+            return Ok(());
+        };
+        ensure!(score.has_replay, "No replay stored for the selected score");
+        let replay = self
+            .score_provider
+            .read()
+            .expect("Lock error")
+            .get_replay(&score)?;
+        self.start_song(AutoPlay::None, Some(replay));
+        Ok(())
+    }
+
+    /// Updates the collections shown for the selected song, and the names of all collections
+    /// when `all` is set.
+    fn update_collections(&mut self, all: bool) -> anyhow::Result<()> {
+        {
+            let song_provider = self.song_provider.read().expect("Lock error");
+            if all {
+                self.state.collections = song_provider.list_collections();
+            }
+            self.state.song_collections = self
+                .state
+                .songs
+                .get(self.state.selected_index as usize)
+                .map(|song| song_provider.song_collections(&song.id))
+                .unwrap_or_default();
+        }
+
+        let raw_state: mlua::Table = self.lua.globals().get("songwheel")?;
+        raw_state.set("collections", self.state.collections.clone())?;
+        raw_state.set("songCollections", self.state.song_collections.clone())?;
+        Ok(())
+    }
+
+    /// Adds the selected song to `collection`, or removes it if it's already in it.
+    fn toggle_collection(&mut self, collection: &str) -> anyhow::Result<()> {
+        let Some(song) = self.state.songs.get(self.state.selected_index as usize) else {
+            return Ok(());
+        };
+
+        {
+            let mut song_provider = self.song_provider.write().expect("Lock error");
+            if self.state.song_collections.iter().any(|x| x == collection) {
+                song_provider.remove_from_collection(&song.id, collection)?;
+            } else {
+                song_provider.add_to_collection(&song.id, collection)?;
+            }
+        }
+        self.update_collections(true)?;
+
+        // Collections are browsed from the folder filters
+        let filter = self.filters.get(self.folder_filter_index).cloned();
+        (self.filters, self.sorts) = self.update_filter_sort_lua()?;
+        match filter.and_then(|f| self.filters.iter().position(|x| *x == f)) {
+            Some(index) if index == self.folder_filter_index => {}
+            Some(index) => {
+                self.folder_filter_index = index;
+                let set_selection: Function = self.filter_lua.globals().get("set_selection")?;
+                set_selection.call((self.folder_filter_index + 1, true))?;
+            }
+            None => {
+                // The browsed collection is empty now
+                self.folder_filter_index = 0;
+                self.song_provider
+                    .write()
+                    .expect("Lock error")
+                    .set_filter(self.song_filter());
+                let set_selection: Function = self.filter_lua.globals().get("set_selection")?;
+                set_selection.call((self.folder_filter_index + 1, true))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Shows the progress of the downloads when they have changed.
+    fn update_downloads(&mut self) -> anyhow::Result<()> {
+        {
+            let queue = self.download_queue.read().expect("Lock error");
+            if queue.version() == self.downloads_version {
+                return Ok(());
+            }
+            self.downloads_version = queue.version();
+            self.state.downloads = queue.downloads().to_vec();
+        }
+
+        let raw_state: mlua::Table = self.lua.globals().get("songwheel")?;
+        raw_state.set("downloads", self.lua.to_value(&self.state.downloads)?)?;
+        Ok(())
+    }
+
+    fn download_selected(&mut self) -> anyhow::Result<()> {
+        let Some(song) = self.state.songs.get(self.state.selected_index as usize) else {
+            return Ok(());
+        };
+        self.song_provider
+            .write()
+            .expect("Lock error")
+            .download(&song.id)
+    }
+
     fn reload_scores(&mut self) -> std::result::Result<(), anyhow::Error> {
         let mut songs = self.state.songs.values();
         self.score_provider
@@ -406,8 +682,11 @@ fn add_preview_source<T: Source<Item = f32> + Send + 'static>(
     owner: &owned_source::Marker,
     song_id_u64: u64,
     mixer: RuscMixer,
+    ducking: Arc<Ducking>,
 ) {
     let mut amp = 1.0f32;
+    let duck_volume = GameConfig::get().preview_duck_volume;
+    let mut duck = None;
     preview_playing.store(song_id_u64, std::sync::atomic::Ordering::Relaxed);
     preview_finished.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
@@ -431,6 +710,11 @@ fn add_preview_source<T: Source<Item = f32> + Send + 'static>(
 
         let amp = &mut amp;
         let current_preview = preview_playing.load(std::sync::atomic::Ordering::Relaxed);
+        let audible =
+            current_preview == song_id_u64 && !suspended.load(std::sync::atomic::Ordering::Relaxed);
+        if audible != duck.is_some() {
+            duck = audible.then(|| ducking.duck(duck_volume));
+        }
         if current_preview != song_id_u64 {
             *amp -= 1.0 / 50.0;
             if *amp < 0.0 {
@@ -529,7 +813,8 @@ impl Scene for SongSelectScene {
                                     diff,
                                     song,
                                     loader,
-                                    autoplay: crate::game_main::AutoPlay::None
+                                    autoplay: crate::game_main::AutoPlay::None,
+                                    replay: None,
                                 })
                                 .is_ok());
                         }
@@ -552,6 +837,14 @@ impl Scene for SongSelectScene {
 
         self.settings_dialog.init_lua(&lua_provider)?;
         self.program_control = Some(app_control_tx);
+        let collection_tx = self.collection_tx.clone();
+        self.lua.globals().set(
+            "toggle_collection",
+            self.lua.create_function(move |_, collection: String| {
+                _ = collection_tx.send(collection);
+                Ok(())
+            })?,
+        )?;
         lua_provider.register_libraries(self.lua.clone(), "songselect/songwheel.lua")?;
         lua_provider
             .register_libraries(self.background_lua.clone(), "songselect/background.lua")?;
@@ -559,29 +852,19 @@ impl Scene for SongSelectScene {
         lua_provider.register_libraries(self.filter_lua.clone(), "songselect/filterwheel.lua")?;
         lua_provider.register_libraries(self.sort_lua.clone(), "songselect/sortwheel.lua")?;
         (self.filters, self.sorts) = self.update_filter_sort_lua()?;
+        self.update_collections(true)?;
 
-        let mut bgm_amp = 1_f32;
-        let preview_playing = self.state.preview_finished.clone();
         let suspended = self.suspended.clone();
         self.mixer.add(owned_source(
-            rodio::source::Zero::new(2, 44100) //TODO: Load something from skin audio
-                .amplify(0.2)
-                .pausable(false)
-                .amplify(1.0)
-                .periodic_access(Duration::from_millis(10), move |state| {
-                    state
-                        .inner_mut()
-                        .set_paused(suspended.load(std::sync::atomic::Ordering::Relaxed));
-
-                    let amp = &mut bgm_amp;
-                    if preview_playing.load(std::sync::atomic::Ordering::SeqCst) == 0 {
-                        *amp += 1.0 / 50.0;
-                    } else {
-                        *amp -= 1.0 / 50.0;
-                    }
-                    *amp = amp.clamp(0.0, 1.0);
-                    state.set_factor(*amp);
-                }),
+            ducked(
+                rodio::source::Zero::new(2, 44100) //TODO: Load something from skin audio
+                    .amplify(0.2)
+                    .pausable(false)
+                    .periodic_access(Duration::from_millis(10), move |state| {
+                        state.set_paused(suspended.load(std::sync::atomic::Ordering::Relaxed));
+                    }),
+                self.ducking.clone(),
+            ),
             &self.sample_owner,
         ));
 
@@ -599,6 +882,29 @@ impl Scene for SongSelectScene {
         let diff_advance_steps = (self.diff_advance / KNOB_NAV_THRESHOLD).trunc() as i32;
         self.diff_advance -= diff_advance_steps as f32 * KNOB_NAV_THRESHOLD;
 
+        let criteria = self.filter_criteria.lock().expect("Lock error").clone();
+        if criteria != self.applied_criteria {
+            self.applied_criteria = criteria;
+            self.state.filter_summary = self.applied_criteria.to_string();
+            self.state.filter_criteria = self.applied_criteria.clone();
+            self.song_provider
+                .write()
+                .expect("Lock error")
+                .set_filter(self.song_filter());
+            self.update_lua()?;
+        }
+
+        let quick_sort = self.quick_sort.load(std::sync::atomic::Ordering::Relaxed);
+        if quick_sort != self.sort_index && quick_sort < self.sorts.len() {
+            self.sort_index = quick_sort;
+            self.song_provider
+                .write()
+                .expect("Lock error")
+                .set_sort(self.sorts[self.sort_index]);
+            let set_selection: Function = self.sort_lua.globals().get("set_selection")?;
+            set_selection.call(self.sort_index + 1)?;
+        }
+
         // Tick song audio preview
         if song_advance_steps == 0
             && self.state.preview_countdown > 0.0
@@ -694,6 +1000,7 @@ impl Scene for SongSelectScene {
             if index_dirty {
                 let set_song_idx: Function = self.lua.globals().get("set_index")?;
                 set_song_idx.call::<_, i32>(self.state.selected_index + 1)?;
+                self.update_collections(false)?;
             }
 
             let diff = self.state.selected_diff_index;
@@ -732,6 +1039,8 @@ impl Scene for SongSelectScene {
                         let set_song_idx: Function = self.lua.globals().get("set_index")?;
 
                         set_song_idx.call::<_, ()>(self.state.selected_index + 1)?;
+                        self.prefetch_jackets();
+                        self.update_collections(false)?;
                     }
 
                     if diff_advance_steps != 0 || song_advance_steps != 0 {
@@ -751,6 +1060,7 @@ impl Scene for SongSelectScene {
                             let set_diff_idx: Function = self.lua.globals().get("set_diff")?;
                             set_diff_idx.call::<_, ()>(self.state.selected_diff_index + 1)?;
                         }
+                        self.set_selected_score(0)?;
                     }
                 }
             }
@@ -767,6 +1077,8 @@ impl Scene for SongSelectScene {
                             .write()
                             .expect("Lock error")
                             .set_sort(self.sorts[self.sort_index]);
+                        self.quick_sort
+                            .store(self.sort_index, std::sync::atomic::Ordering::Relaxed);
                         let set_selection: Function =
                             self.sort_lua.globals().get("set_selection")?;
                         set_selection.call(self.sort_index + 1)?;
@@ -781,10 +1093,7 @@ impl Scene for SongSelectScene {
                     self.song_provider
                         .write()
                         .expect("Lock error")
-                        .set_filter(SongFilter::new(
-                            self.filters[self.folder_filter_index].clone(),
-                            self.level_filter,
-                        ));
+                        .set_filter(self.song_filter());
                     let set_selection: Function = self.filter_lua.globals().get("set_selection")?;
                     set_selection.call((self.level_filter + 1, false))?;
                 }
@@ -796,12 +1105,10 @@ impl Scene for SongSelectScene {
                         .rem_euclid(self.filters.len() as _)
                         as _;
                     if (diff_advance_steps + song_advance_steps) != 0 {
-                        self.song_provider.write().expect("Lock error").set_filter(
-                            SongFilter::new(
-                                self.filters[self.folder_filter_index].clone(),
-                                self.level_filter,
-                            ),
-                        );
+                        self.song_provider
+                            .write()
+                            .expect("Lock error")
+                            .set_filter(self.song_filter());
                         let set_selection: Function =
                             self.filter_lua.globals().get("set_selection")?;
                         set_selection.call((self.folder_filter_index + 1, true))?;
@@ -811,9 +1118,15 @@ impl Scene for SongSelectScene {
         }
 
         if let Ok(autoplay) = self.auto_rx.try_recv() {
-            self.start_song(autoplay);
+            self.start_song(autoplay, None);
         }
 
+        while let Ok(collection) = self.collection_rx.try_recv() {
+            crate::log_result!(self.toggle_collection(&collection));
+        }
+
+        crate::log_result!(self.update_downloads());
+
         Ok(())
     }
 
@@ -858,10 +1171,7 @@ impl Scene for SongSelectScene {
                     self.song_provider
                         .write()
                         .unwrap()
-                        .set_filter(SongFilter::new(
-                            self.filters[self.folder_filter_index].clone(),
-                            self.level_filter,
-                        ));
+                        .set_filter(self.song_filter());
                     _ = self.update_lua();
                     _ = self.update_filter_sort_lua();
                 }
@@ -875,10 +1185,7 @@ impl Scene for SongSelectScene {
                         self.song_provider
                             .write()
                             .unwrap()
-                            .set_filter(SongFilter::new(
-                                song_filter_type.clone(),
-                                self.level_filter,
-                            ));
+                            .set_filter(self.song_filter());
                         _ = self.update_lua();
                         _ = self.update_filter_sort_lua();
                     }
@@ -886,6 +1193,8 @@ impl Scene for SongSelectScene {
                 crate::companion_interface::ClientEvent::SetSongSort(song_sort) => {
                     if let Some(pos) = self.sorts.iter().find_position(|x| **x == *song_sort) {
                         self.sort_index = pos.0;
+                        self.quick_sort
+                            .store(self.sort_index, std::sync::atomic::Ordering::Relaxed);
                         self.song_provider.write().unwrap().set_sort(*song_sort);
                         _ = self.update_lua();
                         _ = self.update_filter_sort_lua();
@@ -973,7 +1282,7 @@ impl Scene for SongSelectScene {
             UscButton::Start => {
                 match self.menu_state {
                     MenuState::Songs => {
-                        self.start_song(AutoPlay::None);
+                        self.start_song(AutoPlay::None, None);
                     }
                     MenuState::Levels => {
                         self.menu_state = MenuState::Folders;
@@ -1008,6 +1317,17 @@ impl Scene for SongSelectScene {
                 let mut song_provider = self.song_provider.write().unwrap();
                 song_provider.refresh()
             }
+            UscButton::BT(lane) if MenuState::Songs == self.menu_state => match lane {
+                kson::BtLane::A => crate::log_result!(self.watch_replay()),
+                kson::BtLane::B => crate::log_result!(self.advance_score(-1)),
+                kson::BtLane::C => crate::log_result!(self.advance_score(1)),
+                kson::BtLane::D if self.state.can_download => {
+                    crate::log_result!(self.download_selected())
+                }
+                kson::BtLane::D => {
+                    crate::log_result!(self.toggle_collection(FAVOURITES_COLLECTION))
+                }
+            },
             _ => (),
         }
     }
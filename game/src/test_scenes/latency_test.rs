@@ -0,0 +1,369 @@
+use std::{
+    collections::VecDeque,
+    f32::consts::TAU,
+    sync::mpsc::Sender,
+    time::{Duration, SystemTime},
+};
+
+use di::ServiceProvider;
+use egui::{Color32, Sense};
+use kson::{Chart, Interval, TimeSignature, KSON_RESOLUTION};
+use kson_rodio_sources::owned_source::{self, owned_source};
+use rodio::Source;
+
+use crate::{
+    button_codes::{LaserState, UscButton},
+    config::GameConfig,
+    scene::Scene,
+    InnerRuscMixer,
+};
+
+const BPM: f64 = 120.0;
+const MEASURES: u32 = 16;
+const SAMPLE_RATE: u32 = 44100;
+const CLICK_SAMPLES: u64 = SAMPLE_RATE as u64 / 50;
+const FLASH_MS: f64 = 80.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TestMode {
+    /// Only the click track is played
+    Audio,
+    /// Only the flashing box is shown
+    Visual,
+}
+
+/// Offset suggested by the latency test
+#[derive(Debug, Clone, Copy)]
+pub enum SuggestedOffset {
+    Global(i32),
+    Button(i32),
+}
+
+struct TapStats {
+    mean: f64,
+    std_dev: f64,
+    /// 95% confidence interval of the mean
+    confidence: f64,
+    count: usize,
+}
+
+impl TapStats {
+    fn new(deltas: &[f64]) -> Option<Self> {
+        if deltas.len() < 2 {
+            return None;
+        }
+
+        let count = deltas.len();
+        let mean = deltas.iter().sum::<f64>() / count as f64;
+        let variance = deltas.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (count - 1) as f64;
+        let std_dev = variance.sqrt();
+
+        Some(Self {
+            mean,
+            std_dev,
+            confidence: 1.96 * std_dev / (count as f64).sqrt(),
+            count,
+        })
+    }
+}
+
+/// Plays a steady 4/4 chart and records how far from each chip the buttons are hit, to verify
+/// and suggest offsets. The first measure is a count-in and is not recorded.
+pub struct LatencyTest {
+    services: ServiceProvider,
+    chart: Chart,
+    chip_times: Vec<f64>,
+    mode: TestMode,
+    start: Option<SystemTime>,
+    source_owner: Option<owned_source::Marker>,
+    audio_deltas: Vec<f64>,
+    visual_deltas: Vec<f64>,
+    offset_tx: Sender<SuggestedOffset>,
+    close: bool,
+}
+
+impl LatencyTest {
+    /// Suggested offsets are sent through `offset_tx` when the user chooses to apply them.
+    pub fn new(services: ServiceProvider, offset_tx: Sender<SuggestedOffset>) -> Self {
+        let chart = synthetic_chart();
+        let chip_times = chart.note.bt[0]
+            .iter()
+            .map(|x| chart.tick_to_ms(x.y))
+            .collect();
+
+        Self {
+            services,
+            chart,
+            chip_times,
+            mode: TestMode::Audio,
+            start: None,
+            source_owner: None,
+            audio_deltas: vec![],
+            visual_deltas: vec![],
+            offset_tx,
+            close: false,
+        }
+    }
+
+    fn elapsed_ms(&self, time: SystemTime) -> Option<f64> {
+        let start = self.start?;
+        Some(match time.duration_since(start) {
+            Ok(d) => d.as_secs_f64() * 1000.0,
+            Err(e) => -e.duration().as_secs_f64() * 1000.0,
+        })
+    }
+
+    fn start(&mut self) {
+        match self.mode {
+            TestMode::Audio => {
+                self.audio_deltas.clear();
+                let owner = owned_source::Marker::new();
+                let clicks = self
+                    .chip_times
+                    .iter()
+                    .enumerate()
+                    .map(|(i, ms)| ((ms / 1000.0 * SAMPLE_RATE as f64) as u64, i % 4 == 0))
+                    .collect();
+
+                self.services
+                    .get_required::<InnerRuscMixer>()
+                    .add(owned_source(ClickTrack { clicks, sample: 0 }, &owner));
+                self.source_owner = Some(owner);
+            }
+            TestMode::Visual => self.visual_deltas.clear(),
+        }
+
+        self.start = Some(SystemTime::now());
+    }
+
+    fn stop(&mut self) {
+        self.start = None;
+        self.source_owner = None;
+    }
+
+    fn running(&self) -> bool {
+        self.start.is_some()
+    }
+
+    fn show_results(&self, ui: &mut egui::Ui) {
+        let current_offset = GameConfig::get().global_offset;
+
+        ui.label("Audio");
+        if let Some(stats) = TapStats::new(&self.audio_deltas) {
+            let suggested = current_offset + stats.mean.round() as i32;
+            ui.label(format!(
+                "{:+.1} ms ±{:.1} ms (σ {:.1} ms, {} taps), suggested global offset: {} ms",
+                stats.mean, stats.confidence, stats.std_dev, stats.count, suggested
+            ));
+            if ui.button("Use").clicked() {
+                _ = self.offset_tx.send(SuggestedOffset::Global(suggested));
+            }
+        } else {
+            ui.label("No data");
+        }
+        ui.end_row();
+
+        // Input timestamps already include the global offset, so what is left over belongs
+        // to the button offset
+        ui.label("Visual");
+        if let Some(stats) = TapStats::new(&self.visual_deltas) {
+            let suggested = stats.mean.round() as i32;
+            ui.label(format!(
+                "{:+.1} ms ±{:.1} ms (σ {:.1} ms, {} taps), suggested button offset: {} ms",
+                stats.mean, stats.confidence, stats.std_dev, stats.count, suggested
+            ));
+            if ui.button("Use").clicked() {
+                _ = self.offset_tx.send(SuggestedOffset::Button(suggested));
+            }
+        } else {
+            ui.label("No data");
+        }
+        ui.end_row();
+    }
+}
+
+fn synthetic_chart() -> Chart {
+    let mut chart = Chart::new();
+    chart.beat.bpm.push((0, BPM));
+    chart.beat.time_sig.push((0, TimeSignature(4, 4)));
+    chart.note.bt[0] = (0..MEASURES * 4)
+        .map(|beat| Interval {
+            y: beat * KSON_RESOLUTION,
+            l: 0,
+        })
+        .collect();
+    chart
+}
+
+/// Mono click on every chip, with a higher pitch on the first beat of each measure.
+struct ClickTrack {
+    /// (start sample, accent)
+    clicks: VecDeque<(u64, bool)>,
+    sample: u64,
+}
+
+impl Iterator for ClickTrack {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self
+            .clicks
+            .front()
+            .is_some_and(|(start, _)| self.sample >= start + CLICK_SAMPLES)
+        {
+            self.clicks.pop_front();
+        }
+
+        let &(start, accent) = self.clicks.front()?;
+        let sample = self.sample;
+        self.sample += 1;
+
+        if sample < start {
+            return Some(0.0);
+        }
+
+        let freq = if accent { 1760.0 } else { 880.0 };
+        let t = (sample - start) as f32 / SAMPLE_RATE as f32;
+        Some((t * freq * TAU).sin() * 0.5)
+    }
+}
+
+impl Source for ClickTrack {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl Scene for LatencyTest {
+    fn render_ui(&mut self, _dt: f64) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn is_suspended(&self) -> bool {
+        false
+    }
+
+    fn debug_ui(&mut self, _ctx: &egui::Context) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn closed(&self) -> bool {
+        self.close
+    }
+
+    fn name(&self) -> &str {
+        "Latency Test"
+    }
+
+    fn has_egui(&self) -> bool {
+        true
+    }
+
+    fn on_button_pressed(&mut self, button: UscButton, timestamp: SystemTime) {
+        match button {
+            UscButton::BT(_) => {}
+            UscButton::Back => {
+                self.close = true;
+                return;
+            }
+            _ => return,
+        }
+
+        let Some(time) = self.elapsed_ms(timestamp) else {
+            return;
+        };
+
+        let half_beat = kson::beat_in_ms(BPM) / 2.0;
+        let nearest = self
+            .chip_times
+            .iter()
+            .skip(4)
+            .map(|chip| time - chip)
+            .find(|delta| delta.abs() < half_beat);
+
+        if let Some(delta) = nearest {
+            match self.mode {
+                TestMode::Audio => self.audio_deltas.push(delta),
+                TestMode::Visual => self.visual_deltas.push(delta),
+            }
+        }
+    }
+
+    fn tick(&mut self, _dt: f64, _knob_state: LaserState) -> anyhow::Result<()> {
+        let end = self.chart.tick_to_ms(MEASURES * 4 * KSON_RESOLUTION);
+        if self
+            .elapsed_ms(SystemTime::now())
+            .is_some_and(|elapsed| elapsed > end)
+        {
+            self.stop();
+        }
+        Ok(())
+    }
+
+    fn render_egui(&mut self, ctx: &egui::Context) -> anyhow::Result<()> {
+        let elapsed = self.elapsed_ms(SystemTime::now());
+
+        egui::Window::new("Latency Test").show(ctx, |ui| {
+            ui.label("Hit any BT button on every beat. The first measure is a count-in.");
+
+            ui.add_enabled_ui(!self.running(), |ui| {
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut self.mode, TestMode::Audio, "Audio (listen)");
+                    ui.radio_value(&mut self.mode, TestMode::Visual, "Visual (watch)");
+                });
+            });
+
+            if let Some(elapsed) = elapsed {
+                let measure = self.chart.tick_to_measure(self.chart.ms_to_tick(elapsed));
+                ui.label(format!(
+                    "Measure {}/{}",
+                    (measure + 1).min(MEASURES),
+                    MEASURES
+                ));
+
+                let (rect, _) = ui.allocate_exact_size(egui::vec2(64.0, 64.0), Sense::hover());
+                let flash = self.mode == TestMode::Visual
+                    && self
+                        .chip_times
+                        .iter()
+                        .any(|chip| (0.0..FLASH_MS).contains(&(elapsed - chip)));
+                ui.painter().rect_filled(
+                    rect,
+                    4.0,
+                    if flash {
+                        Color32::WHITE
+                    } else {
+                        Color32::DARK_GRAY
+                    },
+                );
+
+                if ui.button("Stop").clicked() {
+                    self.stop();
+                }
+            } else if ui.button("Start").clicked() {
+                self.start();
+            }
+
+            ui.separator();
+            egui::Grid::new("latency_results").show(ui, |ui| self.show_results(ui));
+
+            if ui.button("Close").clicked() {
+                self.close = true;
+            }
+        });
+
+        Ok(())
+    }
+}
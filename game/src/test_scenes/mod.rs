@@ -1 +1,2 @@
 pub mod camera_test;
+pub mod latency_test;
@@ -15,11 +15,13 @@ use tealr::mlu::mlua::{Function, Lua, LuaSerdeExt};
 use three_d::{ColorMaterial, Gm, Mat3, Rad, Rectangle, Texture2DRef, Vec2, Zero};
 
 use crate::{
+    game::replay::Replay,
     game_main::AutoPlay,
     log_result,
     main_menu::MainMenuButton,
     results::SongResultData,
     scene::{Scene, SceneData},
+    song_provider::{ChartAudio, ChartImages, LoadedSong},
     songselect::{Song, SongSelect},
     util::{back_pixels, lua_address},
     ControlMessage,
@@ -55,16 +57,19 @@ fn load_chart(
     diff_idx: usize,
     skin_folder: PathBuf,
     audio: Box<dyn Source<Item = f32> + Send>,
+    fx_audio: Option<Box<dyn Source<Item = f32> + Send>>,
+    chart_images: ChartImages,
+    effect_audio: ChartAudio,
     autoplay: AutoPlay,
+    replay: Option<Replay>,
 ) -> anyhow::Result<Box<dyn SceneData + Send>> {
-    Ok(Box::new(crate::game::GameData::new(
-        song,
-        diff_idx,
-        chart,
-        skin_folder,
-        audio,
-        autoplay,
-    )?))
+    Ok(Box::new(
+        crate::game::GameData::new(song, diff_idx, chart, skin_folder, audio, autoplay)?
+            .with_fx_audio(fx_audio)
+            .with_chart_images(chart_images)
+            .with_effect_audio(effect_audio)
+            .with_replay(replay),
+    ))
 }
 
 impl Transition {
@@ -113,7 +118,9 @@ impl Transition {
                     "bpm": song.bpm,
                     "difficulty": diff.difficulty,
                     "level": diff.level,
-                    "effector": diff.effector
+                    "effector": diff.effector,
+                    "illustrator": diff.illustrator,
+                    "information": diff.information
                 }))?
             ));
         }
@@ -228,11 +235,29 @@ impl Scene for Transition {
                             diff,
                             loader,
                             autoplay,
+                            replay,
                         } => {
                             let skin_folder = self.vgfx.read().expect("Lock error").skin_folder();
                             Some(Promise::spawn_thread("Load song", move || {
-                                let (chart, audio) = loader()?;
-                                load_chart(chart, song, diff, skin_folder, audio, autoplay)
+                                let LoadedSong {
+                                    chart,
+                                    audio,
+                                    fx_audio,
+                                    images,
+                                    effect_audio,
+                                } = loader()?;
+                                load_chart(
+                                    chart,
+                                    song,
+                                    diff,
+                                    skin_folder,
+                                    audio,
+                                    fx_audio,
+                                    images,
+                                    effect_audio,
+                                    autoplay,
+                                    replay,
+                                )
                             }))
                         }
                         ControlMessage::Result {
@@ -246,6 +271,8 @@ impl Scene for Transition {
                             max_combo,
                             duration,
                             manual_exit,
+                            retired,
+                            replay,
                         } => Some(Promise::spawn_thread(
                             "Load song",
                             move || -> anyhow::Result<Box<dyn SceneData + Send>> {
@@ -260,6 +287,8 @@ impl Scene for Transition {
                                     max_combo,
                                     duration,
                                     manual_exit,
+                                    retired,
+                                    replay,
                                 )?))
                             },
                         )),
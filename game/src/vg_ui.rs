@@ -13,7 +13,6 @@ use di::{Activator, InjectBuilder, Injectable};
 use femtovg::{renderer::OpenGl, Canvas, Color, FontId, ImageFlags, ImageId, Paint, Path};
 
 use log::warn;
-use poll_promise::Promise;
 use puffin::profile_scope;
 use tealr::{
     mlu::{TealData, UserData, UserDataProxy},
@@ -23,11 +22,20 @@ use tealr::{
 use tealr::mlu::mlua;
 
 use crate::{
-    animation::VgAnimation, config::GameConfig, default_game_dir, help::add_lua_static_method,
-    log_result, settings_screen::skin_select::SkinMeta, shaded_mesh::ShadedMesh, util::lua_address,
+    animation::VgAnimation,
+    config::{find_skin_asset, GameConfig},
+    default_game_dir,
+    help::add_lua_static_method,
+    image_cache::ImageJobs,
+    log_result,
+    settings_screen::skin_select::SkinMeta,
+    shaded_mesh::ShadedMesh,
+    util::lua_address,
 };
 
 const FALLBACK_ID: u32 = u32::MAX;
+/// Shown in place of skin textures that neither the skin nor the default skin have
+const MISSING_IMAGE: &[u8] = include_bytes!("static_assets/missing.png");
 
 #[derive(Debug)]
 enum VgImage {
@@ -132,6 +140,8 @@ pub struct Vgfx {
     image_tint: Option<Color>,
     label_color: Color, // Has some strange behaviour but needed for compat
     label_font: FontId,
+    /// Used for skin fonts that neither the skin nor the default skin have
+    default_font: FontId,
     stroke_paint: Paint,
     gradient_colors: [Color; 2],
     game_folder: std::path::PathBuf,
@@ -140,7 +150,7 @@ pub struct Vgfx {
     next_label_id: u32,
     scoped_assets: HashMap<usize, ScopedAssets>,
     fonts: HashMap<String, FontId>,
-    image_jobs: HashMap<String, Promise<image::DynamicImage>>,
+    image_jobs: ImageJobs,
     label_align: (femtovg::Align, femtovg::Baseline),
 }
 
@@ -175,7 +185,7 @@ struct Label {
 
 impl Vgfx {
     pub fn new(canvas: Arc<Mutex<Canvas<OpenGl>>>, game_folder: std::path::PathBuf) -> Self {
-        let default_fonts = {
+        let default_font = {
             let mut canvas = canvas.lock().expect("Lock error");
 
             let mut font_dir = game_folder.clone();
@@ -188,7 +198,7 @@ impl Vgfx {
                 .add_font_dir(&font_dir)
                 .expect("Failed to load settings fonts");
 
-            default_fonts
+            *default_fonts.first().expect("No default font loaded")
         };
 
         let config = &GameConfig::get();
@@ -218,7 +228,8 @@ impl Vgfx {
             scoped_assets: Default::default(),
             image_tint: None,
             label_color: Color::white(),
-            label_font: *default_fonts.first().expect("No default font loaded"),
+            label_font: default_font,
+            default_font,
             label_align: (femtovg::Align::Left, femtovg::Baseline::Alphabetic),
             _skin_meta: skin_meta,
         }
@@ -226,6 +237,7 @@ impl Vgfx {
 
     pub fn drop_assets(&mut self, lua_index: usize) {
         let removed_assets = self.scoped_assets.remove(&lua_index);
+        self.image_jobs.retain_lua(|x| x != lua_index);
         //TODO: Call deleteimage on canvas for removed images
         if let Some(removed_assets) = removed_assets {
             log::info!(
@@ -257,6 +269,28 @@ impl Vgfx {
         Ok(f(canvas))
     }
 
+    /// See [`find_skin_asset`]
+    fn skin_asset(&self, folder: &str, name: impl AsRef<std::path::Path>) -> Option<PathBuf> {
+        find_skin_asset(&self.game_folder, &self.skin, folder, name)
+    }
+
+    fn missing_image(&mut self) -> Result<ImageId, mlua::Error> {
+        self.with_canvas(|canvas| canvas.load_image_mem(MISSING_IMAGE, ImageFlags::empty()))?
+            .map_err(mlua::Error::external)
+    }
+
+    /// Adds `img` to the assets of the calling script, returns its id for lua.
+    fn add_static_image(&mut self, lua: &mlua::Lua, img: ImageId) -> Result<u32, mlua::Error> {
+        let this_id = self.next_img_id;
+        self.next_img_id += 1;
+        self.scoped_assets
+            .get_mut(&lua_address(lua))
+            .ok_or(mlua::Error::external("Assets not initialized"))?
+            .images
+            .insert(this_id, VgImage::Static(img));
+        Ok(this_id)
+    }
+
     pub fn load_image(
         &mut self,
         path: impl AsRef<std::path::Path>,
@@ -274,6 +308,14 @@ impl Vgfx {
         Ok(result)
     }
 
+    /// Starts loading images the skin is likely to ask for soon, such as jackets of songs next
+    /// to the selected one.
+    pub fn prefetch_images<'a>(&mut self, paths: impl IntoIterator<Item = &'a str>) {
+        for path in paths {
+            self.image_jobs.prefetch(path);
+        }
+    }
+
     pub fn delete_image(&mut self, image: u32, lua_index: usize) {
         if let Some(VgImage::Static(id)) = self.scoped_assets[&lua_index].images.get(&image) {
             let id = *id;
@@ -438,11 +480,11 @@ impl TealData for Vgfx {
                     imageflags,
                 } = p;
 
-                let mut path = _vgfx.game_folder.clone();
-                path.push("skins");
-                path.push(&_vgfx.skin);
-                path.push("textures");
-                path.push(&filename);
+                let Some(path) = _vgfx.skin_asset("textures", &filename) else {
+                    warn!("Skin image \"{}\" not found", &filename);
+                    let img = _vgfx.missing_image()?;
+                    return Ok(Some(_vgfx.add_static_image(lua, img)?));
+                };
                 let img = match _vgfx.with_canvas(|canvas| {
                     canvas
                         .load_image_file(
@@ -468,15 +510,7 @@ impl TealData for Vgfx {
                     }
                 };
 
-                let this_id = _vgfx.next_img_id;
-                _vgfx.next_img_id += 1;
-                _vgfx
-                    .scoped_assets
-                    .get_mut(&lua_address(lua))
-                    .ok_or(mlua::Error::external("Assets not initialized"))?
-                    .images
-                    .insert(this_id, VgImage::Static(img));
-                Ok(Some(this_id))
+                Ok(Some(_vgfx.add_static_image(lua, img)?))
             },
         );
 
@@ -588,6 +622,79 @@ impl TealData for Vgfx {
             }
         });
 
+        //TextBox
+        tealr::mlu::create_named_parameters!(TextBoxParams with
+          x : f32,
+          y : f32,
+          break_row_width : f32,
+          s : Option<String>,
+          max_lines : Option<usize>,
+
+        );
+        add_lua_static_method(methods, "TextBox", |_, _vgfx, p: TextBoxParams| {
+            let TextBoxParams {
+                x,
+                y,
+                break_row_width,
+                s,
+                max_lines,
+            } = p;
+            let Some(s) = s else {
+                return Ok(0.0);
+            };
+            let Some(fill_paint) = _vgfx.fill_paint.as_ref() else {
+                return Err(mlua::Error::external("No text paint set".to_string()));
+            };
+            let canvas = &mut _vgfx
+                .canvas
+                .try_lock()
+                .map_err(|_| mlua::Error::external("Canvas in use".to_string()))?;
+
+            let mut lines = vec![];
+            for paragraph in s.lines() {
+                let rows = canvas
+                    .break_text_vec(break_row_width, paragraph, fill_paint)
+                    .map_err(mlua::Error::external)?;
+                if rows.is_empty() {
+                    lines.push(String::new());
+                }
+                lines.extend(
+                    rows.into_iter()
+                        .map(|row| paragraph[row].trim_end().to_string()),
+                );
+            }
+
+            // Lines past the limit are cut, ending the last shown line with an ellipsis
+            if let Some(max_lines) = max_lines.filter(|x| lines.len() > *x) {
+                lines.truncate(max_lines);
+                if let Some(last) = lines.last_mut() {
+                    last.push('…');
+                    while last.chars().count() > 1
+                        && canvas
+                            .measure_text(0.0, 0.0, &*last, fill_paint)
+                            .map_err(mlua::Error::external)?
+                            .width()
+                            > break_row_width
+                    {
+                        last.pop();
+                        last.pop();
+                        last.push('…');
+                    }
+                }
+            }
+
+            let line_height = canvas
+                .measure_font(fill_paint)
+                .map_err(mlua::Error::external)?
+                .height();
+            for (i, line) in lines.iter().enumerate() {
+                canvas
+                    .fill_text(x, y + i as f32 * line_height, line, fill_paint)
+                    .map_err(mlua::Error::external)?;
+            }
+            Ok(lines.len() as f32 * line_height)
+        });
+
         //TextAlign
         tealr::mlu::create_named_parameters!(TextAlignParams with
           align : u32,
@@ -734,15 +841,15 @@ impl TealData for Vgfx {
                     _vgfx.label_font = *font_id;
                 } else {
                     let path = p.filename.unwrap_or_else(|| name.clone());
-                    let mut font_path = _vgfx.game_folder.clone();
-                    font_path.push("skins");
-                    font_path.push(&_vgfx.skin);
-                    font_path.push("fonts");
-                    font_path.push(path);
-
-                    let font_id = _vgfx
-                        .with_canvas(|canvas| canvas.add_font(&font_path))?
-                        .map_err(mlua::Error::external)?;
+                    let font_id = match _vgfx.skin_asset("fonts", &path) {
+                        Some(font_path) => _vgfx
+                            .with_canvas(|canvas| canvas.add_font(&font_path))?
+                            .map_err(mlua::Error::external)?,
+                        None => {
+                            warn!("Skin font \"{}\" not found", &path);
+                            _vgfx.default_font
+                        }
+                    };
                     _vgfx.label_font = font_id;
 
                     if let Some(paint) = _vgfx.fill_paint.as_mut() {
@@ -1609,76 +1716,49 @@ impl TealData for Vgfx {
                     h,
                 } = p;
 
-                if let Some((key, job)) = _vgfx.image_jobs.remove_entry(&path) {
-                    match job.try_take() {
-                        Ok(img) if img.width() > 0 => {
-                            let img_id = _vgfx.with_canvas(|c| {
-                                c.create_image(
-                                    femtovg::ImageSource::try_from(&img)
-                                        .map_err(mlua::Error::external)?,
-                                    ImageFlags::empty(),
-                                )
-                                .map_err(mlua::Error::external)
-                            })??;
-
-                            _vgfx
-                                .scoped_assets
-                                .get_mut(&lua_address(lua))
-                                .ok_or(mlua::Error::external("Assets not initialized"))?
-                                .images
-                                .insert(_vgfx.next_img_id, VgImage::Static(img_id));
-                            _vgfx
-                                .scoped_assets
-                                .get_mut(&lua_address(lua))
-                                .ok_or(mlua::Error::external("Assets not initialized"))?
-                                .job_imgs
-                                .insert(key, _vgfx.next_img_id);
-                            _vgfx.next_img_id += 1;
-                        }
-                        Ok(_) => {}
-                        Err(job) => {
-                            _vgfx.image_jobs.insert(key, job);
-                        }
-                    }
-                }
+                let lua_index = lua_address(lua);
+                let placeholder = placeholder.unwrap_or_default();
 
-                let key = path.clone();
-                if !_vgfx.scoped_assets[&lua_address(lua)]
-                    .job_imgs
-                    .contains_key(&path)
-                {
-                    _vgfx
-                        .image_jobs
-                        .entry(path.clone())
-                        .or_insert_with(move || {
-                            Promise::spawn_thread("load image", move || {
-                                image::open(key)
-                                    .map(|img| {
-                                        if let (Some(w), Some(h)) = (w, h) {
-                                            img.resize(
-                                                w,
-                                                h,
-                                                image::imageops::FilterType::CatmullRom,
-                                            )
-                                        } else {
-                                            img
-                                        }
-                                    })
-                                    .unwrap_or_default()
-                            })
-                        });
-                    _vgfx
+                if let Some(img) = _vgfx.image_jobs.take(&path) {
+                    let img_id = _vgfx.with_canvas(|c| {
+                        c.create_image(
+                            femtovg::ImageSource::try_from(&img).map_err(mlua::Error::external)?,
+                            ImageFlags::empty(),
+                        )
+                        .map_err(mlua::Error::external)
+                    })??;
+
+                    let next_img_id = _vgfx.next_img_id;
+                    let assets = _vgfx
                         .scoped_assets
-                        .get_mut(&lua_address(lua))
-                        .ok_or(mlua::Error::external("Assets not initialized"))?
-                        .job_imgs
-                        .insert(path.clone(), placeholder.unwrap_or_default());
+                        .get_mut(&lua_index)
+                        .ok_or(mlua::Error::external("Assets not initialized"))?;
+                    assets.images.insert(next_img_id, VgImage::Static(img_id));
+                    let replaced = assets.job_imgs.insert(path.clone(), next_img_id);
+                    let replaced = replaced.and_then(|old| assets.images.remove(&old));
+                    if let Some(VgImage::Static(old)) = replaced {
+                        log_result!(_vgfx.with_canvas(|c| c.delete_image(old)));
+                    }
+                    _vgfx.next_img_id += 1;
+                    _vgfx.image_jobs.loaded(lua_index, &path);
                 }
 
-                Ok(*_vgfx.scoped_assets[&lua_address(lua)]
+                let loaded = _vgfx
+                    .scoped_assets
+                    .get(&lua_index)
+                    .ok_or(mlua::Error::external("Assets not initialized"))?
                     .job_imgs
                     .get(&path)
-                    .unwrap_or(&placeholder.unwrap_or_default()))
+                    .copied();
+
+                match loaded {
+                    Some(id) => Ok(id),
+                    None if _vgfx.image_jobs.failed(&path) => Ok(placeholder),
+                    None => {
+                        _vgfx.image_jobs.request(&path, w.zip(h));
+                        Ok(placeholder)
+                    }
+                }
             },
         );
 
@@ -2026,11 +2106,11 @@ impl TealData for Vgfx {
                     compressed,
                 } = p;
 
-                let mut skinned_path = _vgfx.game_folder.clone();
-                skinned_path.push("skins");
-                skinned_path.push(&_vgfx.skin);
-                skinned_path.push("textures");
-                skinned_path.push(path);
+                let Some(skinned_path) = _vgfx.skin_asset("textures", &path) else {
+                    warn!("Skin animation \"{}\" not found", &path);
+                    let img = _vgfx.missing_image()?;
+                    return _vgfx.add_static_image(lua, img);
+                };
 
                 let anim = VgAnimation::new(
                     skinned_path,
@@ -2130,14 +2210,21 @@ impl TealData for Vgfx {
                 .ok_or(mlua::Error::external("VGFX app data not set"))?;
             let vgfx = vgfx.write().expect("Lock error");
 
-            let mut shader_path = vgfx.game_folder.clone();
-            shader_path.push("skins");
-            shader_path.push(&vgfx.skin);
-            shader_path.push("shaders");
+            let material = p.material.unwrap_or_else(|| "guiTex".to_string());
+            let shader_path = vgfx
+                .skin_asset("shaders", format!("{material}.vs"))
+                .and_then(|x| x.parent().map(PathBuf::from))
+                .unwrap_or_else(|| {
+                    let mut shader_path = vgfx.game_folder.clone();
+                    shader_path.push("skins");
+                    shader_path.push(&vgfx.skin);
+                    shader_path.push("shaders");
+                    shader_path
+                });
 
             ShadedMesh::new(
                 context,
-                &p.material.unwrap_or_else(|| "guiTex".to_string()),
+                &material,
                 p.path.map(PathBuf::from).unwrap_or(shader_path),
             )
             .map_err(mlua::Error::external)
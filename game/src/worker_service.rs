@@ -1,3 +1,29 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
 pub trait WorkerService {
     fn update(&mut self);
 }
+
+/// Failed attempts of a queued job and when it can be tried again.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Backoff {
+    /// Failed attempts so far
+    pub attempts: u32,
+    #[serde(skip)]
+    retry_at: Option<Instant>,
+}
+
+impl Backoff {
+    /// Counts a failed attempt, the wait before the next one is `delay` multiplied by the failed
+    /// attempts, up to `max_steps` times.
+    pub fn failed(&mut self, delay: Duration, max_steps: u32) {
+        self.attempts += 1;
+        self.retry_at = Some(Instant::now() + delay * self.attempts.min(max_steps));
+    }
+
+    pub fn ready(&self, now: Instant) -> bool {
+        self.retry_at.is_none_or(|t| t <= now)
+    }
+}
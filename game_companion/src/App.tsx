@@ -16,7 +16,7 @@ import {
   createWS,
   createWSState,
 } from "@solid-primitives/websocket";
-import { ClientEvent, GameState } from "./schemas/types";
+import { AuthResult, ClientEvent, GameState } from "./schemas/types";
 import { SongSelect } from "./SongSelect";
 
 const App: Component = () => {
@@ -25,9 +25,37 @@ const App: Component = () => {
   const hostConn = createReconnectingWS("ws://" + hostaddr);
   const connState = createWSState(hostConn);
   const states = ["Connecting", "Connected", "Disconnecting", "Disconnected"];
+  const [auth, setAuth] = createSignal<AuthResult>();
+  const [pin, setPin] = createSignal("");
+
+  // The first message of every connection has to pair or authenticate this device
+  hostConn.addEventListener("open", () => {
+    setAuth(undefined);
+    const token = localStorage.getItem("token");
+    if (token) {
+      Send({ variant: "Authenticate", v: { token } });
+    }
+  });
+
   hostConn.addEventListener("message", (e) => {
+    const message: GameState | AuthResult = JSON.parse(e.data);
+    switch (message.variant) {
+      case "Paired":
+        localStorage.setItem("token", message.token);
+        setAuth(message);
+        return;
+      case "Authenticated":
+        setAuth(message);
+        return;
+      case "Rejected":
+        localStorage.removeItem("token");
+        setAuth(message);
+        hostConn.close();
+        return;
+    }
+
     const current = JSON.stringify(state());
-    const newState: GameState = JSON.parse(e.data);
+    const newState: GameState = message;
 
     if (current != JSON.stringify(newState)) {
       setState(newState);
@@ -49,6 +77,31 @@ const App: Component = () => {
         when={connState() == 1}
         fallback={<div class="text-3xl text-amber-500">Disconnected</div>}
       >
+        <Show when={!auth() && !localStorage.getItem("token")}>
+          <div class="flex flex-col gap-3">
+            <div class="text-2xl">
+              Enter the PIN shown under Companion in the game settings
+            </div>
+            <input
+              placeholder="PIN"
+              type="text"
+              inputmode="numeric"
+              class="h-16 bg-slate-700 text-3xl w-full px-2"
+              value={pin()}
+              oninput={(x) => setPin(x.currentTarget.value)}
+            ></input>
+            <button
+              onclick={() =>
+                Send({
+                  variant: "Pair",
+                  v: { device: navigator.userAgent, pin: pin() },
+                })
+              }
+            >
+              Pair
+            </button>
+          </div>
+        </Show>
         <Switch fallback={<>Disconnected</>}>
           <Match when={state()?.variant == "SongSelect"}>
             <SongSelect state={state as any} send={Send}></SongSelect>
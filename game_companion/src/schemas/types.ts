@@ -6,7 +6,11 @@ export type SongFilterType = "None" | { Folder: string } | { Collection: string
 
 export type GameState = { variant: "None" } | { variant: "TitleScreen" } | { variant: "SongSelect"; search_string: string; level_filter: number; folder_filter_index: number; sort_index: number; filters: SongFilterType[]; sorts: SongSort[] }
 
-export type ClientEvent = { variant: "Invalid"; v: string } | { variant: "Start" } | { variant: "Back" } | { variant: "SetSearch"; v: string } | { variant: "SetLevelFilter"; v: number } | { variant: "SetSongFilterType"; v: SongFilterType } | { variant: "SetSongSort"; v: SongSort }
+export type ClientEvent = { variant: "Invalid"; v: string } | { variant: "Start" } | { variant: "Back" } | { variant: "SetSearch"; v: string } | { variant: "SetLevelFilter"; v: number } | { variant: "SetSongFilterType"; v: SongFilterType } | { variant: "SetSongSort"; v: SongSort } | { variant: "Pair"; v: { device: string; pin: string } } | { variant: "Authenticate"; v: { token: string } }
+
+export type AuthResult = { variant: "Paired"; token: string; permission: CompanionPermission } | { variant: "Authenticated"; permission: CompanionPermission } | { variant: "Rejected" }
+
+export type CompanionPermission = "ViewOnly" | "Control"
 
 export type SongSort = { sort_type: SongSortType; direction: SortDir }
 
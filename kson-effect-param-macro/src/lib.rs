@@ -2,13 +2,46 @@ use proc_macro::TokenStream;
 use quote::quote;
 use syn::{parse_macro_input, Data, DeriveInput};
 
-#[proc_macro_derive(Effect)]
+/// Reads `#[param(unit = Rate, min = 0.0, max = 1.0)]` from a field, returns the unit and
+/// range expressions for its descriptor.
+fn param_attributes(
+    field: &syn::Field,
+) -> syn::Result<(proc_macro2::TokenStream, proc_macro2::TokenStream)> {
+    let mut unit = None;
+    let mut min = None;
+    let mut max = None;
+
+    for attr in field.attrs.iter().filter(|x| x.path().is_ident("param")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("unit") {
+                unit = Some(meta.value()?.parse::<syn::Ident>()?);
+            } else if meta.path.is_ident("min") {
+                min = Some(meta.value()?.parse::<syn::Expr>()?);
+            } else if meta.path.is_ident("max") {
+                max = Some(meta.value()?.parse::<syn::Expr>()?);
+            } else {
+                return Err(meta.error("expected unit, min or max"));
+            }
+            Ok(())
+        })?;
+    }
+
+    let unit = unit.map_or(quote!(ParamUnit::Float), |unit| quote!(ParamUnit::#unit));
+    let range = match (min, max) {
+        (Some(min), Some(max)) => quote!(Some((#min as f32)..=(#max as f32))),
+        _ => quote!(None),
+    };
+    Ok((unit, range))
+}
+
+#[proc_macro_derive(Effect, attributes(param))]
 pub fn derive_effect_param(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
     match &input.data {
         Data::Enum(e) => {
             let mut match_arms = vec![];
+            let mut variants = vec![];
             for variant in &e.variants {
                 let new_struct_expr = match &variant.fields {
                     syn::Fields::Named(_) => todo!(),
@@ -22,6 +55,7 @@ pub fn derive_effect_param(input: TokenStream) -> TokenStream {
                 let input = &input.ident;
                 let variant = &variant.ident;
                 match_arms.push(quote!(#input::#variant(a) => #new_struct_expr,));
+                variants.push(quote!(#input::#variant));
             }
 
             match_arms.push(quote!(_ => panic!("Tried to derive from a different type"),));
@@ -34,6 +68,15 @@ pub fn derive_effect_param(input: TokenStream) -> TokenStream {
                 fn param_list() -> &'static [&'static str] {
                     &[]
                 }
+                fn descriptors(&self) -> Vec<ParamDescriptor> {
+                    match self { #(#variants(a) => a.descriptors(),)* }
+                }
+                fn param(&self, key: &str) -> Option<String> {
+                    match self { #(#variants(a) => a.param(key),)* }
+                }
+                fn set_param(&mut self, key: &str, value: &str) -> bool {
+                    match self { #(#variants(a) => a.set_param(key, value),)* }
+                }
                 }
             ))
         }
@@ -41,14 +84,34 @@ pub fn derive_effect_param(input: TokenStream) -> TokenStream {
             //self << other
             let mut match_arms = vec![];
             let mut fields = vec![];
+            let mut descriptors = vec![];
+            let mut getters = vec![];
+            let mut setters = vec![];
 
             for f in &s.fields {
                 if let Some(ident) = &f.ident {
+                    let (unit, range) = match param_attributes(f) {
+                        Ok(v) => v,
+                        Err(e) => return e.to_compile_error().into(),
+                    };
+
                     fields.push(quote!(stringify!(#ident)));
                     match_arms.push(quote!(stringify!(#ident) => Self {
                         #ident: param.parse().unwrap_or_default(),
                         ..self.clone()
-                    },))
+                    },));
+                    descriptors.push(quote!(ParamDescriptor {
+                        name: stringify!(#ident),
+                        unit: #unit,
+                        range: #range,
+                        default: default.#ident.to_string(),
+                        transition: true,
+                    }));
+                    getters.push(quote!(stringify!(#ident) => Some(self.#ident.to_string()),));
+                    setters.push(quote!(stringify!(#ident) => value
+                        .parse()
+                        .map(|v| self.#ident = v)
+                        .is_ok(),));
                 }
             }
 
@@ -64,6 +127,25 @@ pub fn derive_effect_param(input: TokenStream) -> TokenStream {
                 fn param_list() -> &'static [&'static str]  {
                     &[#(#fields),*]
                 }
+
+                fn descriptors(&self) -> Vec<ParamDescriptor> {
+                    let default = Self::default();
+                    vec![#(#descriptors),*]
+                }
+
+                fn param(&self, key: &str) -> Option<String> {
+                    match key {
+                        #(#getters)*
+                        _ => None,
+                    }
+                }
+
+                fn set_param(&mut self, key: &str, value: &str) -> bool {
+                    match key {
+                        #(#setters)*
+                        _ => false,
+                    }
+                }
             }))
         }
         Data::Union(_) => panic!("Unions are not supported"),
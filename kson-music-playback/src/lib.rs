@@ -1,15 +1,17 @@
 use anyhow::Result;
 use itertools::Itertools;
 use kson::overlaps::Overlaps;
-use kson::Chart;
+use kson::{Chart, KSON_RESOLUTION};
 
-use rodio::source::{Buffered, SkipDuration};
+use rodio::source::Buffered;
 pub use rodio::Source;
+use serde::{Deserialize, Serialize};
 
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::fs::File;
-use std::io::BufReader;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::io::{BufReader, Cursor};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -23,25 +25,89 @@ use kson_rodio_sources::{
     pitch_shift::pitch_shift,
     re_trigger::re_trigger,
     side_chain::side_chain,
+    switch_audio::switch_audio,
     tape_stop::tape_stop,
+    time_stretch::time_stretch,
     wobble::wobble,
 };
 
+mod stream;
+mod synth;
+use stream::SongSource;
+
 type ActiveEffect = ((u64, u64), Box<dyn Source<Item = f32> + Send>);
+type SharedSource = Buffered<Box<dyn Source<Item = f32> + Send>>;
+
+/// Volumes of the dry and the effected signal, shared with the playing [`AudioFile`].
+struct MixLevels {
+    bgm_volume: AtomicU32,
+    fx_volume: AtomicU32,
+    bgm_muted: AtomicBool,
+    fx_muted: AtomicBool,
+}
+
+impl MixLevels {
+    fn bgm_gain(&self) -> f32 {
+        if self.bgm_muted.load(Ordering::Relaxed) {
+            0.0
+        } else {
+            f32::from_bits(self.bgm_volume.load(Ordering::Relaxed))
+        }
+    }
+
+    fn fx_gain(&self) -> f32 {
+        f32::from_bits(self.fx_volume.load(Ordering::Relaxed))
+    }
+}
+
+impl Default for MixLevels {
+    fn default() -> Self {
+        Self {
+            bgm_volume: AtomicU32::new(1.0f32.to_bits()),
+            fx_volume: AtomicU32::new(1.0f32.to_bits()),
+            bgm_muted: AtomicBool::new(false),
+            fx_muted: AtomicBool::new(false),
+        }
+    }
+}
+
+/// Volumes of the sounds played along with the chart, 0 leaves a sound out.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChartSounds {
+    /// Click on every beat
+    pub metronome: f32,
+    /// Clap on BT and FX chips and the start of holds
+    pub chip: f32,
+    pub slam: f32,
+}
+
+impl Default for ChartSounds {
+    fn default() -> Self {
+        Self {
+            metronome: 0.0,
+            chip: 0.5,
+            slam: 0.5,
+        }
+    }
+}
 
 pub struct AudioFile {
-    audio: SkipDuration<Buffered<Box<dyn Source<Item = f32> + Send>>>,
-    audio_base: SkipDuration<Buffered<Box<dyn Source<Item = f32> + Send>>>,
-    effected: Option<SkipDuration<Buffered<Box<dyn Source<Item = f32> + Send>>>>,
-    effected_base: Option<SkipDuration<Buffered<Box<dyn Source<Item = f32> + Send>>>>,
+    audio: SongSource,
+    audio_base: SongSource,
+    effected: Option<SongSource>,
+    effected_base: Option<SongSource>,
     leadin: Arc<AtomicUsize>,
     stopped: Arc<AtomicBool>,
     fx_enable: [Arc<AtomicBool>; 2],
+    mix: Arc<MixLevels>,
     channels: u16,
     sample_rate: u32,
     pos: Arc<AtomicUsize>,
     effects: VecDeque<((u64, u64), Box<EffectBuilder>)>,
     active_effects: Vec<ActiveEffect>,
+    key_sounds: VecDeque<(u64, Box<dyn Source<Item = f32> + Send>)>,
+    active_key_sounds: Vec<Box<dyn Source<Item = f32> + Send>>,
 }
 
 pub struct EventList<T> {
@@ -93,6 +159,7 @@ impl Iterator for AudioFile {
 
         let pos = self.pos.fetch_add(1, Ordering::Relaxed);
         let base = self.audio.next();
+        let prerendered = self.effected.as_mut().and_then(|x| x.next());
         let effected = self
             .active_effects
             .iter_mut()
@@ -114,11 +181,37 @@ impl Iterator for AudioFile {
             self.active_effects.push(((start, end), new_effect));
         }
 
-        if effected.is_some() && enable_fx {
-            effected
-        } else {
-            base
+        while let Some((start, key_sound)) = self.key_sounds.pop_front() {
+            if start > pos as _ {
+                self.key_sounds.push_front((start, key_sound));
+                break;
+            }
+
+            self.active_key_sounds.push(key_sound);
         }
+
+        let mut key_sound_sample = 0.0;
+        self.active_key_sounds.retain_mut(|x| match x.next() {
+            Some(s) => {
+                key_sound_sample += s;
+                true
+            }
+            None => false,
+        });
+
+        // A pre-rendered FX track replaces the live effects, muting the effects plays the
+        // dry signal in their place
+        let effected = if enable_fx && !self.mix.fx_muted.load(Ordering::Relaxed) {
+            prerendered.or(effected)
+        } else {
+            None
+        };
+        let sample = match effected {
+            Some(effected) => Some(effected * self.mix.fx_gain()),
+            None => base.map(|x| x * self.mix.bgm_gain()),
+        };
+
+        sample.map(|x| x + key_sound_sample)
     }
 }
 
@@ -155,6 +248,26 @@ impl AudioFile {
         }
     }
 
+    /// Moves playback ahead by `duration` like [`Source::skip_duration`] but without decoding
+    /// the skipped audio when it is streamed. Key sounds before the new position are dropped.
+    pub fn skip(&mut self, duration: Duration) {
+        let mut samples = ((duration.as_millis() * self.sample_rate as u128) / 1000) as usize
+            * self.channels as usize;
+
+        let leadin = self.leadin.load(Ordering::Relaxed);
+        let from_leadin = leadin.min(samples);
+        self.leadin.store(leadin - from_leadin, Ordering::Relaxed);
+        samples -= from_leadin;
+
+        let pos = (self.pos.fetch_add(samples, Ordering::Relaxed) + samples) as u64;
+        self.audio.skip_samples(samples);
+        if let Some(effected) = &mut self.effected {
+            effected.skip_samples(samples);
+        }
+        self.effects.retain(|((_, end), _)| *end > pos);
+        self.key_sounds.retain(|(start, _)| *start >= pos);
+    }
+
     fn set_stopped(&mut self, val: bool) {
         self.stopped.store(val, Ordering::SeqCst);
     }
@@ -175,7 +288,14 @@ pub struct AudioPlayback {
     file: Option<AudioFile>,
     last_file: String,
     effects: Vec<((u64, u64), Box<EffectBuilder>)>,
+    key_sounds: Vec<(u64, Box<dyn Source<Item = f32> + Send>)>,
+    chart_folder: Option<PathBuf>,
+    chart_audio: HashMap<String, Vec<u8>>,
     leadin: Duration,
+    rate: f64,
+    stretch_latency: Duration,
+    mix: Arc<MixLevels>,
+    chart_sounds: ChartSounds,
 }
 
 impl AudioPlayback {
@@ -184,8 +304,138 @@ impl AudioPlayback {
             file: None,
             last_file: String::new(),
             effects: vec![],
+            key_sounds: vec![],
+            chart_folder: None,
+            chart_audio: HashMap::new(),
             leadin: Duration::ZERO,
+            rate: 1.0,
+            stretch_latency: Duration::ZERO,
+            mix: Arc::default(),
+            chart_sounds: ChartSounds::default(),
+        }
+    }
+
+    /// Folder used to resolve audio files referenced by the chart, such as
+    /// SwitchAudio effects and FX chip samples.
+    pub fn set_chart_folder(&mut self, folder: impl AsRef<Path>) {
+        self.chart_folder = Some(folder.as_ref().to_path_buf());
+    }
+
+    /// Contents of the audio files referenced by the chart, for charts that are not read
+    /// from a folder such as those in archives. Used instead of the chart folder.
+    pub fn set_chart_audio(&mut self, files: HashMap<String, Vec<u8>>) {
+        self.chart_audio = files;
+    }
+
+    fn load_chart_audio(&self, filename: &str) -> Option<SharedSource> {
+        let source: Box<dyn Source<Item = f32> + Send> =
+            if let Some(data) = self.chart_audio.get(filename) {
+                let source = rodio::Decoder::new(Cursor::new(data.clone()))
+                    .map_err(|e| log::warn!("Could not decode {}: {}", filename, e))
+                    .ok()?;
+                Box::new(source.convert_samples())
+            } else {
+                let path = self.chart_folder.as_ref()?.join(filename);
+                let file = match File::open(&path) {
+                    Ok(f) => f,
+                    Err(e) => {
+                        log::warn!("Could not open {}: {}", path.display(), e);
+                        return None;
+                    }
+                };
+                let source = rodio::Decoder::new(BufReader::new(file)).ok()?;
+                Box::new(source.convert_samples())
+            };
+        Some(source.buffered())
+    }
+
+    fn build_key_sounds(&mut self, chart: &Chart, sample_rate: u32, channels: u16) {
+        let offset_ms = chart.audio.bgm.offset as f64;
+        let sample_pos = |tick: u32| {
+            let pos = (chart.tick_to_ms(tick) + offset_ms) * (sample_rate as f64 / 1000.0);
+            (pos >= 0.0).then(|| pos as u64 * channels as u64)
+        };
+        let mut key_sounds = vec![];
+
+        for (filename, events) in &chart.audio.key_sound.fx.chip_event {
+            let Some(sample) = self.load_chart_audio(filename) else {
+                continue;
+            };
+
+            for (y, invoke) in events.iter().flatten() {
+                let Some(pos) = sample_pos(*y) else {
+                    continue;
+                };
+                let source = rodio::source::UniformSourceIterator::new(
+                    sample.clone().amplify(invoke.vol as f32),
+                    channels,
+                    sample_rate,
+                );
+                key_sounds.push((pos, Box::new(source) as Box<dyn Source<Item = f32> + Send>));
+            }
+        }
+
+        let mut add = |tick: u32, sound: &SharedSource, volume: f32| {
+            if let Some(pos) = sample_pos(tick) {
+                let source = sound.clone().amplify(volume);
+                key_sounds.push((pos, Box::new(source) as Box<dyn Source<Item = f32> + Send>));
+            }
+        };
+        let sounds = self.chart_sounds;
+
+        if sounds.metronome > 0.0 {
+            // Through the end of the song, or the chart if the song length is unknown
+            let song_end = self
+                .file
+                .as_ref()
+                .and_then(|x| x.audio.total_duration())
+                .map(|x| chart.ms_to_tick(x.as_secs_f64() * 1000.0 - offset_ms))
+                .unwrap_or_default();
+            let end = song_end.max(chart.get_last_tick() + KSON_RESOLUTION * 4);
+            let beat = synth::click(false, sample_rate, channels);
+            let measure = synth::click(true, sample_rate, channels);
+            for (tick, is_measure) in chart.beat_line_iter().take_while(|x| x.0 <= end) {
+                add(
+                    tick,
+                    if is_measure { &measure } else { &beat },
+                    sounds.metronome,
+                );
+            }
+        }
+
+        if sounds.chip > 0.0 {
+            let clap = synth::clap(sample_rate, channels);
+            let notes = chart.note.bt.iter().chain(chart.note.fx.iter()).flatten();
+            for tick in notes.map(|x| x.y).sorted().dedup() {
+                add(tick, &clap, sounds.chip);
+            }
+        }
+
+        if sounds.slam > 0.0 {
+            let slam = synth::slam(sample_rate, channels);
+            let slams = chart.note.laser.iter().flatten().flat_map(|section| {
+                section
+                    .1
+                    .iter()
+                    .filter(|x| x.vf.is_some_and(|vf| vf != x.v))
+                    .map(move |x| section.0 + x.ry)
+            });
+            for tick in slams.sorted().dedup() {
+                add(tick, &slam, sounds.slam);
+            }
         }
+
+        key_sounds.sort_by_key(|x| x.0);
+        self.key_sounds = key_sounds;
+    }
+
+    /// Volumes of the generated sounds, used from the next time playback starts.
+    pub fn set_chart_sounds(&mut self, sounds: ChartSounds) {
+        self.chart_sounds = sounds;
+    }
+
+    pub fn chart_sounds(&self) -> ChartSounds {
+        self.chart_sounds
     }
 
     pub fn set_fx_enable(&mut self, left: bool, right: bool) {
@@ -217,6 +467,22 @@ impl AudioPlayback {
             return;
         };
 
+        self.build_key_sounds(chart, sample_rate, channels);
+
+        let swap_sources: HashMap<String, SharedSource> = chart
+            .audio
+            .audio_effect
+            .fx
+            .def
+            .values()
+            .filter_map(|x| match x {
+                kson::effects::AudioEffect::AudioSwap(f) => {
+                    Some((f.clone(), self.load_chart_audio(f)?))
+                }
+                _ => None,
+            })
+            .collect();
+
         //TODO: Clean up
         //TODO: Effect priority
         self.effects = chart
@@ -267,6 +533,9 @@ impl AudioPlayback {
                         )
                     })
                     .collect_vec();
+                let swap_sources = swap_sources.clone();
+                let audio_start =
+                    Duration::from_secs_f64(((section_start_ms + offset_ms) / 1000.0).max(0.0));
                 (
                     (start_pos as u64, end_pos as u64),
                     Box::new(move |base| {
@@ -277,6 +546,12 @@ impl AudioPlayback {
                                 let end = Duration::from_nanos((end_ms * 1000000.0) as _);
                                 let duration = end - start;
                                 let bpm = *bpm;
+                                // Effects without a usable mix parameter are fully wet
+                                let mix = effect
+                                    .mix()
+                                    .map(|m| m.interpolate(1.0, true))
+                                    .filter(|m| m.is_finite())
+                                    .map_or(1.0, |m| m.clamp(0.0, 1.0));
                                 let effected: Box<dyn MixSource<Item = f32> + Send> = match effect {
                                     kson::effects::AudioEffect::ReTrigger(r) => {
                                         let duration = Duration::from_secs_f64(
@@ -365,9 +640,18 @@ impl AudioPlayback {
                                             s.ratio.interpolate(1.0, true),
                                         ))
                                     }
+                                    kson::effects::AudioEffect::AudioSwap(filename) => {
+                                        match swap_sources.get(filename) {
+                                            Some(alt) => Box::new(switch_audio(
+                                                base,
+                                                alt.clone().skip_duration(audio_start),
+                                            )),
+                                            None => Box::new(NoMix(base)),
+                                        }
+                                    }
                                     _ => Box::new(NoMix(base)),
                                 };
-                                Box::new(effected_part(effected, start, duration, 1.0))
+                                Box::new(effected_part(effected, start, duration, mix))
                                     as Box<dyn Source<Item = f32> + Send>
                             }) as Box<dyn Source<Item = f32> + Send>
                     }) as Box<EffectBuilder>,
@@ -379,6 +663,7 @@ impl AudioPlayback {
     pub fn get_ms(&self) -> f64 {
         if let Some(file) = &self.file {
             file.get_ms() + (self.leadin.as_secs_f64() * 1000.0)
+                - (self.stretch_latency.as_secs_f64() * 1000.0)
         } else {
             0.0
         }
@@ -412,17 +697,84 @@ impl AudioPlayback {
                 leadin: file.leadin.clone(),
                 stopped: file.stopped.clone(),
                 fx_enable: file.fx_enable.clone(),
+                mix: file.mix.clone(),
                 channels: file.channels,
                 sample_rate: file.sample_rate,
                 pos: file.pos.clone(),
                 effects: std::mem::take(&mut self.effects).into_iter().collect(),
                 active_effects: vec![],
+                key_sounds: std::mem::take(&mut self.key_sounds).into_iter().collect(),
+                active_key_sounds: vec![],
             })
         } else {
             None
         }
     }
 
+    /// Speed the source returned by [`Self::stretch`] plays at, pitch is kept the same.
+    pub fn set_rate(&mut self, rate: f64) {
+        self.rate = rate;
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Volume of the song outside of effects, takes effect immediately while playing.
+    pub fn set_bgm_volume(&mut self, volume: f32) {
+        self.mix
+            .bgm_volume
+            .store(volume.max(0.0).to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn bgm_volume(&self) -> f32 {
+        f32::from_bits(self.mix.bgm_volume.load(Ordering::Relaxed))
+    }
+
+    /// Volume of the effected signal, used in place of the song while an effect is active.
+    pub fn set_fx_volume(&mut self, volume: f32) {
+        self.mix
+            .fx_volume
+            .store(volume.max(0.0).to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn fx_volume(&self) -> f32 {
+        f32::from_bits(self.mix.fx_volume.load(Ordering::Relaxed))
+    }
+
+    pub fn set_bgm_muted(&mut self, muted: bool) {
+        self.mix.bgm_muted.store(muted, Ordering::Relaxed);
+    }
+
+    pub fn bgm_muted(&self) -> bool {
+        self.mix.bgm_muted.load(Ordering::Relaxed)
+    }
+
+    /// Muted effects play the dry song instead, to compare a section with and without them.
+    pub fn set_fx_muted(&mut self, muted: bool) {
+        self.mix.fx_muted.store(muted, Ordering::Relaxed);
+    }
+
+    pub fn fx_muted(&self) -> bool {
+        self.mix.fx_muted.load(Ordering::Relaxed)
+    }
+
+    /// Time stretches a source taken from [`Self::get_source`] to the playback rate.
+    /// Positions reported by [`Self::get_ms`] stay in song time and follow the stretched audio.
+    pub fn stretch(
+        &mut self,
+        source: impl Source<Item = f32> + Send + 'static,
+    ) -> Box<dyn Source<Item = f32> + Send> {
+        if self.rate == 1.0 {
+            self.stretch_latency = Duration::ZERO;
+            return Box::new(source);
+        }
+
+        let stretched = time_stretch(source, self.rate);
+        self.stretch_latency = stretched.latency();
+        Box::new(stretched)
+    }
+
     pub fn play(&mut self) -> bool {
         if self.is_playing() {
             true
@@ -445,9 +797,8 @@ impl AudioPlayback {
         let rate = source.sample_rate();
         let channels = source.channels();
 
-        let effected: Option<SkipDuration<Buffered<Box<dyn Source<Item = f32> + Send>>>> =
-            effected.map(|e| e.buffered().skip_duration(Duration::ZERO));
-        let audio = source.buffered().skip_duration(Duration::ZERO);
+        let effected = effected.map(SongSource::new);
+        let audio = SongSource::new(source);
         self.file = Some(AudioFile {
             audio: audio.clone(),
             audio_base: audio,
@@ -459,11 +810,14 @@ impl AudioPlayback {
                 Arc::new(AtomicBool::new(false)),
                 Arc::new(AtomicBool::new(false)),
             ],
+            mix: self.mix.clone(),
             channels,
             sample_rate: rate,
             pos: Arc::new(AtomicUsize::new(0)),
             effects: VecDeque::new(),
             active_effects: vec![],
+            key_sounds: VecDeque::new(),
+            active_key_sounds: vec![],
         });
         self.last_file = filename.to_string();
         Ok(())
@@ -477,6 +831,9 @@ impl AudioPlayback {
         }
 
         self.close();
+        if let Some(folder) = Path::new(path).parent() {
+            self.set_chart_folder(folder);
+        }
         let file = File::open(path)?;
         let source = rodio::Decoder::new(BufReader::new(file))?;
         self.open(
@@ -0,0 +1,334 @@
+//! Song audio decoded on a background thread into a ring buffer, so long songs don't have to be
+//! kept fully decoded in memory.
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::time::Duration;
+
+use rodio::Source;
+
+use crate::SharedSource;
+
+/// Songs taking more memory than this when fully decoded are streamed instead, in bytes
+pub const STREAM_THRESHOLD: u64 = 128 * 1024 * 1024;
+/// Audio decoded ahead of the furthest reader
+const READ_AHEAD: Duration = Duration::from_secs(10);
+/// Audio kept behind the furthest reader for effects tapping the song
+const KEEP_BEHIND: Duration = Duration::from_secs(10);
+/// Samples decoded between locks of the ring buffer
+const CHUNK_SIZE: usize = 4096;
+/// How often an idle decode thread checks if all readers are gone
+const IDLE_POLL: Duration = Duration::from_millis(200);
+
+type BoxedSource = Box<dyn Source<Item = f32> + Send>;
+
+struct Ring {
+    /// Sample index of the first buffered sample
+    start: usize,
+    samples: VecDeque<f32>,
+    /// Sample index after the furthest one read
+    furthest: usize,
+    /// The decoder has no samples after the buffered ones
+    finished: bool,
+    /// Sample index to continue decoding from, set when a reader moves outside the buffer
+    seek: Option<usize>,
+    /// Readers that have read from the buffer and not been dropped yet
+    readers: HashSet<usize>,
+}
+
+struct Shared {
+    ring: Mutex<Ring>,
+    /// Notified when samples are decoded, a reader moves forward or a seek is requested
+    changed: Condvar,
+    /// [`KEEP_BEHIND`] in samples
+    behind: usize,
+    /// [`READ_AHEAD`] in samples
+    ahead: usize,
+    next_reader: AtomicUsize,
+}
+
+impl Shared {
+    fn ring(&self) -> MutexGuard<'_, Ring> {
+        self.ring.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A source reading from a ring buffer filled by a decode thread. Clones read independently,
+/// reading past the buffered window makes the decoder seek there. Readers that fall behind the
+/// window get silence while other readers are using it, so readers far apart don't make the
+/// decoder seek back and forth between them.
+pub struct StreamingSource {
+    shared: Arc<Shared>,
+    /// Identifies the reader in [`Ring::readers`]
+    id: usize,
+    registered: bool,
+    pos: usize,
+    channels: u16,
+    sample_rate: u32,
+    total_duration: Option<Duration>,
+}
+
+impl StreamingSource {
+    /// Starts decoding `source` on a new thread, it has to support seeking.
+    pub fn new(source: BoxedSource) -> Self {
+        let channels = source.channels();
+        let sample_rate = source.sample_rate();
+        let samples_per_sec = sample_rate as f64 * channels as f64;
+        let shared = Arc::new(Shared {
+            ring: Mutex::new(Ring {
+                start: 0,
+                samples: VecDeque::new(),
+                furthest: 0,
+                finished: false,
+                seek: None,
+                readers: HashSet::new(),
+            }),
+            changed: Condvar::new(),
+            behind: (KEEP_BEHIND.as_secs_f64() * samples_per_sec) as usize,
+            ahead: (READ_AHEAD.as_secs_f64() * samples_per_sec) as usize,
+            next_reader: AtomicUsize::new(1),
+        });
+
+        let total_duration = source.total_duration();
+        let weak = Arc::downgrade(&shared);
+        std::thread::spawn(move || decode(weak, source));
+
+        Self {
+            shared,
+            id: 0,
+            registered: false,
+            pos: 0,
+            channels,
+            sample_rate,
+            total_duration,
+        }
+    }
+}
+
+impl Clone for StreamingSource {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            id: self.shared.next_reader.fetch_add(1, Ordering::Relaxed),
+            registered: false,
+            pos: self.pos,
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            total_duration: self.total_duration,
+        }
+    }
+}
+
+impl Drop for StreamingSource {
+    fn drop(&mut self) {
+        if self.registered {
+            self.shared.ring().readers.remove(&self.id);
+        }
+    }
+}
+
+/// Moves `source` to the sample index `pos`, returns false if it couldn't seek.
+fn seek(source: &mut BoxedSource, pos: usize) -> bool {
+    let channels = source.channels() as usize;
+    let time = Duration::from_secs_f64((pos / channels) as f64 / source.sample_rate() as f64);
+    if let Err(e) = source.try_seek(time) {
+        log::warn!("Failed to seek audio stream: {e}");
+        return false;
+    }
+
+    for _ in 0..pos % channels {
+        source.next();
+    }
+    true
+}
+
+fn decode(shared: Weak<Shared>, mut source: BoxedSource) {
+    let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+    while let Some(shared) = shared.upgrade() {
+        let mut ring = shared.ring();
+        if let Some(pos) = ring.seek.take() {
+            drop(ring);
+            let ok = seek(&mut source, pos);
+            let mut ring = shared.ring();
+            if ring.seek.is_none() {
+                ring.finished = !ok;
+                shared.changed.notify_all();
+            }
+            continue;
+        }
+
+        if ring.finished || ring.start + ring.samples.len() >= ring.furthest + shared.ahead {
+            drop(shared.changed.wait_timeout(ring, IDLE_POLL));
+            continue;
+        }
+        drop(ring);
+
+        chunk.clear();
+        chunk.extend(source.by_ref().take(CHUNK_SIZE));
+
+        let mut ring = shared.ring();
+        if ring.seek.is_some() {
+            // Decoded for the old position
+            continue;
+        }
+        ring.finished = chunk.len() < CHUNK_SIZE;
+        ring.samples.extend(&chunk);
+
+        let evict = ring
+            .furthest
+            .saturating_sub(shared.behind)
+            .saturating_sub(ring.start)
+            .min(ring.samples.len());
+        ring.samples.drain(..evict);
+        ring.start += evict;
+        shared.changed.notify_all();
+    }
+}
+
+impl Iterator for StreamingSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let shared = &self.shared;
+        let mut ring = shared.ring();
+        if !self.registered {
+            ring.readers.insert(self.id);
+            self.registered = true;
+        }
+
+        let end = ring.start + ring.samples.len();
+        if self.pos < ring.start && ring.readers.len() > 1 {
+            // Left behind by other readers
+            self.pos += 1;
+            return Some(0.0);
+        }
+
+        if self.pos < ring.start || self.pos > end + shared.ahead {
+            ring.samples.clear();
+            ring.start = self.pos;
+            ring.furthest = self.pos;
+            ring.finished = false;
+            ring.seek = Some(self.pos);
+            shared.changed.notify_all();
+        } else if self.pos < end {
+            let sample = ring.samples[self.pos - ring.start];
+            self.pos += 1;
+            if self.pos > ring.furthest {
+                ring.furthest = self.pos;
+                if self.pos % CHUNK_SIZE == 0 {
+                    shared.changed.notify_all();
+                }
+            }
+            return Some(sample);
+        } else if ring.finished {
+            return None;
+        } else {
+            ring.furthest = ring.furthest.max(self.pos + 1);
+        }
+
+        // Not decoded yet, this runs in the audio callback so it can't wait for the decoder
+        self.pos += 1;
+        Some(0.0)
+    }
+}
+
+impl Source for StreamingSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.total_duration
+    }
+}
+
+/// Song audio, fully buffered or streamed depending on its decoded size.
+#[derive(Clone)]
+pub enum SongSource {
+    Buffered(SharedSource),
+    Streaming(StreamingSource),
+}
+
+impl SongSource {
+    /// Streams `source` if it would take more than [`STREAM_THRESHOLD`] bytes decoded and
+    /// supports seeking, otherwise it gets buffered.
+    pub fn new(mut source: BoxedSource) -> Self {
+        let decoded_size = source.total_duration().map(|x| {
+            (x.as_secs_f64() * source.sample_rate() as f64 * source.channels() as f64) as u64
+                * std::mem::size_of::<f32>() as u64
+        });
+
+        match decoded_size {
+            Some(size) if size > STREAM_THRESHOLD && source.try_seek(Duration::ZERO).is_ok() => {
+                log::info!(
+                    "Streaming audio instead of buffering {} MiB",
+                    size / (1024 * 1024)
+                );
+                Self::Streaming(StreamingSource::new(source))
+            }
+            _ => Self::Buffered(source.buffered()),
+        }
+    }
+
+    /// Skips `samples` without decoding them when streaming.
+    pub fn skip_samples(&mut self, samples: usize) {
+        match self {
+            SongSource::Buffered(source) => {
+                if samples > 0 {
+                    source.nth(samples - 1);
+                }
+            }
+            SongSource::Streaming(source) => source.pos += samples,
+        }
+    }
+}
+
+impl Iterator for SongSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        match self {
+            SongSource::Buffered(source) => source.next(),
+            SongSource::Streaming(source) => source.next(),
+        }
+    }
+}
+
+impl Source for SongSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        match self {
+            SongSource::Buffered(source) => source.current_frame_len(),
+            SongSource::Streaming(source) => source.current_frame_len(),
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        match self {
+            SongSource::Buffered(source) => source.channels(),
+            SongSource::Streaming(source) => source.channels(),
+        }
+    }
+
+    fn sample_rate(&self) -> u32 {
+        match self {
+            SongSource::Buffered(source) => source.sample_rate(),
+            SongSource::Streaming(source) => source.sample_rate(),
+        }
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        match self {
+            SongSource::Buffered(source) => source.total_duration(),
+            SongSource::Streaming(source) => source.total_duration(),
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! Short sounds for the metronome and hit sounds, generated at the output sample rate so no
+//! sample files have to be shipped with the editor.
+
+use std::f32::consts::TAU;
+
+use rodio::buffer::SamplesBuffer;
+use rodio::Source;
+
+use crate::SharedSource;
+
+fn shared(mono: Vec<f32>, sample_rate: u32, channels: u16) -> SharedSource {
+    let data: Vec<f32> = mono
+        .into_iter()
+        .flat_map(|x| std::iter::repeat(x).take(channels as usize))
+        .collect();
+    let source: Box<dyn Source<Item = f32> + Send> =
+        Box::new(SamplesBuffer::new(channels, sample_rate, data));
+    source.buffered()
+}
+
+/// Generates `ms` of audio from a function of the time in seconds.
+fn generate(ms: u32, sample_rate: u32, f: impl FnMut(f32) -> f32) -> Vec<f32> {
+    let len = (sample_rate * ms / 1000) as usize;
+    (0..len)
+        .map(|i| i as f32 / sample_rate as f32)
+        .map(f)
+        .collect()
+}
+
+/// Xorshift noise, the same every time.
+fn noise() -> impl FnMut() -> f32 {
+    let mut state = 0x2545_f491u32;
+    move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+}
+
+/// Metronome tick, higher on the first beat of a measure.
+pub fn click(measure: bool, sample_rate: u32, channels: u16) -> SharedSource {
+    let freq = if measure { 2000.0 } else { 1400.0 };
+    let mono = generate(30, sample_rate, |t| {
+        (t * freq * TAU).sin() * (-t * 150.0).exp() * 0.5
+    });
+    shared(mono, sample_rate, channels)
+}
+
+/// Clap for BT and FX chips.
+pub fn clap(sample_rate: u32, channels: u16) -> SharedSource {
+    let mut noise = noise();
+    let mut last = 0.0;
+    let mono = generate(80, sample_rate, |t| {
+        // Differencing the noise takes out the lows
+        let x = noise();
+        let high = x - last;
+        last = x;
+        high * (-t * 60.0).exp() * 0.4
+    });
+    shared(mono, sample_rate, channels)
+}
+
+/// Falling thump for laser slams.
+pub fn slam(sample_rate: u32, channels: u16) -> SharedSource {
+    let mut noise = noise();
+    let mut phase = 0.0;
+    let dt = 1.0 / sample_rate as f32;
+    let mono = generate(150, sample_rate, |t| {
+        phase += (60.0 + 220.0 * (-t * 20.0).exp()) * dt;
+        ((phase * TAU).sin() + noise() * 0.3) * (-t * 25.0).exp() * 0.6
+    });
+    shared(mono, sample_rate, channels)
+}
@@ -0,0 +1,136 @@
+use std::{
+    sync::{
+        atomic::{AtomicU32, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use rodio::{Sample, Source};
+
+/// Shared volume automation for [`Ducked`] sources. While any [`DuckGuard`] is alive the sources
+/// fade down to the gain of the guard, and back up once all guards are dropped.
+#[derive(Debug)]
+pub struct Ducking {
+    /// Time to fade from full volume to silence
+    ramp: Duration,
+    guards: AtomicUsize,
+    /// `f32` bits
+    gain: AtomicU32,
+}
+
+impl Ducking {
+    pub fn new(ramp: Duration) -> Arc<Self> {
+        Arc::new(Self {
+            ramp,
+            guards: AtomicUsize::new(0),
+            gain: AtomicU32::new(1.0f32.to_bits()),
+        })
+    }
+
+    /// Ducks the sources to `gain` until the returned guard is dropped.
+    pub fn duck(self: &Arc<Self>, gain: f32) -> DuckGuard {
+        self.gain
+            .store(gain.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+        self.guards.fetch_add(1, Ordering::Relaxed);
+        DuckGuard(self.clone())
+    }
+
+    fn target(&self) -> f32 {
+        if self.guards.load(Ordering::Relaxed) == 0 {
+            1.0
+        } else {
+            f32::from_bits(self.gain.load(Ordering::Relaxed))
+        }
+    }
+}
+
+pub struct DuckGuard(Arc<Ducking>);
+
+impl Drop for DuckGuard {
+    fn drop(&mut self) {
+        self.0.guards.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Samples between checks of the ducking target
+const TARGET_INTERVAL: u32 = 64;
+
+/// Fades `source` between full volume and the gain of `ducking`.
+pub fn ducked<I>(source: I, ducking: Arc<Ducking>) -> Ducked<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    let samples =
+        ducking.ramp.as_secs_f32() * source.sample_rate() as f32 * source.channels() as f32;
+    let gain = ducking.target();
+    Ducked {
+        input: source,
+        ducking,
+        gain,
+        target: gain,
+        step: if samples >= 1.0 { 1.0 / samples } else { 1.0 },
+        samples_until_check: TARGET_INTERVAL,
+    }
+}
+
+pub struct Ducked<I> {
+    input: I,
+    ducking: Arc<Ducking>,
+    gain: f32,
+    target: f32,
+    /// Gain change per sample
+    step: f32,
+    samples_until_check: u32,
+}
+
+impl<I> Iterator for Ducked<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.samples_until_check -= 1;
+        if self.samples_until_check == 0 {
+            self.samples_until_check = TARGET_INTERVAL;
+            self.target = self.ducking.target();
+        }
+
+        if self.gain < self.target {
+            self.gain = (self.gain + self.step).min(self.target);
+        } else if self.gain > self.target {
+            self.gain = (self.gain - self.step).max(self.target);
+        }
+
+        self.input.next().map(|x| x.amplify(self.gain))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<I> Source for Ducked<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
@@ -1,5 +1,6 @@
 pub mod biquad;
 pub mod bitcrush;
+pub mod ducking;
 pub mod effected_part;
 pub mod flanger;
 pub mod gate;
@@ -10,7 +11,9 @@ pub mod phaser;
 pub mod pitch_shift;
 pub mod re_trigger;
 pub mod side_chain;
+pub mod switch_audio;
 pub mod takeable_source;
 pub mod tape_stop;
+pub mod time_stretch;
 pub mod triangle;
 pub mod wobble;
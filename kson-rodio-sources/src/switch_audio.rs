@@ -0,0 +1,94 @@
+use std::time::Duration;
+
+use rodio::{source::UniformSourceIterator, Sample, Source};
+
+use super::mix_source::MixSource;
+
+/// Crossfades from `input` to an alternate source by the current mix value.
+pub struct SwitchAudio<I: Source<Item = D>, A: Source, D: Sample>
+where
+    A::Item: Sample,
+{
+    input: I,
+    alt: UniformSourceIterator<A, D>,
+    mix: f32,
+}
+
+pub fn switch_audio<I: Source<Item = D>, A: Source, D: Sample>(
+    source: I,
+    alt: A,
+) -> SwitchAudio<I, A, D>
+where
+    A::Item: Sample,
+{
+    let channels = source.channels();
+    let sample_rate = source.sample_rate();
+
+    SwitchAudio {
+        input: source,
+        alt: UniformSourceIterator::new(alt, channels, sample_rate),
+        mix: 1.0,
+    }
+}
+
+impl<I, A, D> Iterator for SwitchAudio<I, A, D>
+where
+    I: Source<Item = D>,
+    A: Source,
+    A::Item: Sample,
+    D: Sample,
+{
+    type Item = D;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let original = self.input.next()?;
+        //Always advance the alternate audio to keep it in sync with the input
+        let alt = self.alt.next().unwrap_or(D::zero_value());
+
+        if self.mix < f32::EPSILON {
+            return Some(original);
+        }
+
+        Some(
+            original
+                .amplify(1.0 - self.mix)
+                .saturating_add(alt.amplify(self.mix)),
+        )
+    }
+}
+
+impl<I, A, D> Source for SwitchAudio<I, A, D>
+where
+    I: Source<Item = D>,
+    A: Source,
+    A::Item: Sample,
+    D: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+impl<I, A, D> MixSource for SwitchAudio<I, A, D>
+where
+    I: Source<Item = D>,
+    A: Source,
+    A::Item: Sample,
+    D: Sample,
+{
+    fn set_mix(&mut self, mix: f32) {
+        self.mix = mix;
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use rodio::Source;
+use soundtouch::SoundTouch;
+
+/// Plays `input` at `tempo` times its speed while keeping its pitch.
+pub fn time_stretch<I: Source<Item = f32>>(input: I, tempo: f64) -> TimeStretch<I> {
+    let channels = input.channels();
+    let mut st = SoundTouch::new(channels, input.sample_rate());
+    st.set_tempo(tempo);
+    let min_samples = st.get_setting(soundtouch::settings::SETTING_NOMINAL_INPUT_SEQUENCE) as usize
+        * channels as usize;
+    let latency = Duration::from_secs_f64(
+        st.get_setting(soundtouch::settings::SETTING_INITIAL_LATENCY) as f64
+            / input.sample_rate() as f64,
+    );
+
+    TimeStretch {
+        input,
+        min_samples,
+        latency,
+        soundtouch: st,
+        out_buffer: VecDeque::new(),
+        in_buffer: VecDeque::new(),
+    }
+}
+
+pub struct TimeStretch<I: Source<Item = f32>> {
+    input: I,
+    soundtouch: SoundTouch,
+    min_samples: usize,
+    latency: Duration,
+    out_buffer: VecDeque<f32>,
+    in_buffer: VecDeque<f32>,
+}
+
+impl<I> TimeStretch<I>
+where
+    I: Source<Item = f32>,
+{
+    /// How far, in input time, the input has been read ahead of what is being output.
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    fn refill(&mut self) {
+        loop {
+            // Drain what is already processed first, otherwise slowed down audio piles up
+            // inside soundtouch and the latency keeps growing
+            self.out_buffer.resize(self.min_samples, 0.0);
+            self.out_buffer.make_contiguous();
+            let read = self
+                .soundtouch
+                .read_samples(self.out_buffer.as_mut_slices().0);
+            self.out_buffer
+                .truncate((read * self.input.channels() as u32) as usize);
+
+            if !self.out_buffer.is_empty() {
+                return;
+            }
+
+            self.in_buffer.clear();
+            self.input
+                .by_ref()
+                .take(self.min_samples)
+                .for_each(|x| self.in_buffer.push_back(x));
+
+            if self.in_buffer.is_empty() {
+                return;
+            }
+
+            self.soundtouch
+                .put_samples(self.in_buffer.make_contiguous());
+        }
+    }
+}
+
+impl<I> Iterator for TimeStretch<I>
+where
+    I: Source<Item = f32>,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.out_buffer.is_empty() {
+            self.refill();
+        }
+
+        self.out_buffer.pop_front()
+    }
+}
+
+impl<I> Source for TimeStretch<I>
+where
+    I: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        Some(self.min_samples)
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
@@ -45,6 +45,27 @@ pub fn main() -> Result<()> {
     println!("Slam:\t{}", tick_types.3);
     println!("----");
     println!("Total:\t{}", score_ticks.len());
+    println!("----");
+
+    let stats = chart.stats();
+    println!("Hold uptime:\t{:.1}%", stats.hold_uptime * 100.0);
+    println!("Laser uptime:\t{:.1}%", stats.laser_uptime * 100.0);
+    println!("Both hands:\t{:.1}%", stats.both_hands * 100.0);
+    println!(
+        "Hand balance:\t{:.1}% left ({} / {})",
+        stats.hand_balance() * 100.0,
+        stats.hand_notes[0],
+        stats.hand_notes[1]
+    );
+    println!("One hand trills:\t{}", stats.trills.len());
+    for trill in &stats.trills {
+        println!(
+            "\t{:?} hand, {} notes at measure {}",
+            trill.side,
+            trill.notes,
+            chart.tick_to_measure(trill.y) + 1
+        );
+    }
 
     Ok(())
 }
@@ -0,0 +1,114 @@
+//! Converts every chart in a folder between ksh/vox and kson, keeping the folder structure.
+
+use std::{
+    io::BufWriter,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use anyhow::Result;
+use clap::Parser;
+use kson::{Chart, Ksh};
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Folder searched for charts, including subfolders
+    #[clap(value_parser)]
+    input: PathBuf,
+    /// Folder the converted charts are written to, next to the originals if not set
+    #[clap(short, long, value_parser)]
+    output: Option<PathBuf>,
+    /// Convert kson charts to ksh instead
+    #[clap(long)]
+    to_ksh: bool,
+    /// Replace charts that already exist in the output folder
+    #[clap(long)]
+    overwrite: bool,
+    /// Write indented kson
+    #[clap(long)]
+    pretty: bool,
+}
+
+/// Collects the chart files under `dir` with one of the `extensions`.
+fn find_charts(dir: &Path, extensions: &[&str], charts: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_charts(&path, extensions, charts)?;
+        } else if path
+            .extension()
+            .and_then(|x| x.to_str())
+            .is_some_and(|x| extensions.contains(&x.to_ascii_lowercase().as_str()))
+        {
+            charts.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn convert(infile: &Path, outfile: &Path, args: &Args) -> Result<()> {
+    let chart = Chart::from_file(infile)?;
+    if let Some(parent) = outfile.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let out = BufWriter::new(std::fs::File::create(outfile)?);
+    if args.to_ksh {
+        chart.to_ksh(out)?;
+    } else if args.pretty {
+        serde_json::to_writer_pretty(out, &chart)?;
+    } else {
+        serde_json::to_writer(out, &chart)?;
+    }
+    Ok(())
+}
+
+pub fn main() -> Result<ExitCode> {
+    let args = Args::parse();
+    let (extensions, out_extension): (&[&str], _) = if args.to_ksh {
+        (&["kson"], "ksh")
+    } else {
+        (&["ksh", "vox"], "kson")
+    };
+
+    let mut charts = vec![];
+    find_charts(&args.input, extensions, &mut charts)?;
+    charts.sort();
+
+    let (mut converted, mut skipped, mut failed) = (0, 0, 0);
+    for infile in &charts {
+        let relative = infile.strip_prefix(&args.input)?;
+        let outfile = args
+            .output
+            .as_ref()
+            .unwrap_or(&args.input)
+            .join(relative)
+            .with_extension(out_extension);
+
+        if outfile.exists() && !args.overwrite {
+            println!(
+                "Skipped {}: {} exists",
+                relative.display(),
+                outfile.display()
+            );
+            skipped += 1;
+            continue;
+        }
+
+        match convert(infile, &outfile, &args) {
+            Ok(()) => converted += 1,
+            Err(e) => {
+                eprintln!("Failed {}: {e}", relative.display());
+                failed += 1;
+            }
+        }
+    }
+
+    println!("Converted: {converted}, skipped: {skipped}, failed: {failed}");
+    Ok(if failed > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
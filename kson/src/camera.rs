@@ -1,4 +1,4 @@
-use std::cmp::Ordering;
+use std::{cmp::Ordering, ops::Range};
 
 use crate::{ByPulse, Graph, GraphPoint, GraphSectionPoint};
 use serde::{Deserialize, Serialize};
@@ -11,6 +11,11 @@ use schemars::JsonSchema;
 pub struct CameraInfo {
     pub tilt: TiltInfo,
     pub cam: CamInfo,
+    /// Hides or shows the lanes over the given length, each toggle flips the visibility
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub lane_toggle: ByPulse<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub shake: Vec<CamShake>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Default)]
@@ -21,6 +26,105 @@ pub struct TiltInfo {
     pub keep: ByPulse<bool>,
 }
 
+impl CameraInfo {
+    /// Tick ranges covered by full and half spins, with overlapping spins merged. The lanes
+    /// are hard to follow while spinning so laser input is not judged in these ranges.
+    pub fn input_ignore_ranges(&self) -> Vec<Range<u32>> {
+        let slams = &self.cam.pattern.laser.slam_event;
+        let mut spins: Vec<Range<u32>> = slams
+            .spin
+            .iter()
+            .chain(&slams.half_spin)
+            .map(|x| x.0..x.0 + x.2)
+            .collect();
+        spins.sort_by_key(|x| x.start);
+
+        let mut ranges: Vec<Range<u32>> = vec![];
+        for spin in spins {
+            match ranges.last_mut() {
+                Some(last) if spin.start <= last.end => last.end = last.end.max(spin.end),
+                _ => ranges.push(spin),
+            }
+        }
+        ranges
+    }
+
+    /// Visibility of the lanes at `tick`, from 0 when hidden to 1 when shown. The lanes start
+    /// shown and fade over the length of each toggle.
+    pub fn lane_visibility_at(&self, tick: f64) -> f64 {
+        let passed = self.lane_toggle.partition_point(|x| x.0 as f64 <= tick);
+        let Some(&(y, length)) = passed.checked_sub(1).map(|i| &self.lane_toggle[i]) else {
+            return 1.0;
+        };
+
+        let progress = ((tick - y as f64) / length.max(1) as f64).min(1.0);
+        if passed % 2 == 1 {
+            1.0 - progress
+        } else {
+            progress
+        }
+    }
+
+    /// Sideways offset of the track and judgement line from shakes at `tick`, in the same
+    /// units as the shake strength.
+    pub fn shake_at(&self, tick: f64) -> f64 {
+        self.shake
+            .iter()
+            .filter_map(|x| Some((x, spin_progress(x.0, x.2, tick)?)))
+            .map(|(shake, progress)| {
+                (progress * SHAKE_CYCLES * std::f64::consts::TAU).sin()
+                    * shake.1 as f64
+                    * (1.0 - progress)
+            })
+            .sum()
+    }
+}
+
+/// Back and forth movements in a shake
+const SHAKE_CYCLES: f64 = 4.0;
+
+fn last_at<T: Copy>(list: &ByPulse<T>, tick: u32, default: T) -> T {
+    match list.binary_search_by_key(&tick, |x| x.0) {
+        Ok(i) => list[i].1,
+        Err(0) => default,
+        Err(i) => list[i - 1].1,
+    }
+}
+
+impl TiltInfo {
+    /// Multiplier applied to laser tilt at `tick`.
+    pub fn scale_at(&self, tick: u32) -> f64 {
+        last_at(&self.scale, tick, 1.0)
+    }
+
+    /// Whether the current tilt should be held when lasers end at `tick`.
+    pub fn keep_at(&self, tick: u32) -> bool {
+        last_at(&self.keep, tick, false)
+    }
+
+    /// Manual tilt value at `tick`, `None` if laser tilt is in effect.
+    pub fn manual_at(&self, tick: f64) -> Option<f64> {
+        self.manual.value_at(tick)
+    }
+
+    /// KSH name of the tilt mode set at `tick`, if any.
+    pub fn ksh_name_at(&self, tick: u32) -> Option<String> {
+        let i = self.scale.binary_search_by_key(&tick, |x| x.0).ok()?;
+        let name = match self.scale[i].1 {
+            v if v == 0.0 => "zero",
+            v if v >= 2.0 => "biggest",
+            v if v >= 1.5 => "bigger",
+            _ => "normal",
+        };
+
+        Some(if self.keep_at(tick) {
+            format!("keep_{name}")
+        } else {
+            name.to_string()
+        })
+    }
+}
+
 impl Graph<Option<f64>> for ByPulse<Vec<GraphSectionPoint>> {
     fn value_at(&self, tick: f64) -> Option<f64> {
         let tick_u = tick as u32;
@@ -109,11 +213,156 @@ impl CamPatternLaserInvokeList {
     fn is_empty(&self) -> bool {
         self.spin.is_empty() && self.swing.is_empty() && self.half_spin.is_empty()
     }
+
+    /// The latest spin started at or before `tick` that is still going.
+    pub fn spin_at(&self, tick: f64) -> Option<SpinState> {
+        let spins = self
+            .spin
+            .iter()
+            .filter_map(|x| Some((x.0, x.full_at(tick)?)));
+        let half_spins = self
+            .half_spin
+            .iter()
+            .filter_map(|x| Some((x.0, x.half_at(tick)?)));
+        let swings = self
+            .swing
+            .iter()
+            .filter_map(|x| Some((x.0, x.state_at(tick)?)));
+
+        spins
+            .chain(half_spins)
+            .chain(swings)
+            .max_by_key(|x| x.0)
+            .map(|x| x.1)
+    }
+
+    /// The spin, half spin or swing triggered by a slam at `y`, in KSH notation.
+    pub fn ksh_at(&self, y: u32) -> Option<String> {
+        let ksh_length = |l: u32| l * 192 / (4 * crate::KSON_RESOLUTION);
+        let find = |list: &[CamPatternInvokeSpin]| {
+            list.binary_search_by_key(&y, |x| x.0).ok().map(|i| list[i])
+        };
+
+        if let Some(spin) = find(&self.spin) {
+            let dir = if spin.1 < 0 { '(' } else { ')' };
+            return Some(format!("@{dir}{}", ksh_length(spin.2)));
+        }
+        if let Some(spin) = find(&self.half_spin) {
+            let dir = if spin.1 < 0 { '<' } else { '>' };
+            return Some(format!("@{dir}{}", ksh_length(spin.2)));
+        }
+
+        let i = self.swing.binary_search_by_key(&y, |x| x.0).ok()?;
+        let CamPatternInvokeSwing(_, dir, length, value) = self.swing[i];
+        let dir = if dir < 0 { '<' } else { '>' };
+        Some(format!(
+            "S{dir}{};{};{};{}",
+            ksh_length(length),
+            (value.scale * KSH_SWING_AMPLITUDE).round(),
+            value.repeat,
+            value.decay_order
+        ))
+    }
+}
+
+/// KSH swing amplitude matching a KSON swing scale of 1.0
+pub(crate) const KSH_SWING_AMPLITUDE: f32 = 250.0;
+
+/// Roll of a swing with a scale of 1.0, in degrees
+const SWING_DEGREES: f64 = 15.0;
+
+/// Kind of camera spin a slam can trigger.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpinKind {
+    Full,
+    Half,
+    Swing(CamPatternInvokeSwingValue),
+}
+
+/// A camera spin in progress.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpinState {
+    pub kind: SpinKind,
+    /// Negative for spins to the left
+    pub direction: i32,
+    /// From 0 at the start to 1 at the end of the spin
+    pub progress: f64,
+}
+
+impl SpinState {
+    /// Camera roll in degrees. Full spins follow the curve used by KSM, see
+    /// https://github.com/kshootmania/ksm-v2/blob/master/kshootmania/src/music_game/camera/cam_pattern/cam_pattern_spin.cpp#L52
+    pub fn roll(&self) -> f64 {
+        let rate = self.progress;
+        let full_spin = || {
+            if rate < 360.0 / 675.0 {
+                (rate / (360.0 / 675.0) * 0.75).sin() / 0.75f64.sin() * 360.0
+            } else if rate < 440.0 / 675.0 {
+                (((rate * 675.0 - 360.0) * 9.0 / 8.0).to_radians()).sin() * 30.0
+            } else {
+                (1.0 - ((1.0 - rate) * 90.0 / 235.0 * 675.0)
+                    .to_radians()
+                    .cos()
+                    .powf(2.0))
+                    * 30.0
+            }
+        };
+
+        let abs_degrees = match self.kind {
+            SpinKind::Full => full_spin(),
+            SpinKind::Half => full_spin() / 2.0,
+            SpinKind::Swing(value) => {
+                let decay = (1.0 - rate).powi(value.decay_order as i32);
+                (rate * value.repeat as f64 * std::f64::consts::TAU).sin()
+                    * value.scale as f64
+                    * SWING_DEGREES
+                    * decay
+            }
+        };
+
+        abs_degrees * -(self.direction as f64).signum()
+    }
+}
+
+fn spin_progress(y: u32, length: u32, tick: f64) -> Option<f64> {
+    let progress = (tick - y as f64) / length.max(1) as f64;
+    (0.0..=1.0).contains(&progress).then_some(progress)
+}
+
+impl CamPatternInvokeSpin {
+    pub fn full_at(&self, tick: f64) -> Option<SpinState> {
+        Some(SpinState {
+            kind: SpinKind::Full,
+            direction: self.1,
+            progress: spin_progress(self.0, self.2, tick)?,
+        })
+    }
+
+    pub fn half_at(&self, tick: f64) -> Option<SpinState> {
+        Some(SpinState {
+            kind: SpinKind::Half,
+            direction: self.1,
+            progress: spin_progress(self.0, self.2, tick)?,
+        })
+    }
+}
+
+impl CamPatternInvokeSwing {
+    pub fn state_at(&self, tick: f64) -> Option<SpinState> {
+        Some(SpinState {
+            kind: SpinKind::Swing(self.3),
+            direction: self.1,
+            progress: spin_progress(self.0, self.2, tick)?,
+        })
+    }
 }
 
 /// (pulse, direction, duration)
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, Default)]
 pub struct CamPatternInvokeSpin(pub u32, pub i32, pub u32);
+/// (pulse, strength, duration), the shake fades out over its duration
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, Default, PartialEq)]
+pub struct CamShake(pub u32, pub f32, pub u32);
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, Default)]
 pub struct CamPatternInvokeSwing(
     pub u32,
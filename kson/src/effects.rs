@@ -8,7 +8,7 @@ use kson_effect_param_macro::Effect;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use std::{borrow::Cow, collections::BTreeMap, f32, str::FromStr};
+use std::{borrow::Cow, collections::BTreeMap, f32, ops::RangeInclusive, str::FromStr};
 
 #[cfg(feature = "schema")]
 use schemars::JsonSchema;
@@ -16,6 +16,40 @@ use schemars::JsonSchema;
 pub(crate) trait Effect {
     fn derive(&self, key: &str, param: &str) -> Self;
     fn param_list() -> &'static [&'static str];
+    fn descriptors(&self) -> Vec<ParamDescriptor>;
+    fn param(&self, key: &str) -> Option<String>;
+    /// Returns false and leaves the parameter unchanged if `value` can't be parsed
+    fn set_param(&mut self, key: &str, value: &str) -> bool;
+}
+
+/// Kind of value an effect parameter takes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamUnit {
+    /// Note length like `1/4`, or a time in `ms` or `s`
+    Length,
+    Samples,
+    Switch,
+    /// Percentage, stored from 0 to 1
+    Rate,
+    Freq,
+    /// Semitones
+    Pitch,
+    Int,
+    Float,
+    Filename,
+}
+
+/// Describes an effect parameter so editors can be generated for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamDescriptor {
+    pub name: &'static str,
+    pub unit: ParamUnit,
+    /// Range of useful values in the unit of the parameter, `None` if it isn't bounded
+    pub range: Option<RangeInclusive<f32>>,
+    /// Default value as it is written in a chart
+    pub default: String,
+    /// Takes separate values for when the effect is off and on, written as `off>on`
+    pub transition: bool,
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
@@ -57,6 +91,40 @@ impl AudioEffect {
             AudioEffect::PeakingFilter(_) => "PeakingFilter",
         }
     }
+
+    /// Wet/dry balance of the effect, `None` for effects without a mix parameter
+    pub fn mix(&self) -> Option<&EffectParameter<f32>> {
+        match self {
+            AudioEffect::ReTrigger(e) => Some(&e.mix),
+            AudioEffect::Gate(e) => Some(&e.mix),
+            AudioEffect::Flanger(e) => Some(&e.mix),
+            AudioEffect::PitchShift(e) => Some(&e.mix),
+            AudioEffect::BitCrusher(e) => Some(&e.mix),
+            AudioEffect::Phaser(e) => Some(&e.mix),
+            AudioEffect::Wobble(e) => Some(&e.mix),
+            AudioEffect::TapeStop(e) => Some(&e.mix),
+            AudioEffect::Echo(e) => Some(&e.mix),
+            AudioEffect::HighPassFilter(e) => Some(&e.mix),
+            AudioEffect::LowPassFilter(e) => Some(&e.mix),
+            AudioEffect::PeakingFilter(e) => Some(&e.mix),
+            AudioEffect::SideChain(_) | AudioEffect::AudioSwap(_) => None,
+        }
+    }
+
+    /// Parameters of this effect type, in the order they are declared
+    pub fn param_descriptors(&self) -> Vec<ParamDescriptor> {
+        self.descriptors()
+    }
+
+    /// Current value of a parameter as it is written in a chart
+    pub fn param_value(&self, key: &str) -> Option<String> {
+        self.param(key)
+    }
+
+    /// Sets a parameter from its chart notation, returns false if `value` isn't valid for it
+    pub fn set_param_value(&mut self, key: &str, value: &str) -> bool {
+        self.set_param(key, value)
+    }
 }
 
 impl TryFrom<&str> for AudioEffect {
@@ -75,6 +143,9 @@ impl TryFrom<&str> for AudioEffect {
             "Echo" => Ok(AudioEffect::Echo(Echo::default())),
             "SideChain" => Ok(AudioEffect::SideChain(SideChain::default())),
             "SwitchAudio" => Ok(AudioEffect::AudioSwap("".to_owned())),
+            "PeakingFilter" => Ok(AudioEffect::PeakingFilter(PeakingFilter::default())),
+            "HighPassFilter" => Ok(AudioEffect::HighPassFilter(HighPassFilter::default())),
+            "LowPassFilter" => Ok(AudioEffect::LowPassFilter(LowPassFilter::default())),
             "peak" => Ok(AudioEffect::PeakingFilter(PeakingFilter::default())),
             "hpf1" => Ok(AudioEffect::HighPassFilter(HighPassFilter::default())),
             "lpf1" => Ok(AudioEffect::LowPassFilter(LowPassFilter::default())),
@@ -91,128 +162,222 @@ pub enum EffectError {
 }
 
 impl Effect for String {
-    fn derive(&self, _key: &str, param: &str) -> Self {
-        param.to_string()
+    fn derive(&self, key: &str, param: &str) -> Self {
+        match key {
+            "fileName" | "filename" | "file_name" => param.to_string(),
+            _ => self.clone(),
+        }
     }
 
     fn param_list() -> &'static [&'static str] {
         &[]
     }
+
+    fn descriptors(&self) -> Vec<ParamDescriptor> {
+        vec![ParamDescriptor {
+            name: "file_name",
+            unit: ParamUnit::Filename,
+            range: None,
+            default: String::new(),
+            transition: false,
+        }]
+    }
+
+    fn param(&self, key: &str) -> Option<String> {
+        match key {
+            "fileName" | "filename" | "file_name" => Some(self.clone()),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, key: &str, value: &str) -> bool {
+        match key {
+            "fileName" | "filename" | "file_name" => {
+                *self = value.to_string();
+                true
+            }
+            _ => false,
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
 pub struct ReTrigger {
+    #[param(unit = Length)]
     pub update_period: EffectParameter<f32>,
+    #[param(unit = Length)]
     pub wave_length: EffectParameter<f32>,
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub rate: EffectParameter<f32>,
+    #[param(unit = Switch)]
     pub update_trigger: BoolParameter,
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub mix: EffectParameter<f32>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
 pub struct Gate {
+    #[param(unit = Length)]
     pub wave_length: EffectParameter<f32>,
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub rate: EffectParameter<f32>,
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub mix: EffectParameter<f32>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
 pub struct Flanger {
+    #[param(unit = Length)]
     pub period: EffectParameter<f32>,
+    #[param(unit = Samples)]
     pub delay: EffectParameter<i64>,
+    #[param(unit = Samples)]
     pub depth: EffectParameter<i64>,
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub feedback: EffectParameter<f32>,
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub stereo_width: EffectParameter<f32>,
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub vol: EffectParameter<f32>,
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub mix: EffectParameter<f32>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
 pub struct PitchShift {
+    #[param(unit = Pitch, min = -48.0, max = 48.0)]
     pub pitch: EffectParameter<f32>,
+    #[param(unit = Switch)]
     pub pitch_quantize: BoolParameter,
+    #[param(unit = Samples)]
     pub chunk_size: EffectParameter<i64>,
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub overlap: EffectParameter<f32>,
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub mix: EffectParameter<f32>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
 pub struct BitCrusher {
+    #[param(unit = Samples, min = 0, max = 100)]
     pub reduction: EffectParameter<i64>,
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub mix: EffectParameter<f32>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
 pub struct Phaser {
+    #[param(unit = Length)]
     pub period: EffectParameter<f32>,
+    #[param(unit = Int, min = 0, max = 12)]
     pub stage: EffectParameter<i64>,
+    #[param(unit = Freq, min = 10.0, max = 20000.0)]
     pub lo_freq: EffectParameter<f32>,
+    #[param(unit = Freq, min = 10.0, max = 20000.0)]
     pub hi_freq: EffectParameter<f32>,
+    #[param(unit = Float, min = 0.1, max = 50.0)]
     pub q: EffectParameter<f32>,
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub feedback: EffectParameter<f32>,
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub stereo_width: EffectParameter<f32>,
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub mix: EffectParameter<f32>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
 pub struct Wobble {
+    #[param(unit = Length)]
     pub wave_length: EffectParameter<f32>,
+    #[param(unit = Freq, min = 10.0, max = 20000.0)]
     pub lo_freq: EffectParameter<f32>,
+    #[param(unit = Freq, min = 10.0, max = 20000.0)]
     pub hi_freq: EffectParameter<f32>,
+    #[param(unit = Float, min = 0.1, max = 50.0)]
     pub q: EffectParameter<f32>,
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub mix: EffectParameter<f32>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
 pub struct TapeStop {
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub speed: EffectParameter<f32>,
+    #[param(unit = Switch)]
     pub trigger: BoolParameter,
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub mix: EffectParameter<f32>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
 pub struct Echo {
+    #[param(unit = Length)]
     pub update_period: EffectParameter<f32>,
+    #[param(unit = Length)]
     pub wave_length: EffectParameter<f32>,
+    #[param(unit = Switch)]
     pub update_trigger: BoolParameter,
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub feedback_level: EffectParameter<f32>,
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub mix: EffectParameter<f32>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
 pub struct SideChain {
+    #[param(unit = Length)]
     pub period: EffectParameter<f32>,
+    #[param(unit = Length)]
     pub hold_time: EffectParameter<f32>,
+    #[param(unit = Length)]
     pub attack_time: EffectParameter<f32>,
+    #[param(unit = Length)]
     pub release_time: EffectParameter<f32>,
+    #[param(unit = Float, min = 1.0, max = 20.0)]
     pub ratio: EffectParameter<f32>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
 pub struct HighPassFilter {
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub v: EffectParameter<f32>,
+    #[param(unit = Freq, min = 10.0, max = 20000.0)]
     pub freq: EffectParameter<f32>,
+    #[param(unit = Float, min = 0.1, max = 50.0)]
     pub q: EffectParameter<f32>,
+    #[param(unit = Length)]
     pub delay: EffectParameter<f32>,
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub mix: EffectParameter<f32>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
 pub struct LowPassFilter {
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub v: EffectParameter<f32>,
+    #[param(unit = Freq, min = 10.0, max = 20000.0)]
     pub freq: EffectParameter<f32>,
+    #[param(unit = Float, min = 0.1, max = 50.0)]
     pub q: EffectParameter<f32>,
+    #[param(unit = Length)]
     pub delay: EffectParameter<f32>,
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub mix: EffectParameter<f32>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Effect, PartialEq, Debug)]
 pub struct PeakingFilter {
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub v: EffectParameter<f32>,
+    #[param(unit = Freq, min = 10.0, max = 20000.0)]
     pub freq: EffectParameter<f32>,
+    #[param(unit = Float, min = 0.1, max = 50.0)]
     pub q: EffectParameter<f32>,
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub gain: EffectParameter<f32>,
+    #[param(unit = Length)]
     pub delay: EffectParameter<f32>,
+    #[param(unit = Rate, min = 0.0, max = 1.0)]
     pub mix: EffectParameter<f32>,
 }
 
@@ -268,7 +433,7 @@ impl Default for PitchShift {
             pitch_quantize: default_param("0"),
             chunk_size: default_param("0"),
             overlap: default_param("0"),
-            mix: default_param("0"),
+            mix: default_param("0%>100%"),
         }
     }
 }
@@ -290,7 +455,7 @@ impl Default for Phaser {
             q: default_param("0"),
             feedback: default_param("0"),
             stereo_width: default_param("0"),
-            mix: default_param("0"),
+            mix: default_param("0%>50%"),
         }
     }
 }
@@ -321,7 +486,7 @@ impl Default for Echo {
             wave_length: default_param("0"),
             update_trigger: default_param("0"),
             feedback_level: default_param("0"),
-            mix: default_param("0"),
+            mix: default_param("0%>100%"),
         }
     }
 }
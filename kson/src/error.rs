@@ -0,0 +1,28 @@
+use thiserror::Error;
+
+use crate::{KshReadError, KshWriteError, VoxReadError};
+
+/// Errors from loading or saving charts in any of the supported formats.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Failed to access chart file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Unknown chart format: '{0}'")]
+    UnknownFormat(String),
+    #[error("Unsupported kson version: '{0}'")]
+    UnsupportedVersion(String),
+    #[error("Failed to migrate kson {version} at `{field}`: {message}")]
+    Migration {
+        version: String,
+        field: String,
+        message: String,
+    },
+    #[error("Malformed kson: {0}")]
+    Kson(#[from] serde_json::Error),
+    #[error("Malformed ksh: {0}")]
+    KshRead(#[from] KshReadError),
+    #[error("Failed to write ksh: {0}")]
+    KshWrite(#[from] KshWriteError),
+    #[error("Malformed vox: {0}")]
+    VoxRead(#[from] VoxReadError),
+}
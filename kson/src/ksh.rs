@@ -1,4 +1,5 @@
 use std::io;
+use std::io::BufRead;
 use std::io::BufWriter;
 use std::io::Write;
 
@@ -10,6 +11,7 @@ use thiserror::Error;
 use self::camera::CamPatternInvokeSpin;
 use self::camera::CamPatternInvokeSwing;
 use self::camera::CamPatternInvokeSwingValue;
+use self::camera::CamShake;
 
 #[derive(Debug, Error)]
 pub enum KshReadErrorDetails {
@@ -25,6 +27,8 @@ pub enum KshReadErrorDetails {
     EmptyLaserSection,
     #[error("Invalid tilt value: '{0}'")]
     InvalidTiltValue(String),
+    #[error("Failed to read chart: {0}")]
+    Io(#[from] io::Error),
 }
 
 #[derive(Debug, Error)]
@@ -70,11 +74,37 @@ pub enum KshWriteError {
     FileWriteError(#[from] io::Error),
 }
 
+/// Oldest KSM version an exported chart has to load in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KshCompatibility {
+    #[default]
+    Current,
+    /// KSM 1.60, header keys and manual tilt added later are left out and the chart is marked
+    /// as `ver=160`
+    Legacy160,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KshWriteOptions {
+    pub compatibility: KshCompatibility,
+}
+
 pub trait Ksh {
     fn from_ksh(data: &str) -> Result<crate::Chart, KshReadError>;
-    fn to_ksh<W>(&self, out: W) -> Result<(), KshWriteError>
+    /// Parses a chart one line at a time instead of reading the whole file first.
+    fn from_ksh_reader(reader: impl BufRead) -> Result<crate::Chart, KshReadError>;
+    /// Reads only the header, stopping at the first measure. The chart has the metadata, bgm
+    /// and starting BPM but no notes.
+    fn metadata_from_ksh_reader(reader: impl BufRead) -> Result<crate::Chart, KshReadError>;
+    fn to_ksh_with_options<W>(&self, out: W, options: KshWriteOptions) -> Result<(), KshWriteError>
     where
         W: std::io::Write;
+    fn to_ksh<W>(&self, out: W) -> Result<(), KshWriteError>
+    where
+        W: std::io::Write,
+    {
+        self.to_ksh_with_options(out, KshWriteOptions::default())
+    }
 }
 
 #[inline]
@@ -172,22 +202,110 @@ fn split_fx_string(v: String) -> (String, Option<String>, Option<String>) {
     )
 }
 
+/// Effect type as it is written in a KSH `#define_fx` or `#define_filter` line
+fn ksh_effect_type(effect: &AudioEffect) -> &'static str {
+    match effect {
+        AudioEffect::ReTrigger(_) => "Retrigger",
+        AudioEffect::AudioSwap(_) => "SwitchAudio",
+        e => e.name(),
+    }
+}
+
+/// KSH writes effect parameters in camel case, `wave_length` becomes `waveLength`
+fn ksh_param_key(effect: &AudioEffect, key: &str) -> String {
+    match (effect, key) {
+        (AudioEffect::Flanger(_), "vol") => return "volume".to_string(),
+        (AudioEffect::Phaser(_), "q") => return "Q".to_string(),
+        _ => {}
+    }
+
+    let mut parts = key.split('_');
+    let mut ksh_key = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            ksh_key.extend(first.to_uppercase());
+            ksh_key.push_str(chars.as_str());
+        }
+    }
+    ksh_key
+}
+
+/// Inverse of `ksh_param_key`
+fn kson_param_key(key: &str) -> String {
+    if key == "volume" {
+        return "vol".to_string();
+    }
+
+    let mut kson_key = String::with_capacity(key.len() + 2);
+    for (i, c) in key.chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            kson_key.push('_');
+        }
+        kson_key.push(c.to_ascii_lowercase());
+    }
+    kson_key
+}
+
+/// Inverse of `convert_params`, the `fx-l`/`fx-r` value written before a long FX note
+fn fx_long_string(
+    name: &str,
+    effect: Option<&AudioEffect>,
+    params: Option<&Dict<String>>,
+) -> String {
+    let param = |key: &str| params.and_then(|x| x.get(key)).map(String::as_str);
+    let wave_length = || param("wave_length").and_then(|x| x.strip_prefix("1/"));
+
+    let (p1, p2) = match effect {
+        Some(AudioEffect::ReTrigger(_) | AudioEffect::Gate(_) | AudioEffect::Wobble(_)) => {
+            (wave_length(), None)
+        }
+        Some(AudioEffect::PitchShift(_)) => (param("pitch"), None),
+        Some(AudioEffect::BitCrusher(_)) => (
+            param("reduction").and_then(|x| x.strip_suffix("samples")),
+            None,
+        ),
+        Some(AudioEffect::TapeStop(_)) => (param("speed").and_then(|x| x.strip_suffix('%')), None),
+        Some(AudioEffect::Echo(_)) => (
+            wave_length(),
+            param("feedback_level").and_then(|x| x.strip_suffix('%')),
+        ),
+        _ => (None, None),
+    };
+
+    match (p1, p2) {
+        (Some(p1), Some(p2)) => format!("{name};{p1};{p2}"),
+        (Some(p1), None) => format!("{name};{p1}"),
+        _ => name.to_string(),
+    }
+}
+
+/// `#define_fx` and `#define_filter` parameters that differ from the effect's defaults
+fn ksh_effect_definition(effect: &AudioEffect) -> String {
+    let params = effect.param_descriptors().into_iter().filter_map(|param| {
+        let value = effect.param_value(param.name)?;
+        (value != param.default).then(|| format!("{}={}", ksh_param_key(effect, param.name), value))
+    });
+
+    std::iter::once(format!("type={}", ksh_effect_type(effect)))
+        .chain(params)
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
 const PLACEHOLDER_PARAM_1: &str = "_p1";
 const PLACEHOLDER_PARAM_2: &str = "_p2";
 
-impl Ksh for crate::Chart {
-    fn from_ksh(data: &str) -> Result<crate::Chart, KshReadError> {
+impl Chart {
+    /// Reads a KSH chart one line at a time, with `metadata_only` reading stops after the
+    /// header.
+    fn read_ksh<L: AsRef<str>>(
+        mut lines: impl Iterator<Item = Result<L, KshReadError>>,
+        metadata_only: bool,
+    ) -> Result<crate::Chart, KshReadError> {
         let mut new_chart = Chart::new();
         let mut num = 4;
         let mut den = 4;
-        //BOM check
-        let data = if data.starts_with(&String::from_utf8_lossy(&[0xEF, 0xBB, 0xBF]).to_string()) {
-            &data[3..]
-        } else {
-            data
-        };
-        let mut parts: Vec<&str> = data.split("\n--").collect();
-        let meta = parts.first().unwrap_or(&"").lines();
         let mut bgm = BgmInfo::new();
 
         //TODO
@@ -199,8 +317,21 @@ impl Ksh for crate::Chart {
 
         let mut legacy_bg: Option<LegacyBgInfo> = None;
         let mut file_line = 0;
-        for (line_idx, line) in meta.enumerate() {
-            file_line = line_idx + 1;
+        let mut has_measures = false;
+        for line in lines.by_ref() {
+            let line = line?;
+            let line = line.as_ref();
+            file_line += 1;
+            //BOM check
+            let line = if file_line == 1 {
+                line.trim_start_matches('\u{feff}')
+            } else {
+                line
+            };
+            if line.starts_with("--") {
+                has_measures = true;
+                break;
+            }
             let line_data: Vec<&str> = line.split('=').collect();
             if line_data.len() < 2 {
                 continue;
@@ -212,6 +343,7 @@ impl Ksh for crate::Chart {
                 "effect" => new_chart.meta.chart_author = value,
                 "jacket" => new_chart.meta.jacket_filename = value,
                 "illustrator" => new_chart.meta.jacket_author = value,
+                "information" => new_chart.meta.information = Some(value),
                 "t" => {
                     if let Ok(v) = value.parse::<f64>() {
                         new_chart.beat.bpm.push((0, v))
@@ -242,17 +374,52 @@ impl Ksh for crate::Chart {
                 "plength" => bgm.preview.duration = value.parse().with_line(file_line)?,
                 "po" => bgm.preview.offset = value.parse().with_line(file_line)?,
                 "mvol" => bgm.vol = value.parse::<f64>().with_line(file_line)? / 100.0,
+                "bg" => {
+                    legacy_bg.get_or_insert_with(Default::default).bg = Some(
+                        value
+                            .split(';')
+                            .map(|filename| KshBgInfo {
+                                filename: filename.to_string(),
+                            })
+                            .collect(),
+                    )
+                }
                 "layer" => {
-                    //TODO: parse properly
-                    legacy_bg = Some(LegacyBgInfo {
-                        bg: None,
-                        layer: Some(KshLayerInfo {
-                            filename: Some(value),
-                            duration: 0,
-                            rotation: None,
-                        }),
-                        movie: None,
-                    })
+                    let mut params = value.split(';');
+                    let filename = params.next().map(String::from);
+                    let duration = params.next().and_then(|x| x.parse().ok()).unwrap_or(0);
+                    // Bit 0 rotates the layer with tilts, bit 1 with spins
+                    let rotation = params.next().and_then(|x| x.parse::<u8>().ok()).map(|x| {
+                        KshLayerRotationInfo {
+                            tilt: x & 1 != 0,
+                            spin: x & 2 != 0,
+                        }
+                    });
+                    legacy_bg.get_or_insert_with(Default::default).layer = Some(KshLayerInfo {
+                        filename,
+                        duration,
+                        rotation,
+                    });
+                }
+                "v" => {
+                    legacy_bg
+                        .get_or_insert_with(Default::default)
+                        .movie
+                        .get_or_insert(KshMovieInfo {
+                            filename: None,
+                            offset: 0,
+                        })
+                        .filename = Some(value)
+                }
+                "vo" => {
+                    legacy_bg
+                        .get_or_insert_with(Default::default)
+                        .movie
+                        .get_or_insert(KshMovieInfo {
+                            filename: None,
+                            offset: 0,
+                        })
+                        .offset = value.parse().with_line(file_line)?
                 }
                 _ => (),
             }
@@ -260,7 +427,10 @@ impl Ksh for crate::Chart {
 
         new_chart.bg.legacy = legacy_bg;
         new_chart.audio.bgm = bgm;
-        parts.remove(0);
+        if metadata_only {
+            return Ok(new_chart);
+        }
+
         let mut y: u32 = 0;
         let mut measure_index = 0;
         let mut last_char: [u8; 8] = [b'0'; 8];
@@ -276,13 +446,30 @@ impl Ksh for crate::Chart {
         let mut fx_string: [Option<String>; 2] = [None, None];
         let mut manual_tilt: (u32, Vec<GraphSectionPoint>) = (u32::MAX, vec![]);
 
-        for measure in parts {
-            let measure_lines = measure.lines();
-            let line_count = measure.lines().filter(is_beat_line).count() as u32;
+        let mut measure: Vec<L> = vec![];
+        let mut more_measures = has_measures;
+        while more_measures {
+            // Lines up to the next measure separator
+            measure.clear();
+            more_measures = false;
+            for line in lines.by_ref() {
+                let line = line?;
+                if line.as_ref().starts_with("--") {
+                    more_measures = true;
+                    break;
+                }
+                measure.push(line);
+            }
+
+            let line_count = measure
+                .iter()
+                .map(|x| x.as_ref())
+                .filter(is_beat_line)
+                .count() as u32;
             let mut ticks_per_line = (KSON_RESOLUTION * 4 * num / den) / line_count.max(1);
             let mut has_read_notes = false;
-            for line in measure_lines {
-                let line = line.trim();
+            for line in &measure {
+                let line = line.as_ref().trim();
                 file_line += 1;
                 if is_beat_line(&line) {
                     //read bt
@@ -395,12 +582,28 @@ impl Ksh for crate::Chart {
                     }
 
                     if chars.len() > 12 {
-                        let spin_length = String::from_utf8_lossy(&chars[12..])
+                        // Length in 192nds of a 4/4 measure, swings add `;amplitude;repeat;decay`
+                        let spin_params = String::from_utf8_lossy(&chars[12..]);
+                        let mut spin_params = spin_params.split(';').map(str::trim);
+                        let spin_length = spin_params
+                            .next()
+                            .unwrap_or_default()
                             .parse::<u32>()
                             .map(|x| (x * 4 * KSON_RESOLUTION) / 192);
                         let slam_event = &mut new_chart.camera.cam.pattern.laser.slam_event;
 
                         if let Ok(spin_length) = spin_length {
+                            let mut swing_value = CamPatternInvokeSwingValue::default();
+                            if let Some(Ok(amplitude)) = spin_params.next().map(str::parse::<f32>) {
+                                swing_value.scale = amplitude / camera::KSH_SWING_AMPLITUDE;
+                            }
+                            if let Some(Ok(repeat)) = spin_params.next().map(str::parse) {
+                                swing_value.repeat = repeat;
+                            }
+                            if let Some(Ok(decay_order)) = spin_params.next().map(str::parse) {
+                                swing_value.decay_order = decay_order;
+                            }
+
                             match (
                                 chars.get(10).copied().unwrap_or_default(),
                                 chars.get(11).copied().unwrap_or_default(),
@@ -425,18 +628,12 @@ impl Ksh for crate::Chart {
                                         .spin
                                         .push(CamPatternInvokeSpin(y, 1, spin_length))
                                 }
-                                (b'S', b'(') => slam_event.swing.push(CamPatternInvokeSwing(
-                                    y,
-                                    -1,
-                                    spin_length,
-                                    CamPatternInvokeSwingValue::default(),
-                                )),
-                                (b'S', b')') => slam_event.swing.push(CamPatternInvokeSwing(
-                                    y,
-                                    1,
-                                    spin_length,
-                                    CamPatternInvokeSwingValue::default(),
-                                )),
+                                (b'S', b'<' | b'(') => slam_event
+                                    .swing
+                                    .push(CamPatternInvokeSwing(y, -1, spin_length, swing_value)),
+                                (b'S', b'>' | b')') => slam_event
+                                    .swing
+                                    .push(CamPatternInvokeSwing(y, 1, spin_length, swing_value)),
                                 _ => {}
                             }
                         }
@@ -461,7 +658,7 @@ impl Ksh for crate::Chart {
 
                     if let Some(Ok(mut t)) = data.remove("type").map(AudioEffect::try_from) {
                         for (key, param) in data.into_iter() {
-                            t = t.derive(key, param)
+                            t = t.derive(&kson_param_key(key), param)
                         }
 
                         match defined {
@@ -550,10 +747,45 @@ impl Ksh for crate::Chart {
                         "fx-r" => {
                             fx_string[1] = Some(line_value);
                         }
+                        "fx-l_se" | "fx-r_se" => {
+                            let side = if line_prop == "fx-l_se" { 0 } else { 1 };
+                            let (filename, vol) = line_value
+                                .split_once(';')
+                                .unwrap_or((line_value.as_str(), "100"));
+                            let vol = vol.parse::<f64>().with_line(file_line)? / 100.0;
+                            new_chart
+                                .audio
+                                .key_sound
+                                .fx
+                                .chip_event
+                                .entry(filename.to_owned())
+                                .or_default()[side]
+                                .push((y, KeySoundInvokeFX { vol }));
+                        }
                         "tilt" => {
                             parse_tilt(&mut new_chart.camera.tilt, y, &line_value, &mut manual_tilt)
                                 .with_line(file_line)?
                         }
+                        // Lengths in 192nds of a 4/4 measure like spins
+                        "lane_toggle" => {
+                            let length: u32 = line_value.trim().parse().with_line(file_line)?;
+                            new_chart
+                                .camera
+                                .lane_toggle
+                                .push((y, (length * 4 * KSON_RESOLUTION) / 192));
+                        }
+                        "shake" => {
+                            let (strength, length) = line_value
+                                .split_once(';')
+                                .unwrap_or((line_value.as_str(), "48"));
+                            let strength = strength.trim().parse().with_line(file_line)?;
+                            let length: u32 = length.trim().parse().with_line(file_line)?;
+                            new_chart.camera.shake.push(CamShake(
+                                y,
+                                strength,
+                                (length * 4 * KSON_RESOLUTION) / 192,
+                            ));
+                        }
                         "filtertype" => {
                             let laser = &mut new_chart.audio.audio_effect.laser;
                             if let Ok(e) = AudioEffect::try_from(line_value.as_ref()) {
@@ -570,6 +802,8 @@ impl Ksh for crate::Chart {
                 }
             }
             measure_index += 1;
+            // The separator that ended this measure
+            file_line += 1;
         }
         //set slams
         for i in 0..2 {
@@ -640,13 +874,28 @@ impl Ksh for crate::Chart {
 
         Ok(new_chart)
     }
+}
+
+impl Ksh for crate::Chart {
+    fn from_ksh(data: &str) -> Result<crate::Chart, KshReadError> {
+        Self::read_ksh(data.lines().map(Ok), false)
+    }
+
+    fn from_ksh_reader(reader: impl BufRead) -> Result<crate::Chart, KshReadError> {
+        Self::read_ksh(reader.lines().map(|x| x.with_line(usize::MAX)), false)
+    }
+
+    fn metadata_from_ksh_reader(reader: impl BufRead) -> Result<crate::Chart, KshReadError> {
+        Self::read_ksh(reader.lines().map(|x| x.with_line(usize::MAX)), true)
+    }
 
     //TODO: Write optimized charts using lcm, also ksm doesn't seem to like resolution > 48
-    fn to_ksh<W>(&self, out: W) -> Result<(), KshWriteError>
+    fn to_ksh_with_options<W>(&self, out: W, options: KshWriteOptions) -> Result<(), KshWriteError>
     where
         W: std::io::Write,
     {
         let mut w = BufWriter::new(out);
+        let legacy = options.compatibility == KshCompatibility::Legacy160;
 
         //Meta
         {
@@ -666,7 +915,7 @@ impl Ksh for crate::Chart {
             writeln!(&mut w, "jacket={}\r", self.meta.jacket_filename)?;
             writeln!(&mut w, "illustrator={}\r", self.meta.jacket_author)?;
             let bgm = self.audio.bgm.clone();
-            writeln!(&mut w, "m={}\r", bgm.filename)?;
+            writeln!(&mut w, "m={}\r", bgm.ksh_filenames())?;
             writeln!(&mut w, "o={}\r", bgm.offset)?;
             writeln!(&mut w, "po={}\r", bgm.preview.offset)?;
 
@@ -692,15 +941,94 @@ impl Ksh for crate::Chart {
                 writeln!(&mut w, "t={:.1}-{:.1}\r", min_bpm, max_bpm)?;
             }
             writeln!(&mut w, "plength={}\r", bgm.preview.duration)?;
-            writeln!(
-                &mut w,
-                "information={}\r",
-                self.meta.information.clone().unwrap_or_default()
-            )?;
-            writeln!(&mut w, "ver=171\r")?;
+            writeln!(&mut w, "mvol={}\r", (bgm.vol * 100.0).round() as i32)?;
+            if let Some(bg) = &self.bg.legacy {
+                if let Some(images) = &bg.bg {
+                    let images = images.iter().map(|x| x.filename.as_str());
+                    writeln!(&mut w, "bg={}\r", images.collect::<Vec<_>>().join(";"))?;
+                }
+                if let Some(layer) = &bg.layer {
+                    write!(&mut w, "layer={}", layer.filename.as_deref().unwrap_or(""))?;
+                    write!(&mut w, ";{}", layer.duration)?;
+                    if let Some(rotation) = &layer.rotation {
+                        let flags = rotation.tilt as u8 | (rotation.spin as u8) << 1;
+                        write!(&mut w, ";{flags}")?;
+                    }
+                    writeln!(&mut w, "\r")?;
+                }
+                if let Some(movie) = &bg.movie {
+                    if let Some(filename) = &movie.filename {
+                        writeln!(&mut w, "v={}\r", filename)?;
+                    }
+                    writeln!(&mut w, "vo={}\r", movie.offset)?;
+                }
+            }
+            if legacy {
+                writeln!(&mut w, "ver=160\r")?;
+            } else {
+                writeln!(
+                    &mut w,
+                    "information={}\r",
+                    self.meta.information.clone().unwrap_or_default()
+                )?;
+                writeln!(&mut w, "ver=171\r")?;
+            }
             writeln!(&mut w, "--\r")?;
         }
 
+        // Effect and key sound options, written on the tick they apply to
+        let mut tick_options: HashMap<u32, Vec<String>> = HashMap::new();
+        {
+            let audio_effect = &self.audio.audio_effect;
+            for (name, sides) in &audio_effect.fx.long_event {
+                for (side, events) in ["fx-l", "fx-r"].iter().zip(sides) {
+                    for event in events {
+                        let fx = fx_long_string(name, audio_effect.fx.def.get(name), event.value());
+                        tick_options
+                            .entry(event.tick())
+                            .or_default()
+                            .push(format!("{side}={fx}"));
+                    }
+                }
+            }
+
+            for (filename, sides) in &self.audio.key_sound.fx.chip_event {
+                for (side, events) in ["fx-l_se", "fx-r_se"].iter().zip(sides) {
+                    for (y, invoke) in events {
+                        tick_options.entry(*y).or_default().push(format!(
+                            "{side}={filename};{}",
+                            (invoke.vol * 100.0).round() as i32
+                        ));
+                    }
+                }
+            }
+
+            for (name, events) in &audio_effect.laser.pulse_event {
+                for (y, _) in events {
+                    tick_options
+                        .entry(*y)
+                        .or_default()
+                        .push(format!("filtertype={name}"));
+                }
+            }
+
+            let ksh_length = |l: u32| l * 192 / (4 * KSON_RESOLUTION);
+            for (y, length) in &self.camera.lane_toggle {
+                tick_options
+                    .entry(*y)
+                    .or_default()
+                    .push(format!("lane_toggle={}", ksh_length(*length)));
+            }
+            for CamShake(y, strength, length) in &self.camera.shake {
+                tick_options
+                    .entry(*y)
+                    .or_default()
+                    .push(format!("shake={strength};{}", ksh_length(*length)));
+            }
+
+            tick_options.values_mut().for_each(|x| x.sort());
+        }
+
         let mut measure = 0;
         let mut last_laser_write_y = [u32::MAX, u32::MAX];
         let mut last_laser_write_v = [char::MAX, char::MAX];
@@ -745,7 +1073,41 @@ impl Ksh for crate::Chart {
                         }
                     }
 
-                    //Camera Pos
+                    //Tilt, manual values need KSM 1.70
+                    for (start, points) in &self.camera.tilt.manual {
+                        if legacy || y < *start {
+                            continue;
+                        }
+                        if let Ok(i) = points.binary_search_by(|p| p.ry.cmp(&(y - start))) {
+                            writeln!(&mut w, "tilt={}\r", points[i].v)?;
+                            if let Some(vf) = points[i].vf {
+                                writeln!(&mut w, "tilt={}\r", vf)?;
+                            }
+                        }
+                    }
+                    if let Some(tilt) = self.camera.tilt.ksh_name_at(y) {
+                        writeln!(&mut w, "tilt={}\r", tilt)?;
+                    }
+
+                    //Camera
+                    let body = &self.camera.cam.body;
+                    for (key, graph) in [
+                        ("zoom_bottom", &body.zoom),
+                        ("zoom_top", &body.rotation_x),
+                        ("zoom_side", &body.shift_x),
+                    ] {
+                        if let Ok(i) = graph.binary_search_by(|p| p.y.cmp(&y)) {
+                            match graph[i].vf {
+                                Some(vf) => writeln!(&mut w, "{key}={};{vf}\r", graph[i].v)?,
+                                None => writeln!(&mut w, "{key}={}\r", graph[i].v)?,
+                            }
+                        }
+                    }
+
+                    //Effects and key sounds
+                    for option in tick_options.get(&y).into_iter().flatten() {
+                        writeln!(&mut w, "{option}\r")?;
+                    }
                 }
 
                 //BT
@@ -932,6 +1294,11 @@ impl Ksh for crate::Chart {
                         }
                     }
                 }
+
+                //Spins
+                if let Some(spin) = self.camera.cam.pattern.laser.slam_event.ksh_at(y) {
+                    w.write_all(spin.as_bytes())?;
+                }
                 w.write_all(b"\r\n")?;
             }
 
@@ -939,6 +1306,23 @@ impl Ksh for crate::Chart {
             measure += 1;
         }
 
+        //Effect definitions, effects named after a type don't need one
+        let audio_effect = &self.audio.audio_effect;
+        for (defined, defs) in [
+            ("#define_fx", &audio_effect.fx.def),
+            ("#define_filter", &audio_effect.laser.def),
+        ] {
+            let mut names: Vec<_> = defs
+                .keys()
+                .filter(|x| AudioEffect::try_from(x.as_str()).is_err())
+                .collect();
+            names.sort();
+            for name in names {
+                let definition = ksh_effect_definition(&defs[name]);
+                writeln!(&mut w, "{defined} {name} {definition}\r")?;
+            }
+        }
+
         Ok(())
     }
 }
@@ -1,21 +1,30 @@
 pub mod camera;
 pub mod effects;
+mod error;
 mod graph;
 mod ksh;
+mod migrate;
 pub mod overlaps;
 pub mod parameter;
+pub mod render_model;
 pub mod score_ticks;
+mod splice;
+pub mod stats;
+pub mod validate;
 mod vox;
 
 use camera::CameraInfo;
 use effects::AudioEffect;
+pub use error::*;
 pub use graph::*;
 pub use ksh::*;
 use serde::de::Visitor;
 use serde::{Deserialize, Serialize};
+pub use splice::{LaneSelection, PasteConflict};
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::marker::PhantomData;
+use std::path::Path;
 use std::slice::Windows;
 use std::str;
 pub use vox::*;
@@ -84,7 +93,7 @@ enum SingleOrPair<T> {
     Pair(T, T),
 }
 
-#[derive(Copy, Clone, Default)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct GraphPoint {
     pub y: u32,
     pub v: f64,
@@ -92,6 +101,18 @@ pub struct GraphPoint {
     pub a: f64,
     pub b: f64,
 }
+
+impl Default for GraphPoint {
+    fn default() -> Self {
+        Self {
+            y: 0,
+            v: 0.0,
+            vf: None,
+            a: 0.5,
+            b: 0.5,
+        }
+    }
+}
 impl<'de> Deserialize<'de> for GraphPoint {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -139,11 +160,9 @@ impl Serialize for GraphPoint {
         S: serde::Serializer,
     {
         use serde::ser::SerializeTuple;
-        let point_len = if (self.a - self.b).abs() > f64::EPSILON {
-            3
-        } else {
-            2
-        };
+        // Curves with `a == b` are linear but still have to be written unless they match the
+        // default the point is read back with
+        let point_len = if self.a != 0.5 || self.b != 0.5 { 3 } else { 2 };
 
         let mut top_tup = serializer.serialize_tuple(point_len)?;
         top_tup.serialize_element(&self.y)?;
@@ -160,7 +179,7 @@ impl Serialize for GraphPoint {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct GraphSectionPoint {
     pub ry: u32,
     pub v: f64,
@@ -216,11 +235,7 @@ impl Serialize for GraphSectionPoint {
         S: serde::Serializer,
     {
         use serde::ser::SerializeTuple;
-        let point_len = if (self.a - self.b).abs() > f64::EPSILON {
-            3
-        } else {
-            2
-        };
+        let point_len = if self.a != 0.5 || self.b != 0.5 { 3 } else { 2 };
 
         let mut top_tup = serializer.serialize_tuple(point_len)?;
         top_tup.serialize_element(&self.ry)?;
@@ -251,7 +266,7 @@ impl GraphSectionPoint {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Interval {
     pub y: u32,
     pub l: u32,
@@ -347,7 +362,7 @@ fn serde_def_n<T: From<u32> + Copy, const N: u32>() -> T {
 // }
 
 /// (tick, section points, wide)
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct LaserSection(
     pub u32,
     pub Vec<GraphSectionPoint>,
@@ -377,15 +392,129 @@ impl LaserSection {
     pub fn wide(&self) -> u8 {
         self.2
     }
+
+    /// Splits the section at the chart tick `tick` and returns the part from `tick` on, both
+    /// parts keep following the original curves. A slam at `tick` ends up between the two
+    /// sections. Returns `None` unless `tick` is strictly inside the section.
+    pub fn split_at(&mut self, tick: u32) -> Option<LaserSection> {
+        let ry = tick.checked_sub(self.0)?;
+        if ry == 0 || ry >= self.last()?.ry {
+            return None;
+        }
+
+        let mut right = match self.1.binary_search_by_key(&ry, |p| p.ry) {
+            Ok(i) => {
+                let point = &mut self.1[i];
+                let start = GraphSectionPoint {
+                    v: point.vf.take().unwrap_or(point.v),
+                    ..*point
+                };
+                let mut right = self.1.split_off(i + 1);
+                right.insert(0, start);
+                right
+            }
+            Err(i) => {
+                let start = self.1[i - 1];
+                let end = self.1[i];
+                let start_v = start.vf.unwrap_or(start.v);
+                let x = (ry - start.ry) as f64 / (end.ry - start.ry) as f64;
+
+                let mut split = GraphSectionPoint::new(ry, start_v);
+                split.a = start.a;
+                split.b = start.b;
+                if (start.a - start.b).abs() > f64::EPSILON {
+                    let ((left_a, left_b), (right_a, right_b)) = split_curve(x, start.a, start.b);
+                    self.1[i - 1].a = left_a;
+                    self.1[i - 1].b = left_b;
+                    split.a = right_a;
+                    split.b = right_b;
+                    split.v += do_curve(x, start.a, start.b) * (end.v - start_v);
+                } else {
+                    split.v += x * (end.v - start_v);
+                }
+
+                let mut right = self.1.split_off(i);
+                right.insert(0, split);
+                self.1.push(GraphSectionPoint { vf: None, ..split });
+                right
+            }
+        };
+
+        for point in &mut right {
+            point.ry -= ry;
+        }
+
+        Some(LaserSection(tick, right, self.2))
+    }
+
+    /// Joins `next` onto the end of this section when it starts on the tick this one ends at,
+    /// the inverse of [`LaserSection::split_at`]. Differing values where they meet become a slam.
+    /// `next` is handed back if the sections can't be joined.
+    pub fn merge(&mut self, next: LaserSection) -> Result<(), LaserSection> {
+        let (Some(end), Some(start)) = (self.last(), next.first()) else {
+            return Err(next);
+        };
+
+        let ry = end.ry;
+        let mergeable = self.2 == next.2
+            && self.0 + ry == next.0
+            && end.vf.is_none_or(|vf| (vf - start.v).abs() < f64::EPSILON);
+        if !mergeable {
+            return Err(next);
+        }
+
+        let end_v = end.v;
+        let joined = GraphSectionPoint {
+            ry,
+            v: end_v,
+            vf: Some(start.vf.unwrap_or(start.v)).filter(|v| (v - end_v).abs() > f64::EPSILON),
+            a: start.a,
+            b: start.b,
+        };
+
+        *self.1.last_mut().expect("Checked above") = joined;
+        self.1.extend(next.1.into_iter().skip(1).map(|mut p| {
+            p.ry += ry;
+            p
+        }));
+
+        Ok(())
+    }
 }
 
-//https://github.com/m4saka/ksh2kson/issues/4#issuecomment-573343229
-pub fn do_curve(x: f64, a: f64, b: f64) -> f64 {
-    let t = if x < f64::EPSILON || a < f64::EPSILON {
+/// Curve parameter where the curve with the control point `a` reaches `x`.
+fn curve_t(x: f64, a: f64) -> f64 {
+    if x < f64::EPSILON || a < f64::EPSILON {
         (a - (a * a + x - 2.0 * a * x).sqrt()) / (-1.0 + 2.0 * a)
     } else {
         x / (a + (a * a + (1.0 - 2.0 * a) * x).sqrt())
+    }
+}
+
+/// Control points for the two halves of the curve `(a, b)` split where it reaches `x`, each
+/// normalized to its own segment.
+fn split_curve(x: f64, a: f64, b: f64) -> ((f64, f64), (f64, f64)) {
+    let t = curve_t(x, a);
+    let y = do_curve(x, a, b);
+    let normalize = |v: f64, len: f64| {
+        if len > f64::EPSILON {
+            (v / len).clamp(0.0, 1.0)
+        } else {
+            0.5
+        }
     };
+
+    let left = (normalize(t * a, x), normalize(t * b, y));
+    let right = (
+        normalize(a + t * (1.0 - a) - x, 1.0 - x),
+        normalize(b + t * (1.0 - b) - y, 1.0 - y),
+    );
+    (left, right)
+}
+
+//https://github.com/m4saka/ksh2kson/issues/4#issuecomment-573343229
+pub fn do_curve(x: f64, a: f64, b: f64) -> f64 {
+    let t = curve_t(x, a);
     2.0 * (1.0 - t) * t * b + t * t
 }
 
@@ -642,7 +771,7 @@ impl<'a, T> Iterator for ByNotesIter<'a, T> {
 }
 
 /// (Numerator, Denominator)
-#[derive(Serialize, Deserialize, Copy, Clone)]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
 pub struct TimeSignature(pub u32, pub u32);
 
 impl TimeSignature {
@@ -664,6 +793,10 @@ pub struct BeatInfo {
 }
 
 pub const KSON_RESOLUTION: u32 = 240;
+/// Highest major kson version that can be read
+pub const KSON_MAJOR_VERSION: u32 = 0;
+/// Version written to new charts
+pub const KSON_VERSION: &str = "0.7.0";
 
 impl BeatInfo {
     fn new() -> Self {
@@ -712,9 +845,28 @@ impl BgmInfo {
             legacy: LegacyBgmInfo::default(),
         }
     }
+
+    /// Pre-rendered FX track of legacy KSH charts, played while FX buttons are held
+    pub fn fx_filename(&self) -> Option<&str> {
+        self.legacy
+            .fp_filenames
+            .first()
+            .map(String::as_str)
+            .filter(|x| !x.is_empty())
+    }
+
+    /// Value of the KSH `m=` field, the bgm followed by the legacy FX tracks
+    pub fn ksh_filenames(&self) -> String {
+        std::iter::once(&self.filename)
+            .chain(&self.legacy.fp_filenames)
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(";")
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct KeySoundInfo {
     pub fx: KeySoundFXInfo,
     pub laser: KeySoundLaserInfo,
@@ -726,8 +878,10 @@ pub struct KeySoundLaserInfo {
 }
 
 #[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct KeySoundFXInfo {
-    pub chip_event: HashMap<String, [Vec<ByPulse<KeySoundInvokeFX>>; 2]>,
+    /// Samples played on FX chips, keyed by filename relative to the chart
+    pub chip_event: HashMap<String, [ByPulse<KeySoundInvokeFX>; 2]>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -771,7 +925,6 @@ pub struct AudioInfo {
     pub bgm: BgmInfo,
     #[serde(default, skip_serializing_if = "crate::IsDefault::is_default")]
     pub audio_effect: AudioEffectInfo,
-    #[serde(skip_deserializing)]
     pub key_sound: KeySoundInfo,
 }
 
@@ -779,6 +932,24 @@ impl AudioInfo {
     fn new() -> Self {
         Self::default()
     }
+
+    /// Audio files from the chart folder played by SwitchAudio effects and FX chips.
+    pub fn effect_filenames(&self) -> Vec<&str> {
+        let mut filenames: Vec<&str> = self
+            .audio_effect
+            .fx
+            .def
+            .values()
+            .filter_map(|x| match x {
+                AudioEffect::AudioSwap(f) => Some(f.as_str()),
+                _ => None,
+            })
+            .chain(self.key_sound.fx.chip_event.keys().map(String::as_str))
+            .collect();
+        filenames.sort_unstable();
+        filenames.dedup();
+        filenames
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -791,6 +962,8 @@ pub struct Chart {
     pub camera: camera::CameraInfo,
     pub version: String,
     pub bg: BgInfo,
+    #[serde(default)]
+    pub editor: EditorInfo,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -809,6 +982,29 @@ impl BgInfo {
             legacy: None,
         }
     }
+
+    /// Image files from the chart folder used by the background. KSH names without an
+    /// extension refer to built in backgrounds and are left out.
+    pub fn image_filenames(&self) -> Vec<&str> {
+        let legacy = self.legacy.as_ref();
+        self.filename
+            .as_deref()
+            .into_iter()
+            .chain(
+                legacy
+                    .and_then(|x| x.bg.as_ref())
+                    .into_iter()
+                    .flatten()
+                    .map(|x| x.filename.as_str()),
+            )
+            .chain(
+                legacy
+                    .and_then(|x| x.layer.as_ref())
+                    .and_then(|x| x.filename.as_deref()),
+            )
+            .filter(|x| std::path::Path::new(x).extension().is_some())
+            .collect()
+    }
 }
 
 impl Default for BgInfo {
@@ -817,7 +1013,27 @@ impl Default for BgInfo {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct EditorInfo {
+    pub app_name: String,
+    pub app_version: String,
+    pub annotation: Vec<Annotation>,
+}
+
+/// A comment left at a point in the chart, for reviews between charters.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Annotation {
+    pub y: u32,
+    pub text: String,
+    #[serde(default)]
+    pub author: String,
+    /// Unix timestamp in seconds
+    #[serde(default)]
+    pub created: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct LegacyBgInfo {
     pub bg: Option<Vec<KshBgInfo>>,
     pub layer: Option<KshLayerInfo>,
@@ -853,6 +1069,25 @@ pub struct KshBgInfo {
     pub filename: String,
 }
 
+/// Aggregated contents of a single measure, see [`Chart::measure_summaries`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MeasureSummary {
+    /// First tick of the measure
+    pub tick: u32,
+    /// Length of the measure in ticks
+    pub length: u32,
+    /// BPM at the start of the measure
+    pub bpm: f64,
+    pub bt_chips: u32,
+    /// Long BT notes starting in this measure
+    pub bt_holds: u32,
+    pub fx_chips: u32,
+    /// Long FX notes starting in this measure
+    pub fx_holds: u32,
+    /// Fraction of the measure covered by the left and right laser
+    pub laser_coverage: [f32; 2],
+}
+
 type BeatLineFn = dyn Fn(u32) -> Option<(u32, bool)>;
 pub struct MeasureBeatLines {
     tick: u32,
@@ -888,6 +1123,71 @@ impl Default for Chart {
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KsonWriteOptions {
+    /// Write object keys in sorted order so saving the same chart always gives the same output
+    pub sort_keys: bool,
+    /// Indent the output
+    pub pretty: bool,
+}
+
+impl Chart {
+    pub fn to_kson<W>(&self, out: W, options: KsonWriteOptions) -> serde_json::Result<()>
+    where
+        W: std::io::Write,
+    {
+        let KsonWriteOptions { sort_keys, pretty } = options;
+        match (sort_keys, pretty) {
+            // Maps in serde_json values are ordered by key
+            (true, true) => serde_json::to_writer_pretty(out, &serde_json::to_value(self)?),
+            (true, false) => serde_json::to_writer(out, &serde_json::to_value(self)?),
+            (false, true) => serde_json::to_writer_pretty(out, self),
+            (false, false) => serde_json::to_writer(out, self),
+        }
+    }
+
+    /// Reads a kson chart, rejecting charts from a newer major version of the format. Charts
+    /// from older versions are migrated to the current layout.
+    pub fn from_kson(data: &str) -> Result<Self, Error> {
+        #[derive(Deserialize)]
+        struct KsonVersion {
+            version: String,
+        }
+
+        let mut doc: serde_json::Value = serde_json::from_str(data)?;
+        let KsonVersion { version } = KsonVersion::deserialize(&doc)?;
+        let major = version
+            .split('.')
+            .next()
+            .and_then(|x| x.parse::<u32>().ok());
+        if major.is_some_and(|x| x > KSON_MAJOR_VERSION) {
+            return Err(Error::UnsupportedVersion(version));
+        }
+
+        migrate::migrate(&mut doc, &version)?;
+        Ok(serde_json::from_value(doc)?)
+    }
+
+    /// Reads a chart from a `.ksh`, `.kson` or `.vox` file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|x| x.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        match extension.as_str() {
+            "ksh" => Ok(Self::from_ksh_reader(std::io::BufReader::new(
+                std::fs::File::open(path)?,
+            ))?),
+            "kson" => Self::from_kson(&std::fs::read_to_string(path)?),
+            "vox" => Ok(Self::from_vox(&std::fs::read_to_string(path)?)?),
+            _ => Err(Error::UnknownFormat(extension)),
+        }
+    }
+}
+
 //TODO: Duration based API
 impl Chart {
     pub fn new() -> Self {
@@ -897,8 +1197,9 @@ impl Chart {
             beat: BeatInfo::new(),
             audio: AudioInfo::new(),
             camera: CameraInfo::default(),
-            version: "0.7.0".to_string(),
+            version: KSON_VERSION.to_string(),
             bg: BgInfo::new(),
+            editor: EditorInfo::default(),
         }
     }
 
@@ -1007,6 +1308,9 @@ impl Chart {
                 prev_ticks_per_measure = KSON_RESOLUTION * 4 * current_sig.1 .0 / current_sig.1 .1;
             }
             ret += remaining_measures * prev_ticks_per_measure;
+        } else {
+            // Charts without time signatures are in 4/4
+            ret = measure * KSON_RESOLUTION * 4;
         }
         ret
     }
@@ -1060,6 +1364,93 @@ impl Chart {
         }
     }
 
+    /// Summarizes every measure up to the last note, for minimaps and density graphs.
+    pub fn measure_summaries(&self) -> Vec<MeasureSummary> {
+        let mut summaries: Vec<MeasureSummary> = vec![];
+        if self.beat.time_sig.is_empty() {
+            return summaries;
+        }
+
+        let last_tick = self.get_last_tick();
+        let mut bpm_changes = self.beat.bpm.iter().peekable();
+        let mut bpm = self.beat.bpm.first().map(|x| x.1).unwrap_or(120.0);
+        let mut tick = 0;
+
+        for measure in 0.. {
+            let next = self.measure_to_tick(measure + 1);
+            if next <= tick {
+                break;
+            }
+
+            while let Some(change) = bpm_changes.next_if(|x| x.0 <= tick) {
+                bpm = change.1;
+            }
+
+            summaries.push(MeasureSummary {
+                tick,
+                length: next - tick,
+                bpm,
+                ..Default::default()
+            });
+
+            if next > last_tick {
+                break;
+            }
+            tick = next;
+        }
+
+        let measure_index = |tick: u32| {
+            summaries
+                .partition_point(|x| x.tick <= tick)
+                .saturating_sub(1)
+        };
+
+        let mut counts = vec![(0, 0, 0, 0); summaries.len()];
+        for note in self.note.bt.iter().flatten() {
+            let count = &mut counts[measure_index(note.y)];
+            match note.l {
+                0 => count.0 += 1,
+                _ => count.1 += 1,
+            }
+        }
+        for note in self.note.fx.iter().flatten() {
+            let count = &mut counts[measure_index(note.y)];
+            match note.l {
+                0 => count.2 += 1,
+                _ => count.3 += 1,
+            }
+        }
+
+        let mut laser_ticks = vec![[0u32; 2]; summaries.len()];
+        for (side, sections) in self.note.laser.iter().enumerate() {
+            for section in sections {
+                let start = section.tick();
+                let end = start + section.last().map(|x| x.ry).unwrap_or_default();
+                for (i, measure) in summaries
+                    .iter()
+                    .enumerate()
+                    .skip(measure_index(start))
+                    .take_while(|(_, x)| x.tick < end)
+                {
+                    let covered = end.min(measure.tick + measure.length) - start.max(measure.tick);
+                    laser_ticks[i][side] += covered;
+                }
+            }
+        }
+
+        for ((summary, count), laser) in summaries.iter_mut().zip(counts).zip(laser_ticks) {
+            (
+                summary.bt_chips,
+                summary.bt_holds,
+                summary.fx_chips,
+                summary.fx_holds,
+            ) = count;
+            summary.laser_coverage = laser.map(|x| (x as f32 / summary.length as f32).min(1.0));
+        }
+
+        summaries
+    }
+
     pub fn get_last_tick(&self) -> u32 {
         let mut last_tick = 0;
 
@@ -1108,6 +1499,7 @@ mod tests {
     use serde_test::Token;
 
     use crate::parameter::{self, EffectFloat, EffectFreq, EffectParameterValue};
+    use crate::KSON_RESOLUTION;
 
     #[test]
     fn effect_param() {
@@ -1146,4 +1538,763 @@ mod tests {
         param.on = Some(EffectParameterValue::Switch(false..=true));
         serde_test::assert_tokens(&param, &[Token::Str("off>off-on")]);
     }
+
+    #[test]
+    fn sorted_kson_keys() {
+        let mut chart = crate::Chart::new();
+        for key in ["retrigger", "flanger", "tapestop", "bitcrusher"] {
+            chart
+                .audio
+                .audio_effect
+                .fx
+                .param_change
+                .insert(key.to_string(), Default::default());
+        }
+
+        let options = crate::KsonWriteOptions {
+            sort_keys: true,
+            pretty: false,
+        };
+        let mut first = vec![];
+        let mut second = vec![];
+        chart.to_kson(&mut first, options).unwrap();
+        chart.clone().to_kson(&mut second, options).unwrap();
+        assert_eq!(first, second);
+
+        let output = String::from_utf8(first).unwrap();
+        let positions: Vec<_> = ["bitcrusher", "flanger", "retrigger", "tapestop"]
+            .iter()
+            .map(|x| output.find(x).unwrap())
+            .collect();
+        assert!(positions.windows(2).all(|x| x[0] < x[1]));
+    }
+
+    #[test]
+    fn trim_and_append() {
+        use crate::{Interval, TimeSignature};
+
+        let mut chart = crate::Chart::new();
+        chart.beat.bpm = vec![(0, 120.0), (960, 240.0)];
+        chart.beat.time_sig.push((0, TimeSignature(4, 4)));
+        chart.note.bt[0] = vec![
+            Interval { y: 0, l: 0 },
+            Interval { y: 960, l: 0 },
+            Interval { y: 1440, l: 960 },
+            Interval { y: 1920, l: 0 },
+        ];
+
+        let notes = |chart: &crate::Chart| {
+            chart.note.bt[0]
+                .iter()
+                .map(|x| (x.y, x.l))
+                .collect::<Vec<_>>()
+        };
+
+        let mut cut = chart.clone();
+        cut.trim(960, 1920);
+        assert_eq!(notes(&cut), vec![(0, 0), (480, 480)]);
+        assert_eq!(cut.beat.bpm, vec![(0, 240.0)]);
+        assert_eq!(cut.audio.bgm.offset, 2000);
+
+        // The last hold ends on the second measure line, so one measure of gap starts the
+        // appended chart on the fourth measure
+        let mut medley = cut.clone();
+        medley.append(&chart, 1);
+        assert_eq!(notes(&medley).len(), 6);
+        assert_eq!(notes(&medley)[2], (960 * 3, 0));
+        assert_eq!(
+            medley.beat.bpm,
+            vec![(0, 240.0), (960 * 3, 120.0), (960 * 4, 240.0)]
+        );
+    }
+
+    #[test]
+    fn measure_summaries() {
+        use crate::{GraphSectionPoint, Interval, LaserSection, TimeSignature};
+
+        let mut chart = crate::Chart::new();
+        chart.beat.bpm = vec![(0, 120.0), (960 * 2, 240.0)];
+        chart.beat.time_sig.push((0, TimeSignature(4, 4)));
+        chart.note.bt[0] = vec![Interval { y: 0, l: 0 }, Interval { y: 240, l: 0 }];
+        chart.note.bt[1] = vec![Interval { y: 960, l: 480 }];
+        chart.note.fx[0] = vec![Interval { y: 960 * 2, l: 0 }];
+        chart.note.laser[0] = vec![LaserSection(
+            480,
+            vec![
+                GraphSectionPoint::new(0, 0.0),
+                GraphSectionPoint::new(960, 1.0),
+            ],
+            1,
+        )];
+
+        let summaries = chart.measure_summaries();
+        assert_eq!(summaries.len(), 3);
+        assert_eq!(summaries[0].bt_chips, 2);
+        assert_eq!(summaries[1].bt_holds, 1);
+        assert_eq!(summaries[2].fx_chips, 1);
+        assert_eq!(summaries[2].bpm, 240.0);
+        assert_eq!(summaries[0].laser_coverage, [0.5, 0.0]);
+        assert_eq!(summaries[1].laser_coverage, [0.5, 0.0]);
+    }
+
+    #[test]
+    fn kson_version_errors() {
+        let mut chart = crate::Chart::new();
+        let data = serde_json::to_string(&chart).unwrap();
+        assert!(crate::Chart::from_kson(&data).is_ok());
+
+        chart.version = "1.0.0".to_string();
+        let data = serde_json::to_string(&chart).unwrap();
+        assert!(matches!(
+            crate::Chart::from_kson(&data),
+            Err(crate::Error::UnsupportedVersion(v)) if v == "1.0.0"
+        ));
+
+        assert!(matches!(
+            crate::Chart::from_kson("{\"version\": \"0.7.0\"}"),
+            Err(crate::Error::Kson(_))
+        ));
+        assert!(matches!(
+            crate::Chart::from_file("chart.txt"),
+            Err(crate::Error::UnknownFormat(_))
+        ));
+    }
+
+    #[test]
+    fn kson_migration() {
+        use serde_json::json;
+
+        let mut doc = serde_json::to_value(crate::Chart::new()).unwrap();
+        doc["version"] = json!("0.3.0");
+        doc["beat"] = json!({
+            "bpm": [{"y": 0, "v": 120.0}],
+            "time_sig": [{"idx": 0, "v": {"n": 3, "d": 4}}],
+            "scroll_speed": [{"y": 0, "v": 1.0}]
+        });
+        doc["note"] = json!({
+            "bt": [[{"y": 0, "l": 0}, {"y": 240, "l": 480}], [], [], []],
+            "fx": [[], [{"y": 960}]],
+            "laser": [[{"y": 0, "v": [{"ry": 0, "v": 0.0}, {"ry": 240, "v": 1.0, "vf": 0.5}], "w": 2}], []]
+        });
+
+        let chart = crate::Chart::from_kson(&doc.to_string()).unwrap();
+        assert_eq!(chart.version, crate::KSON_VERSION);
+        assert_eq!(chart.beat.bpm[0], (0, 120.0));
+        assert_eq!(
+            (chart.beat.time_sig[0].1 .0, chart.beat.time_sig[0].1 .1),
+            (3, 4)
+        );
+        assert_eq!(chart.note.bt[0][1].y, 240);
+        assert_eq!(chart.note.bt[0][1].l, 480);
+        assert_eq!(chart.note.fx[1][0].y, 960);
+        let laser = &chart.note.laser[0][0];
+        assert_eq!(laser.wide(), 2);
+        assert_eq!(laser.1[1].vf, Some(0.5));
+
+        doc["note"]["fx"][1][0] = json!({"l": 960});
+        assert!(matches!(
+            crate::Chart::from_kson(&doc.to_string()),
+            Err(crate::Error::Migration { field, .. }) if field == "note.fx[1][0]"
+        ));
+    }
+
+    #[test]
+    fn ksh_spins() {
+        use crate::camera::SpinKind;
+        use crate::Ksh;
+
+        let ksh = "t=120\r\n--\r\nbeat=4/4\r\n0000|00|--@(192\r\n0000|00|--S>96;125;2;1\r\n--\r\n";
+        let chart = crate::Chart::from_ksh(ksh).unwrap();
+        let slam_event = &chart.camera.cam.pattern.laser.slam_event;
+
+        let spin = slam_event.spin[0];
+        assert_eq!((spin.0, spin.1, spin.2), (0, -1, KSON_RESOLUTION * 4));
+        let swing = slam_event.swing[0];
+        assert_eq!(
+            (swing.0, swing.1, swing.2),
+            (KSON_RESOLUTION * 2, 1, KSON_RESOLUTION * 2)
+        );
+        assert_eq!(
+            (swing.3.scale, swing.3.repeat, swing.3.decay_order),
+            (0.5, 2, 1)
+        );
+
+        let res = KSON_RESOLUTION as f64;
+        assert_eq!(slam_event.spin_at(res).unwrap().kind, SpinKind::Full);
+        assert_eq!(slam_event.spin_at(res).unwrap().progress, 0.25);
+        assert!(matches!(
+            slam_event.spin_at(res * 3.0).unwrap().kind,
+            SpinKind::Swing(_)
+        ));
+        assert!(slam_event.spin_at(res * 5.0).is_none());
+
+        let mut out = vec![];
+        chart.to_ksh(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("--@(192\r\n"));
+        assert!(out.contains("--S>96;125;2;1\r\n"));
+    }
+
+    #[test]
+    fn ksh_lane_toggle_shake() {
+        use crate::camera::CamShake;
+        use crate::Ksh;
+
+        let ksh = "t=120\r\n--\r\nbeat=4/4\r\nlane_toggle=96\r\nshake=2;48\r\n0000|00|--\r\n0000|00|--\r\n--\r\n";
+        let chart = crate::Chart::from_ksh(ksh).unwrap();
+        let camera = &chart.camera;
+        assert_eq!(camera.lane_toggle, vec![(0, KSON_RESOLUTION * 2)]);
+        assert_eq!(camera.shake, vec![CamShake(0, 2.0, KSON_RESOLUTION)]);
+
+        let res = KSON_RESOLUTION as f64;
+        assert_eq!(camera.lane_visibility_at(-1.0), 1.0);
+        assert_eq!(camera.lane_visibility_at(res), 0.5);
+        assert_eq!(camera.lane_visibility_at(res * 3.0), 0.0);
+        assert!(camera.shake_at(res / 16.0) > 0.0);
+        assert_eq!(camera.shake_at(res * 2.0), 0.0);
+
+        let mut out = vec![];
+        chart.to_ksh(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("lane_toggle=96\r\n"));
+        assert!(out.contains("shake=2;48\r\n"));
+    }
+
+    #[test]
+    fn spin_input_ignore_ranges() {
+        use crate::camera::{CamPatternInvokeSpin, CameraInfo};
+
+        let mut camera = CameraInfo::default();
+        let slam_event = &mut camera.cam.pattern.laser.slam_event;
+        slam_event.spin = vec![
+            CamPatternInvokeSpin(0, 1, 480),
+            CamPatternInvokeSpin(2000, -1, 100),
+        ];
+        slam_event.half_spin = vec![
+            CamPatternInvokeSpin(240, -1, 480),
+            CamPatternInvokeSpin(720, 1, 100),
+            CamPatternInvokeSpin(2050, 1, 20),
+        ];
+
+        assert_eq!(camera.input_ignore_ranges(), vec![0..820, 2000..2100]);
+        assert!(CameraInfo::default().input_ignore_ranges().is_empty());
+    }
+
+    #[test]
+    fn ksh_round_trip() {
+        use crate::effects::AudioEffect;
+        use crate::Ksh;
+
+        let ksh = [
+            "title=Round trip\r\nt=120\r\nmvol=80\r\n--\r\nbeat=4/4\r",
+            "zoom_bottom=50\r\nfx-l=Retrigger;16\r\n0000|10|--\r",
+            "filtertype=hpf1\r\n0000|10|0-\r",
+            "fx-r_se=clap.wav;50\r\n0000|02|o-\r\n0000|00|--\r",
+            "fx-l=myfx\r\n0000|10|--\r\n0000|10|--\r\n0000|00|--\r\n0000|00|--\r\n--\r",
+            "#define_fx myfx type=Flanger;stereoWidth=50%;volume=60%\r\n",
+        ]
+        .join("\n");
+        let chart = crate::Chart::from_ksh(&ksh).unwrap();
+
+        let myfx = chart.audio.audio_effect.fx.def.get("myfx").unwrap();
+        assert!(matches!(myfx, AudioEffect::Flanger(_)));
+        assert_ne!(Some(myfx), AudioEffect::try_from("Flanger").ok().as_ref());
+
+        let mut out = vec![];
+        chart.to_ksh(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let written = crate::Chart::from_ksh(&out).unwrap();
+
+        let fx = &written.audio.audio_effect.fx;
+        assert_eq!(fx.def.get("myfx"), Some(myfx));
+        let retrigger = &fx.long_event["Retrigger"][0][0];
+        assert_eq!(retrigger.tick(), 0);
+        assert_eq!(retrigger.value().unwrap()["wave_length"], "1/16");
+        assert_eq!(fx.long_event["myfx"][0][0].tick(), KSON_RESOLUTION * 2);
+
+        let fx_notes = |c: &crate::Chart| {
+            c.note
+                .fx
+                .clone()
+                .map(|x| x.iter().map(|x| (x.y, x.l)).collect::<Vec<_>>())
+        };
+        assert_eq!(fx_notes(&written), fx_notes(&chart));
+        assert_eq!(written.note.laser[0][0].1.len(), 2);
+
+        assert_eq!(written.audio.bgm.vol, 0.8);
+        assert_eq!(written.camera.cam.body.zoom[0].v, 50.0);
+        assert_eq!(
+            written.audio.audio_effect.laser.pulse_event["hpf1"],
+            vec![(KSON_RESOLUTION / 2, ())]
+        );
+        let clap = &written.audio.key_sound.fx.chip_event["clap.wav"][1];
+        assert_eq!((clap[0].0, clap[0].1.vol), (KSON_RESOLUTION, 0.5));
+    }
+
+    #[test]
+    fn ksh_reader() {
+        use crate::Ksh;
+
+        let ksh = "\u{feff}title=Reader\r\nt=150\r\n--\r\nbeat=4/4\r\n1000|00|--\r\n0100|00|--\r\n--\r\n0010|00|--\r\n--\r\n";
+        let chart = crate::Chart::from_ksh_reader(ksh.as_bytes()).unwrap();
+        assert_eq!(chart.meta.title, "Reader");
+        let bt_notes = |c: &crate::Chart| {
+            c.note
+                .bt
+                .clone()
+                .map(|x| x.iter().map(|x| (x.y, x.l)).collect::<Vec<_>>())
+        };
+        assert_eq!(
+            bt_notes(&chart),
+            bt_notes(&crate::Chart::from_ksh(ksh).unwrap())
+        );
+        assert_eq!(chart.note.bt[2][0].y, KSON_RESOLUTION * 4);
+
+        let meta = crate::Chart::metadata_from_ksh_reader(ksh.as_bytes()).unwrap();
+        assert_eq!(meta.meta.title, "Reader");
+        assert_eq!(meta.beat.bpm, vec![(0, 150.0)]);
+        assert!(meta.note.bt.iter().all(Vec::is_empty));
+    }
+
+    #[test]
+    fn chart_validation() {
+        use crate::validate::{ChartIssue, ChartIssueKind};
+        use crate::{BtLane, GraphSectionPoint, Interval, LaserSection, Side, Track};
+
+        let mut chart = crate::Chart::new();
+        chart.beat.bpm = vec![(0, 120.0), (960, 0.0)];
+        chart.note.bt[0] = vec![Interval { y: 0, l: 480 }, Interval { y: 240, l: 0 }];
+        chart.note.laser[0] = vec![LaserSection(0, vec![GraphSectionPoint::new(0, 1.5)], 1)];
+
+        let kinds = |issues: Vec<ChartIssue>| {
+            issues
+                .into_iter()
+                .map(|x| (x.y, x.kind))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            kinds(chart.validate(None)),
+            vec![
+                (0, ChartIssueKind::MissingTimeSignature),
+                (0, ChartIssueKind::EmptyLaserSection(Side::Left)),
+                (0, ChartIssueKind::LaserOutOfRange(Side::Left)),
+                (240, ChartIssueKind::OverlappingNotes(Track::BT(BtLane::A))),
+                (960, ChartIssueKind::ZeroBpm),
+            ]
+        );
+
+        let past_end = chart.validate(Some(750.0));
+        assert_eq!(
+            past_end
+                .iter()
+                .filter(|x| matches!(x.kind, ChartIssueKind::NotePastAudioEnd(_)))
+                .map(|x| x.y)
+                .collect::<Vec<_>>(),
+            vec![0]
+        );
+    }
+
+    #[test]
+    fn copy_paste_range() {
+        use crate::{Interval, LaneSelection};
+
+        let note = |y| Interval { y, l: 0 };
+        let mut chart = crate::Chart::new();
+        chart.beat.bpm = vec![(0, 120.0), (480, 180.0)];
+        chart.note.bt[0] = vec![note(0), Interval { y: 480, l: 960 }, note(960)];
+        chart.note.bt[1] = vec![note(480)];
+
+        let mut lanes = LaneSelection::ALL.invert();
+        lanes.bt[0] = true;
+        let clip = chart.copy_range(480, 960, lanes);
+        let ticks = |lane: &Vec<Interval>| lane.iter().map(|x| (x.y, x.l)).collect::<Vec<_>>();
+        assert_eq!(ticks(&clip.note.bt[0]), vec![(0, 480)]);
+        assert!(clip.note.bt[1].is_empty());
+        assert!(clip.beat.bpm.is_empty());
+
+        chart.paste(&clip, 1920);
+        assert_eq!(
+            ticks(&chart.note.bt[0]),
+            vec![(0, 0), (480, 960), (960, 0), (1920, 480)]
+        );
+
+        chart.remove_range(0, 960, LaneSelection::ALL);
+        assert_eq!(ticks(&chart.note.bt[0]), vec![(960, 0), (1920, 480)]);
+        assert!(chart.note.bt[1].is_empty());
+        assert!(chart.beat.bpm.is_empty());
+    }
+
+    #[test]
+    fn paste_conflicts() {
+        use crate::{Interval, PasteConflict};
+
+        let note = |y| Interval { y, l: 0 };
+        let mut chart = crate::Chart::new();
+        chart.note.bt[0] = vec![note(0), Interval { y: 480, l: 480 }, note(960)];
+        let mut clip = crate::Chart::new();
+        clip.note.bt[0] = vec![note(0), note(240)];
+
+        assert_eq!(chart.paste_conflicts(&clip, 240), 1);
+        assert_eq!(chart.paste_conflicts(&clip, 720), 2);
+
+        let ticks = |lane: &Vec<Interval>| lane.iter().map(|x| (x.y, x.l)).collect::<Vec<_>>();
+        let mut kept = chart.clone();
+        kept.paste_resolving(&clip, 720, PasteConflict::KeepExisting);
+        assert_eq!(ticks(&kept.note.bt[0]), vec![(0, 0), (480, 480), (960, 0)]);
+
+        chart.paste_resolving(&clip, 720, PasteConflict::Overwrite);
+        assert_eq!(ticks(&chart.note.bt[0]), vec![(0, 0), (720, 0), (960, 0)]);
+    }
+
+    #[test]
+    fn mirror() {
+        use crate::{GraphSectionPoint, Interval, LaserSection};
+
+        let note = |y| Interval { y, l: 0 };
+        let mut chart = crate::Chart::new();
+        chart.note.bt[0] = vec![note(0), note(960)];
+        chart.note.bt[3] = vec![note(480)];
+        chart.note.fx[0] = vec![note(240)];
+        let mut slam = GraphSectionPoint::new(0, 0.0);
+        slam.vf = Some(0.25);
+        chart.note.laser[0] = vec![LaserSection(
+            0,
+            vec![slam, GraphSectionPoint::new(240, 1.0)],
+            1,
+        )];
+
+        chart.mirror(0, 960);
+        let ticks = |lane: &Vec<Interval>| lane.iter().map(|x| x.y).collect::<Vec<_>>();
+        assert_eq!(ticks(&chart.note.bt[0]), vec![480, 960]);
+        assert_eq!(ticks(&chart.note.bt[3]), vec![0]);
+        assert_eq!(ticks(&chart.note.fx[1]), vec![240]);
+        assert!(chart.note.fx[0].is_empty());
+        assert!(chart.note.laser[0].is_empty());
+
+        let points = &chart.note.laser[1][0].1;
+        assert_eq!(points[0].v, 1.0);
+        assert_eq!(points[0].vf, Some(0.75));
+        assert_eq!(points[1].v, 0.0);
+    }
+
+    #[test]
+    fn chart_stats() {
+        use crate::{stats::OneHandTrill, GraphSectionPoint, Interval, LaserSection, Side};
+
+        let note = |y| Interval { y, l: 0 };
+        let mut chart = crate::Chart::new();
+        chart.beat.bpm = vec![(0, 120.0)];
+        chart.note.bt[0] = vec![note(0), note(120), note(240)];
+        chart.note.bt[1] = vec![note(60), note(180), note(300)];
+        chart.note.bt[2] = vec![note(1200)];
+        chart.note.fx[1] = vec![Interval { y: 480, l: 1440 }];
+        chart.note.laser[0] = vec![LaserSection(
+            960,
+            vec![
+                GraphSectionPoint::new(0, 0.0),
+                GraphSectionPoint::new(960, 1.0),
+            ],
+            1,
+        )];
+
+        let stats = chart.stats();
+        assert!((stats.hold_uptime - 0.75).abs() < 1e-9);
+        assert!((stats.laser_uptime - 0.5).abs() < 1e-9);
+        assert_eq!(stats.hand_notes, [6, 2]);
+        assert!((stats.hand_balance() - 0.75).abs() < 1e-9);
+        assert!((stats.both_hands - 0.125).abs() < 1e-9);
+        assert_eq!(
+            stats.trills,
+            vec![OneHandTrill {
+                side: Side::Left,
+                y: 0,
+                end: 300,
+                notes: 6
+            }]
+        );
+    }
+
+    #[test]
+    fn score_calculator() {
+        use crate::score_ticks::{ScoreCalculator, MAX_SCORE};
+        use crate::{GraphSectionPoint, Interval, LaserSection};
+
+        let mut chart = crate::Chart::new();
+        chart.beat.bpm = vec![(0, 120.0)];
+        chart.note.bt[0] = vec![Interval { y: 0, l: 0 }, Interval { y: 240, l: 0 }];
+        chart.note.fx[0] = vec![Interval { y: 480, l: 240 }];
+        let mut slam = GraphSectionPoint::new(0, 0.0);
+        slam.vf = Some(1.0);
+        chart.note.laser[1] = vec![LaserSection(
+            960,
+            vec![slam, GraphSectionPoint::new(240, 1.0)],
+            1,
+        )];
+
+        let calc = ScoreCalculator::new(&chart);
+        let summary = calc.summary;
+        assert_eq!(summary.chip_count, 2);
+        assert_eq!(summary.slam_count, 1);
+        assert_eq!(calc.max_chain(), summary.total);
+        assert_eq!(
+            calc.max_ex_score(),
+            3 * 5 + (summary.hold_count + summary.laser_count) * 2
+        );
+
+        let total = summary.total;
+        assert_eq!(calc.score(total, 0, 0), MAX_SCORE);
+        assert_eq!(calc.score(0, total, 0), MAX_SCORE / 2);
+        assert_eq!(calc.score(0, 0, total), 0);
+        assert_eq!(calc.score(total * 2, 0, 0), MAX_SCORE);
+    }
+
+    #[test]
+    fn laser_split_merge() {
+        use crate::{Graph, GraphSectionPoint, LaserSection};
+
+        let mut curve = GraphSectionPoint::new(0, 0.0);
+        curve.a = 0.8;
+        curve.b = 0.2;
+        let mut slam = GraphSectionPoint::new(480, 1.0);
+        slam.vf = Some(0.5);
+        let original = LaserSection(960, vec![curve, slam, GraphSectionPoint::new(960, 0.0)], 1);
+
+        let mut left = original.clone();
+        assert!(left.split_at(960).is_none());
+        assert!(left.split_at(960 * 2).is_none());
+
+        let right = left.split_at(960 + 120).unwrap();
+        assert_eq!(right.tick(), 960 + 120);
+        for tick in [960 + 60, 960 + 90] {
+            let expected = original.value_at(tick as f64).unwrap();
+            assert!((left.value_at(tick as f64).unwrap() - expected).abs() < 1e-6);
+        }
+        for tick in [960 + 240, 960 + 360, 960 + 720] {
+            let expected = original.value_at(tick as f64).unwrap();
+            assert!((right.value_at(tick as f64).unwrap() - expected).abs() < 1e-6);
+        }
+
+        let mut middle = right;
+        let end = middle.split_at(960 + 480).unwrap();
+        assert_eq!(middle.last().unwrap().v, 1.0);
+        assert_eq!(end.first().unwrap().v, 0.5);
+
+        left.merge(middle).ok().unwrap();
+        left.merge(end).ok().unwrap();
+        assert_eq!(left.1.len(), 4);
+        assert_eq!(left.1[2].vf, Some(0.5));
+        for tick in [960 + 60, 960 + 240, 960 + 720] {
+            let expected = original.value_at(tick as f64).unwrap();
+            assert!((left.value_at(tick as f64).unwrap() - expected).abs() < 1e-6);
+        }
+
+        let apart = LaserSection(960 * 4, vec![GraphSectionPoint::new(0, 0.0)], 1);
+        assert!(left.merge(apart).is_err());
+    }
+
+    #[test]
+    fn render_model() {
+        use crate::render_model::NoteLane;
+        use crate::{GraphSectionPoint, Interval, LaserSection, TimeSignature};
+
+        let mut chart = crate::Chart::new();
+        chart.beat.bpm = vec![(0, 120.0)];
+        chart.beat.time_sig = vec![(0, TimeSignature(4, 4))];
+        chart.note.bt[1] = vec![Interval { y: 480, l: 0 }];
+        chart.note.fx[0] = vec![Interval { y: 0, l: 240 }];
+        let mut curve = GraphSectionPoint::new(0, 0.0);
+        curve.a = 0.8;
+        curve.b = 0.2;
+        let mut slam = GraphSectionPoint::new(480, 1.0);
+        slam.vf = Some(0.5);
+        chart.note.laser[0] = vec![LaserSection(960, vec![curve, slam], 1)];
+
+        let model = chart.render_model();
+        assert_eq!(model.chips[0].lane, NoteLane::Bt(1));
+        assert_eq!(model.chips[0].time.beat, 2.0);
+        assert_eq!(model.chips[0].time.ms, 1000.0);
+        assert_eq!(model.holds[0].end.beat, 1.0);
+
+        let vertices = &model.lasers[0].vertices;
+        assert_eq!(vertices.len(), 18);
+        assert_eq!(vertices[0].time.beat, 4.0);
+        let [.., before_slam, after_slam] = vertices.as_slice() else {
+            unreachable!()
+        };
+        assert_eq!(before_slam.time, after_slam.time);
+        assert_eq!((before_slam.x, after_slam.x), (1.0, 0.5));
+
+        // Up to the end of the second measure, where the laser ends
+        assert_eq!(model.beat_lines.len(), 8);
+        assert_eq!(model.beat_lines.iter().filter(|x| x.measure).count(), 2);
+    }
+
+    /// Serialization round trips of generated charts
+    mod round_trip {
+        use proptest::collection::vec;
+        use proptest::prelude::*;
+
+        use crate::{Chart, GraphPoint, GraphSectionPoint, Interval, LaserSection, TimeSignature};
+
+        /// Multiples of 1/16, written and read back without rounding
+        fn exact(range: std::ops::RangeInclusive<i32>) -> impl Strategy<Value = f64> {
+            range.prop_map(|x| x as f64 / 16.0)
+        }
+
+        fn curve() -> impl Strategy<Value = (f64, f64)> {
+            (exact(0..=16), exact(0..=16))
+        }
+
+        /// Notes with at least one empty tick after each, so KSH can tell them apart
+        fn lane() -> impl Strategy<Value = Vec<Interval>> {
+            vec((1..200u32, prop_oneof![Just(0u32), 1..500u32]), 0..16).prop_map(|notes| {
+                let mut next = 0;
+                notes
+                    .into_iter()
+                    .map(|(gap, l)| {
+                        let y = next + gap - 1;
+                        next = y + l + 1;
+                        Interval { y, l }
+                    })
+                    .collect()
+            })
+        }
+
+        fn lasers() -> impl Strategy<Value = Vec<LaserSection>> {
+            let point = (
+                1..500u32,
+                exact(0..=16),
+                proptest::option::of(exact(0..=16)),
+                curve(),
+            );
+            vec((1..500u32, vec(point, 2..6), 1..=2u8), 0..6).prop_map(|sections| {
+                let mut next = 0;
+                sections
+                    .into_iter()
+                    .map(|(gap, points, wide)| {
+                        let y = next + gap;
+                        let mut ry = 0;
+                        let points: Vec<_> = points
+                            .into_iter()
+                            .enumerate()
+                            .map(|(i, (step, v, vf, (a, b)))| {
+                                if i > 0 {
+                                    ry += step;
+                                }
+                                GraphSectionPoint { ry, v, vf, a, b }
+                            })
+                            .collect();
+                        next = y + ry;
+                        LaserSection(y, points, wide)
+                    })
+                    .collect()
+            })
+        }
+
+        fn graph(values: std::ops::RangeInclusive<i32>) -> impl Strategy<Value = Vec<GraphPoint>> {
+            let point = (
+                1..1000u32,
+                exact(values.clone()),
+                proptest::option::of(exact(values)),
+                curve(),
+            );
+            vec(point, 0..8).prop_map(|points| {
+                let mut y = 0;
+                points
+                    .into_iter()
+                    .map(|(gap, v, vf, (a, b))| {
+                        y += gap;
+                        GraphPoint { y, v, vf, a, b }
+                    })
+                    .collect()
+            })
+        }
+
+        fn chart() -> impl Strategy<Value = Chart> {
+            let notes = (
+                [lane(), lane(), lane(), lane()],
+                [lane(), lane()],
+                [lasers(), lasers()],
+            );
+            let beat = (
+                vec((1..2000u32, exact(16..=4800)), 1..4),
+                vec(
+                    (1..8u32, 1..16u32, prop::sample::select(vec![2, 4, 8, 16])),
+                    1..4,
+                ),
+                graph(-64..=64),
+            );
+            let other = ("\\PC*", graph(0..=64), graph(-64..=64));
+
+            (notes, beat, other).prop_map(
+                |((bt, fx, laser), (bpm, time_sig, scroll_speed), (title, zoom, rotation_x))| {
+                    let mut chart = Chart::new();
+                    chart.meta.title = title;
+                    chart.note.bt = bt;
+                    chart.note.fx = fx;
+                    chart.note.laser = laser;
+
+                    let (mut y, mut measure) = (0, 0);
+                    for (i, (gap, bpm)) in bpm.into_iter().enumerate() {
+                        if i > 0 {
+                            y += gap;
+                        }
+                        chart.beat.bpm.push((y, bpm));
+                    }
+                    for (i, (gap, n, d)) in time_sig.into_iter().enumerate() {
+                        if i > 0 {
+                            measure += gap;
+                        }
+                        chart.beat.time_sig.push((measure, TimeSignature(n, d)));
+                    }
+                    chart.beat.scroll_speed = scroll_speed;
+                    chart.camera.cam.body.zoom = zoom;
+                    chart.camera.cam.body.rotation_x = rotation_x;
+                    chart
+                },
+            )
+        }
+
+        proptest! {
+            #[test]
+            fn kson_round_trip(chart in chart()) {
+                let mut out = vec![];
+                chart.to_kson(&mut out, Default::default()).unwrap();
+                let read = Chart::from_kson(std::str::from_utf8(&out).unwrap()).unwrap();
+
+                prop_assert_eq!(&read.meta.title, &chart.meta.title);
+                prop_assert_eq!(&read.note.bt, &chart.note.bt);
+                prop_assert_eq!(&read.note.fx, &chart.note.fx);
+                prop_assert_eq!(&read.note.laser, &chart.note.laser);
+                prop_assert_eq!(&read.beat.bpm, &chart.beat.bpm);
+                prop_assert_eq!(&read.beat.time_sig, &chart.beat.time_sig);
+                prop_assert_eq!(&read.beat.scroll_speed, &chart.beat.scroll_speed);
+                prop_assert_eq!(&read.camera.cam.body.zoom, &chart.camera.cam.body.zoom);
+                prop_assert_eq!(&read.camera.cam.body.rotation_x, &chart.camera.cam.body.rotation_x);
+                // Everything else is at least written the same way again
+                prop_assert_eq!(
+                    serde_json::to_value(&read).unwrap(),
+                    serde_json::to_value(&chart).unwrap()
+                );
+            }
+
+            #[test]
+            fn ksh_note_round_trip(bt in [lane(), lane(), lane(), lane()], fx in [lane(), lane()]) {
+                use crate::Ksh;
+
+                let mut chart = Chart::new();
+                chart.beat.bpm = vec![(0, 120.0)];
+                chart.beat.time_sig = vec![(0, TimeSignature(4, 4))];
+                chart.note.bt = bt;
+                chart.note.fx = fx;
+
+                let mut out = vec![];
+                chart.to_ksh(&mut out).unwrap();
+                let read = Chart::from_ksh(&String::from_utf8(out).unwrap()).unwrap();
+
+                prop_assert_eq!(&read.note.bt, &chart.note.bt);
+                prop_assert_eq!(&read.note.fx, &chart.note.fx);
+            }
+        }
+    }
 }
@@ -0,0 +1,151 @@
+//! Upgrades documents written with older versions of the kson format to the current layout
+//! before they are deserialized.
+
+use serde_json::{json, Map, Value};
+
+use crate::{Error, KSON_VERSION};
+
+/// First version storing notes, laser sections and graph points as arrays instead of objects
+const ARRAY_LAYOUT_VERSION: (u32, u32) = (0, 4);
+
+type Convert = fn(&Map<String, Value>) -> Result<Value, String>;
+
+/// (major, minor) of a version string, a missing minor version counts as 0
+fn parse_version(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.split('.');
+    let major = parts.next()?.trim().parse().ok()?;
+    let minor = match parts.next() {
+        Some(minor) => minor.trim().parse().ok()?,
+        None => 0,
+    };
+    Some((major, minor))
+}
+
+fn get<'a>(obj: &'a Map<String, Value>, key: &str) -> Result<&'a Value, String> {
+    obj.get(key).ok_or_else(|| format!("missing `{key}`"))
+}
+
+/// `{y, v}` to `[y, v]`
+fn by_pulse(obj: &Map<String, Value>) -> Result<Value, String> {
+    Ok(json!([get(obj, "y")?, get(obj, "v")?]))
+}
+
+/// `{idx, v: {n, d}}` to `[idx, [n, d]]`
+fn time_sig(obj: &Map<String, Value>) -> Result<Value, String> {
+    let sig = get(obj, "v")?
+        .as_object()
+        .ok_or_else(|| "`v` is not an object".to_string())?;
+    Ok(json!([get(obj, "idx")?, [get(sig, "n")?, get(sig, "d")?]]))
+}
+
+/// `{y, l}` to `y` for chips or `[y, l]` for holds
+fn interval(obj: &Map<String, Value>) -> Result<Value, String> {
+    let y = get(obj, "y")?;
+    match obj.get("l").and_then(Value::as_u64).unwrap_or(0) {
+        0 => Ok(y.clone()),
+        l => Ok(json!([y, l])),
+    }
+}
+
+/// `{y, v, vf, a, b}` to `[y, v | [v, vf], [a, b]]`, `y_key` is `ry` for points in laser
+/// sections
+fn graph_point(obj: &Map<String, Value>, y_key: &str) -> Result<Value, String> {
+    let v = get(obj, "v")?;
+    let value = match obj.get("vf") {
+        Some(vf) if !vf.is_null() => json!([v, vf]),
+        _ => v.clone(),
+    };
+    let a = obj.get("a").and_then(Value::as_f64).unwrap_or(0.5);
+    let b = obj.get("b").and_then(Value::as_f64).unwrap_or(0.5);
+    Ok(json!([get(obj, y_key)?, value, [a, b]]))
+}
+
+/// `{y, v: [points], w}` to `[y, [points], w]`
+fn laser_section(obj: &Map<String, Value>) -> Result<Value, String> {
+    let points = get(obj, "v")?
+        .as_array()
+        .ok_or_else(|| "`v` is not an array".to_string())?
+        .iter()
+        .enumerate()
+        .map(|(i, point)| match point {
+            Value::Object(point) => graph_point(point, "ry").map_err(|e| format!("point {i}: {e}")),
+            point => Ok(point.clone()),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let wide = obj.get("w").cloned().unwrap_or(json!(1));
+    Ok(json!([get(obj, "y")?, points, wide]))
+}
+
+struct Migration<'a> {
+    version: &'a str,
+}
+
+impl Migration<'_> {
+    /// Converts the object entries of the array at `path`, entries already in the current
+    /// layout are kept. `per_lane` arrays hold one array of entries for each lane.
+    fn entries(
+        &self,
+        doc: &mut Value,
+        path: &[&str],
+        per_lane: bool,
+        convert: Convert,
+    ) -> Result<(), Error> {
+        let field = path.join(".");
+        let Some(list) = path
+            .iter()
+            .try_fold(doc, |value, key| value.get_mut(*key))
+            .and_then(Value::as_array_mut)
+        else {
+            return Ok(());
+        };
+
+        if !per_lane {
+            return self.convert_all(list, &field, convert);
+        }
+
+        for (lane, entries) in list.iter_mut().enumerate() {
+            if let Some(entries) = entries.as_array_mut() {
+                self.convert_all(entries, &format!("{field}[{lane}]"), convert)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn convert_all(&self, list: &mut [Value], field: &str, convert: Convert) -> Result<(), Error> {
+        for (i, entry) in list.iter_mut().enumerate() {
+            let Value::Object(obj) = entry else {
+                continue;
+            };
+
+            let migrated = convert(obj).map_err(|message| Error::Migration {
+                version: self.version.to_string(),
+                field: format!("{field}[{i}]"),
+                message,
+            })?;
+            *entry = migrated;
+        }
+        Ok(())
+    }
+}
+
+/// Rewrites a document of the given version to the current layout.
+pub(crate) fn migrate(doc: &mut Value, version: &str) -> Result<(), Error> {
+    if parse_version(version).is_some_and(|x| x >= ARRAY_LAYOUT_VERSION) {
+        return Ok(());
+    }
+
+    let migration = Migration { version };
+    migration.entries(doc, &["beat", "bpm"], false, by_pulse)?;
+    migration.entries(doc, &["beat", "time_sig"], false, time_sig)?;
+    migration.entries(doc, &["beat", "scroll_speed"], false, |x| {
+        graph_point(x, "y")
+    })?;
+    migration.entries(doc, &["note", "bt"], true, interval)?;
+    migration.entries(doc, &["note", "fx"], true, interval)?;
+    migration.entries(doc, &["note", "laser"], true, laser_section)?;
+
+    if let Some(doc) = doc.as_object_mut() {
+        doc.insert("version".to_string(), Value::from(KSON_VERSION));
+    }
+    Ok(())
+}
@@ -0,0 +1,267 @@
+//! Charts flattened for renderers. Every kind of object is in its own array sorted by time, with
+//! times in beats and milliseconds so renderers don't depend on the chart resolution or tempo.
+
+use crate::{do_curve, Chart, GraphPoint, LaserSection, Side, KSON_RESOLUTION};
+
+/// Straight lines a curved laser or camera segment is split into
+const CURVE_STEPS: u32 = 16;
+
+/// When an object is in the chart
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderTime {
+    /// Beats from the start of the chart
+    pub beat: f64,
+    pub ms: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteLane {
+    Bt(usize),
+    Fx(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderChip {
+    pub lane: NoteLane,
+    pub time: RenderTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderHold {
+    pub lane: NoteLane,
+    pub start: RenderTime,
+    pub end: RenderTime,
+}
+
+/// Point of a laser line, a slam is two vertices at the same time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LaserVertex {
+    pub time: RenderTime,
+    /// From 0 at the left edge of the laser range to 1 at the right edge
+    pub x: f64,
+}
+
+/// A laser section as straight lines, with curves already split up
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderLaser {
+    pub side: Side,
+    /// Covers twice the width of the track
+    pub wide: bool,
+    pub vertices: Vec<LaserVertex>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderBeatLine {
+    pub time: RenderTime,
+    pub measure: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraParam {
+    Zoom,
+    ShiftX,
+    RotationX,
+    RotationZ,
+    RotationZHighway,
+    RotationZJudgeLine,
+    Split,
+}
+
+/// Value of a camera parameter, linear between keyframes with curves already split up
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraKeyframe {
+    pub param: CameraParam,
+    pub time: RenderTime,
+    pub value: f64,
+    /// Value continued from when the parameter jumps at this keyframe
+    pub final_value: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RenderModel {
+    pub chips: Vec<RenderChip>,
+    pub holds: Vec<RenderHold>,
+    /// Sorted by their first vertex
+    pub lasers: Vec<RenderLaser>,
+    /// Up to the measure of the last note
+    pub beat_lines: Vec<RenderBeatLine>,
+    /// Sorted by time, keyframes at the same time are in [`CameraParam`] order
+    pub camera: Vec<CameraKeyframe>,
+}
+
+/// Points of a curve from `(x0, y0)` to `(x1, y1)` after the start, with the curve split into
+/// straight lines.
+fn tessellate(x0: f64, y0: f64, x1: f64, y1: f64, (a, b): (f64, f64)) -> Vec<(f64, f64)> {
+    if (a - b).abs() <= f64::EPSILON || x1 <= x0 {
+        return vec![(x1, y1)];
+    }
+
+    (1..CURVE_STEPS)
+        .map(|i| {
+            let t = i as f64 / CURVE_STEPS as f64;
+            (x0 + (x1 - x0) * t, y0 + (y1 - y0) * do_curve(t, a, b))
+        })
+        .chain([(x1, y1)])
+        .collect()
+}
+
+impl Chart {
+    fn render_time(&self, tick: f64) -> RenderTime {
+        let whole = tick.floor().max(0.0) as u32;
+        RenderTime {
+            beat: tick / KSON_RESOLUTION as f64,
+            ms: self.tick_to_ms(whole) + (tick - whole as f64) * self.tick_duration_ms_at(whole),
+        }
+    }
+
+    fn render_laser(&self, side: Side, section: &LaserSection) -> RenderLaser {
+        let y = section.tick() as f64;
+        let mut vertices = vec![];
+        for (i, point) in section.1.iter().enumerate() {
+            let tick = y + point.ry as f64;
+            if i == 0 {
+                vertices.push(LaserVertex {
+                    time: self.render_time(tick),
+                    x: point.v,
+                });
+            }
+            if let Some(vf) = point.vf {
+                vertices.push(LaserVertex {
+                    time: self.render_time(tick),
+                    x: vf,
+                });
+            }
+            if let Some(next) = section.1.get(i + 1) {
+                let start = point.vf.unwrap_or(point.v);
+                let end_tick = y + next.ry as f64;
+                vertices.extend(
+                    tessellate(tick, start, end_tick, next.v, (point.a, point.b))
+                        .into_iter()
+                        .map(|(tick, x)| LaserVertex {
+                            time: self.render_time(tick),
+                            x,
+                        }),
+                );
+            }
+        }
+
+        RenderLaser {
+            side,
+            wide: section.wide() >= 2,
+            vertices,
+        }
+    }
+
+    fn render_camera_graph(&self, param: CameraParam, graph: &[GraphPoint]) -> Vec<CameraKeyframe> {
+        let mut keyframes = vec![];
+        for (i, point) in graph.iter().enumerate() {
+            let final_value = point.vf.unwrap_or(point.v);
+            keyframes.push(CameraKeyframe {
+                param,
+                time: self.render_time(point.y as f64),
+                value: point.v,
+                final_value,
+            });
+
+            let Some(next) = graph.get(i + 1) else {
+                continue;
+            };
+            let mut curve = tessellate(
+                point.y as f64,
+                final_value,
+                next.y as f64,
+                next.v,
+                (point.a, point.b),
+            );
+            // The next point is added by itself
+            curve.pop();
+            keyframes.extend(curve.into_iter().map(|(tick, value)| CameraKeyframe {
+                param,
+                time: self.render_time(tick),
+                value,
+                final_value: value,
+            }));
+        }
+        keyframes
+    }
+
+    /// Flattens the chart into sorted arrays for rendering.
+    pub fn render_model(&self) -> RenderModel {
+        let mut model = RenderModel::default();
+
+        let bt = self
+            .note
+            .bt
+            .iter()
+            .enumerate()
+            .map(|(i, x)| (NoteLane::Bt(i), x));
+        let fx = self
+            .note
+            .fx
+            .iter()
+            .enumerate()
+            .map(|(i, x)| (NoteLane::Fx(i), x));
+        for (lane, notes) in bt.chain(fx) {
+            for note in notes {
+                if note.l == 0 {
+                    model.chips.push(RenderChip {
+                        lane,
+                        time: self.render_time(note.y as f64),
+                    });
+                } else {
+                    model.holds.push(RenderHold {
+                        lane,
+                        start: self.render_time(note.y as f64),
+                        end: self.render_time((note.y + note.l) as f64),
+                    });
+                }
+            }
+        }
+        model
+            .chips
+            .sort_by(|a, b| a.time.beat.total_cmp(&b.time.beat));
+        model
+            .holds
+            .sort_by(|a, b| a.start.beat.total_cmp(&b.start.beat));
+
+        for side in Side::iter() {
+            model.lasers.extend(
+                self.note.laser[side as usize]
+                    .iter()
+                    .map(|section| self.render_laser(side, section)),
+            );
+        }
+        model.lasers.sort_by(|a, b| {
+            let start = |x: &RenderLaser| x.vertices.first().map_or(0.0, |v| v.time.beat);
+            start(a).total_cmp(&start(b))
+        });
+
+        let last_measure = self.tick_to_measure(self.get_last_tick());
+        model.beat_lines = self
+            .beat_line_iter()
+            .take_while(|(tick, _)| self.tick_to_measure(*tick) <= last_measure)
+            .map(|(tick, measure)| RenderBeatLine {
+                time: self.render_time(tick as f64),
+                measure,
+            })
+            .collect();
+
+        let body = &self.camera.cam.body;
+        for (param, graph) in [
+            (CameraParam::Zoom, &body.zoom),
+            (CameraParam::ShiftX, &body.shift_x),
+            (CameraParam::RotationX, &body.rotation_x),
+            (CameraParam::RotationZ, &body.rotation_z),
+            (CameraParam::RotationZHighway, &body.rotation_z_highway),
+            (CameraParam::RotationZJudgeLine, &body.rotation_z_jdgline),
+            (CameraParam::Split, &body.split),
+        ] {
+            model.camera.extend(self.render_camera_graph(param, graph));
+        }
+        model
+            .camera
+            .sort_by(|a, b| a.time.beat.total_cmp(&b.time.beat));
+
+        model
+    }
+}
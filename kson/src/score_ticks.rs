@@ -249,3 +249,49 @@ impl ScoreTicker for ScoreTicks {
         }
     }
 }
+
+/// Score of a play with every tick judged critical
+pub const MAX_SCORE: u64 = 10_000_000;
+/// EX score for a critical chip or slam
+pub const EX_CHIP: u32 = 5;
+/// EX score for a critical hold or laser tick
+pub const EX_TICK: u32 = 2;
+
+/// Score math for a chart, for showing what a chart is worth without playing it.
+#[derive(Debug, Clone, Copy)]
+pub struct ScoreCalculator {
+    pub summary: ScoreTickSummary,
+}
+
+impl ScoreCalculator {
+    pub fn new(chart: &Chart) -> Self {
+        Self {
+            summary: generate_score_ticks(chart).summary(),
+        }
+    }
+
+    /// Highest reachable combo, every score tick adds one
+    pub fn max_chain(&self) -> u32 {
+        self.summary.total
+    }
+
+    pub fn max_ex_score(&self) -> u32 {
+        let ScoreTickSummary {
+            chip_count,
+            hold_count,
+            laser_count,
+            slam_count,
+            ..
+        } = self.summary;
+        (chip_count + slam_count) * EX_CHIP + (hold_count + laser_count) * EX_TICK
+    }
+
+    /// Score out of [`MAX_SCORE`] for the given judgement counts, a critical is worth two
+    /// nears. Counts above the ticks in the chart raise the maximum so the score never goes
+    /// over [`MAX_SCORE`].
+    pub fn score(&self, crit: u32, near: u32, miss: u32) -> u64 {
+        let judged = crit as u64 + near as u64 + miss as u64;
+        let max = (self.summary.total as u64).max(judged).max(1) * 2;
+        MAX_SCORE * (crit as u64 * 2 + near as u64) / max
+    }
+}
@@ -0,0 +1,589 @@
+//! Cutting charts down to a range and joining charts together, for practice cuts and medleys,
+//! and copying, moving and mirroring ranges of a chart for the editor.
+
+use std::ops::Range;
+
+use crate::camera::{CamGraphs, CamPatternInvokeSpin, CamPatternInvokeSwing, CamShake};
+use crate::*;
+
+/// Anything placed at a tick in a chart
+trait Timed {
+    fn tick(&self) -> u32;
+    fn tick_mut(&mut self) -> &mut u32;
+}
+
+macro_rules! impl_timed {
+    ($t:ty, $field:tt) => {
+        impl Timed for $t {
+            fn tick(&self) -> u32 {
+                self.$field
+            }
+            fn tick_mut(&mut self) -> &mut u32 {
+                &mut self.$field
+            }
+        }
+    };
+}
+
+impl<T> Timed for (u32, T) {
+    fn tick(&self) -> u32 {
+        self.0
+    }
+    fn tick_mut(&mut self) -> &mut u32 {
+        &mut self.0
+    }
+}
+
+impl<T> Timed for ByPulseOption<T> {
+    fn tick(&self) -> u32 {
+        self.0
+    }
+    fn tick_mut(&mut self) -> &mut u32 {
+        &mut self.0
+    }
+}
+
+impl_timed!(Interval, y);
+impl_timed!(LaserSection, 0);
+impl_timed!(GraphPoint, y);
+impl_timed!(CamPatternInvokeSpin, 0);
+impl_timed!(CamPatternInvokeSwing, 0);
+impl_timed!(CamShake, 0);
+impl_timed!(Annotation, y);
+
+/// Moves every event with `map`, removing the ones it returns `None` for.
+fn retime<T: Timed>(events: &mut Vec<T>, map: &impl Fn(u32) -> Option<u32>) {
+    events.retain_mut(|event| match map(event.tick()) {
+        Some(tick) => {
+            *event.tick_mut() = tick;
+            true
+        }
+        None => false,
+    });
+}
+
+fn merge<T: Timed>(events: &mut Vec<T>, other: Vec<T>) {
+    events.extend(other);
+    events.sort_by_key(|x| x.tick());
+}
+
+fn merge_param_changes(
+    param_changes: &mut Dict<Dict<ByPulse<String>>>,
+    other: Dict<Dict<ByPulse<String>>>,
+) {
+    for (name, params) in other {
+        let target = param_changes.entry(name).or_default();
+        for (param, events) in params {
+            merge(target.entry(param).or_default(), events);
+        }
+    }
+}
+
+/// Repeats the value in effect at `tick` there, so it is kept when everything before is cut.
+fn carry_state<T: Clone>(events: &mut ByPulse<T>, tick: u32) {
+    let i = events.partition_point(|x| x.0 <= tick);
+    if let Some((y, value)) = i.checked_sub(1).map(|i| &events[i]) {
+        if *y < tick {
+            let value = value.clone();
+            events.insert(i, (tick, value));
+        }
+    }
+}
+
+/// Adds a point at `tick` so the graph keeps its value there when everything before is cut.
+fn carry_graph(graph: &mut Vec<GraphPoint>, tick: u32) {
+    let i = graph.partition_point(|x| x.y < tick);
+    if i == 0 || graph.get(i).is_some_and(|x| x.y == tick) {
+        return;
+    }
+
+    let v = graph.value_at(tick as f64);
+    graph.insert(
+        i,
+        GraphPoint {
+            y: tick,
+            v,
+            vf: None,
+            a: 0.5,
+            b: 0.5,
+        },
+    );
+}
+
+/// Lanes covered by [`Chart::copy_range`] and [`Chart::remove_range`]. Effects and key sounds
+/// on FX notes go with their FX lane, everything else not placed on a lane, such as BPM changes,
+/// camera events and comments, is covered by `timeline`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LaneSelection {
+    pub bt: [bool; 4],
+    pub fx: [bool; 2],
+    pub laser: [bool; 2],
+    pub timeline: bool,
+}
+
+impl LaneSelection {
+    pub const ALL: Self = Self {
+        bt: [true; 4],
+        fx: [true; 2],
+        laser: [true; 2],
+        timeline: true,
+    };
+
+    pub fn invert(self) -> Self {
+        Self {
+            bt: self.bt.map(|x| !x),
+            fx: self.fx.map(|x| !x),
+            laser: self.laser.map(|x| !x),
+            timeline: !self.timeline,
+        }
+    }
+}
+
+impl Default for LaneSelection {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+/// How [`Chart::paste_resolving`] handles pasted notes overlapping notes already in the chart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasteConflict {
+    /// Remove the overlapped notes from the chart
+    Overwrite,
+    /// Leave out the pasted notes overlapping notes in the chart
+    KeepExisting,
+}
+
+/// Ticks covered by a note, chips cover the tick they are on.
+fn interval_span(note: &Interval) -> Range<u32> {
+    note.y..note.y + note.l.max(1)
+}
+
+fn laser_span(section: &LaserSection) -> Range<u32> {
+    let length = section.1.last().map_or(0, |x| x.ry);
+    section.0..section.0 + length.max(1)
+}
+
+/// Removes the notes of `lanes` overlapping a note on the same lane of `other`, returns how many
+/// were removed.
+fn remove_overlapping<T>(
+    lanes: &mut [Vec<T>],
+    other: &[Vec<T>],
+    span: impl Fn(&T) -> Range<u32>,
+) -> usize {
+    let mut removed = 0;
+    for (lane, other) in lanes.iter_mut().zip(other) {
+        let count = lane.len();
+        lane.retain(|x| {
+            let a = span(x);
+            !other
+                .iter()
+                .map(&span)
+                .any(|b| a.start < b.end && b.start < a.end)
+        });
+        removed += count - lane.len();
+    }
+    removed
+}
+
+impl NoteInfo {
+    /// Removes the notes overlapping a note on the same lane of `other`, returns how many were
+    /// removed.
+    fn remove_overlapping(&mut self, other: &NoteInfo) -> usize {
+        remove_overlapping(&mut self.bt, &other.bt, interval_span)
+            + remove_overlapping(&mut self.fx, &other.fx, interval_span)
+            + remove_overlapping(&mut self.laser, &other.laser, laser_span)
+    }
+}
+
+/// Moves the events starting in `start..end` to the lane on the opposite side.
+fn mirror_lanes<T: Timed>(lanes: &mut [Vec<T>], start: u32, end: u32) {
+    let moved: Vec<Vec<T>> = lanes
+        .iter_mut()
+        .map(|lane| {
+            let (inside, outside) = std::mem::take(lane)
+                .into_iter()
+                .partition(|x| (start..end).contains(&x.tick()));
+            *lane = outside;
+            inside
+        })
+        .collect();
+
+    for (lane, events) in lanes.iter_mut().rev().zip(moved) {
+        merge(lane, events);
+    }
+}
+
+fn cam_graphs(body: &mut CamGraphs) -> [&mut Vec<GraphPoint>; 7] {
+    [
+        &mut body.zoom,
+        &mut body.shift_x,
+        &mut body.rotation_x,
+        &mut body.rotation_z,
+        &mut body.rotation_z_highway,
+        &mut body.rotation_z_jdgline,
+        &mut body.split,
+    ]
+}
+
+impl Chart {
+    /// Cuts the chart down to `start..end`, moving `start` to tick 0. The audio offset is
+    /// adjusted so the remaining notes still line up with the audio, and time signatures stay
+    /// aligned as long as `start` is on a measure line.
+    pub fn trim(&mut self, start: u32, end: u32) {
+        let start_ms = self.tick_to_ms(start);
+        self.beat.time_sig = self.trimmed_time_sig(start, end);
+
+        carry_state(&mut self.beat.bpm, start);
+        carry_state(&mut self.camera.tilt.scale, start);
+        carry_state(&mut self.camera.tilt.keep, start);
+        carry_state(&mut self.audio.key_sound.laser.vol, start);
+        let effects = &mut self.audio.audio_effect;
+        for params in effects
+            .fx
+            .param_change
+            .values_mut()
+            .chain(effects.laser.param_change.values_mut())
+        {
+            for events in params.values_mut() {
+                carry_state(events, start);
+            }
+        }
+
+        carry_graph(&mut self.beat.scroll_speed, start);
+        for graph in cam_graphs(&mut self.camera.cam.body) {
+            carry_graph(graph, start);
+        }
+
+        for lane in self.note.bt.iter_mut().chain(self.note.fx.iter_mut()) {
+            for note in lane.iter_mut().filter(|x| x.y < end) {
+                note.l = note.l.min(end - note.y);
+            }
+        }
+
+        for lane in &mut self.note.laser {
+            for section in lane.iter_mut() {
+                let y = section.0;
+                section.1.retain(|x| y + x.ry <= end);
+            }
+            lane.retain(|x| x.1.len() > 1);
+        }
+
+        self.retime_events(&|tick| (start..end).contains(&tick).then(|| tick - start));
+        self.audio.bgm.offset += start_ms.round() as i32;
+    }
+
+    /// Places `other` after the last note of this chart, with `gap_measures` empty measures in
+    /// between. The gap keeps the last BPM of this chart and the BPM of `other` takes over where
+    /// it starts. Only the audio of this chart is kept, so audio for the joined chart has to be
+    /// prepared separately.
+    pub fn append(&mut self, other: &Chart, gap_measures: u32) {
+        let join_measure = self.tick_to_measure(self.get_last_tick()) + 1 + gap_measures;
+        let join_tick = self.measure_to_tick(join_measure);
+
+        self.beat.time_sig.retain(|x| x.0 < join_measure);
+        self.beat.time_sig.extend(
+            other
+                .beat
+                .time_sig
+                .iter()
+                .map(|(measure, sig)| (measure + join_measure, *sig)),
+        );
+
+        let mut other = other.clone();
+        other.retime_events(&|tick| Some(tick + join_tick));
+        self.merge_events(other);
+    }
+
+    /// Copies everything in `start..end` on `lanes` to a new chart, moved so `start` is at tick
+    /// 0. Holds and lasers are cut off at `end`. Time signatures are left out, effect definitions
+    /// are kept so the copy can be pasted into another chart.
+    pub fn copy_range(&self, start: u32, end: u32, lanes: LaneSelection) -> Chart {
+        let mut clip = self.clone();
+        clip.beat.time_sig.clear();
+
+        for lane in clip.note.bt.iter_mut().chain(clip.note.fx.iter_mut()) {
+            for note in lane.iter_mut().filter(|x| x.y < end) {
+                note.l = note.l.min(end - note.y);
+            }
+        }
+        for lane in &mut clip.note.laser {
+            for section in lane.iter_mut() {
+                let y = section.0;
+                section.1.retain(|x| y + x.ry <= end);
+            }
+            lane.retain(|x| x.1.len() > 1);
+        }
+
+        let in_range = |tick| (start..end).contains(&tick).then(|| tick - start);
+        clip.retime_lanes(&in_range, lanes);
+        clip.retime_lanes(&|_| None, lanes.invert());
+        if lanes.timeline {
+            clip.retime_timeline(&in_range);
+        } else {
+            clip.retime_timeline(&|_| None);
+        }
+        clip
+    }
+
+    /// Removes everything starting in `start..end` on `lanes`.
+    pub fn remove_range(&mut self, start: u32, end: u32, lanes: LaneSelection) {
+        let outside = |tick| (!(start..end).contains(&tick)).then_some(tick);
+        self.retime_lanes(&outside, lanes);
+        if lanes.timeline {
+            self.retime_timeline(&outside);
+        }
+    }
+
+    /// Adds a copy made with [`Self::copy_range`] with its start at `tick`.
+    pub fn paste(&mut self, clip: &Chart, tick: u32) {
+        let mut clip = clip.clone();
+        clip.retime_events(&|y| Some(y + tick));
+        self.merge_events(clip);
+    }
+
+    /// Notes in the chart that pasting `clip` at `tick` would overlap.
+    pub fn paste_conflicts(&self, clip: &Chart, tick: u32) -> usize {
+        let mut note = clip.note.clone();
+        for lane in note.bt.iter_mut().chain(note.fx.iter_mut()) {
+            retime(lane, &|y| Some(y + tick));
+        }
+        for lane in &mut note.laser {
+            retime(lane, &|y| Some(y + tick));
+        }
+        self.note.clone().remove_overlapping(&note)
+    }
+
+    /// Like [`Self::paste`], without leaving overlapping notes on a lane.
+    pub fn paste_resolving(&mut self, clip: &Chart, tick: u32, conflict: PasteConflict) {
+        let mut clip = clip.clone();
+        clip.retime_events(&|y| Some(y + tick));
+        match conflict {
+            PasteConflict::Overwrite => self.note.remove_overlapping(&clip.note),
+            PasteConflict::KeepExisting => clip.note.remove_overlapping(&self.note),
+        };
+        self.merge_events(clip);
+    }
+
+    /// Flips everything starting in `start..end` horizontally: BT and FX lanes are swapped,
+    /// lasers move to the other side with mirrored values, and manual tilts and laser slam
+    /// camera spins turn the other way.
+    pub fn mirror(&mut self, start: u32, end: u32) {
+        let in_range = |tick: u32| (start..end).contains(&tick);
+
+        for section in self.note.laser.iter_mut().flatten() {
+            if in_range(section.0) {
+                for point in &mut section.1 {
+                    point.v = 1.0 - point.v;
+                    point.vf = point.vf.map(|vf| 1.0 - vf);
+                }
+            }
+        }
+
+        mirror_lanes(&mut self.note.bt, start, end);
+        mirror_lanes(&mut self.note.fx, start, end);
+        mirror_lanes(&mut self.note.laser, start, end);
+        for fx_lanes in self.audio.audio_effect.fx.long_event.values_mut() {
+            mirror_lanes(fx_lanes, start, end);
+        }
+        for fx_lanes in self.audio.key_sound.fx.chip_event.values_mut() {
+            mirror_lanes(fx_lanes, start, end);
+        }
+
+        for (y, section) in &mut self.camera.tilt.manual {
+            if in_range(*y) {
+                for point in section {
+                    point.v = -point.v;
+                    point.vf = point.vf.map(|vf| -vf);
+                }
+            }
+        }
+
+        let slam_event = &mut self.camera.cam.pattern.laser.slam_event;
+        for spin in slam_event.spin.iter_mut().chain(&mut slam_event.half_spin) {
+            if in_range(spin.0) {
+                spin.1 = -spin.1;
+            }
+        }
+        for swing in &mut slam_event.swing {
+            if in_range(swing.0) {
+                swing.1 = -swing.1;
+            }
+        }
+    }
+
+    fn trimmed_time_sig(&self, start: u32, end: u32) -> ByMeasureIdx<TimeSignature> {
+        let start_measure = self.tick_to_measure(start);
+        let time_sig = &self.beat.time_sig;
+
+        time_sig
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| {
+                time_sig
+                    .get(i + 1)
+                    .is_none_or(|next| next.0 > start_measure)
+            })
+            .map(|(_, x)| x)
+            .take_while(|(measure, _)| self.measure_to_tick(*measure) < end)
+            .map(|(measure, sig)| (measure.saturating_sub(start_measure), *sig))
+            .collect()
+    }
+
+    /// Everything placed at a tick, except time signatures which are placed by measure.
+    fn retime_events(&mut self, map: &impl Fn(u32) -> Option<u32>) {
+        self.retime_lanes(map, LaneSelection::ALL);
+        self.retime_timeline(map);
+    }
+
+    /// Notes on `lanes`, with the effects and key sounds on FX notes.
+    fn retime_lanes(&mut self, map: &impl Fn(u32) -> Option<u32>, lanes: LaneSelection) {
+        for (lane, selected) in self.note.bt.iter_mut().zip(lanes.bt) {
+            if selected {
+                retime(lane, map);
+            }
+        }
+        for (lane, selected) in self.note.fx.iter_mut().zip(lanes.fx) {
+            if selected {
+                retime(lane, map);
+            }
+        }
+        for (lane, selected) in self.note.laser.iter_mut().zip(lanes.laser) {
+            if selected {
+                retime(lane, map);
+            }
+        }
+
+        for fx_lanes in self.audio.audio_effect.fx.long_event.values_mut() {
+            for (lane, selected) in fx_lanes.iter_mut().zip(lanes.fx) {
+                if selected {
+                    retime(lane, map);
+                }
+            }
+        }
+        for fx_lanes in self.audio.key_sound.fx.chip_event.values_mut() {
+            for (lane, selected) in fx_lanes.iter_mut().zip(lanes.fx) {
+                if selected {
+                    retime(lane, map);
+                }
+            }
+        }
+    }
+
+    /// Everything placed at a tick that is not on a lane.
+    fn retime_timeline(&mut self, map: &impl Fn(u32) -> Option<u32>) {
+        retime(&mut self.beat.bpm, map);
+        retime(&mut self.beat.scroll_speed, map);
+
+        let tilt = &mut self.camera.tilt;
+        retime(&mut tilt.scale, map);
+        retime(&mut tilt.manual, map);
+        retime(&mut tilt.keep, map);
+        for graph in cam_graphs(&mut self.camera.cam.body) {
+            retime(graph, map);
+        }
+        let slam_event = &mut self.camera.cam.pattern.laser.slam_event;
+        retime(&mut slam_event.spin, map);
+        retime(&mut slam_event.half_spin, map);
+        retime(&mut slam_event.swing, map);
+        retime(&mut self.camera.lane_toggle, map);
+        retime(&mut self.camera.shake, map);
+
+        let effects = &mut self.audio.audio_effect;
+        for params in effects
+            .fx
+            .param_change
+            .values_mut()
+            .chain(effects.laser.param_change.values_mut())
+        {
+            for events in params.values_mut() {
+                retime(events, map);
+            }
+        }
+        for events in effects.laser.pulse_event.values_mut() {
+            retime(events, map);
+        }
+
+        retime(&mut self.audio.key_sound.laser.vol, map);
+
+        retime(&mut self.editor.annotation, map);
+    }
+
+    /// Adds all events of `other` to this chart, except time signatures.
+    fn merge_events(&mut self, other: Chart) {
+        let Chart {
+            note,
+            beat,
+            audio,
+            camera,
+            editor,
+            ..
+        } = other;
+
+        for (lane, other) in self.note.bt.iter_mut().zip(note.bt) {
+            merge(lane, other);
+        }
+        for (lane, other) in self.note.fx.iter_mut().zip(note.fx) {
+            merge(lane, other);
+        }
+        for (lane, other) in self.note.laser.iter_mut().zip(note.laser) {
+            merge(lane, other);
+        }
+
+        merge(&mut self.beat.bpm, beat.bpm);
+        merge(&mut self.beat.scroll_speed, beat.scroll_speed);
+
+        let tilt = &mut self.camera.tilt;
+        merge(&mut tilt.scale, camera.tilt.scale);
+        merge(&mut tilt.manual, camera.tilt.manual);
+        merge(&mut tilt.keep, camera.tilt.keep);
+        let mut other_body = camera.cam.body;
+        for (graph, other) in cam_graphs(&mut self.camera.cam.body)
+            .into_iter()
+            .zip(cam_graphs(&mut other_body))
+        {
+            merge(graph, std::mem::take(other));
+        }
+        let slam_event = &mut self.camera.cam.pattern.laser.slam_event;
+        let other_slam_event = camera.cam.pattern.laser.slam_event;
+        merge(&mut slam_event.spin, other_slam_event.spin);
+        merge(&mut slam_event.half_spin, other_slam_event.half_spin);
+        merge(&mut slam_event.swing, other_slam_event.swing);
+        merge(&mut self.camera.lane_toggle, camera.lane_toggle);
+        merge(&mut self.camera.shake, camera.shake);
+
+        let effects = &mut self.audio.audio_effect;
+        let other_effects = audio.audio_effect;
+        for (name, def) in other_effects.fx.def {
+            effects.fx.def.entry(name).or_insert(def);
+        }
+        for (name, def) in other_effects.laser.def {
+            effects.laser.def.entry(name).or_insert(def);
+        }
+        merge_param_changes(&mut effects.fx.param_change, other_effects.fx.param_change);
+        merge_param_changes(
+            &mut effects.laser.param_change,
+            other_effects.laser.param_change,
+        );
+        for (name, lanes) in other_effects.fx.long_event {
+            let target = effects.fx.long_event.entry(name).or_default();
+            for (lane, other) in target.iter_mut().zip(lanes) {
+                merge(lane, other);
+            }
+        }
+        for (name, events) in other_effects.laser.pulse_event {
+            merge(effects.laser.pulse_event.entry(name).or_default(), events);
+        }
+
+        let key_sound = &mut self.audio.key_sound;
+        for (name, lanes) in audio.key_sound.fx.chip_event {
+            let target = key_sound.fx.chip_event.entry(name).or_default();
+            for (lane, other) in target.iter_mut().zip(lanes) {
+                merge(lane, other);
+            }
+        }
+        merge(&mut key_sound.laser.vol, audio.key_sound.laser.vol);
+
+        merge(&mut self.editor.annotation, editor.annotation);
+    }
+}
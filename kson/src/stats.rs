@@ -0,0 +1,188 @@
+//! Statistics for charters to judge how a chart plays, such as how much of it is spent holding
+//! notes and how the notes are split between the hands.
+
+use crate::{Chart, Side};
+
+/// Longest gap in ms between the notes of a trill
+const TRILL_GAP_MS: f64 = 150.0;
+/// Fewest notes counted as a trill
+const TRILL_MIN_NOTES: usize = 6;
+
+/// Notes alternating between the two BT lanes of one hand, found by [`Chart::stats`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OneHandTrill {
+    pub side: Side,
+    /// Tick of the first note
+    pub y: u32,
+    /// Tick of the last note
+    pub end: u32,
+    pub notes: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChartStats {
+    /// Share of the chart with a BT or FX hold held, from 0 to 1
+    pub hold_uptime: f64,
+    /// Share of the chart with a laser active, from 0 to 1
+    pub laser_uptime: f64,
+    /// Share of BT and FX notes hit while the other hand is also hitting a note, holding a
+    /// note or following a laser, from 0 to 1
+    pub both_hands: f64,
+    /// BT and FX notes for the left and right hand, BT A and B and FX L are on the left
+    pub hand_notes: [usize; 2],
+    pub trills: Vec<OneHandTrill>,
+}
+
+impl ChartStats {
+    /// Share of BT and FX notes on the left hand, 0.5 is an even split
+    pub fn hand_balance(&self) -> f64 {
+        let [left, right] = self.hand_notes;
+        if left + right == 0 {
+            0.5
+        } else {
+            left as f64 / (left + right) as f64
+        }
+    }
+}
+
+/// Joins overlapping ranges, the result is sorted by start.
+fn union<T: Copy + PartialOrd>(mut ranges: Vec<(T, T)>) -> Vec<(T, T)> {
+    ranges.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+    let mut joined: Vec<(T, T)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match joined.last_mut() {
+            Some(last) if start <= last.1 => {
+                if end > last.1 {
+                    last.1 = end;
+                }
+            }
+            _ => joined.push((start, end)),
+        }
+    }
+    joined
+}
+
+fn covered_ms(ranges: Vec<(f64, f64)>) -> f64 {
+    union(ranges).iter().map(|(start, end)| end - start).sum()
+}
+
+impl Chart {
+    /// Hand side of each BT and FX note as (tick, length, side, bt lane).
+    fn hand_notes(&self) -> Vec<(u32, u32, usize, Option<usize>)> {
+        let bt =
+            self.note.bt.iter().enumerate().flat_map(|(lane, notes)| {
+                notes.iter().map(move |x| (x.y, x.l, lane / 2, Some(lane)))
+            });
+        let fx = self
+            .note
+            .fx
+            .iter()
+            .enumerate()
+            .flat_map(|(side, notes)| notes.iter().map(move |x| (x.y, x.l, side, None)));
+        let mut notes: Vec<_> = bt.chain(fx).collect();
+        notes.sort_by_key(|x| x.0);
+        notes
+    }
+
+    /// Finds one hand trills on each side, sorted by tick.
+    fn one_hand_trills(&self, notes: &[(u32, u32, usize, Option<usize>)]) -> Vec<OneHandTrill> {
+        let mut trills = vec![];
+
+        for side in Side::iter() {
+            let mut run: Vec<(u32, usize)> = vec![];
+            let mut end_run = |run: &mut Vec<(u32, usize)>| {
+                if run.len() >= TRILL_MIN_NOTES {
+                    trills.push(OneHandTrill {
+                        side,
+                        y: run[0].0,
+                        end: run[run.len() - 1].0,
+                        notes: run.len(),
+                    });
+                }
+                run.clear();
+            };
+
+            for &(y, l, _, lane) in notes.iter().filter(|x| x.2 == side as usize) {
+                // Holds and FX notes on this hand end a trill
+                let Some(lane) = lane.filter(|_| l == 0) else {
+                    end_run(&mut run);
+                    continue;
+                };
+
+                let continues = run.last().is_some_and(|&(prev_y, prev_lane)| {
+                    lane != prev_lane
+                        && y > prev_y
+                        && self.tick_to_ms(y) - self.tick_to_ms(prev_y) <= TRILL_GAP_MS
+                });
+                if !continues {
+                    end_run(&mut run);
+                }
+                run.push((y, lane));
+            }
+            end_run(&mut run);
+        }
+
+        trills.sort_by_key(|x| x.y);
+        trills
+    }
+
+    /// Hold and laser uptime, hand balance and one hand trills in the chart.
+    pub fn stats(&self) -> ChartStats {
+        let notes = self.hand_notes();
+        let length_ms = self.tick_to_ms(self.get_last_tick()).max(1.0);
+        let ms_range = |start: u32, end: u32| (self.tick_to_ms(start), self.tick_to_ms(end));
+
+        let holds = notes
+            .iter()
+            .filter(|x| x.1 > 0)
+            .map(|x| ms_range(x.0, x.0 + x.1))
+            .collect();
+
+        let lasers: [Vec<(u32, u32)>; 2] = [0, 1].map(|side| {
+            self.note.laser[side]
+                .iter()
+                .map(|x| {
+                    (
+                        x.tick(),
+                        x.tick() + x.last().map(|x| x.ry).unwrap_or_default(),
+                    )
+                })
+                .collect()
+        });
+        let laser_ranges = lasers
+            .iter()
+            .flatten()
+            .map(|&(start, end)| ms_range(start, end))
+            .collect();
+
+        // A hand is busy from the start of a note or laser until it ends
+        let busy = [0, 1].map(|side| {
+            union(
+                notes
+                    .iter()
+                    .filter(|x| x.2 == side)
+                    .map(|x| (x.0, x.0 + x.1))
+                    .chain(lasers[side].iter().copied())
+                    .collect(),
+            )
+        });
+        let is_busy = |side: usize, tick: u32| {
+            let i = busy[side].partition_point(|x| x.0 <= tick);
+            i > 0 && busy[side][i - 1].1 >= tick
+        };
+        let both_hands = notes.iter().filter(|x| is_busy(1 - x.2, x.0)).count();
+
+        let mut hand_notes = [0; 2];
+        for note in &notes {
+            hand_notes[note.2] += 1;
+        }
+
+        ChartStats {
+            hold_uptime: (covered_ms(holds) / length_ms).min(1.0),
+            laser_uptime: (covered_ms(laser_ranges) / length_ms).min(1.0),
+            both_hands: both_hands as f64 / notes.len().max(1) as f64,
+            hand_notes,
+            trills: self.one_hand_trills(&notes),
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! Checks for mistakes that still make a loadable chart, for charters to fix.
+
+use std::fmt::Display;
+
+use crate::{BtLane, Chart, Interval, Side, Track};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChartIssueKind {
+    /// A note starts before the previous note on the same lane has ended
+    OverlappingNotes(Track),
+    /// A laser point is outside of 0 to 1
+    LaserOutOfRange(Side),
+    /// A laser section with fewer than two points
+    EmptyLaserSection(Side),
+    /// A BPM change to zero or less
+    ZeroBpm,
+    /// No time signature at the first measure
+    MissingTimeSignature,
+    /// A note ends after the end of the audio
+    NotePastAudioEnd(Track),
+}
+
+/// A problem found by [`Chart::validate`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChartIssue {
+    /// Tick the issue is at
+    pub y: u32,
+    pub kind: ChartIssueKind,
+}
+
+fn track_name(track: Track) -> String {
+    match track {
+        Track::BT(lane) => format!("BT {:?}", lane),
+        Track::FX(side) => format!("FX {:?}", side),
+        Track::Laser(side) => format!("{:?} laser", side),
+    }
+}
+
+impl Display for ChartIssueKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChartIssueKind::OverlappingNotes(track) => {
+                write!(f, "Overlapping notes on {}", track_name(*track))
+            }
+            ChartIssueKind::LaserOutOfRange(side) => {
+                write!(f, "{:?} laser is outside of the track", side)
+            }
+            ChartIssueKind::EmptyLaserSection(side) => {
+                write!(f, "{:?} laser section has less than two points", side)
+            }
+            ChartIssueKind::ZeroBpm => f.write_str("BPM is zero"),
+            ChartIssueKind::MissingTimeSignature => {
+                f.write_str("No time signature at the first measure")
+            }
+            ChartIssueKind::NotePastAudioEnd(track) => {
+                write!(
+                    f,
+                    "{} note continues past the end of the audio",
+                    track_name(*track)
+                )
+            }
+        }
+    }
+}
+
+impl Display for ChartIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (tick {})", self.kind, self.y)
+    }
+}
+
+fn interval_issues(track: Track, notes: &[Interval], issues: &mut Vec<ChartIssue>) {
+    for pair in notes.windows(2) {
+        let (prev, next) = (&pair[0], &pair[1]);
+        if next.y == prev.y || next.y < prev.y + prev.l {
+            issues.push(ChartIssue {
+                y: next.y,
+                kind: ChartIssueKind::OverlappingNotes(track),
+            });
+        }
+    }
+}
+
+impl Chart {
+    /// Finds mistakes in the chart, sorted by tick. Notes ending after the audio are reported
+    /// if the length of the audio file is given, the bgm offset is taken into account.
+    pub fn validate(&self, audio_length_ms: Option<f64>) -> Vec<ChartIssue> {
+        let mut issues = vec![];
+        let audio_end = audio_length_ms.map(|x| x - self.audio.bgm.offset as f64);
+
+        if self.beat.time_sig.first().map(|x| x.0) != Some(0) {
+            issues.push(ChartIssue {
+                y: 0,
+                kind: ChartIssueKind::MissingTimeSignature,
+            });
+        }
+
+        issues.extend(
+            self.beat
+                .bpm
+                .iter()
+                .filter(|x| x.1 <= 0.0)
+                .map(|x| ChartIssue {
+                    y: x.0,
+                    kind: ChartIssueKind::ZeroBpm,
+                }),
+        );
+
+        let lanes = [BtLane::A, BtLane::B, BtLane::C, BtLane::D];
+        let buttons = lanes
+            .into_iter()
+            .map(|lane| (Track::BT(lane), &self.note.bt[lane as usize]))
+            .chain(Side::iter().map(|side| (Track::FX(side), &self.note.fx[side as usize])));
+
+        for (track, notes) in buttons {
+            interval_issues(track, notes, &mut issues);
+
+            if let Some(audio_end) = audio_end {
+                issues.extend(
+                    notes
+                        .iter()
+                        .filter(|x| self.tick_to_ms(x.y + x.l) > audio_end)
+                        .map(|x| ChartIssue {
+                            y: x.y,
+                            kind: ChartIssueKind::NotePastAudioEnd(track),
+                        }),
+                );
+            }
+        }
+
+        for side in Side::iter() {
+            let track = Track::Laser(side);
+            let sections = &self.note.laser[side as usize];
+
+            for pair in sections.windows(2) {
+                let prev_end = pair[0].tick() + pair[0].last().map(|x| x.ry).unwrap_or(0);
+                if pair[1].tick() <= prev_end {
+                    issues.push(ChartIssue {
+                        y: pair[1].tick(),
+                        kind: ChartIssueKind::OverlappingNotes(track),
+                    });
+                }
+            }
+
+            for section in sections {
+                if section.1.len() < 2 {
+                    issues.push(ChartIssue {
+                        y: section.tick(),
+                        kind: ChartIssueKind::EmptyLaserSection(side),
+                    });
+                }
+
+                let out_of_range = section.1.iter().find(|x| {
+                    !(0.0..=1.0).contains(&x.v) || x.vf.is_some_and(|vf| !(0.0..=1.0).contains(&vf))
+                });
+                if let Some(point) = out_of_range {
+                    issues.push(ChartIssue {
+                        y: section.tick() + point.ry,
+                        kind: ChartIssueKind::LaserOutOfRange(side),
+                    });
+                }
+
+                let end = section.tick() + section.last().map(|x| x.ry).unwrap_or(0);
+                if audio_end.is_some_and(|audio_end| self.tick_to_ms(end) > audio_end) {
+                    issues.push(ChartIssue {
+                        y: section.tick(),
+                        kind: ChartIssueKind::NotePastAudioEnd(track),
+                    });
+                }
+            }
+        }
+
+        issues.sort_by_key(|x| x.y);
+        issues
+    }
+}